@@ -11,7 +11,7 @@ use std::{
 use libafl::{
     corpus::{Corpus, HasCurrentCorpusId, HasTestcase, SchedulerTestcaseMetadata, Testcase},
     events::EventFirer,
-    executors::HasObservers,
+    executors::{HasObservers, HasTimeout},
     inputs::UsesInput,
     observers::MapObserver,
     schedulers::{minimizer::IsFavoredMetadata, HasQueueCycles, Scheduler},
@@ -52,7 +52,6 @@ pub struct AflStatsStage<C, O, E, EM, Z> {
     last_find: Duration,
     last_hang: Duration,
     last_crash: Duration,
-    exec_timeout: u64,
     execs_at_last_objective: u64,
     cycles_wo_finds: u64,
     /// banner text (e.g., the target name)
@@ -194,7 +193,7 @@ where
 
 impl<C, O, E, EM, Z> Stage<E, EM, Z> for AflStatsStage<C, O, E, EM, Z>
 where
-    E: UsesState + HasObservers,
+    E: UsesState + HasObservers + HasTimeout,
     EM: EventFirer<State = E::State>,
     Z: UsesState<State = E::State> + HasScheduler,
     E::State: HasImported
@@ -298,7 +297,7 @@ where
             last_hang: self.last_hang,
             last_crash: self.last_crash,
             execs_since_crash: total_executions - self.execs_at_last_objective,
-            exec_timeout: self.exec_timeout, // TODO
+            exec_timeout: u64::try_from(executor.timeout().as_millis()).unwrap_or(u64::MAX),
             slowest_exec_ms: self.slowest_exec.as_millis(),
             peak_rss_mb: peak_rss_mb_child_processes()?,
             cpu_affinity: 0, // TODO
@@ -375,7 +374,6 @@ where
             slowest_exec: Duration::from_secs(0),
             last_report_time: current_time(),
             pid: process::id(),
-            exec_timeout: opt.hang_timeout,
             target_mode: fuzzer_target_mode(opt),
             afl_banner: Cow::Owned(opt.executable.display().to_string()),
             afl_version: Cow::Borrowed("libafl-fuzz-0.0.1"),
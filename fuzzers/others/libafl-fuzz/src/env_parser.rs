@@ -3,7 +3,7 @@ use std::{collections::HashMap, path::PathBuf, time::Duration};
 use libafl::Error;
 use libafl_bolts::core_affinity::Cores;
 
-use crate::Opt;
+use crate::{Opt, AFL_EXEC_TIMEOUT};
 
 pub fn parse_envs(opt: &mut Opt) -> Result<(), Error> {
     if let Ok(res) = std::env::var("AFL_CORES") {
@@ -26,7 +26,7 @@ pub fn parse_envs(opt: &mut Opt) -> Result<(), Error> {
     if let Ok(res) = std::env::var("AFL_HANG_TMOUT") {
         opt.hang_timeout = res.parse()?;
     } else {
-        opt.hang_timeout = 100;
+        opt.hang_timeout = AFL_EXEC_TIMEOUT;
     }
     if let Ok(res) = std::env::var("AFL_DEBUG_CHILD") {
         opt.debug_child = parse_bool(&res)?;
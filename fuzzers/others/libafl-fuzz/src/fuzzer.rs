@@ -6,7 +6,10 @@ use libafl::{
         CentralizedEventManager, EventManagerHooksTuple, EventProcessor,
         LlmpRestartingEventManager, ProgressReporter,
     },
-    executors::forkserver::{ForkserverExecutor, ForkserverExecutorBuilder},
+    executors::{
+        forkserver::{ForkserverExecutor, ForkserverExecutorBuilder},
+        TimeoutCalibrator,
+    },
     feedback_and, feedback_or, feedback_or_fast,
     feedbacks::{ConstFeedback, CrashFeedback, MaxMapFeedback, TimeFeedback, TimeoutFeedback},
     fuzzer::StdFuzzer,
@@ -52,7 +55,7 @@ use crate::{
     },
     mutational_stage::SupportedMutationalStages,
     scheduler::SupportedSchedulers,
-    Opt, AFL_DEFAULT_INPUT_LEN_MAX, AFL_DEFAULT_INPUT_LEN_MIN, SHMEM_ENV_VAR,
+    Opt, AFL_DEFAULT_INPUT_LEN_MAX, AFL_DEFAULT_INPUT_LEN_MIN, AFL_EXEC_TIMEOUT, SHMEM_ENV_VAR,
 };
 
 pub type LibaflFuzzState =
@@ -220,10 +223,14 @@ where
     }
 
     // Finalize and build our Executor
-    let mut executor = executor
+    let executor = executor
         .build(tuple_list!(time_observer, edges_observer))
         .unwrap();
 
+    // Derive the timeout from the calibrated exec times, and re-run timeouts with AFL_HANG_TMOUT.
+    let mut executor = TimeoutCalibrator::new(executor, Duration::from_millis(AFL_EXEC_TIMEOUT))
+        .hang_timeout(Duration::from_millis(opt.hang_timeout));
+
     // Load our seeds.
     if state.must_load_initial_inputs() {
         state
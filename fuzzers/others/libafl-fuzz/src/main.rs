@@ -35,6 +35,8 @@ use nix::sys::signal::Signal;
 
 const AFL_DEFAULT_INPUT_LEN_MAX: usize = 1_048_576;
 const AFL_DEFAULT_INPUT_LEN_MIN: usize = 1;
+/// The maximum calibrated timeout in milliseconds, AFL's `EXEC_TIMEOUT`
+const AFL_EXEC_TIMEOUT: u64 = 1000;
 const OUTPUT_GRACE: u64 = 25;
 pub const AFL_DEFAULT_BROKER_PORT: u16 = 1337;
 const PERSIST_SIG: &str = "##SIG_AFL_PERSISTENT##\0";
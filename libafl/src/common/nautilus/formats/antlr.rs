@@ -0,0 +1,402 @@
+//! Parser for [ANTLR4](https://github.com/antlr/antlr4/blob/master/doc/grammars.md) `.g4` grammars.
+//!
+//! Parser rules (lowercase names) and lexer rules (uppercase names) are both turned into
+//! Nautilus rules. Actions, predicates, labels, options and lexer modes are ignored.
+//! If the grammar skips some tokens (usually whitespace via `-> skip`), the elements of parser
+//! rules are separated by a single space, so generated tokens don't run into each other.
+
+use alloc::{string::String, vec::Vec};
+
+use libafl_bolts::Error;
+
+use super::{merge_grammars, push_range, Cursor, GrammarExpr, GrammarRule, ParsedGrammar};
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Parse a single ANTLR4 grammar.
+///
+/// The start rule is the first parser rule, or the first rule for lexer-only grammars.
+pub fn parse(src: &str) -> Result<ParsedGrammar, Error> {
+    AntlrParser {
+        cur: Cursor::new(src, true),
+        skips_tokens: false,
+    }
+    .grammar()
+}
+
+/// Parse a grammar split across several files, e.g. a parser grammar and its `tokenVocab` lexer
+/// grammar. The start rule is taken from the first grammar.
+pub fn parse_all(srcs: &[&str]) -> Result<ParsedGrammar, Error> {
+    let grammars = srcs
+        .iter()
+        .map(|src| parse(src))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(merge_grammars(grammars))
+}
+
+#[derive(Debug)]
+struct AntlrParser {
+    cur: Cursor,
+    skips_tokens: bool,
+}
+
+impl AntlrParser {
+    fn grammar(mut self) -> Result<ParsedGrammar, Error> {
+        let mut rules = vec![];
+        loop {
+            self.cur.skip_trivia();
+            if self.cur.is_eof() {
+                break;
+            }
+            if self.cur.peek() == Some('@') {
+                // Named actions like `@header { ... }`
+                self.skip_until('{')?;
+                self.cur.skip_balanced('{', '}')?;
+                continue;
+            }
+            let Some(word) = self.cur.ident(is_ident_char) else {
+                return Err(self.cur.error("expected a rule"));
+            };
+            match word.as_str() {
+                "lexer" | "parser" | "grammar" | "import" | "mode" => self.skip_until(';')?,
+                "options" | "tokens" | "channels" => {
+                    self.cur.skip_trivia();
+                    if self.cur.peek() == Some('{') {
+                        self.cur.skip_balanced('{', '}')?;
+                    }
+                }
+                "fragment" => {
+                    self.cur.skip_trivia();
+                    let Some(name) = self.cur.ident(is_ident_char) else {
+                        return Err(self.cur.error("expected a rule name after `fragment`"));
+                    };
+                    rules.push(self.rule(name)?);
+                }
+                _ => rules.push(self.rule(word)?),
+            }
+        }
+
+        let start = rules
+            .iter()
+            .find(|r| r.separated)
+            .or_else(|| rules.first())
+            .map(|r| r.name.clone());
+        let separator = if self.skips_tokens {
+            b" ".to_vec()
+        } else {
+            vec![]
+        };
+        Ok(ParsedGrammar {
+            rules,
+            start,
+            separator,
+        })
+    }
+
+    /// Skip everything up to and including `c`
+    fn skip_until(&mut self, c: char) -> Result<(), Error> {
+        loop {
+            match self.cur.peek() {
+                None => return Err(self.cur.error(&format!("expected `{c}`"))),
+                Some(x) if x == c => {
+                    if c != '{' {
+                        self.cur.bump();
+                    }
+                    return Ok(());
+                }
+                Some(_) => {
+                    self.cur.bump();
+                }
+            }
+        }
+    }
+
+    fn rule(&mut self, name: String) -> Result<GrammarRule, Error> {
+        // Skip `returns [...]`, `locals [...]`, `options {...}` and `@init {...}`
+        loop {
+            self.cur.skip_trivia();
+            match self.cur.peek() {
+                Some(':') => break,
+                Some('[') => self.cur.skip_balanced('[', ']')?,
+                Some('{') => self.cur.skip_balanced('{', '}')?,
+                Some(_) => {
+                    self.cur.bump();
+                }
+                None => return Err(self.cur.error(&format!("expected `:` for rule {name}"))),
+            }
+        }
+        self.cur.expect(":")?;
+        let expr = self.alternatives()?;
+        self.cur.expect(";")?;
+
+        let separated = name.starts_with(|c: char| c.is_ascii_lowercase());
+        Ok(GrammarRule {
+            name,
+            expr,
+            separated,
+        })
+    }
+
+    fn alternatives(&mut self) -> Result<GrammarExpr, Error> {
+        let mut alts = vec![self.sequence()?];
+        loop {
+            self.cur.skip_trivia();
+            if !self.cur.eat("|") {
+                break;
+            }
+            alts.push(self.sequence()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            GrammarExpr::Alt(alts)
+        })
+    }
+
+    fn sequence(&mut self) -> Result<GrammarExpr, Error> {
+        let mut items = vec![];
+        loop {
+            self.cur.skip_trivia();
+            match self.cur.peek() {
+                None | Some('|' | ';' | ')') => break,
+                Some('#') => {
+                    // Alternative label
+                    self.cur.bump();
+                    self.cur.skip_trivia();
+                    self.cur.ident(is_ident_char);
+                }
+                Some('-') if self.cur.starts_with("->") => {
+                    self.cur.eat("->");
+                    self.lexer_commands()?;
+                }
+                _ => {
+                    if let Some(item) = self.element()? {
+                        items.push(item);
+                    }
+                }
+            }
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            GrammarExpr::Seq(items)
+        })
+    }
+
+    /// Parse `-> skip, channel(HIDDEN), ...` and remember if tokens get hidden from the parser
+    fn lexer_commands(&mut self) -> Result<(), Error> {
+        loop {
+            self.cur.skip_trivia();
+            let Some(command) = self.cur.ident(is_ident_char) else {
+                return Err(self.cur.error("expected a lexer command"));
+            };
+            if command == "skip" || command == "channel" {
+                self.skips_tokens = true;
+            }
+            self.cur.skip_trivia();
+            if self.cur.peek() == Some('(') {
+                self.cur.skip_balanced('(', ')')?;
+                self.cur.skip_trivia();
+            }
+            if !self.cur.eat(",") {
+                return Ok(());
+            }
+        }
+    }
+
+    fn element(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        // Labels like `x=expr` or `xs+=expr`
+        self.cur.attempt(|cur| {
+            cur.ident(is_ident_char)?;
+            cur.skip_trivia();
+            (cur.eat("+=") || cur.eat("=")).then_some(())
+        });
+        self.cur.skip_trivia();
+
+        let Some(atom) = self.atom()? else {
+            return Ok(None);
+        };
+
+        self.cur.skip_trivia();
+        let expr = match self.cur.peek() {
+            Some('?') => {
+                self.cur.bump();
+                GrammarExpr::Optional(atom.into())
+            }
+            Some('*') => {
+                self.cur.bump();
+                GrammarExpr::Star(atom.into())
+            }
+            Some('+') => {
+                self.cur.bump();
+                GrammarExpr::Plus(atom.into())
+            }
+            _ => return Ok(Some(atom)),
+        };
+        // Non-greedy suffix
+        self.cur.eat("?");
+        Ok(Some(expr))
+    }
+
+    fn atom(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let expr = match self.cur.peek() {
+            Some('\'') => {
+                let lo = self.literal()?;
+                self.cur.skip_trivia();
+                if self.cur.eat("..") {
+                    self.cur.skip_trivia();
+                    let hi = self.literal()?;
+                    match (single_char(&lo), single_char(&hi)) {
+                        (Some(lo), Some(hi)) => {
+                            let mut ranges = vec![];
+                            push_range(&mut ranges, lo, hi);
+                            GrammarExpr::Class {
+                                negated: false,
+                                ranges,
+                            }
+                        }
+                        _ => return Err(self.cur.error("ranges need single characters")),
+                    }
+                } else {
+                    GrammarExpr::Literal(lo.into_bytes())
+                }
+            }
+            Some('[') => self.class()?,
+            Some('(') => {
+                self.cur.bump();
+                let expr = self.alternatives()?;
+                self.cur.expect(")")?;
+                expr
+            }
+            Some('~') => {
+                self.cur.bump();
+                self.cur.skip_trivia();
+                let Some(inner) = self.atom()? else {
+                    return Err(self.cur.error("expected a set after `~`"));
+                };
+                if let GrammarExpr::Ref(name) = &inner {
+                    return Err(self.cur.error(&format!(
+                        "negating the token `{name}` is not supported, inline its set instead"
+                    )));
+                }
+                inner
+                    .negate()
+                    .ok_or_else(|| self.cur.error("only sets can be negated"))?
+            }
+            Some('.') => {
+                self.cur.bump();
+                GrammarExpr::Any
+            }
+            Some('{') => {
+                // Actions and semantic predicates
+                self.cur.skip_balanced('{', '}')?;
+                self.cur.eat("?");
+                return Ok(None);
+            }
+            Some('<') => {
+                // Element options like `<assoc=right>`
+                self.cur.skip_balanced('<', '>')?;
+                return Ok(None);
+            }
+            _ => match self.cur.ident(is_ident_char) {
+                Some(name) if name == "EOF" => GrammarExpr::empty(),
+                Some(name) => GrammarExpr::Ref(name),
+                None => return Err(self.cur.error("expected a grammar element")),
+            },
+        };
+        Ok(Some(expr))
+    }
+
+    fn literal(&mut self) -> Result<String, Error> {
+        self.cur.bump();
+        let mut lit = String::new();
+        loop {
+            match self.cur.bump() {
+                None => return Err(self.cur.error("unterminated literal")),
+                Some('\'') => return Ok(lit),
+                Some('\\') => lit.push(self.escape()?),
+                Some(c) => lit.push(c),
+            }
+        }
+    }
+
+    fn class(&mut self) -> Result<GrammarExpr, Error> {
+        self.cur.bump();
+        let mut ranges = vec![];
+        let mut prev: Option<char> = None;
+        loop {
+            let c = match self.cur.bump() {
+                None => return Err(self.cur.error("unterminated character set")),
+                Some(']') => break,
+                Some('\\') if self.cur.peek() == Some('p') => {
+                    // Unicode properties are approximated by printable characters
+                    self.cur.bump();
+                    self.cur.skip_balanced('{', '}')?;
+                    push_range(&mut ranges, ' ', '~');
+                    prev = None;
+                    continue;
+                }
+                Some('\\') => self.escape()?,
+                Some('-') if prev.is_some() && self.cur.peek() != Some(']') => {
+                    let lo = prev.take().unwrap();
+                    let hi = match self.cur.bump() {
+                        Some('\\') => self.escape()?,
+                        Some(hi) => hi,
+                        None => return Err(self.cur.error("unterminated character set")),
+                    };
+                    ranges.pop();
+                    push_range(&mut ranges, lo, hi);
+                    continue;
+                }
+                Some(c) => c,
+            };
+            push_range(&mut ranges, c, c);
+            prev = Some(c);
+        }
+        Ok(GrammarExpr::Class {
+            negated: false,
+            ranges,
+        })
+    }
+
+    /// Parse the escape sequence after a backslash
+    fn escape(&mut self) -> Result<char, Error> {
+        let c = match self.cur.bump() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('u') => {
+                let mut hex = String::new();
+                if self.cur.eat("{") {
+                    while let Some(c) = self.cur.bump() {
+                        if c == '}' {
+                            break;
+                        }
+                        hex.push(c);
+                    }
+                } else {
+                    for _ in 0..4 {
+                        hex.extend(self.cur.bump());
+                    }
+                }
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.cur.error("invalid unicode escape"))?
+            }
+            Some(c) => c,
+            None => return Err(self.cur.error("unterminated escape sequence")),
+        };
+        Ok(c)
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
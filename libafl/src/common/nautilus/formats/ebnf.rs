@@ -0,0 +1,249 @@
+//! Parser for the W3C-style EBNF used in the [XML specification](https://www.w3.org/TR/xml/#sec-notation).
+//!
+//! Rules have the form `symbol ::= expression` and may span several lines.
+//! Exceptions (`A - B`) cannot be expressed in Nautilus and are approximated by `A`,
+//! well-formedness and validity constraints (`[ wfc: ... ]`) are ignored.
+
+use alloc::string::String;
+
+use libafl_bolts::Error;
+
+use super::{push_range, Cursor, GrammarExpr, GrammarRule, ParsedGrammar};
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
+/// Parse a W3C EBNF grammar. The start rule is the first rule.
+pub fn parse(src: &str) -> Result<ParsedGrammar, Error> {
+    let mut parser = EbnfParser {
+        cur: Cursor::new(src, false),
+    };
+    let mut rules = vec![];
+    loop {
+        parser.cur.skip_trivia();
+        if parser.cur.is_eof() {
+            break;
+        }
+        parser.production_number()?;
+        let Some(name) = parser.cur.ident(is_name_char) else {
+            return Err(parser.cur.error("expected a rule"));
+        };
+        parser.cur.expect("::=")?;
+        let expr = parser.choice()?;
+        rules.push(GrammarRule {
+            name,
+            expr,
+            separated: false,
+        });
+    }
+    let start = rules.first().map(|r| r.name.clone());
+    Ok(ParsedGrammar {
+        rules,
+        start,
+        separator: vec![],
+    })
+}
+
+#[derive(Debug)]
+struct EbnfParser {
+    cur: Cursor,
+}
+
+impl EbnfParser {
+    /// Check for a production number like `[42]`
+    fn at_production_number(&mut self) -> bool {
+        self.cur.lookahead(|cur| {
+            cur.bump() == Some('[') && cur.peek().is_some_and(|c| c.is_ascii_digit()) && {
+                while cur.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    cur.bump();
+                }
+                cur.eat("]")
+            }
+        })
+    }
+
+    /// Skip production numbers
+    fn production_number(&mut self) -> Result<(), Error> {
+        if self.at_production_number() {
+            self.cur.skip_balanced('[', ']')?;
+            self.cur.skip_trivia();
+        }
+        Ok(())
+    }
+
+    /// Check if the next rule starts here
+    fn at_rule_start(&mut self) -> bool {
+        if self.at_production_number() {
+            return true;
+        }
+        self.cur.lookahead(|cur| {
+            if cur.ident(is_name_char).is_none() {
+                return false;
+            }
+            cur.skip_trivia();
+            cur.starts_with("::=")
+        })
+    }
+
+    fn choice(&mut self) -> Result<GrammarExpr, Error> {
+        let mut alts = vec![self.sequence()?];
+        loop {
+            self.cur.skip_trivia();
+            if !self.cur.eat("|") {
+                break;
+            }
+            alts.push(self.sequence()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            GrammarExpr::Alt(alts)
+        })
+    }
+
+    fn sequence(&mut self) -> Result<GrammarExpr, Error> {
+        let mut items = vec![];
+        loop {
+            self.cur.skip_trivia();
+            match self.cur.peek() {
+                None | Some('|' | ')') => break,
+                _ if self.at_rule_start() => break,
+                _ => {
+                    if let Some(item) = self.exception()? {
+                        items.push(item);
+                    }
+                }
+            }
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            GrammarExpr::Seq(items)
+        })
+    }
+
+    fn exception(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let Some(expr) = self.postfix()? else {
+            return Ok(None);
+        };
+        self.cur.skip_trivia();
+        if self.cur.peek() == Some('-') {
+            self.cur.bump();
+            self.cur.skip_trivia();
+            // We can't exclude matches of the right side, so we generate from the left side only
+            self.postfix()?;
+        }
+        Ok(Some(expr))
+    }
+
+    fn postfix(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let Some(primary) = self.primary()? else {
+            return Ok(None);
+        };
+        let expr = match self.cur.peek() {
+            Some('?') => GrammarExpr::Optional(primary.into()),
+            Some('*') => GrammarExpr::Star(primary.into()),
+            Some('+') => GrammarExpr::Plus(primary.into()),
+            _ => return Ok(Some(primary)),
+        };
+        self.cur.bump();
+        Ok(Some(expr))
+    }
+
+    fn primary(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let expr = match self.cur.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.cur.bump();
+                let mut lit = String::new();
+                loop {
+                    match self.cur.bump() {
+                        None => return Err(self.cur.error("unterminated literal")),
+                        Some(c) if c == quote => break,
+                        Some(c) => lit.push(c),
+                    }
+                }
+                GrammarExpr::Literal(lit.into_bytes())
+            }
+            Some('#') => {
+                let c = self.hex_char()?;
+                let mut bytes = vec![];
+                super::push_char(&mut bytes, c);
+                GrammarExpr::Literal(bytes)
+            }
+            Some('[') => {
+                if self.is_constraint() {
+                    self.cur.skip_balanced('[', ']')?;
+                    return Ok(None);
+                }
+                self.class()?
+            }
+            Some('(') => {
+                self.cur.bump();
+                let expr = self.choice()?;
+                self.cur.expect(")")?;
+                expr
+            }
+            _ => match self.cur.ident(is_name_char) {
+                Some(name) => GrammarExpr::Ref(name),
+                None => return Err(self.cur.error("expected a grammar element")),
+            },
+        };
+        Ok(Some(expr))
+    }
+
+    /// Constraints look like `[ wfc: Name ]` or `[ vc: Name ]`
+    fn is_constraint(&mut self) -> bool {
+        self.cur.lookahead(|cur| {
+            cur.bump();
+            cur.skip_trivia();
+            cur.eat("wfc:") || cur.eat("vc:")
+        })
+    }
+
+    /// Parse `#xN`
+    fn hex_char(&mut self) -> Result<char, Error> {
+        if !self.cur.eat("#x") {
+            return Err(self.cur.error("expected `#x`"));
+        }
+        let mut hex = String::new();
+        while let Some(c) = self.cur.peek().filter(char::is_ascii_hexdigit) {
+            hex.push(c);
+            self.cur.bump();
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.cur.error("invalid character reference"))
+    }
+
+    fn class_char(&mut self) -> Result<char, Error> {
+        if self.cur.starts_with("#x") {
+            self.hex_char()
+        } else {
+            self.cur
+                .bump()
+                .ok_or_else(|| self.cur.error("unterminated character class"))
+        }
+    }
+
+    fn class(&mut self) -> Result<GrammarExpr, Error> {
+        self.cur.bump();
+        let negated = self.cur.eat("^");
+        let mut ranges = vec![];
+        loop {
+            if self.cur.eat("]") {
+                break;
+            }
+            let lo = self.class_char()?;
+            if self.cur.peek() == Some('-') && self.cur.peek_at(1) != Some(']') {
+                self.cur.bump();
+                let hi = self.class_char()?;
+                push_range(&mut ranges, lo, hi);
+            } else {
+                push_range(&mut ranges, lo, lo);
+            }
+        }
+        Ok(GrammarExpr::Class { negated, ranges })
+    }
+}
@@ -0,0 +1,789 @@
+//! Importers for grammar formats other than the Nautilus JSON rules.
+//!
+//! Both [`antlr`] and [`ebnf`] parse into a shared [`GrammarExpr`] tree, which is then desugared
+//! into plain Nautilus rules: groups, repetitions and optional parts become helper nonterminals,
+//! character classes become one terminal rule per character (or a regex rule, if the class is huge).
+
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::Error;
+
+use crate::common::nautilus::grammartec::context::Context;
+
+pub mod antlr;
+pub mod ebnf;
+
+/// Character classes with more members than this become regex rules instead of terminal rules.
+const MAX_EXPANDED_CLASS: u32 = 256;
+
+/// The characters `.` (any character) and negated classes are drawn from.
+const PRINTABLE: (char, char) = (' ', '~');
+
+/// A parsed grammar expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GrammarExpr {
+    /// A literal terminal
+    Literal(Vec<u8>),
+    /// A reference to another rule
+    Ref(String),
+    /// A set of character ranges, inclusive
+    Class {
+        /// If the class matches everything but the `ranges`
+        negated: bool,
+        /// The inclusive ranges of this class
+        ranges: Vec<(char, char)>,
+    },
+    /// Any single character
+    Any,
+    /// All elements in order
+    Seq(Vec<GrammarExpr>),
+    /// One of the elements
+    Alt(Vec<GrammarExpr>),
+    /// Zero or one occurrence
+    Optional(Box<GrammarExpr>),
+    /// Zero or more occurrences
+    Star(Box<GrammarExpr>),
+    /// One or more occurrences
+    Plus(Box<GrammarExpr>),
+}
+
+impl GrammarExpr {
+    /// The empty expression
+    #[must_use]
+    pub fn empty() -> Self {
+        Self::Seq(vec![])
+    }
+
+    /// Negate a class, a single-character literal or an alternative of those.
+    /// Returns `None` for anything else.
+    #[must_use]
+    pub fn negate(self) -> Option<Self> {
+        match self {
+            Self::Class { negated, ranges } => Some(Self::Class {
+                negated: !negated,
+                ranges,
+            }),
+            Self::Literal(bytes) => {
+                let s = core::str::from_utf8(&bytes).ok()?;
+                let mut chars = s.chars();
+                let c = chars.next()?;
+                chars.next().is_none().then(|| Self::Class {
+                    negated: true,
+                    ranges: vec![(c, c)],
+                })
+            }
+            Self::Alt(alts) => {
+                let mut ranges = vec![];
+                for alt in alts {
+                    match alt.negate()? {
+                        Self::Class {
+                            negated: true,
+                            ranges: r,
+                        } => ranges.extend(r),
+                        _ => return None,
+                    }
+                }
+                Some(Self::Class {
+                    negated: true,
+                    ranges,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A rule of a parsed grammar
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrammarRule {
+    /// The name as written in the grammar
+    pub name: String,
+    /// The right-hand side
+    pub expr: GrammarExpr,
+    /// Elements of this rule get separated by the grammar's separator (e.g. ANTLR parser rules)
+    pub separated: bool,
+}
+
+/// A grammar parsed from one of the supported formats
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParsedGrammar {
+    /// All rules, in the order of the grammar file
+    pub rules: Vec<GrammarRule>,
+    /// The rule inputs are generated from
+    pub start: Option<String>,
+    /// Bytes inserted between the elements of separated rules
+    pub separator: Vec<u8>,
+}
+
+/// A piece of a desugared production
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Piece {
+    /// A terminal
+    Term(Vec<u8>),
+    /// A nonterminal, by its Nautilus name
+    NonTerm(String),
+}
+
+/// A desugared production, ready to be added to a Nautilus [`Context`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Production {
+    /// A plain rule made of terminals and nonterminals
+    Plain(String, Vec<Piece>),
+    /// A rule generating from a regex
+    Regex(String, String),
+}
+
+impl Production {
+    fn nonterm(&self) -> &str {
+        match self {
+            Self::Plain(nt, _) | Self::Regex(nt, _) => nt,
+        }
+    }
+}
+
+/// Turn a grammar rule name into a valid Nautilus nonterminal name.
+#[must_use]
+pub fn nonterm_name(name: &str) -> String {
+    let mut res = "R_".to_owned();
+    res.extend(name.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            c
+        } else {
+            '_'
+        }
+    }));
+    res
+}
+
+/// Stands in for the backslashes a terminal ends with when a nonterminal follows.
+///
+/// Nautilus reads `\{` as an escaped brace, and there is no escape for a backslash itself.
+const BACKSLASH_NONTERM: &str = "BACKSLASH";
+
+/// Append `bytes` to a Nautilus rule `format`, escaping braces.
+///
+/// If `before_nonterm` is set, trailing backslashes are written as [`BACKSLASH_NONTERM`]
+/// and `true` is returned, so the caller has to add a rule for it.
+fn escape_term(bytes: &[u8], before_nonterm: bool, format: &mut Vec<u8>) -> bool {
+    let mut end = bytes.len();
+    if before_nonterm {
+        while end > 0 && bytes[end - 1] == b'\\' {
+            end -= 1;
+        }
+    }
+    for &b in &bytes[..end] {
+        if b == b'{' || b == b'}' {
+            format.push(b'\\');
+        }
+        format.push(b);
+    }
+    for _ in end..bytes.len() {
+        format.push(b'{');
+        format.extend_from_slice(BACKSLASH_NONTERM.as_bytes());
+        format.push(b'}');
+    }
+    end < bytes.len()
+}
+
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+/// Desugars a [`ParsedGrammar`] into [`Production`]s
+#[derive(Debug)]
+struct Desugarer<'a> {
+    separator: &'a [u8],
+    /// The grammar rule currently being desugared, for error messages
+    rule_name: &'a str,
+    productions: Vec<Production>,
+    helpers: usize,
+}
+
+impl Desugarer<'_> {
+    fn helper(&mut self) -> String {
+        self.helpers += 1;
+        format!("G_{}", self.helpers)
+    }
+
+    fn rule(&mut self, nt: &str, expr: &GrammarExpr, separated: bool) -> Result<(), Error> {
+        if let GrammarExpr::Alt(alts) = expr {
+            for alt in alts {
+                let pieces = self.seq(alt, separated)?;
+                self.productions
+                    .push(Production::Plain(nt.to_owned(), pieces));
+            }
+        } else {
+            let pieces = self.seq(expr, separated)?;
+            self.productions
+                .push(Production::Plain(nt.to_owned(), pieces));
+        }
+        Ok(())
+    }
+
+    fn seq(&mut self, expr: &GrammarExpr, separated: bool) -> Result<Vec<Piece>, Error> {
+        let items = match expr {
+            GrammarExpr::Seq(items) => items.as_slice(),
+            _ => core::slice::from_ref(expr),
+        };
+        let mut pieces = vec![];
+        for item in items {
+            let item_pieces = self.item(item, separated)?;
+            if item_pieces.is_empty() {
+                continue;
+            }
+            if separated && !pieces.is_empty() && !self.separator.is_empty() {
+                pieces.push(Piece::Term(self.separator.to_vec()));
+            }
+            pieces.extend(item_pieces);
+        }
+        Ok(pieces)
+    }
+
+    fn item(&mut self, expr: &GrammarExpr, separated: bool) -> Result<Vec<Piece>, Error> {
+        Ok(match expr {
+            GrammarExpr::Literal(bytes) if bytes.is_empty() => vec![],
+            GrammarExpr::Literal(bytes) => vec![Piece::Term(bytes.clone())],
+            GrammarExpr::Ref(name) => vec![Piece::NonTerm(nonterm_name(name))],
+            GrammarExpr::Seq(_) => self.seq(expr, separated)?,
+            GrammarExpr::Class { negated, ranges } => {
+                let nt = self.helper();
+                self.class(&nt, *negated, ranges)?;
+                vec![Piece::NonTerm(nt)]
+            }
+            GrammarExpr::Any => {
+                let nt = self.helper();
+                self.class(&nt, false, &[PRINTABLE])?;
+                vec![Piece::NonTerm(nt)]
+            }
+            GrammarExpr::Alt(_) => {
+                let nt = self.helper();
+                self.rule(&nt, expr, separated)?;
+                vec![Piece::NonTerm(nt)]
+            }
+            GrammarExpr::Optional(inner) => {
+                let nt = self.helper();
+                self.productions.push(Production::Plain(nt.clone(), vec![]));
+                self.rule(&nt, inner, separated)?;
+                vec![Piece::NonTerm(nt)]
+            }
+            GrammarExpr::Star(inner) | GrammarExpr::Plus(inner) => {
+                let nt = self.helper();
+                let body = self.helper();
+                self.rule(&body, inner, separated)?;
+                if matches!(expr, GrammarExpr::Star(_)) {
+                    self.productions.push(Production::Plain(nt.clone(), vec![]));
+                } else {
+                    self.productions.push(Production::Plain(
+                        nt.clone(),
+                        vec![Piece::NonTerm(body.clone())],
+                    ));
+                }
+                let mut repeat = vec![Piece::NonTerm(body)];
+                if separated && !self.separator.is_empty() {
+                    repeat.push(Piece::Term(self.separator.to_vec()));
+                }
+                repeat.push(Piece::NonTerm(nt.clone()));
+                self.productions.push(Production::Plain(nt.clone(), repeat));
+                vec![Piece::NonTerm(nt)]
+            }
+        })
+    }
+
+    fn class(&mut self, nt: &str, negated: bool, ranges: &[(char, char)]) -> Result<(), Error> {
+        let members: u32 = ranges
+            .iter()
+            .map(|&(lo, hi)| (hi as u32).saturating_sub(lo as u32) + 1)
+            .sum();
+        if negated {
+            let excluded = |c: char| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+            let mut empty = true;
+            for c in (PRINTABLE.0..=PRINTABLE.1).chain(['\t', '\n', '\r']) {
+                if !excluded(c) {
+                    let mut bytes = vec![];
+                    push_char(&mut bytes, c);
+                    self.productions
+                        .push(Production::Plain(nt.to_owned(), vec![Piece::Term(bytes)]));
+                    empty = false;
+                }
+            }
+            if empty {
+                return Err(Error::illegal_argument(format!(
+                    "The negated character class in rule {} excludes every character we generate",
+                    self.rule_name
+                )));
+            }
+        } else if members == 0 {
+            return Err(Error::illegal_argument(format!(
+                "The character class in rule {} is empty",
+                self.rule_name
+            )));
+        } else if members > MAX_EXPANDED_CLASS {
+            let mut regex = "[".to_owned();
+            for &(lo, hi) in ranges {
+                write!(regex, "\\x{{{:x}}}-\\x{{{:x}}}", lo as u32, hi as u32).unwrap();
+            }
+            regex.push(']');
+            self.productions
+                .push(Production::Regex(nt.to_owned(), regex));
+        } else {
+            for &(lo, hi) in ranges {
+                for c in lo..=hi {
+                    let mut bytes = vec![];
+                    push_char(&mut bytes, c);
+                    self.productions
+                        .push(Production::Plain(nt.to_owned(), vec![Piece::Term(bytes)]));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ParsedGrammar {
+    /// Desugar this grammar into Nautilus productions.
+    ///
+    /// The first production always expands the start rule. Fails if the grammar has no start rule,
+    /// references undefined rules, contains character classes that match nothing,
+    /// or contains rules that can never produce a finite input.
+    pub fn desugar(&self) -> Result<Vec<Production>, Error> {
+        let start = self
+            .start
+            .as_ref()
+            .or_else(|| self.rules.first().map(|r| &r.name))
+            .ok_or_else(|| Error::illegal_argument("The grammar does not contain any rules"))?;
+
+        let mut desugarer = Desugarer {
+            separator: &self.separator,
+            rule_name: "",
+            productions: vec![],
+            helpers: 0,
+        };
+        let start_nt = nonterm_name(start);
+        for rule in &self.rules {
+            desugarer.rule_name = &rule.name;
+            desugarer.rule(&nonterm_name(&rule.name), &rule.expr, rule.separated)?;
+        }
+        let mut productions = desugarer.productions;
+        // Keep the start rule first, Nautilus derives its root from it.
+        productions.sort_by_key(|p| p.nonterm() != start_nt);
+        if productions.first().map(Production::nonterm) != Some(start_nt.as_str()) {
+            return Err(Error::illegal_argument(format!(
+                "The start rule {start} is not defined"
+            )));
+        }
+
+        check_productive(&productions)?;
+        Ok(productions)
+    }
+}
+
+/// Make sure every nonterminal is defined and can produce a finite input.
+/// Nautilus panics on such grammars, so we rather report them here.
+fn check_productive(productions: &[Production]) -> Result<(), Error> {
+    let defined: HashSet<&str> = productions.iter().map(Production::nonterm).collect();
+    let mut undefined: Vec<&str> = productions
+        .iter()
+        .filter_map(|p| match p {
+            Production::Plain(_, pieces) => Some(pieces),
+            Production::Regex(..) => None,
+        })
+        .flatten()
+        .filter_map(|piece| match piece {
+            Piece::NonTerm(nt) if !defined.contains(nt.as_str()) => Some(nt.as_str()),
+            _ => None,
+        })
+        .collect();
+    if !undefined.is_empty() {
+        undefined.sort_unstable();
+        undefined.dedup();
+        return Err(Error::illegal_argument(format!(
+            "The grammar references undefined rules: {}",
+            undefined.join(", ")
+        )));
+    }
+
+    let mut productive: HashSet<&str> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for production in productions {
+            let nt = production.nonterm();
+            if productive.contains(nt) {
+                continue;
+            }
+            let ok = match production {
+                Production::Regex(..) => true,
+                Production::Plain(_, pieces) => pieces.iter().all(|piece| match piece {
+                    Piece::Term(_) => true,
+                    Piece::NonTerm(n) => productive.contains(n.as_str()),
+                }),
+            };
+            if ok {
+                productive.insert(nt);
+                changed = true;
+            }
+        }
+    }
+    let mut unproductive: Vec<&str> = defined.difference(&productive).copied().collect();
+    if !unproductive.is_empty() {
+        unproductive.sort_unstable();
+        return Err(Error::illegal_argument(format!(
+            "The grammar contains rules without a non-recursive case: {}",
+            unproductive.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Add the desugared `productions` to a Nautilus [`Context`], including the `START` rule.
+pub fn add_to_context(ctx: &mut Context, productions: &[Production]) {
+    let mut needs_backslash = false;
+    for production in productions {
+        match production {
+            Production::Plain(nt, pieces) => {
+                let mut format = vec![];
+                for (i, piece) in pieces.iter().enumerate() {
+                    match piece {
+                        Piece::Term(bytes) => {
+                            let before_nonterm =
+                                matches!(pieces.get(i + 1), Some(Piece::NonTerm(_)));
+                            needs_backslash |= escape_term(bytes, before_nonterm, &mut format);
+                        }
+                        Piece::NonTerm(name) => {
+                            format.push(b'{');
+                            format.extend_from_slice(name.as_bytes());
+                            format.push(b'}');
+                        }
+                    }
+                }
+                ctx.add_rule(nt, &format);
+            }
+            Production::Regex(nt, regex) => {
+                ctx.add_regex(nt, regex);
+            }
+        }
+    }
+    if needs_backslash {
+        ctx.add_term_rule(BACKSLASH_NONTERM, b"\\");
+    }
+    if let Some(first) = productions.first() {
+        let root = format!("{{{}}}", first.nonterm());
+        ctx.add_rule("START", root.as_bytes());
+    }
+}
+
+/// A character cursor shared by the grammar parsers
+#[derive(Debug)]
+pub(crate) struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line_comments: bool,
+}
+
+impl Cursor {
+    pub(crate) fn new(src: &str, line_comments: bool) -> Self {
+        Self {
+            chars: src.chars().collect(),
+            pos: 0,
+            line_comments,
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    pub(crate) fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    pub(crate) fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    pub(crate) fn is_eof(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    pub(crate) fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    /// Consume `s` if the input continues with it
+    pub(crate) fn eat(&mut self, s: &str) -> bool {
+        if self.starts_with(s) {
+            self.pos += s.chars().count();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skip whitespace and comments
+    pub(crate) fn skip_trivia(&mut self) {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+            if self.eat("/*") {
+                while !self.is_eof() && !self.eat("*/") {
+                    self.pos += 1;
+                }
+            } else if self.line_comments && self.starts_with("//") {
+                while self.bump().is_some_and(|c| c != '\n') {}
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Read an identifier whose characters after the first satisfy `cont`
+    pub(crate) fn ident(&mut self, cont: impl Fn(char) -> bool) -> Option<String> {
+        let first = self.peek()?;
+        if !(first.is_ascii_alphabetic() || first == '_') {
+            return None;
+        }
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if !(name.is_empty() || cont(c)) {
+                break;
+            }
+            name.push(c);
+            self.pos += 1;
+        }
+        Some(name)
+    }
+
+    /// Run `f` and rewind the cursor if it returns `None`
+    pub(crate) fn attempt<T>(&mut self, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let pos = self.pos;
+        let res = f(self);
+        if res.is_none() {
+            self.pos = pos;
+        }
+        res
+    }
+
+    /// Check if `f` matches at the current position, without consuming anything
+    pub(crate) fn lookahead(&mut self, f: impl FnOnce(&mut Self) -> bool) -> bool {
+        let pos = self.pos;
+        let res = f(self);
+        self.pos = pos;
+        res
+    }
+
+    /// Skip a balanced block opened by the current character, e.g. `{ ... }` or `[ ... ]`
+    pub(crate) fn skip_balanced(&mut self, open: char, close: char) -> Result<(), Error> {
+        let mut depth = 0_usize;
+        while let Some(c) = self.bump() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            } else if c == '\\' {
+                self.bump();
+            }
+        }
+        Err(self.error(&format!("unterminated block, expected `{close}`")))
+    }
+
+    pub(crate) fn expect(&mut self, s: &str) -> Result<(), Error> {
+        self.skip_trivia();
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{s}`")))
+        }
+    }
+
+    pub(crate) fn error(&self, msg: &str) -> Error {
+        let line = self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count()
+            + 1;
+        let found = self
+            .peek()
+            .map_or_else(|| "end of file".to_string(), |c| format!("`{c}`"));
+        Error::illegal_argument(format!(
+            "Could not parse grammar at line {line}: {msg}, found {found}"
+        ))
+    }
+}
+
+/// Collects the `(lo, hi)` ranges of a class while parsing it
+pub(crate) fn push_range(ranges: &mut Vec<(char, char)>, lo: char, hi: char) {
+    if lo <= hi {
+        ranges.push((lo, hi));
+    } else {
+        ranges.push((hi, lo));
+    }
+}
+
+/// Merge grammars that are split across files, e.g. ANTLR lexer and parser grammars
+pub(crate) fn merge_grammars(grammars: Vec<ParsedGrammar>) -> ParsedGrammar {
+    let mut merged = ParsedGrammar::default();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for grammar in grammars {
+        if merged.start.is_none() {
+            merged.start = grammar.start;
+        }
+        if merged.separator.is_empty() {
+            merged.separator = grammar.separator;
+        }
+        for rule in grammar.rules {
+            if let Some(&idx) = seen.get(&rule.name) {
+                // Later definitions (e.g. from an imported grammar) don't override earlier ones
+                log::debug!(
+                    "Ignoring duplicate definition of rule {}",
+                    merged.rules[idx].name
+                );
+                continue;
+            }
+            seen.insert(rule.name.clone(), merged.rules.len());
+            merged.rules.push(rule);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::{antlr, ebnf, escape_term, GrammarExpr, Piece, Production};
+    use crate::common::nautilus::grammartec::{
+        context::Context,
+        rule::{Rule, RuleChild},
+    };
+
+    #[test]
+    fn test_antlr_desugar() {
+        let grammar = antlr::parse(
+            r"
+            grammar Calc;
+            // the start rule
+            expr : term (('+' | '-') term)* ;
+            term : NUMBER | '(' expr ')' ;
+            NUMBER : [0-9]+ ;
+            WS : [ \t\r\n]+ -> skip ;
+            ",
+        )
+        .unwrap();
+        assert_eq!(grammar.start.as_deref(), Some("expr"));
+        assert_eq!(grammar.separator, b" ".to_vec());
+        assert_eq!(grammar.rules.len(), 4);
+        assert_eq!(
+            grammar.rules[1].expr,
+            GrammarExpr::Alt(vec![
+                GrammarExpr::Ref("NUMBER".into()),
+                GrammarExpr::Seq(vec![
+                    GrammarExpr::Literal(b"(".to_vec()),
+                    GrammarExpr::Ref("expr".into()),
+                    GrammarExpr::Literal(b")".to_vec()),
+                ]),
+            ])
+        );
+
+        let productions = grammar.desugar().unwrap();
+        assert!(matches!(&productions[0], Production::Plain(nt, _) if nt == "R_expr"));
+        assert!(productions.contains(&Production::Plain(
+            "R_term".into(),
+            vec![
+                Piece::Term(b"(".to_vec()),
+                Piece::Term(b" ".to_vec()),
+                Piece::NonTerm("R_expr".into()),
+                Piece::Term(b" ".to_vec()),
+                Piece::Term(b")".to_vec()),
+            ]
+        )));
+    }
+
+    #[test]
+    fn test_ebnf_desugar() {
+        let grammar = ebnf::parse(
+            r#"
+            /* a tiny json-like grammar */
+            value  ::= object | number | "null"
+            object ::= '{' (pair (',' pair)*)? '}'
+            pair   ::= '"' [a-z]+ '"' ':' value
+            number ::= '-'? [0-9]+ ('.' [0-9]+)? [ wfc: no leading zeros ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(grammar.start.as_deref(), Some("value"));
+        assert_eq!(grammar.rules.len(), 4);
+        assert!(grammar.separator.is_empty());
+
+        let productions = grammar.desugar().unwrap();
+        assert!(productions.contains(&Production::Plain(
+            "R_value".into(),
+            vec![Piece::Term(b"null".to_vec())]
+        )));
+    }
+
+    #[test]
+    fn test_unproductive() {
+        let grammar = ebnf::parse("a ::= 'x' a\nb ::= c").unwrap();
+        assert!(grammar.desugar().is_err());
+        let grammar = ebnf::parse("a ::= 'x' a").unwrap();
+        assert!(grammar.desugar().is_err());
+    }
+
+    #[test]
+    fn test_escape_term() {
+        let mut format = vec![];
+        assert!(!escape_term(b"a{b}\\", false, &mut format));
+        assert_eq!(format, b"a\\{b\\}\\".to_vec());
+
+        // A trailing backslash must not escape the following nonterminal
+        let mut format = vec![];
+        assert!(escape_term(b"a{b}\\\\", true, &mut format));
+        format.extend_from_slice(b"{B}");
+        let mut ctx = Context::new();
+        let rule = Rule::from_format(&mut ctx, "A", &format);
+        let expected: Vec<_> = vec![
+            RuleChild::from_lit(b"a{b}"),
+            RuleChild::from_nt("{BACKSLASH}", &mut ctx),
+            RuleChild::from_nt("{BACKSLASH}", &mut ctx),
+            RuleChild::from_nt("{B}", &mut ctx),
+        ];
+        let Rule::Plain(rule) = rule else {
+            unreachable!()
+        };
+        assert_eq!(rule.children, expected);
+    }
+
+    #[test]
+    fn test_negation_errors() {
+        let err = antlr::parse("grammar T;\na : ~B ;\nB : 'x' ;")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("negating the token `B` is not supported"),
+            "{err}"
+        );
+
+        let grammar = ebnf::parse("a ::= 'x' [^#x9-#x7E]").unwrap();
+        let err = grammar.desugar().unwrap_err().to_string();
+        assert!(
+            err.contains("negated character class in rule a excludes every character"),
+            "{err}"
+        );
+    }
+}
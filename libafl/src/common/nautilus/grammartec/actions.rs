@@ -0,0 +1,110 @@
+//! Semantic actions, run while unparsing a tree.
+//!
+//! Nautilus rules are purely syntactic. Actions let a nonterminal rewrite the bytes its children
+//! unparse to, e.g. to prepend a length field, append a checksum, or only use identifiers that
+//! were declared earlier. They only run during unparsing, so the trees (and their mutators) stay
+//! untouched.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug},
+    hash::BuildHasher,
+};
+
+use ahash::RandomState;
+use hashbrown::HashMap;
+
+use super::newtypes::NTermId;
+
+/// An action attached to a nonterminal.
+///
+/// Gets the unparsed bytes of every child of the rule (terminals and nonterminals, in order)
+/// and returns the bytes the rule unparses to instead of their concatenation.
+pub type UnparseAction = Box<dyn Fn(&mut UnparseState, &[Vec<u8>]) -> Vec<u8> + Send + Sync>;
+
+/// A hook run on the whole unparsed input, after all actions ran.
+pub type PostUnparseHook = Box<dyn Fn(&mut UnparseState, &mut Vec<u8>) + Send + Sync>;
+
+/// State shared by all actions during a single unparse, e.g. to track declared identifiers
+#[derive(Debug, Default, Clone)]
+pub struct UnparseState {
+    symbols: HashMap<String, Vec<Vec<u8>>>,
+}
+
+impl UnparseState {
+    /// Record `symbol` in the symbol table `table`
+    pub fn declare(&mut self, table: &str, symbol: &[u8]) {
+        self.symbols
+            .entry(table.into())
+            .or_default()
+            .push(symbol.to_vec());
+    }
+
+    /// All symbols declared so far in `table`
+    #[must_use]
+    pub fn symbols(&self, table: &str) -> &[Vec<u8>] {
+        self.symbols.get(table).map_or(&[], Vec::as_slice)
+    }
+
+    /// Deterministically pick one of the symbols declared in `table`, based on `seed`.
+    ///
+    /// Use the generated bytes as `seed` so that the same tree always unparses to the same input.
+    /// Returns `None` if nothing was declared yet.
+    #[must_use]
+    pub fn pick(&self, table: &str, seed: &[u8]) -> Option<&[u8]> {
+        let symbols = self.symbols(table);
+        if symbols.is_empty() {
+            return None;
+        }
+        let hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(seed);
+        let idx = (hash % symbols.len() as u64) as usize;
+        Some(&symbols[idx])
+    }
+}
+
+/// The actions and hooks registered for a grammar
+#[derive(Default)]
+pub struct UnparseActions {
+    actions: HashMap<NTermId, UnparseAction>,
+    post_hooks: Vec<PostUnparseHook>,
+}
+
+impl Debug for UnparseActions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnparseActions")
+            .field("actions", &self.actions.keys().collect::<Vec<_>>())
+            .field("post_hooks", &self.post_hooks.len())
+            .finish()
+    }
+}
+
+impl UnparseActions {
+    /// Attach `action` to the nonterminal `nt`, replacing any previous action
+    pub fn set_action(&mut self, nt: NTermId, action: UnparseAction) {
+        self.actions.insert(nt, action);
+    }
+
+    /// The action for the nonterminal `nt`, if any
+    #[must_use]
+    pub fn action(&self, nt: NTermId) -> Option<&UnparseAction> {
+        self.actions.get(&nt)
+    }
+
+    /// Add a hook run on the whole unparsed input
+    pub fn add_post_hook(&mut self, hook: PostUnparseHook) {
+        self.post_hooks.push(hook);
+    }
+
+    /// Returns `true` if there is nothing to run
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty() && self.post_hooks.is_empty()
+    }
+
+    /// Run all post-unparse hooks on `bytes`
+    pub fn run_post_hooks(&self, state: &mut UnparseState, bytes: &mut Vec<u8>) {
+        for hook in &self.post_hooks {
+            hook(state, bytes);
+        }
+    }
+}
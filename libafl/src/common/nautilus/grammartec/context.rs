@@ -121,6 +121,11 @@ impl Context {
             .expect(&("no such nonterminal: ".to_owned() + nt));
     }
 
+    #[must_use]
+    pub fn try_nt_id(&self, nt: &str) -> Option<NTermId> {
+        self.names_to_nt_id.get(nt).copied()
+    }
+
     #[must_use]
     pub fn nt_id_to_s(&self, nt: NTermId) -> String {
         self.nt_ids_to_name[&nt].clone()
@@ -375,6 +380,22 @@ mod tests {
         assert_eq!(r.nonterms()[2], ctx.nt_id("C"));
     }
 
+    #[test]
+    fn verbatim_backslash() {
+        let mut ctx = Context::new();
+        let r = Rule::from_format(&mut ctx, "F", b"a\\\\b{B}\\c\\");
+        let soll = vec![
+            RuleChild::from_lit(b"a\\\\b"),
+            RuleChild::from_nt("{B}", &mut ctx),
+            RuleChild::from_lit(b"\\c\\"),
+        ];
+        if let Rule::Plain(rl) = &r {
+            assert_eq!(&rl.children, &soll);
+        } else {
+            unreachable!();
+        }
+    }
+
     #[test]
     fn test_context() {
         let mut rand = StdRand::new();
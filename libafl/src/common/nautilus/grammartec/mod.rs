@@ -1,3 +1,4 @@
+pub mod actions;
 pub mod chunkstore;
 pub mod context;
 pub mod mutator;
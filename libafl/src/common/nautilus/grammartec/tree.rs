@@ -12,6 +12,7 @@ use serde::{Deserialize, Serialize};
 
 use super::{
     super::regex_mutator,
+    actions::{UnparseActions, UnparseState},
     context::Context,
     newtypes::{NTermId, NodeId, RuleId},
     recursion_info::RecursionInfo,
@@ -22,6 +23,7 @@ enum UnparseStep<'dat> {
     Term(&'dat [u8]),
     Nonterm(NTermId),
     Script(usize, PyObject),
+    Action(NTermId, usize),
     PushBuffer(),
 }
 
@@ -32,6 +34,8 @@ struct Unparser<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> {
     w: W,
     i: usize,
     ctx: &'ctx Context,
+    actions: Option<&'ctx UnparseActions>,
+    state: UnparseState,
 }
 
 impl<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> Unparser<'data, 'tree, 'ctx, W, T> {
@@ -47,6 +51,8 @@ impl<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> Unparser<'data, 't
             tree,
             i,
             ctx,
+            actions: None,
+            state: UnparseState::default(),
         }
     }
 
@@ -55,6 +61,7 @@ impl<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> Unparser<'data, 't
             Some(UnparseStep::Term(data)) => self.write(data),
             Some(UnparseStep::Nonterm(nt)) => self.nonterm(nt),
             Some(UnparseStep::Script(num, expr)) => self.unwrap_script(num, &expr),
+            Some(UnparseStep::Action(nt, num)) => self.action(nt, num),
             Some(UnparseStep::PushBuffer()) => self.push_buffer(),
             None => return false,
         };
@@ -101,6 +108,20 @@ impl<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> Unparser<'data, 't
         Ok(())
     }
 
+    fn action(&mut self, nt: NTermId, num: usize) {
+        let bufs = self.buffers.split_off(self.buffers.len() - num);
+        let bufs = bufs
+            .into_iter()
+            .map(io::Cursor::into_inner)
+            .collect::<Vec<_>>();
+        let action = self
+            .actions
+            .and_then(|actions| actions.action(nt))
+            .expect("action step without an action");
+        let res = action(&mut self.state, &bufs);
+        self.write(&res);
+    }
+
     fn push_buffer(&mut self) {
         self.buffers.push(io::Cursor::new(vec![]));
     }
@@ -110,6 +131,22 @@ impl<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> Unparser<'data, 't
         let rule: &'ctx Rule = self.tree.get_rule(nid, self.ctx);
         assert_eq!(nt, rule.nonterm());
         self.i += 1;
+        if self
+            .actions
+            .is_some_and(|actions| actions.action(nt).is_some())
+        {
+            match rule {
+                Rule::Plain(r) => return self.next_plain_with_action(nt, r),
+                Rule::RegExp(_) => {
+                    self.stack.push(UnparseStep::Action(nt, 1));
+                    self.next_regexp(self.tree.get_custom_rule_data(nid));
+                    self.stack.push(UnparseStep::PushBuffer());
+                    return;
+                }
+                // Scripts already post-process their children
+                Rule::Script(_) => {}
+            }
+        }
         match rule {
             Rule::Plain(r) => self.next_plain(r),
             Rule::Script(r) => self.next_script(r),
@@ -127,6 +164,19 @@ impl<'data, 'tree: 'data, 'ctx: 'data, W: Write, T: TreeLike> Unparser<'data, 't
         }
     }
 
+    /// Unparse every child into its own buffer, then let the action combine them
+    fn next_plain_with_action(&mut self, nt: NTermId, r: &'ctx PlainRule) {
+        self.stack.push(UnparseStep::Action(nt, r.children.len()));
+        for rule_child in r.children.iter().rev() {
+            let op = match rule_child {
+                RuleChild::Term(data) => UnparseStep::<'data>::Term(data),
+                RuleChild::NTerm(id) => UnparseStep::<'data>::Nonterm(*id),
+            };
+            self.stack.push(op);
+            self.stack.push(UnparseStep::PushBuffer());
+        }
+    }
+
     fn next_script(&mut self, r: &ScriptRule) {
         Python::with_gil(|py| {
             self.stack.push(UnparseStep::Script(
@@ -168,6 +218,23 @@ where
         Unparser::new(id, &mut w, self, ctx).unparse();
     }
 
+    /// Unparse, running the semantic `actions` attached to the nonterminals.
+    /// `state` is shared by all actions and can be inspected afterwards.
+    fn unparse_with_actions<W: Write>(
+        &self,
+        id: NodeId,
+        ctx: &Context,
+        actions: &UnparseActions,
+        state: &mut UnparseState,
+        mut w: &mut W,
+    ) {
+        let mut unparser = Unparser::new(id, &mut w, self, ctx);
+        unparser.actions = Some(actions);
+        unparser.state = core::mem::take(state);
+        unparser.unparse();
+        *state = unparser.state;
+    }
+
     fn unparse_to<W: Write>(&self, ctx: &Context, w: &mut W) {
         self.unparse(NodeId::from(0), ctx, w);
     }
@@ -463,6 +530,8 @@ impl<'a> TreeLike for TreeMutation<'a> {
 
 #[cfg(test)]
 mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
     use libafl_bolts::rands::StdRand;
 
     use super::{
@@ -558,6 +627,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_unparse_actions() {
+        let mut rand = StdRand::new();
+        let mut ctx = Context::new();
+        let _ = ctx.add_rule("MSG", b"{LEN}:{DATA}");
+        let _ = ctx.add_rule("LEN", b"0");
+        let _ = ctx.add_rule("DATA", b"ab{DATA}");
+        let _ = ctx.add_rule("DATA", b"c");
+        ctx.initialize(50);
+
+        let mut actions = UnparseActions::default();
+        actions.set_action(
+            ctx.nt_id("MSG"),
+            Box::new(|state, children| {
+                state.declare("messages", &children[2]);
+                let mut res = children[2].len().to_string().into_bytes();
+                res.extend_from_slice(&children[1]);
+                res.extend_from_slice(&children[2]);
+                res
+            }),
+        );
+
+        let mut tree = Tree::from_rule_vec(vec![], &ctx);
+        for _ in 0..100 {
+            tree.truncate();
+            tree.generate_from_nt(&mut rand, ctx.nt_id("MSG"), 50, &ctx);
+            let plain = tree.unparse_to_vec(&ctx);
+            let data = &plain[2..];
+
+            let mut state = UnparseState::default();
+            let mut res = vec![];
+            tree.unparse_with_actions(NodeId::from(0), &ctx, &actions, &mut state, &mut res);
+            let mut expected = data.len().to_string().into_bytes();
+            expected.push(b':');
+            expected.extend_from_slice(data);
+            assert_eq!(res, expected);
+            assert_eq!(state.symbols("messages"), &[data.to_vec()]);
+        }
+    }
+
     #[test]
     fn check_find_recursions() {
         let mut rand = StdRand::new();
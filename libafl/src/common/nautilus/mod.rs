@@ -2,5 +2,6 @@
 //!
 #![doc = include_str!("README.md")]
 
+pub mod formats;
 pub mod grammartec;
 pub mod regex_mutator;
@@ -21,12 +21,12 @@ use core::{
 };
 #[cfg(all(unix, feature = "std", feature = "fork"))]
 use std::boxed::Box;
-#[cfg(feature = "std")]
-use std::net::SocketAddr;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use std::process::Stdio;
 #[cfg(all(unix, feature = "std"))]
 use std::{fs::File, os::unix::io::AsRawFd};
+#[cfg(feature = "std")]
+use std::{net::SocketAddr, path::PathBuf};
 
 #[cfg(all(unix, feature = "std", feature = "fork"))]
 use libafl_bolts::llmp::Broker;
@@ -72,7 +72,7 @@ use crate::{
         EventConfig,
     },
     monitors::Monitor,
-    state::{HasExecutions, State},
+    state::{HasExecutions, State, StateCheckpointer},
     Error,
 };
 
@@ -144,6 +144,11 @@ pub struct Launcher<'a, CF, MT, SP> {
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// Resume each client from the latest checkpoint in its subdirectory of this directory, if any.
+    /// Clients write these checkpoints with a [`crate::stages::CheckpointStage`], using
+    /// [`StateCheckpointer::for_client`] with this directory and their [`CoreId`].
+    #[builder(default = None)]
+    checkpoint_dir: Option<PathBuf>,
 }
 
 impl<CF, MT, SP> Debug for Launcher<'_, CF, MT, SP> {
@@ -154,7 +159,8 @@ impl<CF, MT, SP> Debug for Launcher<'_, CF, MT, SP> {
             .field("broker_port", &self.broker_port)
             .field("core", &self.cores)
             .field("spawn_broker", &self.spawn_broker)
-            .field("remote_broker_addr", &self.remote_broker_addr);
+            .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("checkpoint_dir", &self.checkpoint_dir);
         #[cfg(all(unix, feature = "std"))]
         {
             dbg_struct
@@ -199,6 +205,14 @@ where
     MT: Monitor + Clone,
     SP: ShMemProvider,
 {
+    /// The [`StateCheckpointer`] to resume the client on `core_id` from, if a `checkpoint_dir` is set
+    fn client_checkpointer(&self, core_id: CoreId) -> Result<Option<StateCheckpointer>, Error> {
+        self.checkpoint_dir
+            .as_ref()
+            .map(|dir| StateCheckpointer::for_client(dir, core_id))
+            .transpose()
+    }
+
     /// Launch the broker and the clients and fuzz with a user-supplied hook
     #[cfg(all(unix, feature = "std", feature = "fork"))]
     #[allow(clippy::similar_names)]
@@ -282,6 +296,7 @@ where
                             })
                             .configuration(self.configuration)
                             .serialize_state(self.serialize_state)
+                            .checkpointer(self.client_checkpointer(*bind_to)?)
                             .hooks(hooks);
                         let builder = builder.time_ref(self.time_ref.clone());
                         let (state, mgr) = builder.build().launch()?;
@@ -362,6 +377,7 @@ where
                     })
                     .configuration(self.configuration)
                     .serialize_state(self.serialize_state)
+                    .checkpointer(self.client_checkpointer(CoreId(core_id))?)
                     .hooks(hooks);
 
                 let builder = builder.time_ref(self.time_ref.clone());
@@ -9,13 +9,13 @@ use core::{marker::PhantomData, time::Duration};
 use std::net::TcpStream;
 
 #[cfg(feature = "llmp_compression")]
+use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
 use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
-};
-use libafl_bolts::{
-    current_time,
-    llmp::{LlmpClient, LlmpClientDescription},
+    current_time, hash_std,
+    llmp::{
+        LlmpBackpressurePolicy, LlmpClient, LlmpClientDescription, LlmpPriority,
+        LLMP_FLAG_INITIALIZED,
+    },
     shmem::{NopShMemProvider, ShMemProvider},
     tuples::Handle,
     ClientId,
@@ -38,7 +38,7 @@ use crate::{
     },
     executors::{Executor, HasObservers},
     fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor},
-    inputs::{NopInput, UsesInput},
+    inputs::{Input, NopInput, UsesInput},
     observers::{ObserversTuple, TimeObserver, UsesObservers},
     state::{HasExecutions, HasImported, HasLastReportTime, NopState, State, UsesState},
     Error, HasMetadata,
@@ -90,6 +90,8 @@ pub struct LlmpEventManagerBuilder<EMH> {
     throttle: Option<Duration>,
     hooks: EMH,
     always_interesting: bool,
+    out_budget: Option<usize>,
+    backpressure_policy: LlmpBackpressurePolicy,
 }
 
 impl Default for LlmpEventManagerBuilder<()> {
@@ -106,6 +108,8 @@ impl LlmpEventManagerBuilder<()> {
             throttle: None,
             hooks: (),
             always_interesting: false,
+            out_budget: None,
+            backpressure_policy: LlmpBackpressurePolicy::Send,
         }
     }
 
@@ -115,6 +119,8 @@ impl LlmpEventManagerBuilder<()> {
             throttle: self.throttle,
             hooks,
             always_interesting: self.always_interesting,
+            out_budget: self.out_budget,
+            backpressure_policy: self.backpressure_policy,
         }
     }
 
@@ -125,6 +131,8 @@ impl LlmpEventManagerBuilder<()> {
             throttle: self.throttle,
             hooks: self.hooks,
             always_interesting,
+            out_budget: self.out_budget,
+            backpressure_policy: self.backpressure_policy,
         }
     }
 }
@@ -137,10 +145,25 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         self
     }
 
+    /// Limit the unread bytes on our outgoing llmp pages, after which low priority events,
+    /// such as stats, are handled according to the [`Self::backpressure_policy`].
+    #[must_use]
+    pub fn out_budget(mut self, out_budget: usize) -> Self {
+        self.out_budget = Some(out_budget);
+        self
+    }
+
+    /// What to do with low priority events, such as stats, while over the [`Self::out_budget`]
+    #[must_use]
+    pub fn backpressure_policy(mut self, backpressure_policy: LlmpBackpressurePolicy) -> Self {
+        self.backpressure_policy = backpressure_policy;
+        self
+    }
+
     /// Create a manager from a raw LLMP client
     pub fn build_from_client<S, SP>(
         self,
-        llmp: LlmpClient<SP>,
+        mut llmp: LlmpClient<SP>,
         configuration: EventConfig,
         time_ref: Option<Handle<TimeObserver>>,
     ) -> Result<LlmpEventManager<EMH, S, SP>, Error>
@@ -148,6 +171,9 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         SP: ShMemProvider,
         S: State,
     {
+        llmp.sender_mut().set_out_budget(self.out_budget);
+        llmp.sender_mut()
+            .set_backpressure_policy(self.backpressure_policy);
         Ok(LlmpEventManager {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
@@ -184,23 +210,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         S: State,
     {
         let llmp = LlmpClient::create_attach_to_tcp(shmem_provider, port)?;
-        Ok(LlmpEventManager {
-            throttle: self.throttle,
-            last_sent: Duration::from_secs(0),
-            hooks: self.hooks,
-            always_interesting: self.always_interesting,
-            llmp,
-            #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
-            configuration,
-            serialization_time: Duration::ZERO,
-            deserialization_time: Duration::ZERO,
-            serializations_cnt: 0,
-            should_serialize_cnt: 0,
-            time_ref,
-            phantom: PhantomData,
-            custom_buf_handlers: vec![],
-        })
+        Self::build_from_client(self, llmp, configuration, time_ref)
     }
 
     /// If a client respawns, it may reuse the existing connection, previously
@@ -218,23 +228,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         S: State,
     {
         let llmp = LlmpClient::on_existing_from_env(shmem_provider, env_name)?;
-        Ok(LlmpEventManager {
-            throttle: self.throttle,
-            last_sent: Duration::from_secs(0),
-            hooks: self.hooks,
-            always_interesting: self.always_interesting,
-            llmp,
-            #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
-            configuration,
-            serialization_time: Duration::ZERO,
-            deserialization_time: Duration::ZERO,
-            serializations_cnt: 0,
-            should_serialize_cnt: 0,
-            time_ref,
-            phantom: PhantomData,
-            custom_buf_handlers: vec![],
-        })
+        Self::build_from_client(self, llmp, configuration, time_ref)
     }
 
     /// Create an existing client from description
@@ -250,23 +244,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         S: State,
     {
         let llmp = LlmpClient::existing_client_from_description(shmem_provider, description)?;
-        Ok(LlmpEventManager {
-            throttle: self.throttle,
-            last_sent: Duration::from_secs(0),
-            hooks: self.hooks,
-            always_interesting: self.always_interesting,
-            llmp,
-            #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
-            configuration,
-            serialization_time: Duration::ZERO,
-            deserialization_time: Duration::ZERO,
-            serializations_cnt: 0,
-            should_serialize_cnt: 0,
-            time_ref,
-            phantom: PhantomData,
-            custom_buf_handlers: vec![],
-        })
+        Self::build_from_client(self, llmp, configuration, time_ref)
     }
 }
 
@@ -491,6 +469,36 @@ impl<EMH, S: State, SP: ShMemProvider> LlmpEventManager<EMH, S, SP> {
     pub fn send_exiting(&mut self) -> Result<(), Error> {
         self.llmp.sender_mut().send_exiting()
     }
+
+    /// Record the queue depth of our outgoing llmp pages in the [`crate::monitors::ScalabilityMonitor`]
+    #[cfg(feature = "scalability_introspection")]
+    fn update_scalability_monitor(&self, state: &mut S) {
+        let sender = self.llmp.sender();
+        let monitor = state.scalability_monitor_mut();
+        monitor.llmp_pending_bytes = sender.pending_bytes();
+        monitor.llmp_dropped_msgs = sender.dropped_msgs();
+        monitor.llmp_coalesced_msgs = sender.coalesced_msgs();
+    }
+
+    #[cfg(not(feature = "scalability_introspection"))]
+    #[allow(clippy::unused_self)]
+    fn update_scalability_monitor(&self, _state: &mut S) {}
+}
+
+/// The llmp priority of an [`Event`], and its key for coalescing, if it may be coalesced.
+/// Objectives and control messages go first, stats may be coalesced or dropped under load.
+fn llmp_priority<I>(event: &Event<I>) -> (LlmpPriority, Option<u64>)
+where
+    I: Input,
+{
+    match event {
+        Event::Objective { .. } | Event::Stop => (LlmpPriority::High, None),
+        Event::UpdateExecStats { .. } => (LlmpPriority::Low, Some(0)),
+        Event::UpdateUserStats { name, .. } => (LlmpPriority::Low, Some(hash_std(name.as_bytes()))),
+        #[cfg(feature = "introspection")]
+        Event::UpdatePerfMonitor { .. } => (LlmpPriority::Low, Some(1)),
+        _ => (LlmpPriority::Normal, None),
+    }
 }
 
 impl<EMH, S, SP> UsesState for LlmpEventManager<EMH, S, SP>
@@ -517,25 +525,35 @@ where
     #[cfg(feature = "llmp_compression")]
     fn fire(
         &mut self,
-        _state: &mut Self::State,
+        state: &mut Self::State,
         event: Event<<Self::State as UsesInput>::Input>,
     ) -> Result<(), Error> {
+        let (priority, coalesce_key) = llmp_priority(&event);
         let serialized = postcard::to_allocvec(&event)?;
         let flags = LLMP_FLAG_INITIALIZED;
 
         match self.compressor.maybe_compress(&serialized) {
             Some(comp_buf) => {
-                self.llmp.send_buf_with_flags(
+                self.llmp.send_buf_prioritized(
                     LLMP_TAG_EVENT_TO_BOTH,
                     flags | LLMP_FLAG_COMPRESSED,
+                    priority,
+                    coalesce_key,
                     &comp_buf,
                 )?;
             }
             None => {
-                self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
+                self.llmp.send_buf_prioritized(
+                    LLMP_TAG_EVENT_TO_BOTH,
+                    flags,
+                    priority,
+                    coalesce_key,
+                    &serialized,
+                )?;
             }
         }
         self.last_sent = current_time();
+        self.update_scalability_monitor(state);
 
         Ok(())
     }
@@ -543,11 +561,19 @@ where
     #[cfg(not(feature = "llmp_compression"))]
     fn fire(
         &mut self,
-        _state: &mut Self::State,
+        state: &mut Self::State,
         event: Event<<Self::State as UsesInput>::Input>,
     ) -> Result<(), Error> {
+        let (priority, coalesce_key) = llmp_priority(&event);
         let serialized = postcard::to_allocvec(&event)?;
-        self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
+        self.llmp.send_buf_prioritized(
+            LLMP_TAG_EVENT_TO_BOTH,
+            LLMP_FLAG_INITIALIZED,
+            priority,
+            coalesce_key,
+            &serialized,
+        )?;
+        self.update_scalability_monitor(state);
         Ok(())
     }
 
@@ -598,6 +624,9 @@ where
         state: &mut Self::State,
         executor: &mut E,
     ) -> Result<usize, Error> {
+        // Send out stats we held back, if the broker caught up in the meantime
+        self.llmp.sender_mut().flush_coalesced()?;
+
         // TODO: Get around local event copy by moving handle_in_client
         let self_id = self.llmp.sender().id();
         let mut count = 0;
@@ -39,6 +39,8 @@ use typed_builder::TypedBuilder;
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 #[cfg(feature = "std")]
 use crate::events::{AdaptiveSerializer, CustomBufEventResult, HasCustomBufHandlers};
+#[cfg(feature = "std")]
+use crate::state::StateCheckpointer;
 use crate::{
     events::{
         Event, EventConfig, EventFirer, EventManager, EventManagerHooksTuple, EventManagerId,
@@ -442,6 +444,10 @@ pub struct RestartingMgr<EMH, MT, S, SP> {
     hooks: EMH,
     #[builder(default = None)]
     time_ref: Option<Handle<TimeObserver>>,
+    /// Resume from the latest checkpoint of this [`StateCheckpointer`] on the first run, if there is one.
+    /// Restarts after a crash still restore the state from the previous client instead.
+    #[builder(default = None)]
+    checkpointer: Option<StateCheckpointer>,
     #[builder(setter(skip), default = PhantomData)]
     phantom_data: PhantomData<(EMH, S)>,
 }
@@ -680,6 +686,16 @@ where
                 )
             } else {
                 log::info!("First run. Let's set it all up");
+                let state_opt = match &self.checkpointer {
+                    Some(checkpointer) => {
+                        let state_opt = checkpointer.load()?;
+                        if state_opt.is_some() {
+                            log::info!("Resuming from checkpoint in {:?}", checkpointer.dir());
+                        }
+                        state_opt
+                    }
+                    None => None,
+                };
                 // Mgr to send and receive msgs from/to all other fuzzer instances
                 let mgr = LlmpEventManager::builder()
                     .hooks(self.hooks)
@@ -691,7 +707,7 @@ where
                     )?;
 
                 (
-                    None,
+                    state_opt,
                     LlmpRestartingEventManager::with_save_state(
                         mgr,
                         staterestorer,
@@ -843,4 +859,96 @@ mod tests {
             )
             .unwrap();
     }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_mgr_checkpoint_resume() {
+        use core::time::Duration;
+        use std::{env, fs, process};
+
+        use crate::{
+            events::llmp::restarting::{RestartingMgr, _ENV_FUZZER_BROKER_CLIENT_INITIAL},
+            monitors::NopMonitor,
+            stages::CheckpointStage,
+            state::{HasCorpus, HasExecutions, StateCheckpointer},
+        };
+
+        let dir = env::temp_dir().join(format!("libafl_checkpoint_resume_test_{}", process::id()));
+        let checkpointer = StateCheckpointer::new(&dir).unwrap();
+
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus.add(Testcase::new(vec![0; 4].into())).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::<BytesInput>::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let mut llmp_client = LlmpClient::new(
+            shmem_provider.clone(),
+            LlmpSharedMap::new(ClientId(0), shmem_provider.new_shmem(1024).unwrap()),
+            ClientId(0),
+        )
+        .unwrap();
+        // A little hack for CI. Don't do that in a real-world scenario.
+        unsafe {
+            llmp_client.mark_safe_to_unmap();
+        }
+        let mut llmp_mgr = LlmpEventManager::builder()
+            .build_from_client(llmp_client, "fuzzer".into(), None)
+            .unwrap();
+
+        let mut fuzzer = StdFuzzer::new(
+            RandScheduler::new(),
+            ConstFeedback::new(false),
+            ConstFeedback::new(false),
+        );
+        let mut harness = |_buf: &BytesInput| ExitKind::Ok;
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut llmp_mgr,
+        )
+        .unwrap();
+
+        // Write the checkpoint after fuzzing, through the stage
+        let mut stages = tuple_list!(
+            StdMutationalStage::new(BitFlipMutator::new()),
+            CheckpointStage::new(checkpointer.clone(), Duration::ZERO)
+        );
+        fuzzer
+            .fuzz_one(&mut stages, &mut executor, &mut state, &mut llmp_mgr)
+            .unwrap();
+        assert!(*state.executions() > 0);
+
+        // Pretend to be a freshly spawned client on its first run
+        llmp_mgr.to_env(_ENV_FUZZER_BROKER_CLIENT_INITIAL);
+        let staterestorer =
+            StateRestorer::<StdShMemProvider>::new(shmem_provider.new_shmem(1024 * 1024).unwrap());
+        staterestorer.write_to_env(_ENV_FUZZER_SENDER).unwrap();
+
+        let (restored, _mgr) = RestartingMgr::<_, NopMonitor, _, _>::builder()
+            .shmem_provider(shmem_provider)
+            .configuration("fuzzer".into())
+            .hooks(tuple_list!())
+            .checkpointer(Some(checkpointer))
+            .build()
+            .launch()
+            .unwrap();
+        let restored: StdState<BytesInput, InMemoryCorpus<_>, StdRand, InMemoryCorpus<_>> =
+            restored.expect("The checkpoint was not resumed");
+        assert_eq!(restored.executions(), state.executions());
+        assert_eq!(restored.corpus().count(), state.corpus().count());
+
+        env::remove_var(_ENV_FUZZER_SENDER);
+        env::remove_var(_ENV_FUZZER_BROKER_CLIENT_INITIAL);
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -534,6 +534,26 @@ where
                     phantom: PhantomData,
                 },
             )?;
+
+            let scalability_monitor = state.scalability_monitor();
+            let queue_stats = [
+                (
+                    "llmp pending bytes",
+                    scalability_monitor.llmp_pending_bytes as u64,
+                ),
+                ("llmp dropped", scalability_monitor.llmp_dropped_msgs),
+                ("llmp coalesced", scalability_monitor.llmp_coalesced_msgs),
+            ];
+            for (name, value) in queue_stats {
+                self.fire(
+                    state,
+                    Event::UpdateUserStats {
+                        name: Cow::from(name),
+                        value: UserStats::new(UserStatsValue::Number(value), AggregatorOps::Max),
+                        phantom: PhantomData,
+                    },
+                )?;
+            }
         }
 
         *state.last_report_time_mut() = Some(cur);
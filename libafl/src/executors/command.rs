@@ -26,7 +26,7 @@ use libafl_bolts::{
 #[cfg(all(feature = "std", unix))]
 use crate::executors::{Executor, ExitKind};
 use crate::{
-    executors::HasObservers,
+    executors::{HasObservers, HasTimeout, ReportsTimeouts},
     inputs::{HasTargetBytes, UsesInput},
     observers::{ObserversTuple, StdErrObserver, StdOutObserver, UsesObservers},
     state::{HasExecutions, State, UsesState},
@@ -312,6 +312,20 @@ where
     }
 }
 
+impl<OT, S> HasTimeout for CommandExecutor<OT, S, StdCommandConfigurator> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.configurer.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.configurer.timeout = timeout;
+    }
+}
+
+impl<OT, S> ReportsTimeouts for CommandExecutor<OT, S, StdCommandConfigurator> {}
+
 /// The builder for a default [`CommandExecutor`] that should fit most use-cases.
 #[derive(Debug, Clone)]
 pub struct CommandExecutorBuilder {
@@ -1,14 +1,16 @@
 //! Executor for differential fuzzing.
 //! It wraps two executors that will be run after each other with the same input.
 //! In comparison to the [`crate::executors::CombinedExecutor`] it also runs the secondary executor in `run_target`.
+//! The [`MultiDiffExecutor`] does the same for any number of executors.
 //!
+use alloc::vec::Vec;
 use core::{cell::UnsafeCell, fmt::Debug, ptr};
 
 use libafl_bolts::{
     ownedref::OwnedMutPtr,
     tuples::{MatchName, RefIndexable},
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     executors::{Executor, ExitKind, HasObservers},
@@ -242,3 +244,392 @@ where
         }
     }
 }
+
+/// A tuple of executors run by a [`MultiDiffExecutor`]
+pub trait DiffExecutorsTuple {
+    /// The tuple of pointers to the observers of each executor
+    type ObserversPtrs: ObserversPtrsTuple;
+
+    /// The number of executors in this tuple
+    const LEN: usize;
+
+    /// Null pointers, set before the observers are used
+    fn null_observers_ptrs() -> Self::ObserversPtrs;
+
+    /// Pointers to the observers of each executor
+    fn observers_ptrs(&self) -> Self::ObserversPtrs;
+
+    /// Pointers to the observers of each executor, for mutable access
+    fn observers_ptrs_mut(&mut self) -> Self::ObserversPtrs;
+}
+
+impl DiffExecutorsTuple for () {
+    type ObserversPtrs = ();
+
+    const LEN: usize = 0;
+
+    fn null_observers_ptrs() -> Self::ObserversPtrs {}
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {}
+
+    fn observers_ptrs_mut(&mut self) -> Self::ObserversPtrs {}
+}
+
+impl<Head, Tail> DiffExecutorsTuple for (Head, Tail)
+where
+    Head: HasObservers,
+    Tail: DiffExecutorsTuple,
+{
+    type ObserversPtrs = (OwnedMutPtr<Head::Observers>, Tail::ObserversPtrs);
+
+    const LEN: usize = Tail::LEN + 1;
+
+    fn null_observers_ptrs() -> Self::ObserversPtrs {
+        (
+            OwnedMutPtr::Ptr(ptr::null_mut()),
+            Tail::null_observers_ptrs(),
+        )
+    }
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {
+        (
+            OwnedMutPtr::Ptr(ptr::from_ref(&*self.0.observers()) as *mut Head::Observers),
+            self.1.observers_ptrs(),
+        )
+    }
+
+    fn observers_ptrs_mut(&mut self) -> Self::ObserversPtrs {
+        (
+            OwnedMutPtr::Ptr(ptr::from_mut(&mut *self.0.observers_mut())),
+            self.1.observers_ptrs_mut(),
+        )
+    }
+}
+
+/// A tuple of executors that can all be run on the same input
+pub trait ExecutorsTuple<EM, Z>: DiffExecutorsTuple
+where
+    EM: UsesState,
+{
+    /// Run every executor on `input`, each one wrapped in the pre and post exec hooks of its
+    /// observers. The exit kinds are appended to `exit_kinds`, in order.
+    fn run_target_all(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut EM::State,
+        mgr: &mut EM,
+        input: &<EM::State as UsesInput>::Input,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error>;
+}
+
+impl<EM, Z> ExecutorsTuple<EM, Z> for ()
+where
+    EM: UsesState,
+{
+    fn run_target_all(
+        &mut self,
+        _fuzzer: &mut Z,
+        _state: &mut EM::State,
+        _mgr: &mut EM,
+        _input: &<EM::State as UsesInput>::Input,
+        _exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<EM, Head, Tail, Z> ExecutorsTuple<EM, Z> for (Head, Tail)
+where
+    EM: UsesState<State = Head::State>,
+    Head: Executor<EM, Z> + HasObservers,
+    Tail: ExecutorsTuple<EM, Z>,
+    Z: UsesState<State = Head::State>,
+{
+    fn run_target_all(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut EM::State,
+        mgr: &mut EM,
+        input: &<EM::State as UsesInput>::Input,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        self.0.observers_mut().pre_exec_all(state, input)?;
+        let ret = self.0.run_target(fuzzer, state, mgr, input)?;
+        self.0.observers_mut().post_exec_all(state, input, &ret)?;
+        exit_kinds.push(ret);
+        self.1.run_target_all(fuzzer, state, mgr, input, exit_kinds)
+    }
+}
+
+/// A tuple of pointers to the observers of the executors of a [`MultiDiffExecutor`]
+pub trait ObserversPtrsTuple {
+    /// Get the first observer with the given type and name, from any of the executors
+    fn match_name_any<T>(&self, name: &str) -> Option<&T>;
+
+    /// Get the first observer with the given type and name, from any of the executors
+    fn match_name_any_mut<T>(&mut self, name: &str) -> Option<&mut T>;
+}
+
+impl ObserversPtrsTuple for () {
+    fn match_name_any<T>(&self, _name: &str) -> Option<&T> {
+        None
+    }
+
+    fn match_name_any_mut<T>(&mut self, _name: &str) -> Option<&mut T> {
+        None
+    }
+}
+
+impl<Head, Tail> ObserversPtrsTuple for (OwnedMutPtr<Head>, Tail)
+where
+    Head: MatchName,
+    Tail: ObserversPtrsTuple,
+{
+    #[allow(deprecated)]
+    fn match_name_any<T>(&self, name: &str) -> Option<&T> {
+        if let Some(t) = self.0.as_ref().match_name::<T>(name) {
+            Some(t)
+        } else {
+            self.1.match_name_any::<T>(name)
+        }
+    }
+
+    #[allow(deprecated)]
+    fn match_name_any_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        if let Some(t) = self.0.as_mut().match_name_mut::<T>(name) {
+            Some(t)
+        } else {
+            self.1.match_name_any_mut::<T>(name)
+        }
+    }
+}
+
+/// A [`MultiDiffExecutor`] runs every executor of a tuple, one after the other, on the same input.
+///
+/// All executors need to have distinct observer names, so that a
+/// [`crate::feedbacks::differential::MultiDiffFeedback`] can compare them.
+/// If the exit kinds differ, [`ExitKind::Diff`] is returned, with the exit kind of the majority of
+/// executors as `primary` and the first deviating one as `secondary`.
+#[derive(Debug)]
+pub struct MultiDiffExecutor<ET, DOT>
+where
+    ET: DiffExecutorsTuple,
+{
+    executors: ET,
+    exit_kinds: Vec<ExitKind>,
+    observers: UnsafeCell<MultiProxyObserversTuple<ET::ObserversPtrs, DOT>>,
+}
+
+impl<ET, DOT> MultiDiffExecutor<ET, DOT>
+where
+    ET: DiffExecutorsTuple,
+{
+    /// Create a new `MultiDiffExecutor`, wrapping the given tuple of `executors`.
+    ///
+    /// The `observers` are run once around all executors.
+    pub fn new(executors: ET, observers: DOT) -> Self {
+        Self {
+            executors,
+            exit_kinds: Vec::with_capacity(ET::LEN),
+            observers: UnsafeCell::new(MultiProxyObserversTuple {
+                executors: ET::null_observers_ptrs(),
+                differential: observers,
+            }),
+        }
+    }
+
+    /// Retrieve the wrapped executors.
+    pub fn executors(&mut self) -> &mut ET {
+        &mut self.executors
+    }
+
+    /// The exit kinds of each executor during the last run, in order.
+    #[must_use]
+    pub fn exit_kinds(&self) -> &[ExitKind] {
+        &self.exit_kinds
+    }
+}
+
+/// Merge the exit kinds of several executors, see [`MultiDiffExecutor`]
+fn merge_exit_kinds(exit_kinds: &[ExitKind]) -> ExitKind {
+    let Some(first) = exit_kinds.first() else {
+        return ExitKind::Ok;
+    };
+    if exit_kinds.iter().all(|kind| kind == first) {
+        return *first;
+    }
+    // On ties, the exit kind seen first wins
+    let count = |kind: &ExitKind| exit_kinds.iter().filter(|k| *k == kind).count();
+    let mut majority = *first;
+    let mut majority_count = count(first);
+    for kind in exit_kinds {
+        let kind_count = count(kind);
+        if kind_count > majority_count {
+            majority = *kind;
+            majority_count = kind_count;
+        }
+    }
+    let minority = *exit_kinds.iter().find(|kind| **kind != majority).unwrap();
+    ExitKind::Diff {
+        primary: majority.into(),
+        secondary: minority.into(),
+    }
+}
+
+impl<ET, DOT, EM, Z> Executor<EM, Z> for MultiDiffExecutor<ET, DOT>
+where
+    Self: UsesState,
+    ET: ExecutorsTuple<EM, Z>,
+    EM: UsesState<State = <Self as UsesState>::State>,
+    Z: UsesState<State = <Self as UsesState>::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        self.exit_kinds.clear();
+        self.executors
+            .run_target_all(fuzzer, state, mgr, input, &mut self.exit_kinds)?;
+        Ok(merge_exit_kinds(&self.exit_kinds))
+    }
+}
+
+/// Proxy the observers of the inner executors of a [`MultiDiffExecutor`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "OP: Serialize + DeserializeOwned, DOT: Serialize + DeserializeOwned")]
+pub struct MultiProxyObserversTuple<OP, DOT> {
+    executors: OP,
+    differential: DOT,
+}
+
+impl<OP, DOT, S> ObserversTuple<S> for MultiProxyObserversTuple<OP, DOT>
+where
+    OP: ObserversPtrsTuple,
+    DOT: ObserversTuple<S>,
+    S: UsesInput,
+{
+    fn pre_exec_all(&mut self, state: &mut S, input: &S::Input) -> Result<(), Error> {
+        self.differential.pre_exec_all(state, input)
+    }
+
+    fn post_exec_all(
+        &mut self,
+        state: &mut S,
+        input: &S::Input,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.differential.post_exec_all(state, input, exit_kind)
+    }
+
+    fn pre_exec_child_all(&mut self, state: &mut S, input: &S::Input) -> Result<(), Error> {
+        self.differential.pre_exec_child_all(state, input)
+    }
+
+    fn post_exec_child_all(
+        &mut self,
+        state: &mut S,
+        input: &S::Input,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.differential
+            .post_exec_child_all(state, input, exit_kind)
+    }
+}
+
+impl<OP, DOT> MatchName for MultiProxyObserversTuple<OP, DOT>
+where
+    OP: ObserversPtrsTuple,
+    DOT: MatchName,
+{
+    #[allow(deprecated)]
+    fn match_name<T>(&self, name: &str) -> Option<&T> {
+        if let Some(t) = self.executors.match_name_any::<T>(name) {
+            Some(t)
+        } else {
+            self.differential.match_name::<T>(name)
+        }
+    }
+
+    #[allow(deprecated)]
+    fn match_name_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        if let Some(t) = self.executors.match_name_any_mut::<T>(name) {
+            Some(t)
+        } else {
+            self.differential.match_name_mut::<T>(name)
+        }
+    }
+}
+
+impl<Head, Tail, DOT> UsesState for MultiDiffExecutor<(Head, Tail), DOT>
+where
+    Head: UsesState,
+    (Head, Tail): DiffExecutorsTuple,
+{
+    type State = Head::State;
+}
+
+impl<Head, Tail, DOT> UsesObservers for MultiDiffExecutor<(Head, Tail), DOT>
+where
+    Head: UsesState,
+    (Head, Tail): DiffExecutorsTuple,
+    DOT: ObserversTuple<Head::State>,
+{
+    type Observers =
+        MultiProxyObserversTuple<<(Head, Tail) as DiffExecutorsTuple>::ObserversPtrs, DOT>;
+}
+
+impl<Head, Tail, DOT> HasObservers for MultiDiffExecutor<(Head, Tail), DOT>
+where
+    Head: UsesState,
+    (Head, Tail): DiffExecutorsTuple,
+    DOT: ObserversTuple<Head::State>,
+{
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        unsafe {
+            self.observers.get().as_mut().unwrap().executors = self.executors.observers_ptrs();
+            RefIndexable::from(self.observers.get().as_ref().unwrap())
+        }
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        let executors = self.executors.observers_ptrs_mut();
+        let observers = self.observers.get_mut();
+        observers.executors = executors;
+        RefIndexable::from(observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_exit_kinds;
+    use crate::executors::{DiffExitKind, ExitKind};
+
+    #[test]
+    fn test_merge_exit_kinds() {
+        assert_eq!(merge_exit_kinds(&[]), ExitKind::Ok);
+        assert_eq!(
+            merge_exit_kinds(&[ExitKind::Crash, ExitKind::Crash]),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            merge_exit_kinds(&[ExitKind::Ok, ExitKind::Crash, ExitKind::Crash]),
+            ExitKind::Diff {
+                primary: DiffExitKind::Crash,
+                secondary: DiffExitKind::Ok,
+            }
+        );
+        assert_eq!(
+            merge_exit_kinds(&[ExitKind::Timeout, ExitKind::Ok]),
+            ExitKind::Diff {
+                primary: DiffExitKind::Timeout,
+                secondary: DiffExitKind::Ok,
+            }
+        );
+    }
+}
@@ -1,24 +1,32 @@
 //! Expose an `Executor` based on a `Forkserver` in order to execute AFL/AFL++ binaries
 
-use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
+use alloc::{
+    borrow::ToOwned,
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
 use core::{
-    fmt::{self, Debug, Formatter},
+    fmt::{self, Debug, Formatter, Write as _},
     marker::PhantomData,
     time::Duration,
 };
 use std::{
     env,
     ffi::{OsStr, OsString},
+    fs,
     io::{self, prelude::*, ErrorKind},
     os::{
         fd::{AsRawFd, BorrowedFd},
-        unix::{io::RawFd, process::CommandExt},
+        unix::{fs::FileExt, io::RawFd, process::CommandExt},
     },
-    path::Path,
-    process::{Child, Command, Stdio},
+    path::{Path, PathBuf},
+    process::{Child, ChildStderr, Command, Stdio},
 };
 
 use libafl_bolts::{
+    current_nanos, current_time,
     fs::{get_unique_std_input_file, InputFile},
     os::{dup2, pipes::Pipe},
     ownedref::OwnedSlice,
@@ -39,10 +47,13 @@ use nix::{
 #[cfg(feature = "regex")]
 use crate::observers::{get_asan_runtime_flags_with_log_path, AsanBacktraceObserver};
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{BatchExecutor, Executor, ExitKind, HasObservers, HasTimeout, ReportsTimeouts},
     inputs::{HasTargetBytes, Input, UsesInput},
     mutators::Tokens,
-    observers::{MapObserver, Observer, ObserversTuple, UsesObservers},
+    observers::{
+        CoreDumpObserver, MapObserver, MemoryObserver, Observer, ObserversTuple, StdErrObserver,
+        TimeObserver, UsesObservers,
+    },
     state::{HasExecutions, State, UsesState},
     Error,
 };
@@ -51,14 +62,46 @@ const FORKSRV_FD: i32 = 198;
 #[allow(clippy::cast_possible_wrap)]
 const FS_NEW_ERROR: i32 = 0xeffe0000_u32 as i32;
 
+/// The error reported by targets speaking the old protocol, with the `FS_ERROR_*` in bits 8 to 23
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_ERROR: i32 = 0xf800008f_u32 as i32;
+
+/// The hello of `AFL++` targets, "AFL" followed by the protocol version
+const FS_AFLPP_VERSION_BASE: u32 = 0x41464c00;
+/// The hello of `LibAFL` targets speaking version 2 or later, "LIB" followed by the protocol version.
+///
+/// The `LibAFL` options are only valid after this hello, so that they never clash with new options of `AFL++`.
+const FS_LIBAFL_VERSION_BASE: u32 = 0x4c494200;
 const FS_NEW_VERSION_MIN: u32 = 1;
-const FS_NEW_VERSION_MAX: u32 = 1;
+/// The highest version of `AFL++` targets
+const FS_AFLPP_VERSION_MAX: u32 = 1;
+/// Version 2 adds the `LibAFL` options `MAP_ADDR`, `CMPLOG` and `EXTENSIONS`, and the fuzzer acknowledges the options
+const FS_NEW_VERSION_MAX: u32 = 2;
+/// The first version announced with the [`FS_LIBAFL_VERSION_BASE`]
+const FS_LIBAFL_VERSION_MIN: u32 = 2;
 #[allow(clippy::cast_possible_wrap)]
 const FS_NEW_OPT_MAPSIZE: i32 = 1_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_NEW_OPT_SHDMEM_FUZZ: i32 = 2_u32 as i32;
+/// The target maps the coverage map at a fixed address, parameter: 64 bit address (version 2)
+#[allow(clippy::cast_possible_wrap)]
+const FS_NEW_OPT_MAP_ADDR: i32 = 4_u32 as i32;
+/// The target logs comparisons to the map in `__AFL_CMPLOG_SHM_ID`, parameter: 32 bit map size (version 2)
+#[allow(clippy::cast_possible_wrap)]
+const FS_NEW_OPT_CMPLOG: i32 = 8_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_NEW_OPT_AUTODICT: i32 = 0x00000800_u32 as i32;
+/// The target supports [`ForkserverExtension`]s, parameter: 32 bit count, then the 32 bit ids (version 2)
+#[allow(clippy::cast_possible_wrap)]
+const FS_NEW_OPT_EXTENSIONS: i32 = 0x00001000_u32 as i32;
+/// The maximum number of extensions a target may announce
+const FS_EXTENSIONS_MAX: u32 = 256;
+
+/// Tells `LibAFL` targets the highest protocol version we speak, as `AFL++` only knows version 1
+const FS_VERSION_ENV: &str = "__LIBAFL_FORKSERVER_VERSION";
+
+/// The id of the [`TimeoutExtension`]
+pub const FS_EXT_TIMEOUT: u32 = 1;
 
 #[allow(clippy::cast_possible_wrap)]
 const FS_ERROR_MAP_SIZE: i32 = 1_u32 as i32;
@@ -75,28 +118,40 @@ const FS_ERROR_OLD_CMPLOG: i32 = 32_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_ERROR_OLD_CMPLOG_QEMU: i32 = 64_u32 as i32;
 
+/// The likely cause for each of the `FS_ERROR_*` bits reported by the target
+const FS_ERROR_CAUSES: [(i32, &str); 7] = [
+    (FS_ERROR_MAP_SIZE, "AFL_MAP_SIZE is not set and fuzzing target reports that the required size is very large. Solution: Run the fuzzing target stand-alone with the environment variable AFL_DEBUG=1 set and set the value for __afl_final_loc in the AFL_MAP_SIZE environment variable for afl-fuzz."),
+    (FS_ERROR_MAP_ADDR, "the fuzzing target reports that hardcoded map address might be the reason the mmap of the shared memory failed. Solution: recompile the target with either afl-clang-lto and do not set AFL_LLVM_MAP_ADDR or recompile with afl-clang-fast."),
+    (FS_ERROR_SHM_OPEN, "the fuzzing target reports that the shm_open() call failed."),
+    (FS_ERROR_SHMAT, "the fuzzing target reports that the shmat() call failed."),
+    (FS_ERROR_MMAP, "the fuzzing target reports that the mmap() call to the shared memory failed."),
+    (FS_ERROR_OLD_CMPLOG, "the -c cmplog target was instrumented with an too old AFL++ version, you need to recompile it."),
+    (FS_ERROR_OLD_CMPLOG_QEMU, "The AFL++ QEMU/FRIDA loaders are from an older version, for -c you need to recompile it."),
+];
+
+/// The likely causes of a forkserver that did not answer the initial handshake
+const FS_STARTUP_FAILURE_CAUSES: [&str; 5] = [
+    "the target is not instrumented with a forkserver (compile it with afl-cc or libafl_cc, or run it in an instrumenting emulator)",
+    "the target crashed or exited during startup, before the forkserver was up (run it stand-alone, or set `debug_child` to see its output)",
+    "the target could not map the coverage map: `__AFL_SHM_ID` is not set, or the map is too small for the target (set AFL_MAP_SIZE, or `coverage_map_size`)",
+    "the target ran out of memory during startup, e.g. an ASan target under a memory limit",
+    "the program path or the arguments are wrong, e.g. the input file `@@` is missing",
+];
+
 fn report_error_and_exit(status: i32) -> Result<(), Error> {
     /* Report on the error received via the forkserver controller and exit */
-    match status {
-    FS_ERROR_MAP_SIZE =>
-        Err(Error::unknown(
-            "AFL_MAP_SIZE is not set and fuzzing target reports that the required size is very large. Solution: Run the fuzzing target stand-alone with the environment variable AFL_DEBUG=1 set and set the value for __afl_final_loc in the AFL_MAP_SIZE environment variable for afl-fuzz.".to_string())),
-    FS_ERROR_MAP_ADDR =>
-        Err(Error::unknown(
-            "the fuzzing target reports that hardcoded map address might be the reason the mmap of the shared memory failed. Solution: recompile the target with either afl-clang-lto and do not set AFL_LLVM_MAP_ADDR or recompile with afl-clang-fast.".to_string())),
-    FS_ERROR_SHM_OPEN =>
-        Err(Error::unknown("the fuzzing target reports that the shm_open() call failed.".to_string())),
-    FS_ERROR_SHMAT =>
-        Err(Error::unknown("the fuzzing target reports that the shmat() call failed.".to_string())),
-    FS_ERROR_MMAP =>
-        Err(Error::unknown("the fuzzing target reports that the mmap() call to the shared memory failed.".to_string())),
-    FS_ERROR_OLD_CMPLOG =>
-        Err(Error::unknown(
-            "the -c cmplog target was instrumented with an too old AFL++ version, you need to recompile it.".to_string())),
-    FS_ERROR_OLD_CMPLOG_QEMU =>
-        Err(Error::unknown("The AFL++ QEMU/FRIDA loaders are from an older version, for -c you need to recompile it.".to_string())),
-    _ =>
-        Err(Error::unknown(format!("unknown error code {status} from fuzzing target!"))),
+    let causes: Vec<&str> = FS_ERROR_CAUSES
+        .iter()
+        .filter(|(error, _)| status & error != 0)
+        .map(|(_, cause)| *cause)
+        .collect();
+    if causes.is_empty() {
+        Err(Error::unknown(format!(
+            "unknown error code {status} from fuzzing target!"
+        )))
+    } else {
+        // Several bits can be set at once, report all of them
+        Err(Error::unknown(causes.join("\n")))
     }
 }
 
@@ -107,6 +162,336 @@ const MIN_INPUT_SIZE_DEFAULT: usize = 1;
 
 /// The default signal to use to kill child processes
 const KILL_SIGNAL_DEFAULT: Signal = Signal::SIGTERM;
+/// The default size of the captured stderr, if a [`StdErrObserver`] is set
+const STDERR_CAPTURE_SIZE_DEFAULT: usize = 64 * 1024;
+
+/// Write the input for the next run to the shared memory testcase, if used, or to the input file.
+///
+/// Inputs are truncated to `max_input_size`, or extended to `min_input_size`, like AFL++ does.
+fn write_input<SHM>(
+    input_bytes: &[u8],
+    min_input_size: usize,
+    max_input_size: usize,
+    testcase_shmem: Option<&mut SHM>,
+    input_file: &mut InputFile,
+) -> Result<(), Error>
+where
+    SHM: ShMem,
+{
+    let mut input_bytes = OwnedSlice::from(input_bytes);
+    let mut input_size = input_bytes.as_slice().len();
+    if input_size > max_input_size {
+        // Truncate like AFL++ does
+        input_size = max_input_size;
+    } else if input_size < min_input_size {
+        // Extend like AFL++ does
+        input_size = min_input_size;
+        let mut input_bytes_copy = input_bytes.as_slice().to_vec();
+        input_bytes_copy.resize(input_size, 0);
+        input_bytes = OwnedSlice::from(input_bytes_copy);
+    }
+    let input_size_in_bytes = input_size.to_ne_bytes();
+    if let Some(map) = testcase_shmem {
+        // The first four bytes declares the size of the shmem.
+        map.as_slice_mut()[..SHMEM_FUZZ_HDR_SIZE]
+            .copy_from_slice(&input_size_in_bytes[..SHMEM_FUZZ_HDR_SIZE]);
+        map.as_slice_mut()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + input_size)]
+            .copy_from_slice(&input_bytes.as_slice()[..input_size]);
+    } else {
+        input_file.write_buf(&input_bytes.as_slice()[..input_size])?;
+    }
+    Ok(())
+}
+
+/// What the executor knows about the next execution, passed to the [`ForkserverExtension`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecContext {
+    /// The current timeout of the executor
+    pub timeout: Duration,
+}
+
+/// A `LibAFL`-specific extension of the forkserver protocol, negotiated in the handshake of protocol version 2.
+///
+/// The target announces the ids of the extensions it supports, and the fuzzer accepts those it has an extension for.
+/// After each request for a new child, the fuzzer sends the payload of each accepted extension, in the order
+/// the target announced them. The length of the payload is part of the definition of the extension.
+pub trait ForkserverExtension: Debug + Send + Sync {
+    /// The id of this extension
+    fn id(&self) -> u32;
+
+    /// Append the payload for the next execution, described by `ctx`
+    fn exec_payload(&self, ctx: &ExecContext, payload: &mut Vec<u8>);
+}
+
+/// Sends the timeout of each execution to the target, as 32 bit milliseconds.
+///
+/// This is the current timeout of the executor, which follows [`HasTimeout::set_timeout`], e.g. by a
+/// [`crate::stages::TimeoutCalibrator`].
+/// `LibAFL` targets store it in `__libafl_forkserver_exec_timeout_ms`, e.g. for watchdogs of their own.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutExtension;
+
+impl TimeoutExtension {
+    /// Create a new [`TimeoutExtension`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ForkserverExtension for TimeoutExtension {
+    fn id(&self) -> u32 {
+        FS_EXT_TIMEOUT
+    }
+
+    fn exec_payload(&self, ctx: &ExecContext, payload: &mut Vec<u8>) {
+        let timeout_ms = u32::try_from(ctx.timeout.as_millis()).unwrap_or(u32::MAX);
+        payload.extend_from_slice(&timeout_ms.to_ne_bytes());
+    }
+}
+
+/// What the fuzzer offers to the target in the handshake
+struct HandshakeOffer<'a> {
+    /// If we prepared a shared memory testcase
+    has_testcase_shmem: bool,
+    /// The size of the cmplog map in `__AFL_CMPLOG_SHM_ID`, if any
+    cmplog_map_size: Option<usize>,
+    /// The autotokens to fill from the autodict of the target
+    autotokens: Option<&'a mut Tokens>,
+    /// The extensions we support
+    extensions: &'a [Arc<dyn ForkserverExtension>],
+}
+
+/// Read a 32 bit parameter of the handshake from the forkserver
+fn read_handshake_param(forkserver: &mut Forkserver, param: &str) -> Result<i32, Error> {
+    let (read_len, val) = forkserver.read_st()?;
+    if read_len != 4 {
+        return Err(Error::unknown(format!(
+            "Failed to read {param} from forkserver"
+        )));
+    }
+    Ok(val)
+}
+
+/// Perform the initial handshake with a freshly spawned [`Forkserver`].
+///
+/// `map_size` is the size of the coverage map we provide, if known. It is updated to the size the target
+/// reports, which must fit. The other results of the negotiation are stored in the [`Forkserver`].
+/// Returns if the target uses the shared memory testcase, which needs `has_testcase_shmem`.
+#[allow(clippy::pedantic)]
+fn forkserver_handshake(
+    forkserver: &mut Forkserver,
+    map_size: &mut Option<usize>,
+    offer: HandshakeOffer,
+) -> Result<bool, Error> {
+    let mut uses_shmem_testcase = false;
+
+    let (rlen, version_status) = forkserver.read_st()?; // Initial handshake, read 4-bytes hello message from the forkserver.
+
+    if rlen != 4 {
+        forkserver.report_startup_failure();
+        return Err(Error::unknown("Failed to start a forkserver".to_string()));
+    }
+
+    if (version_status & FS_NEW_ERROR) == FS_NEW_ERROR {
+        report_error_and_exit(version_status & 0x0000ffff)?;
+    }
+    if (version_status as u32 & 0xff0000ff) == FS_OPT_ERROR as u32 {
+        // `LibAFL` and older `AFL++` targets report errors the old way
+        report_error_and_exit((version_status & 0x00ffff00) >> 8)?;
+    }
+
+    let keep = version_status;
+    let hello = version_status as u32;
+    let version = match hello & 0xffffff00 {
+        FS_AFLPP_VERSION_BASE => match hello & 0xff {
+            0 => {
+                return Err(Error::unknown("Fork server version is not assigned, this should not happen. Recompile target."));
+            }
+            // The `LibAFL` versions are never announced as `AFL++`
+            version @ FS_NEW_VERSION_MIN..=FS_AFLPP_VERSION_MAX => version,
+            _ => {
+                return Err(Error::unknown(
+                    "Fork server version is not supported. Recompile the target.",
+                ));
+            }
+        },
+        FS_LIBAFL_VERSION_BASE => match hello & 0xff {
+            version @ FS_LIBAFL_VERSION_MIN..=FS_NEW_VERSION_MAX => version,
+            _ => {
+                return Err(Error::unknown(
+                    "LibAFL fork server version is not supported. Recompile the target.",
+                ));
+            }
+        },
+        // A target speaking the old protocol
+        _ => 0,
+    };
+    forkserver.version = version;
+
+    let xored_version_status = (version_status as u32 ^ 0xffffffff) as i32;
+
+    let send_len = forkserver.write_ctl(xored_version_status)?;
+    if send_len != 4 {
+        return Err(Error::unknown("Writing to forkserver failed.".to_string()));
+    }
+
+    log::info!(
+        "All right - new fork server model version {} is up",
+        version
+    );
+
+    let (read_len, mut status) = forkserver.read_st()?;
+    if read_len != 4 {
+        return Err(Error::unknown(
+            "Reading from forkserver failed.".to_string(),
+        ));
+    }
+    if version < FS_LIBAFL_VERSION_MIN {
+        // These options do not exist in version 1
+        status &= !(FS_NEW_OPT_MAP_ADDR | FS_NEW_OPT_CMPLOG | FS_NEW_OPT_EXTENSIONS);
+    }
+    // The options we acknowledge in version 2
+    let mut accepted = status & (FS_NEW_OPT_MAPSIZE | FS_NEW_OPT_AUTODICT);
+
+    if status & FS_NEW_OPT_MAPSIZE == FS_NEW_OPT_MAPSIZE {
+        let target_map_size =
+            (read_handshake_param(forkserver, "map size")? as u32 as usize).next_multiple_of(64);
+
+        if let Some(available) = *map_size {
+            if target_map_size > available {
+                return Err(Error::illegal_state(format!(
+                    "The target needs a coverage map of {target_map_size} bytes, but the map only has {available} bytes. Increase the size of the coverage map."
+                )));
+            }
+        }
+
+        // we'll use this later when we truncate the observer
+        *map_size = Some(target_map_size);
+    }
+
+    if status & FS_NEW_OPT_SHDMEM_FUZZ != 0 {
+        if offer.has_testcase_shmem {
+            log::info!("Using SHARED MEMORY FUZZING feature.");
+            uses_shmem_testcase = true;
+            accepted |= FS_NEW_OPT_SHDMEM_FUZZ;
+        } else {
+            return Err(Error::unknown(
+                "Target requested sharedmem fuzzing, but you didn't prepare shmem",
+            ));
+        }
+    }
+
+    if status & FS_NEW_OPT_MAP_ADDR != 0 {
+        let (read_len, buf) = forkserver.read_st_size(8)?;
+        if read_len != 8 {
+            return Err(Error::unknown(
+                "Failed to read map address from forkserver".to_string(),
+            ));
+        }
+        let map_addr = u64::from_ne_bytes(buf.try_into().unwrap());
+        log::info!("The target maps the coverage map at the fixed address {map_addr:#x}");
+        forkserver.map_addr = Some(map_addr);
+        accepted |= FS_NEW_OPT_MAP_ADDR;
+    }
+
+    if status & FS_NEW_OPT_CMPLOG != 0 {
+        let target_cmplog_map_size =
+            read_handshake_param(forkserver, "cmplog map size")? as u32 as usize;
+        match offer.cmplog_map_size {
+            Some(cmplog_map_size) if cmplog_map_size == target_cmplog_map_size => {
+                log::info!("Using the cmplog map of the target");
+                forkserver.cmplog_map_size = Some(cmplog_map_size);
+                accepted |= FS_NEW_OPT_CMPLOG;
+            }
+            Some(cmplog_map_size) => {
+                log::warn!("The target logs comparisons to a cmplog map of {target_cmplog_map_size} bytes, but our map has {cmplog_map_size} bytes. Disabling cmplog.");
+            }
+            None => {
+                log::info!("The target supports cmplog, but we have no cmplog map");
+            }
+        }
+    } else if version >= FS_LIBAFL_VERSION_MIN && offer.cmplog_map_size.is_some() {
+        log::warn!("We have a cmplog map, but the target does not support cmplog");
+    }
+
+    if status & FS_NEW_OPT_AUTODICT != 0 {
+        // Here unlike shmem input fuzzing, we are forced to read things
+        // hence no self.autotokens.is_some() to check if we proceed
+        let autotokens_size = read_handshake_param(forkserver, "autotokens size")?;
+
+        let tokens_size_max = 0xffffff;
+
+        if !(2..=tokens_size_max).contains(&autotokens_size) {
+            return Err(Error::illegal_state(
+                format!("Autotokens size is incorrect, expected 2 to {tokens_size_max} (inclusive), but got {autotokens_size}. Make sure your afl-cc verison is up to date."),
+            ));
+        }
+        log::info!("Autotokens size {autotokens_size:x}");
+        let (rlen, buf) = forkserver.read_st_size(autotokens_size as usize)?;
+
+        if rlen != autotokens_size as usize {
+            return Err(Error::unknown("Failed to load autotokens".to_string()));
+        }
+        if let Some(t) = offer.autotokens {
+            t.parse_autodict(&buf, autotokens_size as usize);
+        }
+    }
+
+    let mut extensions = Vec::new();
+    if status & FS_NEW_OPT_EXTENSIONS != 0 {
+        let count = read_handshake_param(forkserver, "extension count")? as u32;
+        if count > FS_EXTENSIONS_MAX {
+            return Err(Error::illegal_state(format!(
+                "The target announced {count} extensions, expected at most {FS_EXTENSIONS_MAX}"
+            )));
+        }
+        for _ in 0..count {
+            let id = read_handshake_param(forkserver, "extension id")? as u32;
+            if let Some(extension) = offer.extensions.iter().find(|ext| ext.id() == id) {
+                extensions.push(extension.clone());
+            }
+        }
+        accepted |= FS_NEW_OPT_EXTENSIONS;
+    }
+    for extension in offer.extensions {
+        if !extensions.iter().any(|ext| ext.id() == extension.id()) {
+            log::warn!(
+                "The target does not support the forkserver extension {}",
+                extension.id()
+            );
+        }
+    }
+
+    if version >= FS_LIBAFL_VERSION_MIN {
+        // Acknowledge the options, and tell the target which extensions we will send
+        let mut ack = vec![accepted];
+        if accepted & FS_NEW_OPT_EXTENSIONS != 0 {
+            ack.push(extensions.len() as i32);
+            ack.extend(extensions.iter().map(|ext| ext.id() as i32));
+        }
+        for val in ack {
+            if forkserver.write_ctl(val)? != 4 {
+                return Err(Error::unknown("Writing to forkserver failed.".to_string()));
+            }
+        }
+    }
+    forkserver.extensions = extensions;
+
+    let (read_len, aflx) = forkserver.read_st()?;
+    if read_len != 4 {
+        return Err(Error::unknown("Reading from forkserver failed".to_string()));
+    }
+
+    if aflx != version_status {
+        return Err(Error::unknown(format!(
+            "Error in forkserver communication ({:x}=>{:x})",
+            keep, aflx
+        )));
+    }
+
+    Ok(uses_shmem_testcase)
+}
 
 /// Configure the target, `limit`, `setsid`, `pipe_stdin`, the code was borrowed from the [`Angora`](https://github.com/AngoraFuzzer/Angora) fuzzer
 pub trait ConfigTarget {
@@ -114,6 +499,8 @@ pub trait ConfigTarget {
     fn setsid(&mut self) -> &mut Self;
     /// Sets a mem limit
     fn setlimit(&mut self, memlimit: u64) -> &mut Self;
+    /// Raises the core dump size limit to the hard limit, if `core_dumps` is set
+    fn setcorelimit(&mut self, core_dumps: bool) -> &mut Self;
     /// Sets the stdin
     fn setstdin(&mut self, fd: RawFd, use_stdin: bool) -> &mut Self;
     /// Sets the AFL forkserver pipes
@@ -222,6 +609,30 @@ impl ConfigTarget for Command {
         // This calls our non-shady function from above.
         unsafe { self.pre_exec(func) }
     }
+
+    fn setcorelimit(&mut self, core_dumps: bool) -> &mut Self {
+        if !core_dumps {
+            return self;
+        }
+        let func = move || {
+            let mut r = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            if unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut r) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Unprivileged processes may raise the soft limit up to the hard limit
+            r.rlim_cur = r.rlim_max;
+            if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &r) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        };
+        // # Safety
+        // This only calls libc functions, see `setlimit`.
+        unsafe { self.pre_exec(func) }
+    }
 }
 
 /// The [`Forkserver`] is communication channel with a child process that forks on request of the fuzzer.
@@ -242,6 +653,30 @@ pub struct Forkserver {
     last_run_timed_out: i32,
     /// The signal this [`Forkserver`] will use to kill (defaults to [`self.kill_signal`])
     kill_signal: Signal,
+    /// The stderr of the target, if captured
+    stderr: Option<ChildStderr>,
+    /// The last `stderr_capture_size` bytes the target wrote to stderr since the last child was started
+    stderr_buffer: VecDeque<u8>,
+    /// The maximum size of the `stderr_buffer`
+    stderr_capture_size: usize,
+    /// The protocol version negotiated in the handshake
+    version: u32,
+    /// The fixed address of the coverage map in the target, if any
+    map_addr: Option<u64>,
+    /// The size of the cmplog map, if the target logs comparisons to it
+    cmplog_map_size: Option<usize>,
+    /// The extensions accepted in the handshake, in the order of their payloads
+    extensions: Vec<Arc<dyn ForkserverExtension>>,
+}
+
+/// The diagnostics a [`Forkserver`] collects about its children, see [`Forkserver::with_diagnostics`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ForkserverDiagnostics {
+    /// Capture the last bytes the target writes to stderr, up to this size, instead of discarding them.
+    pub stderr_capture_size: Option<usize>,
+    /// Raise the core dump size limit of the target, so that crashing children dump core.
+    /// Core dumps can not be combined with a memory limit, which disables them.
+    pub core_dumps: bool,
 }
 
 impl Drop for Forkserver {
@@ -249,6 +684,11 @@ impl Drop for Forkserver {
         // Modelled after <https://github.com/AFLplusplus/AFLplusplus/blob/dee76993812fa9b5d8c1b75126129887a10befae/src/afl-forkserver.c#L1429>
         log::debug!("Dropping forkserver",);
 
+        // The forkserver may already be gone (and reaped), e.g. after a failed handshake
+        if let Ok(Some(_)) = self.fsrv_handle.try_wait() {
+            return;
+        }
+
         if let Some(pid) = self.child_pid {
             log::debug!("Sending {} to child {pid}", self.kill_signal);
             if let Err(err) = kill(pid, self.kill_signal) {
@@ -325,14 +765,55 @@ impl Forkserver {
         debug_output: bool,
         kill_signal: Signal,
     ) -> Result<Self, Error> {
-        if env::var("AFL_MAP_SIZE").is_err() {
+        Self::with_diagnostics(
+            target,
+            args,
+            envs,
+            input_filefd,
+            use_stdin,
+            memlimit,
+            is_persistent,
+            is_deferred_frksrv,
+            debug_output,
+            kill_signal,
+            ForkserverDiagnostics::default(),
+        )
+    }
+
+    /// Create a new [`Forkserver`] that will kill child processes
+    /// with the given `kill_signal`, and collects the given [`ForkserverDiagnostics`].
+    ///
+    /// If stderr is captured, it is no longer printed with `debug_output`, see [`Self::last_stderr`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_diagnostics(
+        target: OsString,
+        args: Vec<OsString>,
+        envs: Vec<(OsString, OsString)>,
+        input_filefd: RawFd,
+        use_stdin: bool,
+        memlimit: u64,
+        is_persistent: bool,
+        is_deferred_frksrv: bool,
+        debug_output: bool,
+        kill_signal: Signal,
+        diagnostics: ForkserverDiagnostics,
+    ) -> Result<Self, Error> {
+        let is_set = |key: &str| env::var_os(key).is_some() || envs.iter().any(|(k, _)| k == key);
+
+        if !is_set("AFL_MAP_SIZE") {
             log::warn!("AFL_MAP_SIZE not set. If it is unset, the forkserver may fail to start up");
         }
 
-        if env::var("__AFL_SHM_ID").is_err() {
+        if !is_set("__AFL_SHM_ID") {
             log::warn!("__AFL_SHM_ID not set. It is necessary to set this env, otherwise the forkserver cannot communicate with the fuzzer");
         }
 
+        if diagnostics.core_dumps && memlimit != 0 {
+            return Err(Error::illegal_argument(
+                "Core dumps can not be collected with a memory limit, which disables them",
+            ));
+        }
+
         let mut st_pipe = Pipe::new().unwrap();
         let mut ctl_pipe = Pipe::new().unwrap();
 
@@ -342,6 +823,12 @@ impl Forkserver {
             (Stdio::null(), Stdio::null())
         };
 
+        let stderr = if diagnostics.stderr_capture_size.is_some() {
+            Stdio::piped()
+        } else {
+            stderr
+        };
+
         let mut command = Command::new(target);
 
         // Setup args, stdio
@@ -361,12 +848,21 @@ impl Forkserver {
         }
 
         #[cfg(feature = "regex")]
-        command.env("ASAN_OPTIONS", get_asan_runtime_flags_with_log_path());
+        {
+            let mut asan_options = get_asan_runtime_flags_with_log_path();
+            if diagnostics.core_dumps {
+                // ASan disables core dumps on 64-bit targets by default
+                asan_options.push_str(":disable_coredump=0");
+            }
+            command.env("ASAN_OPTIONS", asan_options);
+        }
 
-        let fsrv_handle = match command
+        let mut fsrv_handle = match command
             .env("LD_BIND_NOW", "1")
+            .env(FS_VERSION_ENV, FS_NEW_VERSION_MAX.to_string())
             .envs(envs)
             .setlimit(memlimit)
+            .setcorelimit(diagnostics.core_dumps)
             .setsid()
             .setstdin(input_filefd, use_stdin)
             .setpipe(
@@ -389,6 +885,22 @@ impl Forkserver {
         ctl_pipe.close_read_end();
         st_pipe.close_write_end();
 
+        let stderr = fsrv_handle.stderr.take();
+        if let Some(stderr) = &stderr {
+            // We only ever drain what is there, never wait for more
+            let fd = stderr.as_raw_fd();
+            let ret = unsafe {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+            };
+            if ret < 0 {
+                return Err(Error::os_error(
+                    io::Error::last_os_error(),
+                    "Could not make the stderr of the forkserver non-blocking",
+                ));
+            }
+        }
+
         Ok(Self {
             fsrv_handle,
             st_pipe,
@@ -397,9 +909,22 @@ impl Forkserver {
             status: 0,
             last_run_timed_out: 0,
             kill_signal,
+            stderr,
+            stderr_buffer: VecDeque::new(),
+            stderr_capture_size: diagnostics.stderr_capture_size.unwrap_or(0),
+            version: 0,
+            map_addr: None,
+            cmplog_map_size: None,
+            extensions: Vec::new(),
         })
     }
 
+    /// The pid of the forkserver process
+    #[must_use]
+    pub fn forkserver_pid(&self) -> Pid {
+        Pid::from_raw(self.fsrv_handle.id().try_into().unwrap())
+    }
+
     /// If the last run timed out (as in-target i32)
     #[must_use]
     pub fn last_run_timed_out_raw(&self) -> i32 {
@@ -475,79 +1000,607 @@ impl Forkserver {
         Ok(slen)
     }
 
-    /// Read a message from the child process.
-    pub fn read_st_timed(&mut self, timeout: &TimeSpec) -> Result<Option<i32>, Error> {
-        let mut buf: [u8; 4] = [0_u8; 4];
-        let Some(st_read) = self.st_pipe.read_end() else {
-            return Err(Error::os_error(
-                io::Error::new(ErrorKind::BrokenPipe, "Read pipe end was already closed"),
-                "read_st_timed failed",
-            ));
+    /// The protocol version negotiated with the target
+    #[must_use]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The fixed address the target maps the coverage map at, if it reported one
+    #[must_use]
+    pub fn map_addr(&self) -> Option<u64> {
+        self.map_addr
+    }
+
+    /// The size of the cmplog map, if the target logs comparisons to the map in `__AFL_CMPLOG_SHM_ID`
+    #[must_use]
+    pub fn cmplog_map_size(&self) -> Option<usize> {
+        self.cmplog_map_size
+    }
+
+    /// The [`ForkserverExtension`]s accepted by the target
+    #[must_use]
+    pub fn extensions(&self) -> &[Arc<dyn ForkserverExtension>] {
+        &self.extensions
+    }
+
+    /// The last bytes the target wrote to stderr since the last child was started, if stderr is captured.
+    ///
+    /// This includes what the target wrote after the child has finished, e.g. in persistent mode.
+    #[must_use]
+    pub fn last_stderr(&self) -> Vec<u8> {
+        self.stderr_buffer.iter().copied().collect()
+    }
+
+    /// Read what is available from the captured stderr, keeping the last `stderr_capture_size` bytes.
+    fn drain_stderr(&mut self) {
+        let Some(stderr) = &mut self.stderr else {
+            return;
         };
+        let mut buf = [0_u8; 4096];
+        loop {
+            match stderr.read(&mut buf) {
+                Ok(0) => {
+                    // The forkserver and all children are gone
+                    self.stderr = None;
+                    break;
+                }
+                Ok(len) => {
+                    self.stderr_buffer.extend(&buf[..len]);
+                    let excess = self
+                        .stderr_buffer
+                        .len()
+                        .saturating_sub(self.stderr_capture_size);
+                    self.stderr_buffer.drain(..excess);
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                // Nothing left to read
+                Err(_) => break,
+            }
+        }
+    }
 
-        // # Safety
-        // The FDs are valid as this point in time.
-        let st_read = unsafe { BorrowedFd::borrow_raw(st_read) };
+    /// Log the likely causes of a forkserver that did not answer the initial handshake,
+    /// with its exit status and its stderr, if captured.
+    fn report_startup_failure(&mut self) {
+        let mut message =
+            String::from("The forkserver did not answer the initial handshake. Likely causes:");
+        for cause in FS_STARTUP_FAILURE_CAUSES {
+            message.push_str("\n - ");
+            message.push_str(cause);
+        }
 
-        let mut readfds = FdSet::new();
-        readfds.insert(st_read);
-        // We'll pass a copied timeout to keep the original timeout intact, because select updates timeout to indicate how much time was left. See select(2)
-        let sret = pselect(
-            Some(readfds.highest().unwrap().as_raw_fd() + 1),
-            &mut readfds,
-            None,
-            None,
-            Some(timeout),
-            Some(&SigSet::empty()),
-        )?;
-        if sret > 0 {
-            if self.st_pipe.read_exact(&mut buf).is_ok() {
-                let val: i32 = i32::from_ne_bytes(buf);
-                Ok(Some(val))
+        // The forkserver closed the status pipe, give it a moment to exit
+        let mut exit_status = None;
+        for _ in 0..10 {
+            match self.fsrv_handle.try_wait() {
+                Ok(Some(status)) => {
+                    exit_status = Some(status);
+                    break;
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(_) => break,
+            }
+        }
+        match exit_status {
+            Some(status) => {
+                let _ = write!(message, "\nThe forkserver exited with {status}");
+            }
+            None => message.push_str("\nThe forkserver is still running"),
+        }
+
+        if self.stderr_capture_size > 0 {
+            self.drain_stderr();
+            if self.stderr_buffer.is_empty() {
+                message.push_str("\nThe target did not write to stderr");
             } else {
-                Err(Error::unknown(
-                    "Unable to communicate with fork server (OOM?)".to_string(),
-                ))
+                let _ = write!(
+                    message,
+                    "\nThe last output of the target on stderr:\n{}",
+                    String::from_utf8_lossy(&self.last_stderr())
+                );
             }
         } else {
-            Ok(None)
+            message.push_str("\nCapture the stderr of the target (`stderr_capture_size`) or set `debug_child` for details");
         }
+        log::error!("{message}");
     }
-}
 
-/// This [`Executor`] can run binaries compiled for AFL/AFL++ that make use of a forkserver.
-/// Shared memory feature is also available, but you have to set things up in your code.
-/// Please refer to AFL++'s docs. <https://github.com/AFLplusplus/AFLplusplus/blob/stable/instrumentation/README.persistent_mode.md>
-pub struct ForkserverExecutor<OT, S, SP>
-where
-    SP: ShMemProvider,
-{
-    target: OsString,
-    args: Vec<OsString>,
-    input_file: InputFile,
-    uses_shmem_testcase: bool,
-    forkserver: Forkserver,
-    observers: OT,
-    map: Option<SP::ShMem>,
-    phantom: PhantomData<S>,
-    map_size: Option<usize>,
-    min_input_size: usize,
-    max_input_size: usize,
-    #[cfg(feature = "regex")]
-    asan_obs: Handle<AsanBacktraceObserver>,
-    timeout: TimeSpec,
-    crash_exitcode: Option<i8>,
-}
+    /// Request a new child from the forkserver, returning its pid.
+    ///
+    /// The accepted [`ForkserverExtension`]s send their payloads for the execution described by `ctx`.
+    /// Wait for it to finish with [`Self::wait_child`].
+    pub fn start_child(&mut self, ctx: &ExecContext) -> Result<Pid, Error> {
+        // Only keep the output of the new child
+        self.drain_stderr();
+        self.stderr_buffer.clear();
 
-impl<OT, S, SP> Debug for ForkserverExecutor<OT, S, SP>
-where
-    OT: Debug,
-    SP: ShMemProvider,
-{
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("ForkserverExecutor")
-            .field("target", &self.target)
-            .field("args", &self.args)
+        let last_run_timed_out = self.last_run_timed_out_raw();
+        let send_len = self.write_ctl(last_run_timed_out)?;
+
+        self.set_last_run_timed_out(false);
+
+        if send_len != 4 {
+            return Err(Error::unknown(
+                "Unable to request new process from fork server (OOM?)".to_string(),
+            ));
+        }
+
+        if !self.extensions.is_empty() {
+            let mut payload = Vec::new();
+            for extension in &self.extensions {
+                extension.exec_payload(ctx, &mut payload);
+            }
+            self.ctl_pipe.write_all(&payload)?;
+        }
+
+        let (recv_pid_len, pid) = self.read_st()?;
+        if recv_pid_len != 4 {
+            return Err(Error::unknown(
+                "Unable to request new process from fork server (OOM?)".to_string(),
+            ));
+        }
+
+        if pid <= 0 {
+            return Err(Error::unknown(
+                "Fork server is misbehaving (OOM?)".to_string(),
+            ));
+        }
+
+        let pid = Pid::from_raw(pid);
+        self.set_child_pid(pid);
+        Ok(pid)
+    }
+
+    /// Wait for the child started by [`Self::start_child`], killing it after `timeout`.
+    ///
+    /// The child crashed if it was killed by a signal, or exited with `crash_exitcode`.
+    pub fn wait_child(
+        &mut self,
+        timeout: &TimeSpec,
+        crash_exitcode: Option<i8>,
+    ) -> Result<ExitKind, Error> {
+        let mut exit_kind = ExitKind::Ok;
+
+        if let Some(status) = self.read_st_timed(timeout)? {
+            self.set_status(status);
+            let exitcode_is_crash = if let Some(crash_exitcode) = crash_exitcode {
+                (libc::WEXITSTATUS(status) as i8) == crash_exitcode
+            } else {
+                false
+            };
+            if libc::WIFSIGNALED(status) || exitcode_is_crash {
+                exit_kind = ExitKind::Crash;
+            }
+        } else {
+            self.set_last_run_timed_out(true);
+
+            // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
+            let _ = kill(self.child_pid(), self.kill_signal);
+            let (recv_status_len, _) = self.read_st()?;
+            if recv_status_len != 4 {
+                return Err(Error::unknown("Could not kill timed-out child".to_string()));
+            }
+            exit_kind = ExitKind::Timeout;
+        }
+
+        if !libc::WIFSTOPPED(self.status()) {
+            self.reset_child_pid();
+        }
+
+        Ok(exit_kind)
+    }
+
+    /// Read a message from the child process.
+    ///
+    /// If stderr is captured, it is drained while waiting, so that the target never blocks on a full pipe.
+    pub fn read_st_timed(&mut self, timeout: &TimeSpec) -> Result<Option<i32>, Error> {
+        let mut buf: [u8; 4] = [0_u8; 4];
+        let Some(st_read) = self.st_pipe.read_end() else {
+            return Err(Error::os_error(
+                io::Error::new(ErrorKind::BrokenPipe, "Read pipe end was already closed"),
+                "read_st_timed failed",
+            ));
+        };
+
+        // # Safety
+        // The FDs are valid as this point in time.
+        let st_read = unsafe { BorrowedFd::borrow_raw(st_read) };
+
+        let deadline = current_time() + Duration::from(*timeout);
+        loop {
+            // # Safety
+            // The stderr pipe stays open while we select on it.
+            let stderr = self
+                .stderr
+                .as_ref()
+                .map(|stderr| unsafe { BorrowedFd::borrow_raw(stderr.as_raw_fd()) });
+
+            let mut readfds = FdSet::new();
+            readfds.insert(st_read);
+            if let Some(stderr) = stderr {
+                readfds.insert(stderr);
+            }
+            // We pass the remaining time to keep the original timeout intact, because select updates timeout to indicate how much time was left. See select(2)
+            let remaining: TimeSpec = deadline.saturating_sub(current_time()).into();
+            let sret = pselect(
+                Some(readfds.highest().unwrap().as_raw_fd() + 1),
+                &mut readfds,
+                None,
+                None,
+                Some(&remaining),
+                Some(&SigSet::empty()),
+            )?;
+            if sret <= 0 {
+                self.drain_stderr();
+                return Ok(None);
+            }
+            if readfds.contains(st_read) {
+                return if self.st_pipe.read_exact(&mut buf).is_ok() {
+                    // Collect what the child wrote right before it finished
+                    self.drain_stderr();
+                    let val: i32 = i32::from_ne_bytes(buf);
+                    Ok(Some(val))
+                } else {
+                    Err(Error::unknown(
+                        "Unable to communicate with fork server (OOM?)".to_string(),
+                    ))
+                };
+            }
+            // Only stderr is ready
+            self.drain_stderr();
+        }
+    }
+}
+
+/// Collects the core dumps of crashing children into a directory.
+///
+/// The core dumps are found following the `core_pattern` of the kernel, which must write them to a file,
+/// i.e. not pipe them to a program such as `systemd-coredump` or `apport`. Set it with
+/// `echo core | sudo tee /proc/sys/kernel/core_pattern`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreDumpCollector {
+    core_pattern: String,
+    core_uses_pid: bool,
+    /// The name of the target process, as in `/proc/<pid>/comm`
+    comm: String,
+    hostname: String,
+    dir: PathBuf,
+}
+
+impl CoreDumpCollector {
+    /// Create a new [`CoreDumpCollector`] for the `target`, moving the core dumps into `dir`.
+    ///
+    /// Fails if the `core_pattern` of the kernel is not supported.
+    pub fn new<P>(target: &OsStr, dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let core_pattern = fs::read_to_string("/proc/sys/kernel/core_pattern")?;
+        let core_uses_pid = fs::read_to_string("/proc/sys/kernel/core_uses_pid")
+            .is_ok_and(|core_uses_pid| core_uses_pid.trim() != "0");
+
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) } == 0 && limit.rlim_max == 0 {
+            log::warn!("The hard limit of the core dump size is 0, the target will not dump core. Raise it with `ulimit -H -c unlimited`");
+        }
+
+        Self::with_core_pattern(core_pattern.trim(), core_uses_pid, target, dir)
+    }
+
+    /// Create a new [`CoreDumpCollector`] for the given `core_pattern` and `core_uses_pid` settings of the kernel.
+    ///
+    /// Only the `%p`, `%P`, `%e`, `%s`, `%u`, `%g`, `%h` and `%%` specifiers are supported.
+    pub fn with_core_pattern<P>(
+        core_pattern: &str,
+        core_uses_pid: bool,
+        target: &OsStr,
+        dir: P,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        if core_pattern.starts_with('|') {
+            return Err(Error::illegal_state(format!(
+                "The core_pattern `{core_pattern}` pipes core dumps to a program, set it to a file with `echo core | sudo tee /proc/sys/kernel/core_pattern`"
+            )));
+        }
+        let mut chars = core_pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some('p' | 'P' | 'e' | 's' | 'u' | 'g' | 'h' | '%') => {}
+                specifier => {
+                    return Err(Error::illegal_state(format!(
+                        "The core_pattern `{core_pattern}` contains the unsupported specifier `%{}`, set it to a simpler pattern, e.g. with `echo core.%p | sudo tee /proc/sys/kernel/core_pattern`",
+                        specifier.map(String::from).unwrap_or_default()
+                    )));
+                }
+            }
+        }
+
+        // The kernel truncates the name to `TASK_COMM_LEN`
+        let mut comm = Path::new(target)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        while comm.len() > 15 {
+            comm.pop();
+        }
+
+        let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|hostname| hostname.trim().to_string())
+            .unwrap_or_default();
+
+        fs::create_dir_all(dir.as_ref())?;
+
+        Ok(Self {
+            // An empty pattern is the default of the kernel
+            core_pattern: if core_pattern.is_empty() {
+                "core".to_string()
+            } else {
+                core_pattern.to_string()
+            },
+            core_uses_pid,
+            comm,
+            hostname,
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    /// The path the kernel writes the core dump of the child `pid` to, which exited with `status`.
+    ///
+    /// Relative paths are relative to the working directory of the child, i.e. ours.
+    #[must_use]
+    pub fn core_dump_path(&self, pid: Pid, status: i32) -> PathBuf {
+        let mut path = String::new();
+        let mut has_pid = false;
+        let mut chars = self.core_pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                path.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('p' | 'P') => {
+                    has_pid = true;
+                    path.push_str(&pid.to_string());
+                }
+                // Slashes in the name are replaced, as it is a single path component
+                Some('e') => path.push_str(&self.comm.replace('/', "!")),
+                Some('s') => path.push_str(&libc::WTERMSIG(status).to_string()),
+                Some('u') => path.push_str(&unsafe { libc::getuid() }.to_string()),
+                Some('g') => path.push_str(&unsafe { libc::getgid() }.to_string()),
+                Some('h') => path.push_str(&self.hostname),
+                Some('%') => path.push('%'),
+                _ => {}
+            }
+        }
+        if self.core_uses_pid && !has_pid {
+            let _ = write!(path, ".{pid}");
+        }
+        PathBuf::from(path)
+    }
+
+    /// Move the core dump of the child `pid`, which exited with `status`, into the directory.
+    ///
+    /// Returns the new path of the core dump, if the child dumped core.
+    pub fn collect(&self, pid: Pid, status: i32) -> Result<Option<PathBuf>, Error> {
+        if !libc::WIFSIGNALED(status) || !libc::WCOREDUMP(status) {
+            return Ok(None);
+        }
+        let core_dump = self.core_dump_path(pid, status);
+        if !core_dump.exists() {
+            log::warn!(
+                "The child {pid} dumped core, but there is no core dump at {}",
+                core_dump.display()
+            );
+            return Ok(None);
+        }
+
+        let dest = self.dir.join(format!("core.{pid}.{}", current_nanos()));
+        if fs::rename(&core_dump, &dest).is_err() {
+            // Probably on another file system
+            fs::copy(&core_dump, &dest)?;
+            fs::remove_file(&core_dump)?;
+        }
+        Ok(Some(dest))
+    }
+}
+
+/// A cgroup (v2) limiting the memory of the target, see [`ForkserverExecutorBuilder::memory_cgroup`]
+/// and [`crate::executors::inprocess_fork::GenericInProcessForkExecutor::memory_cgroup`].
+///
+/// Unlike a memory limit with `RLIMIT_AS`, this limits the memory actually used, so it also works for
+/// targets reserving large amounts of address space, such as `ASan` builds.
+/// Children exceeding the limit are killed by the OOM killer of the kernel, which we detect in the
+/// `oom_kill` counter in `memory.events`.
+/// The cgroup is removed again when dropped.
+#[derive(Debug)]
+pub struct MemoryCgroup {
+    path: PathBuf,
+    /// `memory.peak`, if the kernel supports resetting it (Linux 6.12 and later)
+    peak: Option<fs::File>,
+    /// The number of processes the OOM killer killed, up to the last run
+    oom_kills: u64,
+}
+
+impl MemoryCgroup {
+    /// Create a new cgroup below the `parent` cgroup, limiting the memory to `limit` bytes.
+    ///
+    /// The `parent` must be a cgroup we may create child cgroups in, e.g. one delegated by
+    /// `systemd-run --user --scope -p Delegate=yes`.
+    pub fn new<P>(parent: P, limit: u64) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let parent = parent.as_ref();
+        let subtree_control = fs::read_to_string(parent.join("cgroup.subtree_control"))?;
+        if !subtree_control.split_whitespace().any(|c| c == "memory") {
+            fs::write(parent.join("cgroup.subtree_control"), "+memory").map_err(|err| {
+                Error::os_error(
+                    err,
+                    format!(
+                        "Could not enable the memory controller in the cgroup {}",
+                        parent.display()
+                    ),
+                )
+            })?;
+        }
+
+        let path = parent.join(format!("libafl-{}-{}", std::process::id(), current_nanos()));
+        fs::create_dir(&path).map_err(|err| {
+            Error::os_error(
+                err,
+                format!("Could not create the cgroup {}", path.display()),
+            )
+        })?;
+        let mut cgroup = Self {
+            path,
+            peak: None,
+            oom_kills: 0,
+        };
+
+        fs::write(cgroup.path.join("memory.max"), limit.to_string())?;
+        // Otherwise the target would swap instead of being killed, if swap accounting is enabled
+        if let Err(err) = fs::write(cgroup.path.join("memory.swap.max"), "0") {
+            log::debug!("Could not disable swap in the memory cgroup: {err}");
+        }
+
+        cgroup.peak = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(cgroup.path.join("memory.peak"))
+            .ok()
+            .filter(|peak| peak.write_at(b"reset", 0).is_ok());
+        if cgroup.peak.is_none() {
+            log::info!(
+                "The kernel can not reset the peak memory usage of a cgroup, it is not reported"
+            );
+        }
+        cgroup.oom_kills = cgroup.read_oom_kills()?;
+
+        Ok(cgroup)
+    }
+
+    /// The path of the cgroup
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the process `pid` into the cgroup. The children it forks afterwards are in it as well.
+    pub fn add_process(&self, pid: Pid) -> Result<(), Error> {
+        fs::write(self.path.join("cgroup.procs"), pid.to_string()).map_err(|err| {
+            Error::os_error(
+                err,
+                format!(
+                    "Could not move {pid} into the cgroup {}",
+                    self.path.display()
+                ),
+            )
+        })
+    }
+
+    /// Reset the peak memory usage before a run
+    pub fn reset_peak(&self) {
+        if let Some(peak) = &self.peak {
+            let _ = peak.write_at(b"reset", 0);
+        }
+    }
+
+    /// The peak memory usage in bytes since the last [`Self::reset_peak`], if the kernel supports it
+    #[must_use]
+    pub fn peak(&self) -> Option<u64> {
+        let mut buf = [0_u8; 32];
+        let len = self.peak.as_ref()?.read_at(&mut buf, 0).ok()?;
+        core::str::from_utf8(&buf[..len]).ok()?.trim().parse().ok()
+    }
+
+    /// If the OOM killer killed a process in the cgroup since the last call
+    pub fn oom_killed(&mut self) -> Result<bool, Error> {
+        let oom_kills = self.read_oom_kills()?;
+        let oom_killed = oom_kills > self.oom_kills;
+        self.oom_kills = oom_kills;
+        Ok(oom_killed)
+    }
+
+    /// Read the `oom_kill` counter from `memory.events`
+    fn read_oom_kills(&self) -> Result<u64, Error> {
+        let events = fs::read_to_string(self.path.join("memory.events"))?;
+        parse_oom_kills(&events)
+            .ok_or_else(|| Error::illegal_state("No oom_kill counter in memory.events"))
+    }
+}
+
+impl Drop for MemoryCgroup {
+    fn drop(&mut self) {
+        self.peak = None;
+        // This fails while processes are left in the cgroup, the killed children may take a moment to exit
+        let mut retries = 10;
+        while let Err(err) = fs::remove_dir(&self.path) {
+            retries -= 1;
+            if retries == 0 {
+                log::warn!("Could not remove the cgroup {}: {err}", self.path.display());
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Parse the `oom_kill` counter of the `memory.events` of a cgroup
+fn parse_oom_kills(events: &str) -> Option<u64> {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+/// This [`Executor`] can run binaries compiled for AFL/AFL++ that make use of a forkserver.
+/// Shared memory feature is also available, but you have to set things up in your code.
+/// Please refer to AFL++'s docs. <https://github.com/AFLplusplus/AFLplusplus/blob/stable/instrumentation/README.persistent_mode.md>
+pub struct ForkserverExecutor<OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    target: OsString,
+    args: Vec<OsString>,
+    input_file: InputFile,
+    uses_shmem_testcase: bool,
+    forkserver: Forkserver,
+    observers: OT,
+    map: Option<SP::ShMem>,
+    phantom: PhantomData<S>,
+    map_size: Option<usize>,
+    min_input_size: usize,
+    max_input_size: usize,
+    #[cfg(feature = "regex")]
+    asan_obs: Handle<AsanBacktraceObserver>,
+    timeout: TimeSpec,
+    crash_exitcode: Option<i8>,
+    stderr_obs: Option<Handle<StdErrObserver>>,
+    core_dumps: Option<CoreDumpCollector>,
+    core_dump_obs: Option<Handle<CoreDumpObserver>>,
+    /// Dropped after the forkserver, which must leave the cgroup empty
+    memory_cgroup: Option<MemoryCgroup>,
+    memory_obs: Option<Handle<MemoryObserver>>,
+}
+
+impl<OT, S, SP> Debug for ForkserverExecutor<OT, S, SP>
+where
+    OT: Debug,
+    SP: ShMemProvider,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForkserverExecutor")
+            .field("target", &self.target)
+            .field("args", &self.args)
             .field("input_file", &self.input_file)
             .field("uses_shmem_testcase", &self.uses_shmem_testcase)
             .field("forkserver", &self.forkserver)
@@ -625,6 +1678,16 @@ pub struct ForkserverExecutorBuilder<'a, SP> {
     #[cfg(feature = "regex")]
     asan_obs: Option<Handle<AsanBacktraceObserver>>,
     crash_exitcode: Option<i8>,
+    stderr_observer: Option<Handle<StdErrObserver>>,
+    stderr_capture_size: Option<usize>,
+    core_dump_dir: Option<PathBuf>,
+    core_dump_observer: Option<Handle<CoreDumpObserver>>,
+    time_observer: Option<Handle<TimeObserver>>,
+    memory_limit: u64,
+    memory_cgroup: Option<PathBuf>,
+    memory_observer: Option<Handle<MemoryObserver>>,
+    cmplog_map_size: Option<usize>,
+    extensions: Vec<Arc<dyn ForkserverExtension>>,
 }
 
 impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
@@ -641,9 +1704,10 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         S::Input: Input + HasTargetBytes,
         SP: ShMemProvider,
     {
-        let (forkserver, input_file, map) = self.build_helper()?;
+        let (forkserver, input_file, map, memory_cgroup) = self.build_helper()?;
 
         let target = self.program.take().unwrap();
+        let core_dumps = self.core_dump_collector(&target)?;
         log::info!(
             "ForkserverExecutor: program: {:?}, arguments: {:?}, use_stdin: {:?}",
             target,
@@ -689,10 +1753,17 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            stderr_obs: self.stderr_observer.clone(),
+            core_dumps,
+            core_dump_obs: self.core_dump_observer.clone(),
+            memory_cgroup,
+            memory_obs: self.memory_observer.clone(),
         })
     }
 
     /// Builds `ForkserverExecutor` downsizing the coverage map to fit exaclty the AFL++ map size.
+    ///
+    /// Without a `coverage_map_size`, the size of the `map_observer` is the size available to the target.
     #[allow(clippy::pedantic)]
     pub fn build_dynamic_map<A, MO, OT, S>(
         &mut self,
@@ -707,9 +1778,14 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         S::Input: Input + HasTargetBytes,
         SP: ShMemProvider,
     {
-        let (forkserver, input_file, map) = self.build_helper()?;
+        if self.map_size.is_none() {
+            self.map_size = Some(map_observer.as_ref().usable_count());
+        }
+
+        let (forkserver, input_file, map, memory_cgroup) = self.build_helper()?;
 
         let target = self.program.take().unwrap();
+        let core_dumps = self.core_dump_collector(&target)?;
         log::info!(
             "ForkserverExecutor: program: {:?}, arguments: {:?}, use_stdin: {:?}, map_size: {:?}",
             target,
@@ -753,11 +1829,26 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            stderr_obs: self.stderr_observer.clone(),
+            core_dumps,
+            core_dump_obs: self.core_dump_observer.clone(),
+            memory_cgroup,
+            memory_obs: self.memory_observer.clone(),
         })
     }
 
-    #[allow(clippy::pedantic)]
-    fn build_helper(&mut self) -> Result<(Forkserver, InputFile, Option<SP::ShMem>), Error>
+    #[allow(clippy::pedantic, clippy::type_complexity)]
+    fn build_helper(
+        &mut self,
+    ) -> Result<
+        (
+            Forkserver,
+            InputFile,
+            Option<SP::ShMem>,
+            Option<MemoryCgroup>,
+        ),
+        Error,
+    >
     where
         SP: ShMemProvider,
     {
@@ -784,151 +1875,284 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             }
         };
 
-        let mut forkserver = match &self.program {
-            Some(t) => Forkserver::with_kill_signal(
-                t.clone(),
-                self.arguments.clone(),
-                self.envs.clone(),
-                input_file.as_raw_fd(),
-                self.use_stdin,
-                0,
-                self.is_persistent,
-                self.is_deferred_frksrv,
-                self.debug_child,
-                self.kill_signal.unwrap_or(KILL_SIGNAL_DEFAULT),
-            )?,
-            None => {
-                return Err(Error::illegal_argument(
-                    "ForkserverExecutorBuilder::build: target file not found".to_string(),
-                ))
-            }
-        };
+        let mut forkserver =
+            self.spawn_forkserver(self.arguments.clone(), Vec::new(), &input_file)?;
+
+        // Move the forkserver into the cgroup before it forks the first child
+        let memory_cgroup = self
+            .memory_cgroup
+            .as_ref()
+            .map(|parent| MemoryCgroup::new(parent, self.memory_limit))
+            .transpose()?;
+        if let Some(memory_cgroup) = &memory_cgroup {
+            memory_cgroup.add_process(forkserver.forkserver_pid())?;
+        }
 
-        let (rlen, version_status) = forkserver.read_st()?; // Initial handshake, read 4-bytes hello message from the forkserver.
+        if forkserver_handshake(
+            &mut forkserver,
+            &mut self.map_size,
+            HandshakeOffer {
+                has_testcase_shmem: map.is_some(),
+                cmplog_map_size: self.cmplog_map_size,
+                autotokens: self.autotokens.as_deref_mut(),
+                extensions: &self.extensions,
+            },
+        )? {
+            self.uses_shmem_testcase = true;
+        }
+
+        Ok((forkserver, input_file, map, memory_cgroup))
+    }
 
-        if rlen != 4 {
-            return Err(Error::unknown("Failed to start a forkserver".to_string()));
+    /// Builds a [`ForkserverPoolExecutor`] running `workers` instances of the target concurrently.
+    ///
+    /// Each forkserver gets its own coverage map (passed in `__AFL_SHM_ID`), input file and
+    /// shared memory testcase, if a `shmem_provider` is set. After each run, the coverage map of the
+    /// worker is copied to the `map_observer`.
+    /// The size of the coverage maps is the `coverage_map_size`, or the size of the `map_observer`.
+    #[allow(clippy::pedantic)]
+    pub fn build_pool<C, OT, S>(
+        &mut self,
+        workers: usize,
+        map_observer: Handle<C>,
+        observers: OT,
+    ) -> Result<ForkserverPoolExecutor<C, OT, S, SP>, Error>
+    where
+        C: MapObserver<Entry = u8> + for<'it> AsSliceMut<'it, Entry = u8>,
+        OT: ObserversTuple<S>,
+        S: UsesInput,
+        S::Input: Input + HasTargetBytes,
+        SP: ShMemProvider,
+    {
+        if workers == 0 {
+            return Err(Error::illegal_argument(
+                "A forkserver pool needs at least one worker",
+            ));
+        }
+        if self.memory_cgroup.is_some() {
+            return Err(Error::illegal_argument(
+                "Memory cgroups are not supported by the forkserver pool, use a plain memory limit",
+            ));
+        }
+        if self.min_input_size > self.max_input_size {
+            return Err(Error::illegal_argument(
+                format!(
+                    "Minimum input size ({}) must not exceed maximum input size ({})",
+                    self.min_input_size, self.max_input_size
+                )
+                .as_str(),
+            ));
         }
 
-        if (version_status & FS_NEW_ERROR) == FS_NEW_ERROR {
-            report_error_and_exit(version_status & 0x0000ffff)?;
+        let map_size = match self.map_size {
+            Some(map_size) => map_size,
+            None => observers
+                .get(&map_observer)
+                .ok_or_else(|| Error::illegal_argument("The map observer is missing"))?
+                .usable_count(),
+        };
+        self.map_size = Some(map_size);
+
+        // Without a provider, we only need shared memory for the coverage maps
+        let has_testcase_shmem = self.shmem_provider.is_some();
+        let mut own_shmem_provider = None;
+        let mut shmems = Vec::with_capacity(workers);
+        {
+            let provider = match self.shmem_provider.as_deref_mut() {
+                Some(provider) => provider,
+                None => own_shmem_provider.insert(SP::new()?),
+            };
+            for _ in 0..workers {
+                let coverage_map = provider.new_shmem(map_size)?;
+                let testcase_shmem = if has_testcase_shmem {
+                    let mut shmem =
+                        provider.new_shmem(self.max_input_size + SHMEM_FUZZ_HDR_SIZE)?;
+                    let size_in_bytes = (self.max_input_size + SHMEM_FUZZ_HDR_SIZE).to_ne_bytes();
+                    shmem.as_slice_mut()[..4].clone_from_slice(&size_in_bytes[..4]);
+                    Some(shmem)
+                } else {
+                    None
+                };
+                shmems.push((coverage_map, testcase_shmem));
+            }
         }
 
-        let keep = version_status;
-        let version: u32 = version_status as u32 - 0x41464c00_u32;
-        if (0x41464c00..=0x41464cff).contains(&version_status) {
-            match version {
-                0 => {
-                    return Err(Error::unknown("Fork server version is not assigned, this should not happen. Recompile target."));
-                }
-                FS_NEW_VERSION_MIN..=FS_NEW_VERSION_MAX => {
-                    // good, do nothing
+        let mut pool_workers = Vec::with_capacity(workers);
+        for (idx, (coverage_map, testcase_shmem)) in shmems.into_iter().enumerate() {
+            // Each worker needs its own input file
+            let (input_filename, arguments) = match &self.input_filename {
+                Some(name) => {
+                    let mut worker_filename = name.clone();
+                    worker_filename.push(format!(".{idx}"));
+                    let arguments = self
+                        .arguments
+                        .iter()
+                        .map(|arg| {
+                            if arg == name {
+                                worker_filename.clone()
+                            } else {
+                                arg.clone()
+                            }
+                        })
+                        .collect();
+                    (worker_filename, arguments)
                 }
-                _ => {
-                    return Err(Error::unknown(
-                        "Fork server version is not supported. Recompile the target.",
-                    ));
+                None => {
+                    self.use_stdin = true;
+                    (
+                        OsString::from(format!("{}_{idx}", get_unique_std_input_file())),
+                        self.arguments.clone(),
+                    )
                 }
+            };
+            let input_file = InputFile::create(input_filename)?;
+
+            let mut envs = vec![
+                (
+                    OsString::from("__AFL_SHM_ID"),
+                    OsString::from(coverage_map.id().to_string()),
+                ),
+                (
+                    OsString::from("__AFL_SHM_ID_SIZE"),
+                    OsString::from(map_size.to_string()),
+                ),
+            ];
+            if let Some(shmem) = &testcase_shmem {
+                envs.push((
+                    OsString::from("__AFL_SHM_FUZZ_ID"),
+                    OsString::from(shmem.id().to_string()),
+                ));
             }
-        }
+            let mut forkserver = self.spawn_forkserver(arguments, envs, &input_file)?;
 
-        let xored_version_status = (version_status as u32 ^ 0xffffffff) as i32;
+            let mut target_map_size = Some(map_size);
+            // The autodict is the same for all workers
+            let autotokens = if idx == 0 {
+                self.autotokens.as_deref_mut()
+            } else {
+                None
+            };
+            let uses_shmem_testcase = forkserver_handshake(
+                &mut forkserver,
+                &mut target_map_size,
+                HandshakeOffer {
+                    has_testcase_shmem: testcase_shmem.is_some(),
+                    cmplog_map_size: self.cmplog_map_size,
+                    autotokens,
+                    extensions: &self.extensions,
+                },
+            )?;
+            if idx == 0 {
+                self.map_size = target_map_size;
+            }
 
-        let send_len = forkserver.write_ctl(xored_version_status)?;
-        if send_len != 4 {
-            return Err(Error::unknown("Writing to forkserver failed.".to_string()));
+            pool_workers.push(ForkserverPoolWorker {
+                forkserver,
+                input_file,
+                coverage_map,
+                testcase_shmem: testcase_shmem.filter(|_| uses_shmem_testcase),
+                pid: None,
+                exit_kind: ExitKind::Ok,
+                runtime: Duration::ZERO,
+                core_dump: None,
+            });
         }
 
+        let target = self.program.take().unwrap();
+        let core_dumps = self.core_dump_collector(&target)?;
         log::info!(
-            "All right - new fork server model version {} is up",
-            version
-        );
-
-        let (read_len, status) = forkserver.read_st()?;
-        if read_len != 4 {
-            return Err(Error::unknown(
-                "Reading from forkserver failed.".to_string(),
-            ));
-        }
+            "ForkserverPoolExecutor: program: {:?}, arguments: {:?}, use_stdin: {:?}, workers: {workers}",
+            target,
+            self.arguments.clone(),
+            self.use_stdin
+        );
 
-        if status & FS_NEW_OPT_MAPSIZE == FS_NEW_OPT_MAPSIZE {
-            // When 0, we assume that map_size was filled by the user or const
-            /* TODO autofill map size from the observer
+        Ok(ForkserverPoolExecutor {
+            target,
+            args: self.arguments.clone(),
+            workers: pool_workers,
+            observers,
+            map_observer,
+            min_input_size: self.min_input_size,
+            max_input_size: self.max_input_size,
+            timeout: self.timeout.unwrap_or(Duration::from_millis(5000)),
+            crash_exitcode: self.crash_exitcode,
+            #[cfg(feature = "regex")]
+            asan_obs: self
+                .asan_obs
+                .clone()
+                .unwrap_or(AsanBacktraceObserver::default().handle()),
+            stderr_obs: self.stderr_observer.clone(),
+            core_dumps,
+            core_dump_obs: self.core_dump_observer.clone(),
+            time_obs: self.time_observer.clone(),
+            _shmem_provider: own_shmem_provider,
+            phantom: PhantomData,
+        })
+    }
 
-            if map_size > 0 {
-                self.map_size = Some(map_size as usize);
-            }
-            */
-            let (read_len, mut map_size) = forkserver.read_st()?;
-            if read_len != 4 {
-                return Err(Error::unknown(
-                    "Failed to read map size from forkserver".to_string(),
+    /// Spawn the forkserver of the target, with the given arguments and additional environment variables
+    fn spawn_forkserver(
+        &self,
+        arguments: Vec<OsString>,
+        extra_envs: Vec<(OsString, OsString)>,
+        input_file: &InputFile,
+    ) -> Result<Forkserver, Error> {
+        let Some(program) = &self.program else {
+            return Err(Error::illegal_argument(
+                "ForkserverExecutorBuilder::build: target file not found".to_string(),
+            ));
+        };
+        let mut envs = self.envs.clone();
+        envs.extend(extra_envs);
+        if let Some(map_size) = self.map_size {
+            // Let the target know how large the map is, unless the user did
+            if env::var_os("AFL_MAP_SIZE").is_none()
+                && !envs.iter().any(|(key, _)| key == "AFL_MAP_SIZE")
+            {
+                envs.push((
+                    OsString::from("AFL_MAP_SIZE"),
+                    OsString::from(map_size.to_string()),
                 ));
             }
-
-            if map_size % 64 != 0 {
-                map_size = ((map_size + 63) >> 6) << 6;
-            }
-
-            // TODO set AFL_MAP_SIZE
-            assert!(self.map_size.is_none() || map_size as usize <= self.map_size.unwrap());
-
-            // we'll use this later when we truncate the observer
-            self.map_size = Some(map_size as usize);
         }
-
-        if status & FS_NEW_OPT_SHDMEM_FUZZ != 0 {
-            if map.is_some() {
-                log::info!("Using SHARED MEMORY FUZZING feature.");
-                self.uses_shmem_testcase = true;
+        Forkserver::with_diagnostics(
+            program.clone(),
+            arguments,
+            envs,
+            input_file.as_raw_fd(),
+            self.use_stdin,
+            // The forkserver takes the limit of the address space in MB, unless a cgroup limits the memory
+            if self.memory_cgroup.is_some() {
+                0
             } else {
-                return Err(Error::unknown(
-                    "Target requested sharedmem fuzzing, but you didn't prepare shmem",
-                ));
-            }
-        }
-
-        if status & FS_NEW_OPT_AUTODICT != 0 {
-            // Here unlike shmem input fuzzing, we are forced to read things
-            // hence no self.autotokens.is_some() to check if we proceed
-            let (read_len, autotokens_size) = forkserver.read_st()?;
-            if read_len != 4 {
-                return Err(Error::unknown(
-                    "Failed to read autotokens size from forkserver".to_string(),
-                ));
-            }
-
-            let tokens_size_max = 0xffffff;
-
-            if !(2..=tokens_size_max).contains(&autotokens_size) {
-                return Err(Error::illegal_state(
-                    format!("Autotokens size is incorrect, expected 2 to {tokens_size_max} (inclusive), but got {autotokens_size}. Make sure your afl-cc verison is up to date."),
-                ));
-            }
-            log::info!("Autotokens size {autotokens_size:x}");
-            let (rlen, buf) = forkserver.read_st_size(autotokens_size as usize)?;
-
-            if rlen != autotokens_size as usize {
-                return Err(Error::unknown("Failed to load autotokens".to_string()));
-            }
-            if let Some(t) = &mut self.autotokens {
-                t.parse_autodict(&buf, autotokens_size as usize);
-            }
-        }
-
-        let (read_len, aflx) = forkserver.read_st()?;
-        if read_len != 4 {
-            return Err(Error::unknown("Reading from forkserver failed".to_string()));
-        }
+                self.memory_limit.div_ceil(1 << 20)
+            },
+            self.is_persistent,
+            self.is_deferred_frksrv,
+            self.debug_child,
+            self.kill_signal.unwrap_or(KILL_SIGNAL_DEFAULT),
+            self.diagnostics(),
+        )
+    }
 
-        if aflx != version_status {
-            return Err(Error::unknown(format!(
-                "Error in forkserver communication ({:x}=>{:x})",
-                keep, aflx
-            )));
+    /// The [`ForkserverDiagnostics`] to collect, depending on the configured observers
+    fn diagnostics(&self) -> ForkserverDiagnostics {
+        ForkserverDiagnostics {
+            stderr_capture_size: self.stderr_capture_size.or(self
+                .stderr_observer
+                .as_ref()
+                .map(|_| STDERR_CAPTURE_SIZE_DEFAULT)),
+            core_dumps: self.core_dump_dir.is_some(),
         }
+    }
 
-        Ok((forkserver, input_file, map))
+    /// The [`CoreDumpCollector`] for the `target`, if core dumps are collected
+    fn core_dump_collector(&self, target: &OsStr) -> Result<Option<CoreDumpCollector>, Error> {
+        self.core_dump_dir
+            .as_ref()
+            .map(|dir| CoreDumpCollector::new(target, dir))
+            .transpose()
     }
 
     /// Use autodict?
@@ -1140,6 +2364,109 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         self.kill_signal = Some(kill_signal);
         self
     }
+
+    /// Capture the stderr of the target and pass it to the given [`StdErrObserver`] after each execution.
+    ///
+    /// Only the last `stderr_capture_size` bytes are kept, 64 KiB by default.
+    #[must_use]
+    pub fn stderr_observer(mut self, stderr_observer: Handle<StdErrObserver>) -> Self {
+        self.stderr_observer = Some(stderr_observer);
+        self
+    }
+
+    /// Capture the last `size` bytes the target writes to stderr in each execution.
+    ///
+    /// The captured stderr is also logged if the forkserver fails to start up.
+    #[must_use]
+    pub fn stderr_capture_size(mut self, size: usize) -> Self {
+        self.stderr_capture_size = Some(size);
+        self
+    }
+
+    /// Collect the core dumps of crashing children into `dir`.
+    ///
+    /// This raises the core dump size limit of the target and requires a `core_pattern` writing the
+    /// core dumps to a file, see [`CoreDumpCollector`].
+    /// Use a [`CoreDumpObserver`] and a [`crate::feedbacks::CoreDumpToMetadataFeedback`] to only keep
+    /// the core dumps of the objectives.
+    #[must_use]
+    pub fn core_dumps<P>(mut self, dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.core_dump_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Pass the path of the collected core dump to the given [`CoreDumpObserver`] after each execution.
+    #[must_use]
+    pub fn core_dump_observer(mut self, core_dump_observer: Handle<CoreDumpObserver>) -> Self {
+        self.core_dump_observer = Some(core_dump_observer);
+        self
+    }
+
+    /// Pass the runtime of each run of a [`ForkserverPoolExecutor`] to the given [`TimeObserver`].
+    ///
+    /// The runs of a batch are observed after the whole batch finished, so the [`TimeObserver`]
+    /// can't measure them itself.
+    #[must_use]
+    pub fn time_observer(mut self, time_observer: Handle<TimeObserver>) -> Self {
+        self.time_observer = Some(time_observer);
+        self
+    }
+
+    /// Limit the memory of the target to `limit` bytes, `0` (the default) for no limit.
+    ///
+    /// Without a [`Self::memory_cgroup`], this limits the address space of the target with
+    /// `RLIMIT_AS`, like AFL's `-m`. Allocations beyond it fail, which the target usually reports
+    /// as a crash. The address space limit can not be combined with core dumps.
+    #[must_use]
+    pub fn memory_limit(mut self, limit: u64) -> Self {
+        self.memory_limit = limit;
+        self
+    }
+
+    /// Enforce the [`Self::memory_limit`] with a new cgroup (v2) below the `parent` cgroup, see [`MemoryCgroup`].
+    ///
+    /// Children the OOM killer kills for exceeding the limit are reported as [`ExitKind::Oom`].
+    #[must_use]
+    pub fn memory_cgroup<P>(mut self, parent: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.memory_cgroup = Some(parent.as_ref().to_path_buf());
+        self
+    }
+
+    /// Pass the peak memory usage of each execution to the given [`MemoryObserver`].
+    ///
+    /// The peak memory usage is only known with a [`Self::memory_cgroup`], on Linux 6.12 and later.
+    #[must_use]
+    pub fn memory_observer(mut self, memory_observer: Handle<MemoryObserver>) -> Self {
+        self.memory_observer = Some(memory_observer);
+        self
+    }
+
+    /// The size of the cmplog map passed to the target in `__AFL_CMPLOG_SHM_ID`.
+    ///
+    /// Targets speaking protocol version 2 only log comparisons if the size of their cmplog map matches.
+    #[must_use]
+    pub fn cmplog_map_size(mut self, size: usize) -> Self {
+        self.cmplog_map_size = Some(size);
+        self
+    }
+
+    /// Offer the given [`ForkserverExtension`] to the target, e.g. a [`TimeoutExtension`].
+    ///
+    /// It is only used if the target supports it, which needs protocol version 2.
+    #[must_use]
+    pub fn extension<E>(mut self, extension: E) -> Self
+    where
+        E: ForkserverExtension + 'static,
+    {
+        self.extensions.push(Arc::new(extension));
+        self
+    }
 }
 
 impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
@@ -1170,6 +2497,16 @@ impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
             timeout: None,
             asan_obs: None,
             crash_exitcode: None,
+            stderr_observer: None,
+            stderr_capture_size: None,
+            core_dump_dir: None,
+            core_dump_observer: None,
+            time_observer: None,
+            memory_limit: 0,
+            memory_cgroup: None,
+            memory_observer: None,
+            cmplog_map_size: None,
+            extensions: Vec::new(),
         }
     }
 
@@ -1197,6 +2534,16 @@ impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
             timeout: None,
             asan_obs: None,
             crash_exitcode: None,
+            stderr_observer: self.stderr_observer,
+            stderr_capture_size: self.stderr_capture_size,
+            core_dump_dir: self.core_dump_dir,
+            core_dump_observer: self.core_dump_observer,
+            time_observer: self.time_observer,
+            memory_limit: self.memory_limit,
+            memory_cgroup: self.memory_cgroup,
+            memory_observer: self.memory_observer,
+            cmplog_map_size: self.cmplog_map_size,
+            extensions: self.extensions,
         }
     }
 }
@@ -1226,103 +2573,445 @@ where
     ) -> Result<ExitKind, Error> {
         *state.executions_mut() += 1;
 
-        let mut exit_kind = ExitKind::Ok;
-
-        let last_run_timed_out = self.forkserver.last_run_timed_out_raw();
-
-        let mut input_bytes = input.target_bytes();
-        let mut input_size = input_bytes.as_slice().len();
-        if input_size > self.max_input_size {
-            // Truncate like AFL++ does
-            input_size = self.max_input_size;
-        } else if input_size < self.min_input_size {
-            // Extend like AFL++ does
-            input_size = self.min_input_size;
-            let mut input_bytes_copy = Vec::with_capacity(input_size);
-            input_bytes_copy
-                .as_slice_mut()
-                .copy_from_slice(input_bytes.as_slice());
-            input_bytes = OwnedSlice::from(input_bytes_copy);
-        }
-        let input_size_in_bytes = input_size.to_ne_bytes();
-        if self.uses_shmem_testcase {
+        let testcase_shmem = if self.uses_shmem_testcase {
             debug_assert!(
                 self.map.is_some(),
                 "The uses_shmem_testcase() bool can only exist when a map is set"
             );
-            // # Safety
-            // Struct can never be created when uses_shmem_testcase is true and map is none.
-            let map = unsafe { self.map.as_mut().unwrap_unchecked() };
-            // The first four bytes declares the size of the shmem.
-            map.as_slice_mut()[..SHMEM_FUZZ_HDR_SIZE]
-                .copy_from_slice(&input_size_in_bytes[..SHMEM_FUZZ_HDR_SIZE]);
-            map.as_slice_mut()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + input_size)]
-                .copy_from_slice(&input_bytes.as_slice()[..input_size]);
+            self.map.as_mut()
         } else {
-            self.input_file
-                .write_buf(&input_bytes.as_slice()[..input_size])?;
+            None
+        };
+        write_input(
+            input.target_bytes().as_slice(),
+            self.min_input_size,
+            self.max_input_size,
+            testcase_shmem,
+            &mut self.input_file,
+        )?;
+
+        if let Some(memory_cgroup) = &self.memory_cgroup {
+            memory_cgroup.reset_peak();
         }
 
-        let send_len = self.forkserver.write_ctl(last_run_timed_out)?;
+        let pid = self.forkserver.start_child(&ExecContext {
+            timeout: self.timeout.into(),
+        })?;
 
-        self.forkserver.set_last_run_timed_out(false);
+        let mut exit_kind = self
+            .forkserver
+            .wait_child(&self.timeout, self.crash_exitcode)?;
 
-        if send_len != 4 {
-            return Err(Error::unknown(
-                "Unable to request new process from fork server (OOM?)".to_string(),
-            ));
+        if let Some(memory_cgroup) = &mut self.memory_cgroup {
+            if exit_kind == ExitKind::Crash && memory_cgroup.oom_killed()? {
+                exit_kind = ExitKind::Oom;
+            }
+            if let Some(memory_observer) = self
+                .memory_obs
+                .as_ref()
+                .and_then(|memory_obs| self.observers.get_mut(memory_obs))
+            {
+                memory_observer.set_peak_memory(memory_cgroup.peak());
+            }
         }
 
-        let (recv_pid_len, pid) = self.forkserver.read_st()?;
-        if recv_pid_len != 4 {
-            return Err(Error::unknown(
-                "Unable to request new process from fork server (OOM?)".to_string(),
-            ));
+        if let Some(stderr_obs) = &self.stderr_obs {
+            if let Some(stderr_observer) = self.observers.get_mut(stderr_obs) {
+                stderr_observer.observe_stderr(&self.forkserver.last_stderr());
+            }
         }
 
-        if pid <= 0 {
-            return Err(Error::unknown(
-                "Fork server is misbehaving (OOM?)".to_string(),
-            ));
+        if exit_kind == ExitKind::Crash {
+            if let Some(core_dumps) = &self.core_dumps {
+                let core_dump = core_dumps.collect(pid, self.forkserver.status())?;
+                if let Some(core_dump_observer) = self
+                    .core_dump_obs
+                    .as_ref()
+                    .and_then(|core_dump_obs| self.observers.get_mut(core_dump_obs))
+                {
+                    core_dump_observer.set_core_dump(core_dump);
+                }
+            }
         }
 
-        self.forkserver.set_child_pid(Pid::from_raw(pid));
+        #[cfg(feature = "regex")]
+        if exit_kind == ExitKind::Crash {
+            if let Some(asan_observer) = self.observers.get_mut(&self.asan_obs) {
+                asan_observer.parse_asan_output_from_asan_log_file(pid.as_raw())?;
+            }
+        }
 
-        if let Some(status) = self.forkserver.read_st_timed(&self.timeout)? {
-            self.forkserver.set_status(status);
-            let exitcode_is_crash = if let Some(crash_exitcode) = self.crash_exitcode {
-                (libc::WEXITSTATUS(self.forkserver().status()) as i8) == crash_exitcode
-            } else {
-                false
-            };
-            if libc::WIFSIGNALED(self.forkserver().status()) || exitcode_is_crash {
-                exit_kind = ExitKind::Crash;
-                #[cfg(feature = "regex")]
-                if let Some(asan_observer) = self.observers.get_mut(&self.asan_obs) {
-                    asan_observer.parse_asan_output_from_asan_log_file(pid)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S, SP> UsesState for ForkserverExecutor<OT, S, SP>
+where
+    S: State,
+    SP: ShMemProvider,
+{
+    type State = S;
+}
+
+impl<OT, S, SP> UsesObservers for ForkserverExecutor<OT, S, SP>
+where
+    OT: ObserversTuple<S>,
+    S: State,
+    SP: ShMemProvider,
+{
+    type Observers = OT;
+}
+
+impl<OT, S, SP> HasObservers for ForkserverExecutor<OT, S, SP>
+where
+    OT: ObserversTuple<S>,
+    S: State,
+    SP: ShMemProvider,
+{
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+impl<OT, S, SP> HasTimeout for ForkserverExecutor<OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout.into()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout.into();
+    }
+}
+
+impl<OT, S, SP> ReportsTimeouts for ForkserverExecutor<OT, S, SP> where SP: ShMemProvider {}
+
+/// A forkserver of a [`ForkserverPoolExecutor`], with its own coverage map and input
+#[derive(Debug)]
+struct ForkserverPoolWorker<SHM> {
+    forkserver: Forkserver,
+    input_file: InputFile,
+    coverage_map: SHM,
+    /// The shared memory testcase, if used by the target
+    testcase_shmem: Option<SHM>,
+    /// The pid of the last child
+    pid: Option<Pid>,
+    /// How the last run ended
+    exit_kind: ExitKind,
+    /// How long the last run took
+    runtime: Duration,
+    /// The collected core dump of the last run
+    core_dump: Option<PathBuf>,
+}
+
+/// This [`Executor`] runs several forkservers of the same AFL/AFL++ binary, to run multiple inputs
+/// concurrently within one client, see [`BatchExecutor`].
+///
+/// This keeps the cores busy for targets with a high latency per execution (I/O, sleeps),
+/// without spawning more clients. Build it with [`ForkserverExecutorBuilder::build_pool`].
+pub struct ForkserverPoolExecutor<C, OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    target: OsString,
+    args: Vec<OsString>,
+    workers: Vec<ForkserverPoolWorker<SP::ShMem>>,
+    observers: OT,
+    map_observer: Handle<C>,
+    min_input_size: usize,
+    max_input_size: usize,
+    timeout: Duration,
+    crash_exitcode: Option<i8>,
+    #[cfg(feature = "regex")]
+    asan_obs: Handle<AsanBacktraceObserver>,
+    stderr_obs: Option<Handle<StdErrObserver>>,
+    core_dumps: Option<CoreDumpCollector>,
+    core_dump_obs: Option<Handle<CoreDumpObserver>>,
+    time_obs: Option<Handle<TimeObserver>>,
+    /// The provider of the coverage maps, kept alive if none was given to the builder
+    _shmem_provider: Option<SP>,
+    phantom: PhantomData<S>,
+}
+
+impl<C, OT, S, SP> Debug for ForkserverPoolExecutor<C, OT, S, SP>
+where
+    OT: Debug,
+    SP: ShMemProvider,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForkserverPoolExecutor")
+            .field("target", &self.target)
+            .field("args", &self.args)
+            .field("workers", &self.workers)
+            .field("observers", &self.observers)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, OT, S, SP> ForkserverPoolExecutor<C, OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    /// The `target` binary that's going to run.
+    pub fn target(&self) -> &OsString {
+        &self.target
+    }
+
+    /// The `args` used for the binary.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The number of forkservers in this pool
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Get a reference to the [`Forkserver`] of the worker `idx`.
+    pub fn forkserver(&self, idx: usize) -> Option<&Forkserver> {
+        self.workers.get(idx).map(|worker| &worker.forkserver)
+    }
+
+    /// Get a mutable reference to the [`Forkserver`] of the worker `idx`.
+    pub fn forkserver_mut(&mut self, idx: usize) -> Option<&mut Forkserver> {
+        self.workers
+            .get_mut(idx)
+            .map(|worker| &mut worker.forkserver)
+    }
+}
+
+impl<C, OT, S, SP> ForkserverPoolExecutor<C, OT, S, SP>
+where
+    C: MapObserver<Entry = u8> + for<'it> AsSliceMut<'it, Entry = u8>,
+    OT: ObserversTuple<S>,
+    S: UsesInput,
+    SP: ShMemProvider,
+{
+    /// Wait until one of the `pending` workers reported the status of its child, or the `deadline` passed.
+    ///
+    /// Returns the workers that have a status to read, none after the `deadline`.
+    /// The stderr of the children is drained meanwhile, so that they never block on a full pipe.
+    fn wait_workers(&mut self, pending: &[usize], deadline: Duration) -> Result<Vec<usize>, Error> {
+        loop {
+            let mut st_fds = Vec::with_capacity(pending.len());
+            let mut readfds = FdSet::new();
+            for &idx in pending {
+                let forkserver = &self.workers[idx].forkserver;
+                let st_read = forkserver.st_pipe.read_end().ok_or_else(|| {
+                    Error::os_error(
+                        io::Error::new(ErrorKind::BrokenPipe, "Read pipe end was already closed"),
+                        "wait_workers failed",
+                    )
+                })?;
+                // # Safety
+                // The pipes stay open while we select on them.
+                let st_read = unsafe { BorrowedFd::borrow_raw(st_read) };
+                readfds.insert(st_read);
+                st_fds.push((idx, st_read));
+                if let Some(stderr) = &forkserver.stderr {
+                    readfds.insert(unsafe { BorrowedFd::borrow_raw(stderr.as_raw_fd()) });
                 }
             }
-        } else {
-            self.forkserver.set_last_run_timed_out(true);
 
-            // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
-            let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
-            let (recv_status_len, _) = self.forkserver.read_st()?;
-            if recv_status_len != 4 {
-                return Err(Error::unknown("Could not kill timed-out child".to_string()));
+            let remaining: TimeSpec = deadline.saturating_sub(current_time()).into();
+            let sret = pselect(
+                Some(readfds.highest().unwrap().as_raw_fd() + 1),
+                &mut readfds,
+                None,
+                None,
+                Some(&remaining),
+                Some(&SigSet::empty()),
+            )?;
+            if sret <= 0 {
+                return Ok(Vec::new());
+            }
+
+            let finished: Vec<usize> = st_fds
+                .into_iter()
+                .filter(|(_, st_read)| readfds.contains(*st_read))
+                .map(|(idx, _)| idx)
+                .collect();
+            if !finished.is_empty() {
+                return Ok(finished);
+            }
+            // Only stderr is ready
+            for &idx in pending {
+                self.workers[idx].forkserver.drain_stderr();
             }
-            exit_kind = ExitKind::Timeout;
         }
+    }
 
-        if !libc::WIFSTOPPED(self.forkserver().status()) {
-            self.forkserver.reset_child_pid();
+    /// Copy the coverage map, the stderr, the core dump and the runtime of the worker `idx` to the observers
+    fn load_worker_result(&mut self, idx: usize) -> Result<(), Error> {
+        let worker = self.workers.get(idx).ok_or_else(|| {
+            Error::illegal_argument(format!("No worker {idx} in the forkserver pool"))
+        })?;
+
+        {
+            let map_observer = self
+                .observers
+                .get_mut(&self.map_observer)
+                .ok_or_else(|| Error::illegal_state("The map observer is missing"))?;
+            let mut map = map_observer.as_slice_mut();
+            let len = map.len().min(worker.coverage_map.len());
+            map[..len].copy_from_slice(&worker.coverage_map.as_slice()[..len]);
         }
 
+        if let Some(stderr_observer) = self
+            .stderr_obs
+            .as_ref()
+            .and_then(|stderr_obs| self.observers.get_mut(stderr_obs))
+        {
+            stderr_observer.observe_stderr(&worker.forkserver.last_stderr());
+        }
+
+        if let Some(core_dump_observer) = self
+            .core_dump_obs
+            .as_ref()
+            .and_then(|core_dump_obs| self.observers.get_mut(core_dump_obs))
+        {
+            core_dump_observer.set_core_dump(worker.core_dump.clone());
+        }
+
+        if let Some(time_observer) = self
+            .time_obs
+            .as_ref()
+            .and_then(|time_obs| self.observers.get_mut(time_obs))
+        {
+            time_observer.set_runtime(worker.runtime);
+        }
+
+        #[cfg(feature = "regex")]
+        if worker.exit_kind == ExitKind::Crash {
+            if let (Some(asan_observer), Some(pid)) =
+                (self.observers.get_mut(&self.asan_obs), worker.pid)
+            {
+                asan_observer.parse_asan_output_from_asan_log_file(pid.as_raw())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C, EM, OT, S, SP, Z> Executor<EM, Z> for ForkserverPoolExecutor<C, OT, S, SP>
+where
+    C: MapObserver<Entry = u8> + for<'it> AsSliceMut<'it, Entry = u8>,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    #[inline]
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        let exit_kind = self.run_batch(fuzzer, state, mgr, core::slice::from_ref(input))?[0];
+        self.load_worker_result(0)?;
         Ok(exit_kind)
     }
 }
 
-impl<OT, S, SP> UsesState for ForkserverExecutor<OT, S, SP>
+impl<C, EM, OT, S, SP, Z> BatchExecutor<EM, Z> for ForkserverPoolExecutor<C, OT, S, SP>
+where
+    C: MapObserver<Entry = u8> + for<'it> AsSliceMut<'it, Entry = u8>,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    fn batch_size(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn run_batch(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        inputs: &[Self::Input],
+    ) -> Result<Vec<ExitKind>, Error> {
+        if inputs.len() > self.workers.len() {
+            return Err(Error::illegal_argument(format!(
+                "Batch of {} inputs exceeds the {} workers of the pool",
+                inputs.len(),
+                self.workers.len()
+            )));
+        }
+        *state.executions_mut() += inputs.len() as u64;
+
+        // Start all children, then collect them as they finish
+        let ctx = ExecContext {
+            timeout: self.timeout,
+        };
+        let start = current_time();
+        for (worker, input) in self.workers.iter_mut().zip(inputs) {
+            worker.coverage_map.as_slice_mut().fill(0);
+            write_input(
+                input.target_bytes().as_slice(),
+                self.min_input_size,
+                self.max_input_size,
+                worker.testcase_shmem.as_mut(),
+                &mut worker.input_file,
+            )?;
+            worker.pid = Some(worker.forkserver.start_child(&ctx)?);
+        }
+
+        let deadline = start + self.timeout;
+        let mut pending: Vec<usize> = (0..inputs.len()).collect();
+        while !pending.is_empty() {
+            let finished = self.wait_workers(&pending, deadline)?;
+            // Past the deadline, all pending children time out
+            let timed_out = finished.is_empty();
+            for idx in pending.clone() {
+                if !timed_out && !finished.contains(&idx) {
+                    continue;
+                }
+                let worker = &mut self.workers[idx];
+                let remaining = deadline.saturating_sub(current_time());
+                worker.exit_kind = worker
+                    .forkserver
+                    .wait_child(&remaining.into(), self.crash_exitcode)?;
+                worker.runtime = current_time().saturating_sub(start).min(self.timeout);
+                worker.core_dump = match (&self.core_dumps, worker.pid) {
+                    (Some(core_dumps), Some(pid)) if worker.exit_kind == ExitKind::Crash => {
+                        core_dumps.collect(pid, worker.forkserver.status())?
+                    }
+                    _ => None,
+                };
+                pending.retain(|&pending_idx| pending_idx != idx);
+            }
+        }
+        Ok(self.workers[..inputs.len()]
+            .iter()
+            .map(|worker| worker.exit_kind)
+            .collect())
+    }
+
+    fn load_batch_result(&mut self, idx: usize) -> Result<(), Error> {
+        self.load_worker_result(idx)
+    }
+}
+
+impl<C, OT, S, SP> UsesState for ForkserverPoolExecutor<C, OT, S, SP>
 where
     S: State,
     SP: ShMemProvider,
@@ -1330,7 +3019,7 @@ where
     type State = S;
 }
 
-impl<OT, S, SP> UsesObservers for ForkserverExecutor<OT, S, SP>
+impl<C, OT, S, SP> UsesObservers for ForkserverPoolExecutor<C, OT, S, SP>
 where
     OT: ObserversTuple<S>,
     S: State,
@@ -1339,7 +3028,7 @@ where
     type Observers = OT;
 }
 
-impl<OT, S, SP> HasObservers for ForkserverExecutor<OT, S, SP>
+impl<C, OT, S, SP> HasObservers for ForkserverPoolExecutor<C, OT, S, SP>
 where
     OT: ObserversTuple<S>,
     S: State,
@@ -1356,20 +3045,56 @@ where
     }
 }
 
+impl<C, OT, S, SP> HasTimeout for ForkserverPoolExecutor<C, OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    /// The timeout of a whole batch of executions
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<C, OT, S, SP> ReportsTimeouts for ForkserverPoolExecutor<C, OT, S, SP> where SP: ShMemProvider {}
+
 #[cfg(test)]
 mod tests {
-    use std::ffi::OsString;
+    use alloc::{sync::Arc, vec::Vec};
+    use core::time::Duration;
+    use std::{
+        collections::VecDeque,
+        env,
+        ffi::OsString,
+        fs::{self, File},
+        io::{Read, Write},
+        os::fd::FromRawFd,
+        path::PathBuf,
+        process::Command,
+    };
 
     use libafl_bolts::{
+        os::pipes::Pipe,
         shmem::{ShMem, ShMemProvider, UnixShMemProvider},
-        tuples::tuple_list,
+        tuples::{tuple_list, Handled},
         AsSliceMut,
     };
+    use nix::{sys::signal::Signal, unistd::Pid};
     use serial_test::serial;
 
     use crate::{
-        executors::forkserver::ForkserverExecutor,
-        observers::{ConstMapObserver, HitcountsMapObserver},
+        executors::forkserver::{
+            forkserver_handshake, parse_oom_kills, CoreDumpCollector, ExecContext, Forkserver,
+            ForkserverExecutor, ForkserverExtension, HandshakeOffer, TimeoutExtension,
+            FS_AFLPP_VERSION_BASE, FS_EXT_TIMEOUT, FS_LIBAFL_VERSION_BASE, FS_NEW_OPT_EXTENSIONS,
+            FS_NEW_OPT_MAPSIZE, FS_NEW_OPT_MAP_ADDR,
+        },
+        observers::{ConstMapObserver, HitcountsMapObserver, StdMapObserver},
         Error,
     };
 
@@ -1409,4 +3134,271 @@ mod tests {
         };
         assert!(result);
     }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_forkserver_pool() {
+        let bin = OsString::from("echo");
+        let args = vec![OsString::from("@@")];
+
+        let mut map = vec![0_u8; 65536];
+        let edges_observer = unsafe { StdMapObserver::new("shared_mem", &mut map) };
+        let edges_handle = edges_observer.handle();
+
+        let executor = ForkserverExecutor::builder()
+            .program(bin)
+            .args(args)
+            .debug_child(false)
+            .coverage_map_size(65536)
+            .build_pool::<_, _, ()>(4, edges_handle, tuple_list!(edges_observer));
+
+        // As with the single forkserver, echo fails the initial handshake
+        let result = match executor {
+            Ok(_) => true,
+            Err(e) => match e {
+                Error::Unknown(s, _) => s == "Failed to start a forkserver",
+                _ => false,
+            },
+        };
+        assert!(result);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_core_dump_collector() {
+        let dir = env::temp_dir().join("libafl_test_core_dumps");
+        let target = OsString::from("/usr/bin/a_very_long_target_name");
+        // A crash by SIGSEGV that dumped core
+        let status = libc::SIGSEGV | 0x80;
+        let pid = Pid::from_raw(42);
+
+        // Piping to a program, and unsupported specifiers
+        assert!(CoreDumpCollector::with_core_pattern(
+            "|/usr/lib/systemd/systemd-coredump %P",
+            false,
+            &target,
+            &dir
+        )
+        .is_err());
+        assert!(CoreDumpCollector::with_core_pattern("core.%t", false, &target, &dir).is_err());
+        assert!(CoreDumpCollector::with_core_pattern("core.%", false, &target, &dir).is_err());
+
+        let collector =
+            CoreDumpCollector::with_core_pattern("/cores/%e.%p.%s.%%", true, &target, &dir)
+                .unwrap();
+        assert_eq!(
+            collector.core_dump_path(pid, status),
+            PathBuf::from(format!("/cores/a_very_long_tar.42.{}.%", libc::SIGSEGV))
+        );
+
+        let collector = CoreDumpCollector::with_core_pattern("core", true, &target, &dir).unwrap();
+        assert_eq!(
+            collector.core_dump_path(pid, status),
+            PathBuf::from("core.42")
+        );
+
+        let collector = CoreDumpCollector::with_core_pattern("", false, &target, &dir).unwrap();
+        assert_eq!(collector.core_dump_path(pid, status), PathBuf::from("core"));
+
+        // No core dump without a crash
+        assert_eq!(collector.collect(pid, 0).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_oom_kills() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), Some(2));
+        assert_eq!(parse_oom_kills("low 0\nhigh 0\n"), None);
+    }
+
+    /// A [`Forkserver`] with the test as its target, returning the ends of the status and control pipes of the target
+    fn pipe_forkserver() -> (Forkserver, File, File) {
+        let mut st_pipe = Pipe::new().unwrap();
+        let mut ctl_pipe = Pipe::new().unwrap();
+        // # Safety
+        // We own the duplicated fds.
+        let (target_st, target_ctl) = unsafe {
+            (
+                File::from_raw_fd(libc::dup(st_pipe.write_end().unwrap())),
+                File::from_raw_fd(libc::dup(ctl_pipe.read_end().unwrap())),
+            )
+        };
+        st_pipe.close_write_end();
+        ctl_pipe.close_read_end();
+
+        let forkserver = Forkserver {
+            // Only stands in for the forkserver process, to be killed on drop
+            fsrv_handle: Command::new("sleep").arg("60").spawn().unwrap(),
+            st_pipe,
+            ctl_pipe,
+            child_pid: None,
+            status: 0,
+            last_run_timed_out: 0,
+            kill_signal: Signal::SIGKILL,
+            stderr: None,
+            stderr_buffer: VecDeque::new(),
+            stderr_capture_size: 0,
+            version: 0,
+            map_addr: None,
+            cmplog_map_size: None,
+            extensions: Vec::new(),
+        };
+        (forkserver, target_st, target_ctl)
+    }
+
+    fn write_u32s(file: &mut File, vals: &[u32]) {
+        for val in vals {
+            file.write_all(&val.to_ne_bytes()).unwrap();
+        }
+    }
+
+    fn read_u32s(file: &mut File, count: usize) -> Vec<u32> {
+        (0..count)
+            .map(|_| {
+                let mut buf = [0; 4];
+                file.read_exact(&mut buf).unwrap();
+                u32::from_ne_bytes(buf)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_handshake_libafl() {
+        let (mut forkserver, mut target_st, mut target_ctl) = pipe_forkserver();
+
+        // The target side of the handshake does not depend on our answers, so it is written upfront
+        let hello = FS_LIBAFL_VERSION_BASE + 2;
+        write_u32s(
+            &mut target_st,
+            &[
+                hello,
+                (FS_NEW_OPT_MAPSIZE | FS_NEW_OPT_EXTENSIONS) as u32,
+                // The map size, rounded up to 128
+                100,
+                // Two extensions, we only know the first one
+                2,
+                FS_EXT_TIMEOUT,
+                1234,
+                hello,
+            ],
+        );
+
+        let extensions: Vec<Arc<dyn ForkserverExtension>> = vec![Arc::new(TimeoutExtension)];
+        let mut map_size = Some(1024);
+        let uses_shmem_testcase = forkserver_handshake(
+            &mut forkserver,
+            &mut map_size,
+            HandshakeOffer {
+                has_testcase_shmem: false,
+                cmplog_map_size: None,
+                autotokens: None,
+                extensions: &extensions,
+            },
+        )
+        .unwrap();
+        assert!(!uses_shmem_testcase);
+        assert_eq!(forkserver.version(), 2);
+        assert_eq!(map_size, Some(128));
+        assert_eq!(forkserver.extensions().len(), 1);
+
+        // The reply to the hello, then the accepted options and extensions
+        assert_eq!(
+            read_u32s(&mut target_ctl, 4),
+            [
+                !hello,
+                (FS_NEW_OPT_MAPSIZE | FS_NEW_OPT_EXTENSIONS) as u32,
+                1,
+                FS_EXT_TIMEOUT
+            ]
+        );
+
+        // Each new child gets the timeout of its own execution
+        for (timeout_ms, pid) in [(1000, 4242), (250, 4243)] {
+            write_u32s(&mut target_st, &[pid]);
+            let child = forkserver
+                .start_child(&ExecContext {
+                    timeout: Duration::from_millis(timeout_ms.into()),
+                })
+                .unwrap();
+            assert_eq!(child, Pid::from_raw(pid.try_into().unwrap()));
+            // The timed out flag, then the payload of the timeout extension
+            assert_eq!(read_u32s(&mut target_ctl, 2), [0, timeout_ms]);
+            // There is no such child to kill on drop
+            forkserver.reset_child_pid();
+        }
+    }
+
+    #[test]
+    fn test_handshake_aflpp() {
+        let (mut forkserver, mut target_st, mut target_ctl) = pipe_forkserver();
+
+        // AFL++ may assign the `LibAFL` options, which must not be read after its hello
+        let hello = FS_AFLPP_VERSION_BASE + 1;
+        write_u32s(
+            &mut target_st,
+            &[
+                hello,
+                (FS_NEW_OPT_MAPSIZE | FS_NEW_OPT_MAP_ADDR) as u32,
+                64,
+                hello,
+            ],
+        );
+
+        let extensions: Vec<Arc<dyn ForkserverExtension>> = vec![Arc::new(TimeoutExtension)];
+        let mut map_size = None;
+        forkserver_handshake(
+            &mut forkserver,
+            &mut map_size,
+            HandshakeOffer {
+                has_testcase_shmem: false,
+                cmplog_map_size: None,
+                autotokens: None,
+                extensions: &extensions,
+            },
+        )
+        .unwrap();
+        assert_eq!(forkserver.version(), 1);
+        assert_eq!(map_size, Some(64));
+        assert_eq!(forkserver.map_addr(), None);
+        assert!(forkserver.extensions().is_empty());
+
+        // Version 1 has no acknowledgement, and the children get no payload
+        write_u32s(&mut target_st, &[4242]);
+        forkserver
+            .start_child(&ExecContext {
+                timeout: Duration::from_secs(1),
+            })
+            .unwrap();
+        forkserver.reset_child_pid();
+        drop(forkserver);
+        let mut rest = Vec::new();
+        target_ctl.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [(!hello).to_ne_bytes(), 0_u32.to_ne_bytes()].concat());
+    }
+
+    #[test]
+    fn test_timeout_extension() {
+        let extension = TimeoutExtension::new();
+        let mut payload = vec![];
+        extension.exec_payload(
+            &ExecContext {
+                timeout: Duration::from_millis(1234),
+            },
+            &mut payload,
+        );
+        assert_eq!(payload, 1234_u32.to_ne_bytes());
+
+        // Timeouts are capped to what the protocol can express
+        let mut payload = vec![];
+        extension.exec_payload(
+            &ExecContext {
+                timeout: Duration::MAX,
+            },
+            &mut payload,
+        );
+        assert_eq!(payload, u32::MAX.to_ne_bytes());
+    }
 }
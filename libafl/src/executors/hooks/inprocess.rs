@@ -1,6 +1,8 @@
 //! The hook for `InProcessExecutor`
 #[cfg(any(unix, feature = "std"))]
 use core::ptr::addr_of_mut;
+#[cfg(all(unix, feature = "std"))]
+use core::sync::atomic::AtomicUsize;
 #[cfg(any(unix, all(windows, feature = "std")))]
 use core::sync::atomic::{compiler_fence, Ordering};
 use core::{
@@ -11,6 +13,8 @@ use core::{
 };
 #[cfg(all(target_os = "linux", feature = "std"))]
 use core::{mem::zeroed, ptr::addr_of};
+#[cfg(all(unix, feature = "std"))]
+use std::thread;
 
 #[cfg(all(target_os = "linux", feature = "std"))]
 use libafl_bolts::current_time;
@@ -210,6 +214,9 @@ where
             (*data).timeout_handler = self.timeout_handler;
         }
 
+        #[cfg(all(unix, feature = "std"))]
+        FUZZER_THREAD.store(current_thread(), Ordering::Release);
+
         #[cfg(all(feature = "std", not(all(miri, target_vendor = "apple"))))]
         self.timer_mut().set_timer();
     }
@@ -217,6 +224,10 @@ where
     /// Call after running a target.
     #[allow(clippy::unused_self)]
     fn post_exec(&mut self, _state: &mut S, _input: &S::Input) {
+        // Another thread of the harness may still be saving its crash, don't continue fuzzing meanwhile
+        #[cfg(all(unix, feature = "std"))]
+        wait_for_handler();
+
         // timeout stuff
         #[cfg(all(feature = "std", not(all(miri, target_vendor = "apple"))))]
         self.timer_mut().unset_timer();
@@ -451,6 +462,68 @@ pub(crate) static mut GLOBAL_STATE: InProcessExecutorHandlerData = InProcessExec
     critical: null_mut(),
 };
 
+/// The thread running a crash or timeout handler, `0` if none.
+///
+/// Harnesses may run the target on several threads, which may crash or time out at the same time.
+/// Only one of them handles it, as the handlers save the state and exit.
+#[cfg(all(unix, feature = "std"))]
+static HANDLER_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+/// The thread running the harness, set before each run
+#[cfg(all(unix, feature = "std"))]
+static FUZZER_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+/// An id of the current thread
+#[cfg(all(unix, feature = "std"))]
+fn current_thread() -> usize {
+    #[allow(clippy::cast_possible_truncation, clippy::useless_conversion)]
+    unsafe {
+        libc::pthread_self() as usize
+    }
+}
+
+/// Enter a crash or timeout handler on the current thread, waiting while another thread is in one.
+///
+/// Returns `false` if the current thread already is in a handler, i.e. it crashed in the handler.
+#[cfg(all(unix, feature = "std"))]
+pub(crate) fn enter_handler() -> bool {
+    let thread = current_thread();
+    loop {
+        match HANDLER_THREAD.compare_exchange(0, thread, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return true,
+            Err(handler_thread) if handler_thread == thread => return false,
+            // The other handler usually exits the process, so we never continue
+            Err(_) => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+}
+
+/// Leave the crash or timeout handler entered with [`enter_handler`]
+#[cfg(all(unix, feature = "std"))]
+pub(crate) fn leave_handler() {
+    HANDLER_THREAD.store(0, Ordering::Release);
+}
+
+/// Wait while another thread is in a crash or timeout handler
+#[cfg(all(unix, feature = "std"))]
+fn wait_for_handler() {
+    let thread = current_thread();
+    loop {
+        let handler_thread = HANDLER_THREAD.load(Ordering::Acquire);
+        if handler_thread == 0 || handler_thread == thread {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// If the current thread runs the harness, as opposed to a thread the harness spawned
+#[cfg(all(unix, feature = "std"))]
+#[must_use]
+pub fn inprocess_is_fuzzer_thread() -> bool {
+    FUZZER_THREAD.load(Ordering::Acquire) == current_thread()
+}
+
 /// Get the inprocess [`crate::state::State`]
 #[must_use]
 pub fn inprocess_get_state<'a, S>() -> Option<&'a mut S> {
@@ -486,3 +559,44 @@ pub fn inprocess_get_input<'a, I>() -> Option<&'a I> {
 pub fn inprocess_in_handler() -> bool {
     unsafe { GLOBAL_STATE.in_handler }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[serial_test::serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(all(unix, feature = "std"))]
+    fn test_handler_serialization() {
+        use alloc::sync::Arc;
+        use core::{
+            sync::atomic::{AtomicBool, Ordering},
+            time::Duration,
+        };
+        use std::thread;
+
+        use super::{enter_handler, leave_handler, wait_for_handler};
+
+        assert!(enter_handler());
+        // Crashing in the handler must not wait for the handler itself
+        assert!(!enter_handler());
+        wait_for_handler();
+
+        let entered = Arc::new(AtomicBool::new(false));
+        let other = thread::spawn({
+            let entered = entered.clone();
+            move || {
+                assert!(enter_handler());
+                entered.store(true, Ordering::Release);
+                leave_handler();
+            }
+        });
+        // The other thread waits for the first handler to leave
+        thread::sleep(Duration::from_millis(50));
+        assert!(!entered.load(Ordering::Acquire));
+
+        leave_handler();
+        other.join().unwrap();
+        assert!(entered.load(Ordering::Acquire));
+        wait_for_handler();
+    }
+}
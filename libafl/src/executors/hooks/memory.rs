@@ -0,0 +1,301 @@
+//! The [`MemoryLimitHook`] enforces a memory limit on the target of the in-process executors.
+//!
+//! The peak memory usage of the last run, recorded by the hook or the executor,
+//! is passed to the [`crate::observers::MemoryObserver`].
+
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use core::{
+    mem::size_of,
+    ptr,
+    sync::atomic::{AtomicI32, AtomicPtr, AtomicU8, AtomicUsize},
+    time::Duration,
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::thread;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use libafl_bolts::os::{current_rss, peak_rss, process_rss, reset_peak_rss};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::{
+    executors::{hooks::ExecutorHook, HasObservers},
+    inputs::UsesInput,
+    Error,
+};
+
+/// The exit code of fork children the [`MemoryLimitHook`] aborted for exceeding the memory limit.
+///
+/// The hook aborts them with `SIGABRT`, but `128 + SIGABRT` is what the crash handler exits with
+/// for real aborts. `128 + SIGKILL` is the status shells report for processes the OOM killer
+/// killed, and as `SIGKILL` can not be handled, no crash handler ever exits with it otherwise.
+pub const OOM_EXIT_CODE: i32 = 128 + libc::SIGKILL;
+
+/// The peak memory usage of the last run in bytes, `0` if unknown
+static LAST_PEAK_MEMORY: AtomicU64 = AtomicU64::new(0);
+
+/// The target is not running
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TARGET_IDLE: u8 = 0;
+/// The target is running, the watchdog may abort it
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TARGET_RUNNING: u8 = 1;
+/// The target exceeded the memory limit, and the watchdog aborts it
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TARGET_OOM: u8 = 2;
+
+/// The target watched by the watchdog of the [`MemoryLimitHook`].
+///
+/// This lives in memory shared with the fork children, so that the watchdog in the fuzzer
+/// sees the targets run by the [`crate::executors::InProcessForkExecutor`], and their crash
+/// handlers see when the watchdog aborts them.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+#[derive(Debug)]
+struct WatchedTarget {
+    /// One of `TARGET_IDLE`, `TARGET_RUNNING` or `TARGET_OOM`
+    state: AtomicU8,
+    /// The process running the target
+    pid: AtomicI32,
+}
+
+/// The [`WatchedTarget`], mapped by the first [`MemoryLimitHook`]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+static WATCHED_TARGET: AtomicPtr<WatchedTarget> = AtomicPtr::new(ptr::null_mut());
+
+/// The thread running the target, aborted by the watchdog if it runs in the same process
+#[cfg(any(target_os = "linux", target_os = "android"))]
+static TARGET_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+/// The [`WatchedTarget`], if a [`MemoryLimitHook`] was initialized
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn watched_target() -> Option<&'static WatchedTarget> {
+    unsafe { WATCHED_TARGET.load(Ordering::Acquire).as_ref() }
+}
+
+/// Map the [`WatchedTarget`] in memory shared with the fork children, once
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn map_watched_target() -> Result<&'static WatchedTarget, Error> {
+    if let Some(target) = watched_target() {
+        return Ok(target);
+    }
+    let target = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size_of::<WatchedTarget>(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if target == libc::MAP_FAILED {
+        return Err(Error::last_os_error(
+            "Could not map the state of the memory watchdog",
+        ));
+    }
+    // The anonymous mapping is zeroed, i.e. `TARGET_IDLE`, and never unmapped
+    WATCHED_TARGET.store(target.cast(), Ordering::Release);
+    Ok(unsafe { &*target.cast::<WatchedTarget>() })
+}
+
+/// Record the peak memory usage of the last run in bytes, for the [`crate::observers::MemoryObserver`]
+pub fn set_last_peak_memory(peak: Option<u64>) {
+    LAST_PEAK_MEMORY.store(peak.unwrap_or(0), Ordering::Release);
+}
+
+/// Take the peak memory usage of the last run in bytes, if it was recorded
+pub fn take_last_peak_memory() -> Option<u64> {
+    let peak = LAST_PEAK_MEMORY.swap(0, Ordering::AcqRel);
+    (peak != 0).then_some(peak)
+}
+
+/// If the target exceeded the memory limit of the [`MemoryLimitHook`] in the current run.
+///
+/// The crash handlers report [`crate::executors::ExitKind::Oom`] instead of a crash, if so.
+#[must_use]
+pub fn is_oom() -> bool {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    return watched_target()
+        .is_some_and(|target| target.state.load(Ordering::Acquire) == TARGET_OOM);
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    false
+}
+
+/// Reset the state of the watched target, after the fork child running it exited
+pub(crate) fn reset_target() {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(target) = watched_target() {
+        target.state.store(TARGET_IDLE, Ordering::Release);
+    }
+}
+
+/// The default interval the [`MemoryLimitHook`] polls the memory usage in
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const POLL_INTERVAL_DEFAULT: Duration = Duration::from_millis(10);
+
+/// Aborts the target if the rss (Resident Set Size) of the process exceeds the limit, like
+/// libFuzzer's `-rss_limit_mb`. Add it to the user hooks of the
+/// [`crate::executors::InProcessExecutor`] or [`crate::executors::InProcessForkExecutor`].
+///
+/// A watchdog thread, started once in the process creating the executor, polls the rss while the
+/// target runs, and aborts the target with `SIGABRT` when it exceeds the limit.
+/// The crash handlers then report [`crate::executors::ExitKind::Oom`].
+/// For the [`crate::executors::InProcessExecutor`], the rss is that of the whole process,
+/// including the memory of the fuzzer; for the [`crate::executors::InProcessForkExecutor`], that of the
+/// fork child.
+/// The hook also records the peak rss of each run, for the [`crate::observers::MemoryObserver`].
+///
+/// Only use one [`MemoryLimitHook`] per process.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug, Clone)]
+pub struct MemoryLimitHook {
+    limit: u64,
+    poll_interval: Duration,
+    /// If the watchdog thread was started
+    watchdog: bool,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl MemoryLimitHook {
+    /// Create a new [`MemoryLimitHook`], limiting the rss to `limit` bytes.
+    #[must_use]
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            poll_interval: POLL_INTERVAL_DEFAULT,
+            watchdog: false,
+        }
+    }
+
+    /// Set the interval to poll the rss in, 10 ms by default.
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// The memory limit in bytes
+    #[must_use]
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Start the watchdog thread in the current process
+    fn spawn_watchdog(&self) -> Result<(), Error> {
+        let target = map_watched_target()?;
+        let limit = self.limit;
+        let poll_interval = self.poll_interval;
+        #[allow(clippy::cast_possible_wrap)]
+        let own_pid = std::process::id() as i32;
+        thread::Builder::new()
+            .name("memory-watchdog".into())
+            .spawn(move || loop {
+                thread::sleep(poll_interval);
+                if target.state.load(Ordering::Acquire) != TARGET_RUNNING {
+                    continue;
+                }
+                let pid = target.pid.load(Ordering::Acquire);
+                let rss = if pid == own_pid {
+                    current_rss()
+                } else {
+                    process_rss(pid)
+                };
+                let Ok(rss) = rss else {
+                    continue;
+                };
+                if rss > limit
+                    && target
+                        .state
+                        .compare_exchange(
+                            TARGET_RUNNING,
+                            TARGET_OOM,
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        )
+                        .is_ok()
+                {
+                    LAST_PEAK_MEMORY.store(rss, Ordering::Release);
+                    unsafe {
+                        if pid == own_pid {
+                            libc::pthread_kill(
+                                TARGET_THREAD.load(Ordering::Acquire) as libc::pthread_t,
+                                libc::SIGABRT,
+                            );
+                        } else {
+                            libc::kill(pid, libc::SIGABRT);
+                        }
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl<S> ExecutorHook<S> for MemoryLimitHook
+where
+    S: UsesInput,
+{
+    fn init<E: HasObservers>(&mut self, _state: &mut S) {
+        // The executors initialize the hooks before forking, so fork children share this watchdog
+        if !self.watchdog {
+            self.spawn_watchdog()
+                .expect("Failed to start the memory watchdog");
+            self.watchdog = true;
+        }
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) {
+        let target = watched_target().expect("The MemoryLimitHook was not initialized");
+        if let Err(err) = reset_peak_rss() {
+            log::debug!("Could not reset the peak rss: {err}");
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        TARGET_THREAD.store(unsafe { libc::pthread_self() } as usize, Ordering::Release);
+        #[allow(clippy::cast_possible_wrap)]
+        target
+            .pid
+            .store(std::process::id() as i32, Ordering::Release);
+        target.state.store(TARGET_RUNNING, Ordering::Release);
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &S::Input) {
+        let target = watched_target().expect("The MemoryLimitHook was not initialized");
+        if target
+            .state
+            .compare_exchange(
+                TARGET_RUNNING,
+                TARGET_IDLE,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // The watchdog is aborting the target, wait for the signal to arrive
+            loop {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        set_last_peak_memory(peak_rss().ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::{set_last_peak_memory, take_last_peak_memory};
+
+    #[test]
+    #[serial]
+    fn test_last_peak_memory() {
+        set_last_peak_memory(Some(42));
+        assert_eq!(take_last_peak_memory(), Some(42));
+        // Taken once per run
+        assert_eq!(take_last_peak_memory(), None);
+
+        set_last_peak_memory(None);
+        assert_eq!(take_last_peak_memory(), None);
+    }
+}
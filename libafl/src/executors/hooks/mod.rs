@@ -18,6 +18,10 @@ pub mod inprocess_fork;
 /// The hook for inprocess executor
 pub mod inprocess;
 
+/// The memory limit hook and the peak memory usage of the last run
+#[cfg(all(unix, feature = "std"))]
+pub mod memory;
+
 /// Timer-related stuff
 #[cfg(feature = "std")]
 pub mod timer;
@@ -162,7 +162,7 @@ impl TimerStruct {
         let milli_sec = exec_tmout.as_millis();
         let it_value = Timeval {
             tv_sec: (milli_sec / 1000) as i64,
-            tv_usec: (milli_sec % 1000) as i64,
+            tv_usec: ((milli_sec % 1000) * 1000) as i64,
         };
         let it_interval = Timeval {
             tv_sec: 0,
@@ -244,6 +244,59 @@ impl TimerStruct {
         }
     }
 
+    /// The timeout of each execution
+    #[cfg(all(unix, not(target_os = "linux")))]
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.itimerval.it_value.tv_sec as u64)
+            + Duration::from_micros(self.itimerval.it_value.tv_usec as u64)
+    }
+
+    /// The timeout of each execution
+    #[cfg(windows)]
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.milli_sec as u64)
+    }
+
+    /// The timeout of each execution
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn timeout(&self) -> Duration {
+        self.exec_tmout
+    }
+
+    /// Set the timeout of the next executions
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn set_timeout(&mut self, exec_tmout: Duration) {
+        let milli_sec = exec_tmout.as_millis();
+        self.itimerval.it_value = Timeval {
+            tv_sec: (milli_sec / 1000) as i64,
+            tv_usec: ((milli_sec % 1000) * 1000) as i64,
+        };
+    }
+
+    /// Set the timeout of the next executions
+    #[cfg(windows)]
+    pub fn set_timeout(&mut self, exec_tmout: Duration) {
+        self.milli_sec = exec_tmout.as_millis() as i64;
+    }
+
+    /// Set the timeout of the next executions
+    ///
+    /// In batch mode, the timer that is already armed keeps its old timeout.
+    #[cfg(target_os = "linux")]
+    pub fn set_timeout(&mut self, exec_tmout: Duration) {
+        let milli_sec = exec_tmout.as_millis();
+        self.exec_tmout = exec_tmout;
+        self.itimerspec.it_value = libc::timespec {
+            tv_sec: (milli_sec / 1000) as _,
+            tv_nsec: ((milli_sec % 1000) * 1000 * 1000) as _,
+        };
+    }
+
     #[cfg(target_os = "linux")]
     #[must_use]
     /// Constructor but use batch mode
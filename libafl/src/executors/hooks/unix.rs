@@ -12,7 +12,13 @@ pub mod unix_signal_handler {
         events::{EventFirer, EventRestarter},
         executors::{
             common_signals,
-            hooks::inprocess::{HasTimeout, InProcessExecutorHandlerData, GLOBAL_STATE},
+            hooks::{
+                inprocess::{
+                    enter_handler, inprocess_is_fuzzer_thread, leave_handler, HasTimeout,
+                    InProcessExecutorHandlerData, GLOBAL_STATE,
+                },
+                memory,
+            },
             inprocess::{run_observers_and_save_state, HasInProcessHooks},
             Executor, ExitKind, HasObservers,
         },
@@ -48,6 +54,8 @@ pub mod unix_signal_handler {
             context: Option<&mut ucontext_t>,
         ) {
             unsafe {
+                // Threads of the harness crashing at the same time wait for the first one
+                let first_handler = enter_handler();
                 let data = addr_of_mut!(GLOBAL_STATE);
                 let in_handler = (*data).set_in_handler(true);
                 match signal {
@@ -65,6 +73,9 @@ pub mod unix_signal_handler {
                     }
                 }
                 (*data).set_in_handler(in_handler);
+                if first_handler {
+                    leave_handler();
+                }
             }
         }
 
@@ -85,6 +96,7 @@ pub mod unix_signal_handler {
         let old_hook = panic::take_hook();
         panic::set_hook(Box::new(move |panic_info| unsafe {
             old_hook(panic_info);
+            let first_handler = enter_handler();
             let data = addr_of_mut!(GLOBAL_STATE);
             let in_handler = (*data).set_in_handler(true);
             if (*data).is_valid() {
@@ -107,9 +119,35 @@ pub mod unix_signal_handler {
                 libc::_exit(128 + 6); // SIGABRT exit code
             }
             (*data).set_in_handler(in_handler);
+            if first_handler {
+                leave_handler();
+            }
         }));
     }
 
+    /// Log the threads of the process, which a timeout interrupts all, with their scheduling state
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn log_threads() {
+        let Ok(tasks) = std::fs::read_dir("/proc/self/task") else {
+            return;
+        };
+        for task in tasks.flatten() {
+            let path = task.path();
+            let name = std::fs::read_to_string(path.join("comm")).unwrap_or_default();
+            // The state follows the name in parentheses, which may contain spaces
+            let stat = std::fs::read_to_string(path.join("stat")).unwrap_or_default();
+            let state = stat
+                .rsplit_once(')')
+                .and_then(|(_, stat)| stat.split_whitespace().next())
+                .unwrap_or("?");
+            log::error!(
+                "Thread {} ({}): state {state}",
+                task.file_name().to_string_lossy(),
+                name.trim()
+            );
+        }
+    }
+
     /// Timeout-Handler for in-process fuzzing.
     /// It will store the current State to shmem, then exit.
     ///
@@ -151,6 +189,8 @@ pub mod unix_signal_handler {
         let input = data.take_current_input::<<E::State as UsesInput>::Input>();
 
         log::error!("Timeout in fuzz run.");
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        log_threads();
 
         run_observers_and_save_state::<E, EM, OF, Z>(
             executor,
@@ -200,6 +240,17 @@ pub mod unix_signal_handler {
             let input = data.take_current_input::<<E::State as UsesInput>::Input>();
 
             log::error!("Child crashed!");
+            if !inprocess_is_fuzzer_thread() {
+                log::error!("The crash happened on another thread of the harness");
+            }
+
+            // The watchdog of the `MemoryLimitHook` aborts targets exceeding the memory limit
+            let exit_kind = if memory::is_oom() {
+                log::error!("Out of memory!");
+                ExitKind::Oom
+            } else {
+                ExitKind::Crash
+            };
 
             {
                 let mut bsod = Vec::new();
@@ -223,12 +274,12 @@ pub mod unix_signal_handler {
             }
 
             run_observers_and_save_state::<E, EM, OF, Z>(
-                executor,
-                state,
-                input,
-                fuzzer,
-                event_mgr,
-                ExitKind::Crash,
+                executor, state, input, fuzzer, event_mgr, exit_kind,
+            );
+        } else if !inprocess_is_fuzzer_thread() {
+            // The harness returned, so we don't know the input that caused the crash
+            log::error!(
+                "A thread of the harness crashed with {signal} after the run. Harnesses need to wait for their threads before returning."
             );
         } else {
             {
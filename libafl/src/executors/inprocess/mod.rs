@@ -20,6 +20,8 @@ use libafl_bolts::tuples::{tuple_list, RefIndexable};
 
 #[cfg(any(unix, feature = "std"))]
 use crate::executors::hooks::inprocess::GLOBAL_STATE;
+#[cfg(feature = "std")]
+use crate::executors::HasTimeout;
 use crate::{
     events::{EventFirer, EventRestarter},
     executors::{
@@ -57,6 +59,13 @@ pub type OwnedInProcessExecutor<OT, S> = GenericInProcessExecutor<
 >;
 
 /// The inmem executor simply calls a target function, then returns afterwards.
+///
+/// The harness may run the target on several threads, e.g. in a thread pool. On unix, crashes and
+/// panics on any of them are reported for the current input, and a timeout interrupts all of them.
+/// The coverage maps are shared by all threads, so the harness needs to wait for its threads
+/// before returning, for their coverage to be observed, and their crashes to be attributed to the input.
+/// The signal handlers need a larger alternate signal stack than the one Rust sets up for new threads,
+/// so each thread of the harness has to install a [`libafl_bolts::os::unix_signals::SignalStack`] first.
 #[allow(dead_code)]
 pub struct GenericInProcessExecutor<H, HB, HT, OT, S>
 where
@@ -161,6 +170,26 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<H, HB, HT, OT, S> HasTimeout for GenericInProcessExecutor<H, HB, HT, OT, S>
+where
+    H: FnMut(&S::Input) -> ExitKind + ?Sized,
+    HB: BorrowMut<H>,
+    HT: ExecutorHooksTuple<S>,
+    OT: ObserversTuple<S>,
+    S: State,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.inner.hooks.0.timer.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.inner.hooks.0.timer.set_timeout(timeout);
+    }
+}
+
 impl<'a, H, OT, S> InProcessExecutor<'a, H, OT, S>
 where
     H: FnMut(&S::Input) -> ExitKind + ?Sized,
@@ -562,4 +591,107 @@ mod tests {
             .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
             .unwrap();
     }
+
+    #[test]
+    #[serial_test::serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    fn test_inprocess_memory_limit() {
+        use alloc::{borrow::Cow, vec::Vec};
+        use core::{hint::black_box, time::Duration};
+
+        use libafl_bolts::{os::current_rss, Named};
+
+        use crate::{
+            events::EventFirer,
+            executors::{hooks::memory::MemoryLimitHook, inprocess::HookableInProcessExecutor},
+            feedbacks::Feedback,
+            observers::ObserversTuple,
+            state::State,
+            Error,
+        };
+
+        /// The exit code of the fork child once a run was reported as [`ExitKind::Oom`]
+        const OOM_REPORTED: i32 = 42;
+
+        /// Exits with [`OOM_REPORTED`] when the crash handler reports [`ExitKind::Oom`]
+        #[derive(Debug)]
+        struct OomReportedFeedback;
+
+        impl Named for OomReportedFeedback {
+            fn name(&self) -> &Cow<'static, str> {
+                static NAME: Cow<'static, str> = Cow::Borrowed("OomReportedFeedback");
+                &NAME
+            }
+        }
+
+        impl<S> Feedback<S> for OomReportedFeedback
+        where
+            S: State,
+        {
+            fn is_interesting<EM, OT>(
+                &mut self,
+                _state: &mut S,
+                _manager: &mut EM,
+                _input: &S::Input,
+                _observers: &OT,
+                exit_kind: &ExitKind,
+            ) -> Result<bool, Error>
+            where
+                EM: EventFirer<State = S>,
+                OT: ObserversTuple<S>,
+            {
+                if *exit_kind == ExitKind::Oom {
+                    unsafe { libc::_exit(OOM_REPORTED) };
+                }
+                Ok(false)
+            }
+        }
+
+        const CHUNK_SIZE: usize = 16 << 20;
+
+        // The crash handler exits the process, so the executor runs in a fork child
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0, "fork failed");
+        if pid == 0 {
+            let rand = libafl_bolts::rands::XkcdRand::new();
+            let corpus = InMemoryCorpus::<NopInput>::new();
+            let solutions = InMemoryCorpus::new();
+            let mut objective = OomReportedFeedback;
+            let mut feedback = tuple_list!();
+            let mut mgr = NopEventManager::new();
+            let mut state =
+                StdState::new(rand, corpus, solutions, &mut feedback, &mut objective).unwrap();
+            let mut fuzzer = StdFuzzer::<_, _, _>::new(RandScheduler::new(), feedback, objective);
+
+            // The target exceeds the limit after a few chunks, and gives up long after that
+            let limit = current_rss().unwrap() + 4 * CHUNK_SIZE as u64;
+            let mut harness = |_input: &NopInput| {
+                let mut chunks = Vec::new();
+                for _ in 0..64 {
+                    chunks.push(black_box(vec![1_u8; CHUNK_SIZE]));
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                ExitKind::Ok
+            };
+            let mut executor = HookableInProcessExecutor::with_timeout_generic(
+                tuple_list!(MemoryLimitHook::new(limit).poll_interval(Duration::from_millis(1))),
+                &mut harness,
+                tuple_list!(),
+                &mut fuzzer,
+                &mut state,
+                &mut mgr,
+                Duration::from_secs(10),
+            )
+            .unwrap();
+            let _ = executor.run_target(&mut fuzzer, &mut state, &mut mgr, &NopInput {});
+            // The watchdog did not abort the target
+            unsafe { libc::_exit(1) };
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFEXITED(status), "the child was killed: {status:#x}");
+        assert_eq!(libc::WEXITSTATUS(status), OOM_REPORTED);
+    }
 }
@@ -10,6 +10,8 @@ use core::{
 
 use libafl_bolts::tuples::{tuple_list, RefIndexable};
 
+#[cfg(feature = "std")]
+use crate::executors::HasTimeout;
 use crate::{
     events::{EventFirer, EventRestarter},
     executors::{
@@ -152,6 +154,26 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<H, HB, HT, OT, S, ES> HasTimeout for StatefulGenericInProcessExecutor<H, HB, HT, OT, S, ES>
+where
+    H: FnMut(&S::Input, &mut ES) -> ExitKind + ?Sized,
+    HB: BorrowMut<H>,
+    HT: ExecutorHooksTuple<S>,
+    OT: ObserversTuple<S>,
+    S: State,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.inner.hooks.0.timer.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.inner.hooks.0.timer.set_timeout(timeout);
+    }
+}
+
 impl<'a, H, OT, S, ES> StatefulInProcessExecutor<'a, H, OT, S, ES>
 where
     H: FnMut(&<S as UsesInput>::Input, &mut ES) -> ExitKind + ?Sized,
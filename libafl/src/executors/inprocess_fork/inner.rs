@@ -2,6 +2,7 @@ use core::{
     ffi::c_void,
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
+    mem::MaybeUninit,
     ptr::{self, addr_of_mut, null_mut, write_volatile},
     sync::atomic::{compiler_fence, Ordering},
     time::Duration,
@@ -12,11 +13,10 @@ use libafl_bolts::{
     shmem::ShMemProvider,
     tuples::{tuple_list, Merge, RefIndexable},
 };
-use nix::{
-    sys::wait::{waitpid, WaitStatus},
-    unistd::Pid,
-};
+use nix::{sys::wait::WaitStatus, unistd::Pid};
 
+#[cfg(feature = "fork")]
+use crate::executors::forkserver::MemoryCgroup;
 #[cfg(all(unix, not(target_os = "linux")))]
 use crate::executors::hooks::timer::{setitimer, Itimerval, Timeval, ITIMER_REAL};
 use crate::{
@@ -24,9 +24,9 @@ use crate::{
     executors::{
         hooks::{
             inprocess_fork::{InChildProcessHooks, FORK_EXECUTOR_GLOBAL_DATA},
-            ExecutorHooksTuple,
+            memory, ExecutorHooksTuple,
         },
-        ExitKind, HasObservers,
+        ExitKind, HasObservers, HasTimeout,
     },
     inputs::UsesInput,
     observers::{ObserversTuple, UsesObservers},
@@ -51,6 +51,8 @@ where
     pub(super) itimerspec: libc::itimerspec,
     #[cfg(all(unix, not(target_os = "linux")))]
     pub(super) itimerval: Itimerval,
+    #[cfg(feature = "fork")]
+    pub(super) memory_cgroup: Option<MemoryCgroup>,
     pub(super) phantom: PhantomData<(S, EM, Z)>,
 }
 
@@ -114,6 +116,13 @@ where
     ) -> Result<(), Error> {
         self.shmem_provider.post_fork(true)?;
 
+        #[cfg(feature = "fork")]
+        if let Some(memory_cgroup) = &self.memory_cgroup {
+            memory_cgroup
+                .add_process(nix::unistd::getpid())
+                .expect("Failed to move the child into the memory cgroup");
+        }
+
         self.enter_target(fuzzer, state, mgr, input);
         self.hooks.pre_exec_all(state, input);
 
@@ -161,32 +170,70 @@ where
         // log::trace!("from parent {} child is {}", std::process::id(), child);
         self.shmem_provider.post_fork(false)?;
 
-        let res = waitpid(child, None)?;
+        let mut status = 0;
+        let mut rusage = MaybeUninit::<libc::rusage>::uninit();
+        if unsafe { libc::wait4(child.as_raw(), &mut status, 0, rusage.as_mut_ptr()) } == -1 {
+            return Err(Error::last_os_error(format!(
+                "Error waiting for the child {child}"
+            )));
+        }
+        let rusage = unsafe { rusage.assume_init() };
+        // The peak rss of the child, for the `MemoryObserver`, in kilobytes (bytes on Apple platforms)
+        #[allow(clippy::cast_sign_loss)]
+        let max_rss = rusage.ru_maxrss as u64;
+        #[cfg(not(target_vendor = "apple"))]
+        let max_rss = max_rss << 10;
+        // The child may have been aborted by the `MemoryLimitHook` before leaving the target
+        memory::reset_target();
+
+        let res = WaitStatus::from_raw(child, status)?;
         log::trace!("{res:#?}");
-        match res {
-            WaitStatus::Signaled(_, signal, _) => match signal {
-                nix::sys::signal::Signal::SIGALRM | nix::sys::signal::Signal::SIGUSR2 => {
-                    Ok(ExitKind::Timeout)
-                }
-                _ => Ok(ExitKind::Crash),
-            },
-            WaitStatus::Exited(_, code) => {
-                if code > 128 && code < 160 {
-                    // Signal exit codes
-                    let signal = code - 128;
-                    if signal == Signal::SigAlarm as libc::c_int
-                        || signal == Signal::SigUser2 as libc::c_int
-                    {
-                        Ok(ExitKind::Timeout)
-                    } else {
-                        Ok(ExitKind::Crash)
-                    }
+
+        #[cfg(feature = "fork")]
+        if let Some(memory_cgroup) = &mut self.memory_cgroup {
+            let oom_killed = memory_cgroup.oom_killed()?;
+            memory::set_last_peak_memory(Some(memory_cgroup.peak().unwrap_or(max_rss)));
+            // The cgroup is empty again, the peak of the next run starts here
+            memory_cgroup.reset_peak();
+            return Ok(child_exit_kind(res, oom_killed));
+        }
+
+        memory::set_last_peak_memory(Some(max_rss));
+        Ok(child_exit_kind(res, false))
+    }
+}
+
+/// The [`ExitKind`] of a fork child, from its [`WaitStatus`].
+///
+/// Children killed with `SIGKILL` are only reported as [`ExitKind::Oom`] if `oom_killed`, i.e. the
+/// OOM killer of their memory cgroup killed them. Children the `MemoryLimitHook` aborted exit
+/// with [`memory::OOM_EXIT_CODE`].
+pub(super) fn child_exit_kind(status: WaitStatus, oom_killed: bool) -> ExitKind {
+    match status {
+        WaitStatus::Signaled(_, signal, _) => match signal {
+            nix::sys::signal::Signal::SIGALRM | nix::sys::signal::Signal::SIGUSR2 => {
+                ExitKind::Timeout
+            }
+            nix::sys::signal::Signal::SIGKILL if oom_killed => ExitKind::Oom,
+            _ => ExitKind::Crash,
+        },
+        WaitStatus::Exited(_, memory::OOM_EXIT_CODE) => ExitKind::Oom,
+        WaitStatus::Exited(_, code) => {
+            if code > 128 && code < 160 {
+                // Signal exit codes
+                let signal = code - 128;
+                if signal == Signal::SigAlarm as libc::c_int
+                    || signal == Signal::SigUser2 as libc::c_int
+                {
+                    ExitKind::Timeout
                 } else {
-                    Ok(ExitKind::Ok)
+                    ExitKind::Crash
                 }
+            } else {
+                ExitKind::Ok
             }
-            _ => Ok(ExitKind::Ok),
         }
+        _ => ExitKind::Ok,
     }
 }
 
@@ -273,6 +320,8 @@ where
             observers,
             hooks,
             itimerspec,
+            #[cfg(feature = "fork")]
+            memory_cgroup: None,
             phantom: PhantomData,
         })
     }
@@ -296,7 +345,7 @@ where
         let milli_sec = timeout.as_millis();
         let it_value = Timeval {
             tv_sec: (milli_sec / 1000) as i64,
-            tv_usec: (milli_sec % 1000) as i64,
+            tv_usec: ((milli_sec % 1000) * 1000) as i64,
         };
         let it_interval = Timeval {
             tv_sec: 0,
@@ -312,6 +361,8 @@ where
             observers,
             hooks,
             itimerval,
+            #[cfg(feature = "fork")]
+            memory_cgroup: None,
             phantom: PhantomData,
         })
     }
@@ -329,6 +380,48 @@ where
     type Observers = OT;
 }
 
+impl<HT, OT, S, SP, EM, Z> HasTimeout for GenericInProcessForkExecutorInner<HT, OT, S, SP, EM, Z>
+where
+    HT: ExecutorHooksTuple<S>,
+    S: State,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    #[cfg(target_os = "linux")]
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        let it_value = &self.itimerspec.it_value;
+        Duration::new(it_value.tv_sec as _, it_value.tv_nsec as _)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[allow(clippy::cast_sign_loss)]
+    fn timeout(&self) -> Duration {
+        let it_value = &self.itimerval.it_value;
+        Duration::from_secs(it_value.tv_sec as u64) + Duration::from_micros(it_value.tv_usec as u64)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_timeout(&mut self, timeout: Duration) {
+        let milli_sec = timeout.as_millis();
+        self.itimerspec.it_value = libc::timespec {
+            tv_sec: (milli_sec / 1000) as _,
+            tv_nsec: ((milli_sec % 1000) * 1000 * 1000) as _,
+        };
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_timeout(&mut self, timeout: Duration) {
+        let milli_sec = timeout.as_millis();
+        self.itimerval.it_value = Timeval {
+            tv_sec: (milli_sec / 1000) as i64,
+            tv_usec: ((milli_sec % 1000) * 1000) as i64,
+        };
+    }
+}
+
 impl<HT, OT, S, SP, EM, Z> HasObservers for GenericInProcessForkExecutorInner<HT, OT, S, SP, EM, Z>
 where
     HT: ExecutorHooksTuple<S>,
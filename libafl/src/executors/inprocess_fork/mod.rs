@@ -13,11 +13,14 @@ use libc::siginfo_t;
 use nix::unistd::{fork, ForkResult};
 
 use super::hooks::ExecutorHooksTuple;
+#[cfg(feature = "fork")]
+use crate::executors::forkserver::MemoryCgroup;
 use crate::{
     events::{EventFirer, EventRestarter},
     executors::{
         hooks::inprocess_fork::InProcessForkExecutorGlobalData,
         inprocess_fork::inner::GenericInProcessForkExecutorInner, Executor, ExitKind, HasObservers,
+        HasTimeout, ReportsTimeouts,
     },
     feedbacks::Feedback,
     fuzzer::HasObjective,
@@ -218,6 +221,18 @@ where {
         })
     }
 
+    /// Limit the memory of the fork children with the given [`MemoryCgroup`].
+    ///
+    /// Children the OOM killer kills for exceeding the limit are reported as [`ExitKind::Oom`],
+    /// and the peak memory usage of the cgroup is passed to the [`crate::observers::MemoryObserver`].
+    #[cfg(feature = "fork")]
+    #[must_use]
+    pub fn memory_cgroup(mut self, memory_cgroup: MemoryCgroup) -> Self {
+        memory_cgroup.reset_peak();
+        self.inner.memory_cgroup = Some(memory_cgroup);
+        self
+    }
+
     /// Retrieve the harness function.
     #[inline]
     pub fn harness(&self) -> &H {
@@ -245,6 +260,41 @@ where
     type Observers = OT;
 }
 
+impl<'a, H, HT, OT, S, SP, EM, Z> HasTimeout
+    for GenericInProcessForkExecutor<'a, H, HT, OT, S, SP, EM, Z>
+where
+    H: FnMut(&S::Input) -> ExitKind + ?Sized,
+    HT: ExecutorHooksTuple<S>,
+    S: State,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.inner.set_timeout(timeout);
+    }
+}
+
+impl<'a, H, HT, OT, S, SP, EM, Z> ReportsTimeouts
+    for GenericInProcessForkExecutor<'a, H, HT, OT, S, SP, EM, Z>
+where
+    H: FnMut(&S::Input) -> ExitKind + ?Sized,
+    HT: ExecutorHooksTuple<S>,
+    S: State,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+}
+
 impl<'a, H, HT, OT, S, SP, EM, Z> HasObservers
     for GenericInProcessForkExecutor<'a, H, HT, OT, S, SP, EM, Z>
 where
@@ -279,7 +329,10 @@ pub mod child_signal_handlers {
 
     use crate::{
         executors::{
-            hooks::inprocess_fork::{InProcessForkExecutorGlobalData, FORK_EXECUTOR_GLOBAL_DATA},
+            hooks::{
+                inprocess_fork::{InProcessForkExecutorGlobalData, FORK_EXECUTOR_GLOBAL_DATA},
+                memory,
+            },
             ExitKind, HasObservers,
         },
         inputs::UsesInput,
@@ -326,17 +379,25 @@ pub mod child_signal_handlers {
     ) where
         E: HasObservers,
     {
+        // The watchdog of the `MemoryLimitHook` aborts targets exceeding the memory limit,
+        // which we report to the parent with a dedicated exit code
+        let (exit_kind, exit_code) = if memory::is_oom() {
+            (ExitKind::Oom, memory::OOM_EXIT_CODE)
+        } else {
+            (ExitKind::Crash, 128 + (_signal as i32))
+        };
+
         if data.is_valid() {
             let executor = data.executor_mut::<E>();
             let mut observers = executor.observers_mut();
             let state = data.state_mut::<E::State>();
             let input = data.take_current_input::<<E::State as UsesInput>::Input>();
             observers
-                .post_exec_child_all(state, input, &ExitKind::Crash)
+                .post_exec_child_all(state, input, &exit_kind)
                 .expect("Failed to run post_exec on observers");
         }
 
-        libc::_exit(128 + (_signal as i32));
+        libc::_exit(exit_code);
     }
 
     #[cfg(unix)]
@@ -429,6 +490,8 @@ mod tests {
                 shmem_provider: provider,
                 observers: tuple_list!(),
                 itimerspec,
+                #[cfg(feature = "fork")]
+                memory_cgroup: None,
                 phantom: PhantomData,
             },
         };
@@ -440,6 +503,8 @@ mod tests {
                 shmem_provider: provider,
                 observers: tuple_list!(),
                 itimerval: itimerspec,
+                #[cfg(feature = "fork")]
+                memory_cgroup: None,
                 phantom: PhantomData,
             },
         };
@@ -451,4 +516,114 @@ mod tests {
             .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
             .unwrap();
     }
+
+    #[test]
+    fn test_child_exit_kind() {
+        use nix::{
+            sys::{signal::Signal, wait::WaitStatus},
+            unistd::Pid,
+        };
+
+        use crate::executors::{
+            hooks::memory::OOM_EXIT_CODE, inprocess_fork::inner::child_exit_kind,
+        };
+
+        let child = Pid::from_raw(1);
+        let signaled = |signal| WaitStatus::Signaled(child, signal, false);
+        assert_eq!(
+            child_exit_kind(WaitStatus::Exited(child, 0), false),
+            ExitKind::Ok
+        );
+        assert_eq!(
+            child_exit_kind(signaled(Signal::SIGSEGV), false),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            child_exit_kind(signaled(Signal::SIGALRM), false),
+            ExitKind::Timeout
+        );
+        // Only the OOM killer of the memory cgroup makes a `SIGKILL` an OOM
+        assert_eq!(
+            child_exit_kind(signaled(Signal::SIGKILL), false),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            child_exit_kind(signaled(Signal::SIGKILL), true),
+            ExitKind::Oom
+        );
+        // The crash handler exits with this when the `MemoryLimitHook` aborted the child
+        assert_eq!(
+            child_exit_kind(WaitStatus::Exited(child, OOM_EXIT_CODE), false),
+            ExitKind::Oom
+        );
+        assert_eq!(
+            child_exit_kind(WaitStatus::Exited(child, 128 + libc::SIGABRT), false),
+            ExitKind::Crash
+        );
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(all(feature = "std", feature = "fork", target_os = "linux"))]
+    fn test_inprocessfork_memory_limit() {
+        use alloc::vec::Vec;
+        use core::{hint::black_box, time::Duration};
+
+        use libafl_bolts::{
+            os::current_rss,
+            shmem::{ShMemProvider, StdShMemProvider},
+        };
+
+        use crate::{
+            corpus::InMemoryCorpus,
+            events::NopEventManager,
+            executors::{
+                hooks::memory::MemoryLimitHook, inprocess_fork::GenericInProcessForkExecutor,
+            },
+            feedbacks::CrashFeedback,
+            schedulers::RandScheduler,
+            state::StdState,
+            StdFuzzer,
+        };
+
+        const CHUNK_SIZE: usize = 16 << 20;
+
+        let rand = libafl_bolts::rands::XkcdRand::new();
+        let corpus = InMemoryCorpus::<NopInput>::new();
+        let solutions = InMemoryCorpus::new();
+        let mut objective = CrashFeedback::new();
+        let mut feedback = tuple_list!();
+        let mut mgr = NopEventManager::new();
+        let mut state =
+            StdState::new(rand, corpus, solutions, &mut feedback, &mut objective).unwrap();
+        let mut fuzzer = StdFuzzer::<_, _, _>::new(RandScheduler::new(), feedback, objective);
+
+        // The child exceeds the limit after a few chunks, and gives up long after that
+        let limit = current_rss().unwrap() + 4 * CHUNK_SIZE as u64;
+        let mut harness = |_input: &NopInput| {
+            let mut chunks = Vec::new();
+            for _ in 0..64 {
+                chunks.push(black_box(vec![1_u8; CHUNK_SIZE]));
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            ExitKind::Ok
+        };
+        let mut executor = GenericInProcessForkExecutor::with_hooks(
+            tuple_list!(MemoryLimitHook::new(limit).poll_interval(Duration::from_millis(1))),
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+            Duration::from_secs(10),
+            StdShMemProvider::new().unwrap(),
+        )
+        .unwrap();
+
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &NopInput {})
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Oom);
+    }
 }
@@ -11,11 +11,13 @@ use libafl_bolts::{
 };
 use nix::unistd::{fork, ForkResult};
 
+#[cfg(feature = "fork")]
+use crate::executors::forkserver::MemoryCgroup;
 use crate::{
     events::{EventFirer, EventRestarter},
     executors::{
         hooks::ExecutorHooksTuple, inprocess_fork::GenericInProcessForkExecutorInner, Executor,
-        ExitKind, HasObservers,
+        ExitKind, HasObservers, HasTimeout, ReportsTimeouts,
     },
     feedbacks::Feedback,
     fuzzer::HasObjective,
@@ -209,6 +211,18 @@ where
         })
     }
 
+    /// Limit the memory of the fork children with the given [`MemoryCgroup`].
+    ///
+    /// Children the OOM killer kills for exceeding the limit are reported as [`ExitKind::Oom`],
+    /// and the peak memory usage of the cgroup is passed to the [`crate::observers::MemoryObserver`].
+    #[cfg(feature = "fork")]
+    #[must_use]
+    pub fn memory_cgroup(mut self, memory_cgroup: MemoryCgroup) -> Self {
+        memory_cgroup.reset_peak();
+        self.inner.memory_cgroup = Some(memory_cgroup);
+        self
+    }
+
     /// Retrieve the harness function.
     #[inline]
     pub fn harness(&self) -> &H {
@@ -236,6 +250,41 @@ where
     type Observers = OT;
 }
 
+impl<'a, H, HT, OT, S, SP, ES, EM, Z> HasTimeout
+    for StatefulGenericInProcessForkExecutor<'a, H, HT, OT, S, SP, ES, EM, Z>
+where
+    H: FnMut(&S::Input, &mut ES) -> ExitKind + ?Sized,
+    HT: ExecutorHooksTuple<S>,
+    S: State,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.inner.set_timeout(timeout);
+    }
+}
+
+impl<'a, H, HT, OT, S, SP, ES, EM, Z> ReportsTimeouts
+    for StatefulGenericInProcessForkExecutor<'a, H, HT, OT, S, SP, ES, EM, Z>
+where
+    H: FnMut(&S::Input, &mut ES) -> ExitKind + ?Sized,
+    HT: ExecutorHooksTuple<S>,
+    S: State,
+    OT: ObserversTuple<S>,
+    SP: ShMemProvider,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+}
+
 impl<'a, H, HT, OT, S, SP, ES, EM, Z> HasObservers
     for StatefulGenericInProcessForkExecutor<'a, H, HT, OT, S, SP, ES, EM, Z>
 where
@@ -1,23 +1,30 @@
 //! Executors take input, and run it in the target.
 
-#[cfg(unix)]
 use alloc::vec::Vec;
-use core::fmt::Debug;
+use core::{fmt::Debug, time::Duration};
 
 pub use combined::CombinedExecutor;
 #[cfg(all(feature = "std", any(unix, doc)))]
 pub use command::CommandExecutor;
-pub use differential::DiffExecutor;
+pub use differential::{DiffExecutor, MultiDiffExecutor};
 #[cfg(all(feature = "std", feature = "fork", unix))]
-pub use forkserver::{Forkserver, ForkserverExecutor};
+pub use forkserver::{Forkserver, ForkserverExecutor, ForkserverPoolExecutor};
 pub use inprocess::InProcessExecutor;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use inprocess_fork::InProcessForkExecutor;
 #[cfg(unix)]
 use libafl_bolts::os::unix_signals::Signal;
 use libafl_bolts::tuples::RefIndexable;
+#[cfg(all(
+    feature = "std",
+    feature = "fork",
+    target_os = "linux",
+    target_arch = "x86_64"
+))]
+pub use ptrace::PtraceBreakpointExecutor;
 use serde::{Deserialize, Serialize};
 pub use shadow::ShadowExecutor;
+pub use timeout_calibrator::{ReportsTimeouts, TimeoutCalibrationMetadata, TimeoutCalibrator};
 pub use with_observers::WithObservers;
 
 use crate::{
@@ -38,8 +45,19 @@ pub mod inprocess;
 #[cfg(all(feature = "std", unix))]
 pub mod inprocess_fork;
 
+/// The module for the ptrace breakpoint executor
+#[cfg(all(
+    feature = "std",
+    feature = "fork",
+    target_os = "linux",
+    target_arch = "x86_64"
+))]
+pub mod ptrace;
+
 pub mod shadow;
 
+pub mod timeout_calibrator;
+
 pub mod with_observers;
 
 /// The module for all the hooks
@@ -145,6 +163,45 @@ where
     }
 }
 
+/// An executor that can run several inputs concurrently, such as the `ForkserverPoolExecutor`.
+///
+/// The results of a batch are kept by the executor, and loaded into the observers one by one,
+/// so that the observers and feedbacks see the runs in order, as if they were executed sequentially.
+pub trait BatchExecutor<EM, Z>: Executor<EM, Z> + HasObservers
+where
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    /// The maximum number of inputs run concurrently
+    fn batch_size(&self) -> usize;
+
+    /// Run up to [`Self::batch_size`] inputs concurrently, returning their [`ExitKind`]s in order.
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        inputs: &[Self::Input],
+    ) -> Result<Vec<ExitKind>, Error>;
+
+    /// Load the results of the `idx`-th run of the last batch into the observers.
+    ///
+    /// Call this between the `pre_exec` and the `post_exec` of the observers.
+    /// As the run already happened, observers measuring it, like the [`crate::observers::TimeObserver`],
+    /// must get their result from here, see [`crate::observers::TimeObserver::set_runtime`].
+    fn load_batch_result(&mut self, idx: usize) -> Result<(), Error>;
+}
+
+/// An executor with a timeout for each execution, which can be adjusted while fuzzing,
+/// e.g. by the [`TimeoutCalibrator`].
+pub trait HasTimeout {
+    /// The timeout of each execution
+    fn timeout(&self) -> Duration;
+
+    /// Set the timeout of the next executions
+    fn set_timeout(&mut self, timeout: Duration);
+}
+
 /// The common signals we want to handle
 #[cfg(unix)]
 #[inline]
@@ -166,15 +223,20 @@ pub fn common_signals() -> Vec<Signal> {
 
 #[cfg(test)]
 pub mod test {
-    use core::marker::PhantomData;
+    use alloc::vec::Vec;
+    use core::{marker::PhantomData, time::Duration};
 
-    use libafl_bolts::{AsSlice, Error};
+    use libafl_bolts::{
+        tuples::{tuple_list, tuple_list_type, RefIndexable},
+        AsSlice, AsSliceMut, Error,
+    };
 
     use crate::{
         events::NopEventManager,
-        executors::{Executor, ExitKind},
+        executors::{BatchExecutor, Executor, ExitKind, HasObservers},
         fuzzer::test::NopFuzzer,
         inputs::{BytesInput, HasTargetBytes},
+        observers::{MapObserver, StdMapObserver, TimeObserver, UsesObservers},
         state::{HasExecutions, NopState, State, UsesState},
     };
 
@@ -231,6 +293,131 @@ pub mod test {
         }
     }
 
+    /// The observers of the [`NopBatchExecutor`]
+    pub type NopBatchObservers = tuple_list_type!(StdMapObserver<'static, u8, false>, TimeObserver);
+
+    /// A [`BatchExecutor`] that does nothing, but reports fake results for each run.
+    ///
+    /// A run covers the map index of the first byte of the input, and takes as many milliseconds.
+    #[derive(Debug)]
+    pub struct NopBatchExecutor<S> {
+        observers: NopBatchObservers,
+        batch_size: usize,
+        /// The results of the last batch
+        results: Vec<(usize, Duration)>,
+        /// The length of each batch run so far
+        pub batches: Vec<usize>,
+        phantom: PhantomData<S>,
+    }
+
+    impl<S> NopBatchExecutor<S> {
+        /// Creates a new [`NopBatchExecutor`], with a map of size `map_size`
+        #[must_use]
+        pub fn new(batch_size: usize, map_size: usize) -> Self {
+            Self {
+                observers: tuple_list!(
+                    StdMapObserver::owned("map", vec![0; map_size]),
+                    TimeObserver::new("time")
+                ),
+                batch_size,
+                results: Vec::new(),
+                batches: Vec::new(),
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    impl<S> UsesState for NopBatchExecutor<S>
+    where
+        S: State,
+    {
+        type State = S;
+    }
+
+    impl<S> UsesObservers for NopBatchExecutor<S>
+    where
+        S: State,
+    {
+        type Observers = NopBatchObservers;
+    }
+
+    impl<S> HasObservers for NopBatchExecutor<S>
+    where
+        S: State,
+    {
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    impl<EM, S, Z> Executor<EM, Z> for NopBatchExecutor<S>
+    where
+        EM: UsesState<State = S>,
+        S: State + HasExecutions,
+        S::Input: HasTargetBytes,
+        Z: UsesState<State = S>,
+    {
+        fn run_target(
+            &mut self,
+            fuzzer: &mut Z,
+            state: &mut Self::State,
+            mgr: &mut EM,
+            input: &Self::Input,
+        ) -> Result<ExitKind, Error> {
+            let exit_kind = self.run_batch(fuzzer, state, mgr, core::slice::from_ref(input))?[0];
+            BatchExecutor::<EM, Z>::load_batch_result(self, 0)?;
+            Ok(exit_kind)
+        }
+    }
+
+    impl<EM, S, Z> BatchExecutor<EM, Z> for NopBatchExecutor<S>
+    where
+        EM: UsesState<State = S>,
+        S: State + HasExecutions,
+        S::Input: HasTargetBytes,
+        Z: UsesState<State = S>,
+    {
+        fn batch_size(&self) -> usize {
+            self.batch_size
+        }
+
+        fn run_batch(
+            &mut self,
+            _fuzzer: &mut Z,
+            state: &mut Self::State,
+            _mgr: &mut EM,
+            inputs: &[Self::Input],
+        ) -> Result<Vec<ExitKind>, Error> {
+            assert!(inputs.len() <= self.batch_size);
+            *state.executions_mut() += inputs.len() as u64;
+            self.batches.push(inputs.len());
+            self.results = inputs
+                .iter()
+                .map(|input| {
+                    let first = input.target_bytes().as_slice().first().copied();
+                    let first = first.unwrap_or_default();
+                    (
+                        usize::from(first) % self.observers.0.usable_count(),
+                        Duration::from_millis(first.into()),
+                    )
+                })
+                .collect();
+            Ok(vec![ExitKind::Ok; inputs.len()])
+        }
+
+        fn load_batch_result(&mut self, idx: usize) -> Result<(), Error> {
+            let (edge, runtime) = self.results[idx];
+            self.observers.0.as_slice_mut().fill(0);
+            self.observers.0.as_slice_mut()[edge] = 1;
+            self.observers.1 .0.set_runtime(runtime);
+            Ok(())
+        }
+    }
+
     #[test]
     fn nop_executor() {
         let empty_input = BytesInput::new(vec![]);
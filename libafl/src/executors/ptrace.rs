@@ -0,0 +1,1105 @@
+//! A lightweight [`Executor`] collecting basic block coverage of binary-only targets with one-shot `int3` breakpoints.
+//!
+//! Like [`UnTracer`](https://github.com/FoRTE-Research/UnTracer-AFL), a breakpoint is placed at the start of
+//! every known basic block of the target, and removed for good once the block is hit.
+//! After coverage saturates, the target runs at native speed, without any instrumentation or runtime.
+//!
+//! The target is run under a `ptrace` based fork-server: it is started once and stopped at its entry point,
+//! and every execution forks a fresh child from this stopped process.
+//! The threads and processes spawned by the child are traced as well, so they hit the breakpoints too.
+//! Only `x86_64` Linux targets are supported.
+
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    time::Instant,
+};
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::{
+    fs::{get_unique_std_input_file, InputFile},
+    tuples::{Handle, MatchNameRef, RefIndexable},
+    AsSlice,
+};
+use nix::{
+    sys::{
+        ptrace::{self, AddressType, Options},
+        signal::{kill, SigSet, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+
+use crate::{
+    executors::{forkserver::ConfigTarget, Executor, ExitKind, HasObservers},
+    inputs::{HasTargetBytes, UsesInput},
+    observers::{MapObserver, ObserversTuple, UsesObservers},
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The `int3` opcode
+const INT3: u8 = 0xcc;
+/// `syscall; int3`, injected into the fork-server to make it fork
+const SYSCALL_INT3: u64 = 0x00cc_050f;
+
+const PT_LOAD: u32 = 1;
+
+/// How long to wait for killed tracees to be reaped
+const REAP_TIMEOUT: Duration = Duration::from_secs(1);
+/// The longest wait for a `SIGCHLD` before polling again, it may be delivered to another thread
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The options of the children forked from the fork-server
+fn child_options() -> Options {
+    // Follow threads and processes, they share or inherit the breakpoints
+    Options::PTRACE_O_EXITKILL
+        | Options::PTRACE_O_TRACECLONE
+        | Options::PTRACE_O_TRACEFORK
+        | Options::PTRACE_O_TRACEVFORK
+        | Options::PTRACE_O_TRACEEXEC
+}
+
+/// The basic blocks to place breakpoints at.
+///
+/// Blocks are stored as offsets from the start of the first mapping of the target binary, the same way
+/// `DrCov` files store them, so the coverage map index of a block is its position in this list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BreakpointBlocks {
+    offsets: Vec<u64>,
+}
+
+impl BreakpointBlocks {
+    /// Create [`BreakpointBlocks`] from offsets into the target binary.
+    ///
+    /// Duplicate offsets are dropped.
+    pub fn from_offsets<I>(offsets: I) -> Self
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let mut seen = HashSet::new();
+        Self {
+            offsets: offsets
+                .into_iter()
+                .filter(|offset| seen.insert(*offset))
+                .collect(),
+        }
+    }
+
+    /// Create [`BreakpointBlocks`] from the virtual addresses of a disassembly.
+    ///
+    /// `image_base` is the address the binary was loaded at in the disassembler, usually `0` for PIE
+    /// and `0x400000` for non-PIE `x86_64` binaries.
+    pub fn from_addresses<I>(addresses: I, image_base: u64) -> Self
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        Self::from_offsets(
+            addresses
+                .into_iter()
+                .map(|address| address.wrapping_sub(image_base)),
+        )
+    }
+
+    /// Parse a list of basic block addresses, one per line, in hex with or without `0x` prefix.
+    ///
+    /// Only the first field of each line, up to a whitespace or a comma, is read: block lists exported from
+    /// the control flow graph of a disassembler, with the block size or function name in the other columns,
+    /// can be used as they are.
+    /// Empty lines and lines starting with `#` are skipped. See [`Self::from_addresses`] for `image_base`.
+    pub fn parse(list: &str, image_base: u64) -> Result<Self, Error> {
+        let mut addresses = Vec::new();
+        for line in list.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let field = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .next()
+                .unwrap_or_default();
+            let hex = field.trim_start_matches("0x").trim_start_matches("0X");
+            let address = u64::from_str_radix(hex, 16).map_err(|_| {
+                Error::illegal_argument(format!("Invalid basic block address {line}"))
+            })?;
+            addresses.push(address);
+        }
+        Ok(Self::from_addresses(addresses, image_base))
+    }
+
+    /// Parse the blocks of `module` from a `DrCov` file, for example the coverage of a frida or QEMU run.
+    ///
+    /// `module` is matched against the trailing components of the module paths, e.g. the file name of the
+    /// target. `DrCov` offsets are relative to the module base already, so no image base is needed.
+    /// The basic block table must be binary.
+    pub fn from_drcov(data: &[u8], module: &str) -> Result<Self, Error> {
+        let malformed =
+            |what: &str| Error::illegal_argument(format!("Malformed DrCov file: {what}"));
+
+        let mut pos = 0;
+        if !drcov_line(data, &mut pos)
+            .ok_or_else(|| malformed("truncated header"))?
+            .starts_with("DRCOV VERSION:")
+        {
+            return Err(Error::illegal_argument("Not a DrCov file"));
+        }
+
+        // Version 1 module tables have no `Columns` line
+        let (mut columns, mut id_col) = (5, 0);
+        let mut ids: HashSet<u16> = HashSet::new();
+        loop {
+            let line = drcov_line(data, &mut pos).ok_or_else(|| malformed("truncated header"))?;
+            if let Some(count) = line.strip_prefix("BB Table:") {
+                let count: usize = count
+                    .trim()
+                    .trim_end_matches("bbs")
+                    .trim()
+                    .parse()
+                    .map_err(|_| malformed("invalid block count"))?;
+                let table = count
+                    .checked_mul(8)
+                    .and_then(|len| data[pos..].get(..len))
+                    .ok_or_else(|| malformed("truncated or non-binary basic block table"))?;
+                if ids.is_empty() {
+                    return Err(Error::illegal_argument(format!(
+                        "No module {module} in the DrCov file"
+                    )));
+                }
+                // start: u32, size: u16, mod_id: u16
+                return Ok(Self::from_offsets(
+                    table
+                        .chunks_exact(8)
+                        .filter(|entry| ids.contains(&u16::from_le_bytes([entry[6], entry[7]])))
+                        .map(|entry| {
+                            u64::from(u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]))
+                        }),
+                ));
+            }
+            if let Some(names) = line.strip_prefix("Columns:") {
+                let names: Vec<&str> = names.split(',').map(str::trim).collect();
+                columns = names.len();
+                id_col = names
+                    .iter()
+                    .position(|name| *name == "id")
+                    .ok_or_else(|| malformed("no id column"))?;
+                continue;
+            }
+            // A module entry, the path is the last column and may contain commas
+            let fields: Vec<&str> = line.splitn(columns, ',').map(str::trim).collect();
+            if fields.len() == columns && Path::new(fields[columns - 1]).ends_with(module) {
+                if let Ok(id) = fields[id_col].parse() {
+                    ids.insert(id);
+                }
+            }
+        }
+    }
+
+    /// Read the blocks of `module` from a `DrCov` file, see [`Self::from_drcov`].
+    pub fn from_drcov_file<P>(path: P, module: &str) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_drcov(&fs::read(path)?, module)
+    }
+
+    /// Read a list of basic block addresses from a file, see [`Self::parse`].
+    pub fn from_file<P>(path: P, image_base: u64) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse(&fs::read_to_string(path)?, image_base)
+    }
+
+    /// The block offsets, in map index order
+    #[must_use]
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// The number of blocks
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// If there are no blocks
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+/// Read the next `\n`-terminated line of a `DrCov` header, starting at `pos`
+fn drcov_line<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    let rest = &data[*pos..];
+    let len = rest.iter().position(|c| *c == b'\n')?;
+    *pos += len + 1;
+    core::str::from_utf8(&rest[..len])
+        .ok()
+        .map(|line| line.trim_end_matches('\r'))
+}
+
+/// Read a word of the tracee memory
+#[allow(clippy::cast_sign_loss)]
+fn peek(pid: Pid, addr: u64) -> Result<u64, Error> {
+    Ok(ptrace::read(pid, addr as AddressType)? as u64)
+}
+
+/// Write a word of the tracee memory
+fn poke(pid: Pid, addr: u64, word: u64) -> Result<(), Error> {
+    #[allow(clippy::cast_possible_wrap)]
+    ptrace::write(pid, addr as AddressType, word as i64)?;
+    Ok(())
+}
+
+/// Write a byte of the tracee memory, returning the previous value
+fn set_byte(pid: Pid, addr: u64, byte: u8) -> Result<u8, Error> {
+    let word = peek(pid, addr)?;
+    poke(pid, addr, (word & !0xff) | u64::from(byte))?;
+    Ok(word as u8)
+}
+
+/// Wait for a state change of any thread of any tracee in the process group `pgid` until `deadline`,
+/// returning `None` on timeout.
+///
+/// `SIGCHLD` must be blocked in the calling thread.
+fn wait_until(pgid: Pid, deadline: Instant) -> Result<Option<WaitStatus>, Error> {
+    let mut sigchld = SigSet::empty();
+    sigchld.add(Signal::SIGCHLD);
+    loop {
+        match waitpid(
+            Pid::from_raw(-pgid.as_raw()),
+            Some(WaitPidFlag::WNOHANG | WaitPidFlag::__WALL),
+        )? {
+            WaitStatus::StillAlive => {}
+            status => return Ok(Some(status)),
+        }
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            return Ok(None);
+        };
+        let remaining = remaining.min(POLL_INTERVAL);
+        #[allow(clippy::cast_possible_wrap)]
+        let timeout = libc::timespec {
+            tv_sec: remaining.as_secs() as libc::time_t,
+            tv_nsec: libc::c_long::from(remaining.subsec_nanos()),
+        };
+        // Wakes up on any tracee stop or exit, a stale pending `SIGCHLD` only causes another `waitpid`
+        unsafe {
+            libc::sigtimedwait(sigchld.as_ref(), core::ptr::null_mut(), &timeout);
+        }
+    }
+}
+
+/// The entry point and the first mapped address of a 64 bit ELF file
+fn elf_entry_and_base(path: &Path) -> Result<(u64, u64), Error> {
+    let mut elf = Vec::new();
+    File::open(path)?.read_to_end(&mut elf)?;
+    let u16_at = |offset: usize| {
+        elf.get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |offset: usize| {
+        elf.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    };
+    let u64_at = |offset: usize| {
+        elf.get(offset..offset + 8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    };
+    if !elf.starts_with(b"\x7fELF\x02\x01") {
+        return Err(Error::illegal_argument(format!(
+            "{} is not a little endian 64 bit ELF file",
+            path.display()
+        )));
+    }
+    let malformed = || Error::illegal_argument(format!("Malformed ELF file {}", path.display()));
+
+    let entry = u64_at(24).ok_or_else(malformed)?;
+    let phoff = u64_at(32).ok_or_else(malformed)? as usize;
+    let phentsize = u16_at(54).ok_or_else(malformed)? as usize;
+    let phnum = u16_at(56).ok_or_else(malformed)? as usize;
+    let mut first_vaddr = u64::MAX;
+    for i in 0..phnum {
+        let phdr = phoff + i * phentsize;
+        if u32_at(phdr).ok_or_else(malformed)? == PT_LOAD {
+            first_vaddr = first_vaddr.min(u64_at(phdr + 16).ok_or_else(malformed)?);
+        }
+    }
+    if first_vaddr == u64::MAX {
+        return Err(malformed());
+    }
+    Ok((entry, first_vaddr & !0xfff))
+}
+
+/// The start of the first mapping of `path` in the process `pid`
+fn mapping_base(pid: Pid, path: &Path) -> Result<u64, Error> {
+    let maps = fs::read_to_string(format!("/proc/{pid}/maps"))?;
+    for line in maps.lines() {
+        // start-end perms offset dev inode path
+        let mut fields = line.splitn(6, ' ');
+        let range = fields.next().unwrap_or_default();
+        if fields.nth(4).map(str::trim) == path.to_str() {
+            let start = range.split('-').next().unwrap_or_default();
+            return u64::from_str_radix(start, 16)
+                .map_err(|_| Error::illegal_state(format!("Malformed mapping {line}")));
+        }
+    }
+    Err(Error::illegal_state(format!(
+        "{} is not mapped in the target",
+        path.display()
+    )))
+}
+
+/// This [`Executor`] runs binary-only targets, collecting basic block coverage with one-shot breakpoints.
+///
+/// Every block has an entry in the map observer, set to `1` the first time the block is hit by any execution.
+/// Since the breakpoint is removed afterwards, the map only ever contains new coverage, which is enough for
+/// a [`crate::feedbacks::MaxMapFeedback`] to find interesting inputs.
+pub struct PtraceBreakpointExecutor<C, OT, S> {
+    target: OsString,
+    args: Vec<OsString>,
+    input_file: InputFile,
+    forkserver: Pid,
+    /// Runtime address of the active breakpoints, mapped to their map index and the original byte
+    breakpoints: HashMap<u64, (usize, u8)>,
+    /// Runtime address of the breakpoints hit already, mapped to the original byte.
+    /// Processes forked before the hit may still have them.
+    removed: HashMap<u64, u8>,
+    base: u64,
+    observers: OT,
+    map_observer: Handle<C>,
+    timeout: Duration,
+    crash_exitcode: Option<i8>,
+    phantom: PhantomData<S>,
+}
+
+impl<C, OT, S> Debug for PtraceBreakpointExecutor<C, OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtraceBreakpointExecutor")
+            .field("target", &self.target)
+            .field("args", &self.args)
+            .field("input_file", &self.input_file)
+            .field("forkserver", &self.forkserver)
+            .field("breakpoints", &self.breakpoints.len())
+            .field("base", &self.base)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PtraceBreakpointExecutor<(), (), ()> {
+    /// Builder for [`PtraceBreakpointExecutor`]
+    #[must_use]
+    pub fn builder() -> PtraceBreakpointExecutorBuilder {
+        PtraceBreakpointExecutorBuilder::new()
+    }
+}
+
+impl<C, OT, S> PtraceBreakpointExecutor<C, OT, S> {
+    /// The `target` binary that's going to run.
+    pub fn target(&self) -> &OsString {
+        &self.target
+    }
+
+    /// The `args` used for the binary.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The [`InputFile`] used by this [`Executor`].
+    pub fn input_file(&self) -> &InputFile {
+        &self.input_file
+    }
+
+    /// The address the target binary is loaded at
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// The number of breakpoints not hit yet
+    pub fn remaining_breakpoints(&self) -> usize {
+        self.breakpoints.len()
+    }
+
+    /// Fork a child from the fork-server, by injecting a `fork` syscall at its current position.
+    ///
+    /// The child is returned stopped, in the same state as the fork-server.
+    fn fork_child(&mut self) -> Result<Pid, Error> {
+        let server = self.forkserver;
+        let regs = ptrace::getregs(server)?;
+        let code = peek(server, regs.rip)?;
+        poke(server, regs.rip, (code & !0xff_ffff) | SYSCALL_INT3)?;
+        let mut fork_regs = regs;
+        fork_regs.rax = libc::SYS_fork as u64;
+        ptrace::setregs(server, fork_regs)?;
+        ptrace::cont(server, None)?;
+
+        let mut child = None;
+        loop {
+            match waitpid(server, Some(WaitPidFlag::__WALL))? {
+                WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_FORK) => {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let pid = ptrace::getevent(server)? as libc::pid_t;
+                    child = Some(Pid::from_raw(pid));
+                    ptrace::cont(server, None)?;
+                }
+                WaitStatus::Stopped(_, Signal::SIGTRAP) => break,
+                // Signals sent to the fork-server are dropped
+                WaitStatus::Stopped(..) => ptrace::cont(server, None)?,
+                status => {
+                    return Err(Error::illegal_state(format!(
+                        "The ptrace fork-server died: {status:?}"
+                    )))
+                }
+            }
+        }
+        poke(server, regs.rip, code)?;
+        ptrace::setregs(server, regs)?;
+
+        let child = child.ok_or_else(|| {
+            Error::illegal_state("The ptrace fork-server could not fork (out of memory?)")
+        })?;
+        // The child is auto-attached and starts with a `SIGSTOP`, in the middle of the injected code
+        match waitpid(child, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::Stopped(_, Signal::SIGSTOP) => {}
+            status => {
+                return Err(Error::illegal_state(format!(
+                    "Unexpected state of the forked child: {status:?}"
+                )))
+            }
+        }
+        ptrace::setoptions(child, child_options())?;
+        poke(child, regs.rip, code)?;
+        ptrace::setregs(child, regs)?;
+        Ok(child)
+    }
+
+    /// Kill and reap the remaining tracees of a run
+    fn kill_tracees(&self, tracees: &mut HashSet<Pid>) -> Result<(), Error> {
+        for tracee in tracees.iter() {
+            let _ = kill(*tracee, Signal::SIGKILL);
+        }
+        let deadline = Instant::now() + REAP_TIMEOUT;
+        while !tracees.is_empty() {
+            let Some(status) = wait_until(self.forkserver, deadline)? else {
+                log::warn!("Could not reap the tracees {tracees:?}");
+                tracees.clear();
+                break;
+            };
+            if let WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, ..) = status {
+                tracees.remove(&pid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a `SIGTRAP` stop of the tracee `pid`
+    fn handle_trap(&mut self, pid: Pid) -> Result<(), Error>
+    where
+        C: MapObserver<Entry = u8>,
+        OT: MatchNameRef,
+    {
+        let mut regs = ptrace::getregs(pid)?;
+        let addr = regs.rip - 1;
+        if let Some((idx, code)) = self.breakpoints.remove(&addr) {
+            if let Some(map) = self.observers.get_mut(&self.map_observer) {
+                map.set(idx, 1);
+            }
+            set_byte(self.forkserver, addr, code)?;
+            self.removed.insert(addr, code);
+        }
+        let Some(code) = self.removed.get(&addr) else {
+            // Not one of ours
+            ptrace::cont(pid, Signal::SIGTRAP)?;
+            return Ok(());
+        };
+        // Threads share the memory, but forked processes have their own copy
+        set_byte(pid, addr, *code)?;
+        regs.rip = addr;
+        ptrace::setregs(pid, regs)?;
+        ptrace::cont(pid, None)?;
+        Ok(())
+    }
+}
+
+impl<C, OT, S> Drop for PtraceBreakpointExecutor<C, OT, S> {
+    fn drop(&mut self) {
+        let _ = kill(self.forkserver, Signal::SIGKILL);
+        let _ = waitpid(self.forkserver, Some(WaitPidFlag::__WALL));
+    }
+}
+
+/// The builder for [`PtraceBreakpointExecutor`]
+#[derive(Debug, Default)]
+pub struct PtraceBreakpointExecutorBuilder {
+    program: Option<OsString>,
+    arguments: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    debug_child: bool,
+    input_filename: Option<OsString>,
+    blocks: BreakpointBlocks,
+    timeout: Option<Duration>,
+    crash_exitcode: Option<i8>,
+}
+
+impl PtraceBreakpointExecutorBuilder {
+    /// Creates a new [`PtraceBreakpointExecutorBuilder`]. The input is passed via `stdin` unless an
+    /// input file is given.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The harness
+    #[must_use]
+    pub fn program<O>(mut self, program: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument to the harness's commandline
+    #[must_use]
+    pub fn arg<O>(mut self, arg: O) -> Self
+    where
+        O: AsRef<OsStr>,
+    {
+        self.arguments.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds arguments to the harness's commandline
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arguments.push(arg.as_ref().to_owned());
+        }
+        self
+    }
+
+    /// Adds an environmental var to the harness's commandline
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds environmental vars to the harness's commandline
+    #[must_use]
+    pub fn envs<IT, K, V>(mut self, vars: IT) -> Self
+    where
+        IT: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (key, val) in vars {
+            self.envs
+                .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        }
+        self
+    }
+
+    /// Place the input at this position and set the filename for the input.
+    ///
+    /// Note: If you use this, you should ensure that there is only one instance using this
+    /// file at any given time.
+    #[must_use]
+    pub fn arg_input_file<P: AsRef<Path>>(self, path: P) -> Self {
+        let mut moved = self.arg(path.as_ref());
+        moved.input_filename = Some(path.as_ref().as_os_str().to_os_string());
+        moved
+    }
+
+    /// Place the input at this position and set the default filename for the input.
+    /// The filename includes the PID of the fuzzer to ensure that no two fuzzers write to the same file
+    #[must_use]
+    pub fn arg_input_file_std(self) -> Self {
+        self.arg_input_file(get_unique_std_input_file())
+    }
+
+    /// If `debug_child` is set, the child will print to `stdout`/`stderr`.
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// The basic blocks to place breakpoints at
+    #[must_use]
+    pub fn blocks(mut self, blocks: BreakpointBlocks) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// The timeout for each execution, 1 second by default
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Treat an exit with this exit code as a crash
+    #[must_use]
+    pub fn crash_exitcode(mut self, exitcode: i8) -> Self {
+        self.crash_exitcode = Some(exitcode);
+        self
+    }
+
+    /// Start the target, run it to its entry point and place the breakpoints.
+    ///
+    /// Hit blocks are reported in the map observer referred to by `map_observer`, which needs one entry per block.
+    /// Blocks `SIGCHLD` in the calling thread, which needs to be the one running the executor.
+    pub fn build<C, OT, S>(
+        self,
+        map_observer: Handle<C>,
+        observers: OT,
+    ) -> Result<PtraceBreakpointExecutor<C, OT, S>, Error>
+    where
+        C: MapObserver<Entry = u8>,
+        OT: ObserversTuple<S>,
+        S: UsesInput,
+    {
+        let Some(program) = self.program else {
+            return Err(Error::illegal_argument(
+                "PtraceBreakpointExecutor needs a program to run",
+            ));
+        };
+        let map_len = observers
+            .get(&map_observer)
+            .ok_or_else(|| Error::key_not_found("Map observer not found in observers"))?
+            .len();
+        if map_len < self.blocks.len() {
+            return Err(Error::illegal_argument(format!(
+                "The map observer has {map_len} entries, but there are {} blocks",
+                self.blocks.len()
+            )));
+        }
+
+        let use_stdin = self.input_filename.is_none();
+        let input_filename = self
+            .input_filename
+            .unwrap_or_else(|| OsString::from(get_unique_std_input_file()));
+        let input_file = InputFile::create(input_filename)?;
+
+        let mut sigchld = SigSet::empty();
+        sigchld.add(Signal::SIGCHLD);
+        sigchld.thread_block()?;
+
+        let (stdout, stderr) = if self.debug_child {
+            (Stdio::inherit(), Stdio::inherit())
+        } else {
+            (Stdio::null(), Stdio::null())
+        };
+        let mut command = Command::new(&program);
+        command
+            .args(&self.arguments)
+            .envs(self.envs)
+            .env("LD_BIND_NOW", "1")
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            .setsid()
+            .setstdin(input_file.as_raw_fd(), use_stdin);
+        // # Safety
+        // Only calls async-signal-safe functions.
+        unsafe {
+            std::os::unix::process::CommandExt::pre_exec(&mut command, || {
+                ptrace::traceme().map_err(std::io::Error::from)?;
+                Ok(())
+            });
+        }
+        let child = command.spawn().map_err(|err| {
+            Error::illegal_state(format!(
+                "Could not spawn {}: {err}",
+                program.to_string_lossy()
+            ))
+        })?;
+        #[allow(clippy::cast_possible_wrap)]
+        let server = Pid::from_raw(child.id() as libc::pid_t);
+
+        // Stopped right after `execve`
+        match waitpid(server, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => {}
+            status => {
+                return Err(Error::illegal_state(format!(
+                    "Could not execute {}: {status:?}",
+                    program.to_string_lossy()
+                )))
+            }
+        }
+        ptrace::setoptions(
+            server,
+            Options::PTRACE_O_EXITKILL | Options::PTRACE_O_TRACEFORK,
+        )?;
+
+        let exe = fs::read_link(format!("/proc/{server}/exe"))?;
+        let (entry, first_vaddr) = elf_entry_and_base(&exe)?;
+        let base = mapping_base(server, &exe)?;
+        let entry = base + (entry - first_vaddr);
+
+        // Run to the entry point, shared libraries are loaded by now
+        let code = set_byte(server, entry, INT3)?;
+        ptrace::cont(server, None)?;
+        let mut regs = match waitpid(server, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => ptrace::getregs(server)?,
+            status => {
+                return Err(Error::illegal_state(format!(
+                    "{} did not reach its entry point: {status:?}",
+                    program.to_string_lossy()
+                )))
+            }
+        };
+        if regs.rip != entry + 1 {
+            return Err(Error::illegal_state(format!(
+                "Unexpected trap at {:#x} instead of the entry point {entry:#x}",
+                regs.rip - 1
+            )));
+        }
+        set_byte(server, entry, code)?;
+        regs.rip = entry;
+        ptrace::setregs(server, regs)?;
+
+        // Children inherit the breakpoints from the fork-server
+        let mut breakpoints = HashMap::with_capacity(self.blocks.len());
+        for (idx, offset) in self.blocks.offsets().iter().enumerate() {
+            let addr = base + offset;
+            let code = set_byte(server, addr, INT3).map_err(|err| {
+                Error::illegal_argument(format!(
+                    "Could not place a breakpoint at offset {offset:#x}: {err:?}"
+                ))
+            })?;
+            breakpoints.insert(addr, (idx, code));
+        }
+        log::info!(
+            "PtraceBreakpointExecutor: {} loaded at {base:#x}, {} breakpoints",
+            exe.display(),
+            breakpoints.len()
+        );
+
+        Ok(PtraceBreakpointExecutor {
+            target: program,
+            args: self.arguments,
+            input_file,
+            forkserver: server,
+            breakpoints,
+            removed: HashMap::new(),
+            base,
+            observers,
+            map_observer,
+            timeout: self.timeout.unwrap_or(Duration::from_secs(1)),
+            crash_exitcode: self.crash_exitcode,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<C, EM, OT, S, Z> Executor<EM, Z> for PtraceBreakpointExecutor<C, OT, S>
+where
+    C: MapObserver<Entry = u8>,
+    OT: ObserversTuple<S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+
+        let child = self.fork_child()?;
+        let deadline = Instant::now() + self.timeout;
+        ptrace::cont(child, None)?;
+
+        // The threads and processes of this run, all in the process group of the fork-server
+        let mut tracees = HashSet::new();
+        tracees.insert(child);
+        // New tracees, until their initial `SIGSTOP`
+        let mut starting = HashSet::new();
+
+        let exit_kind = loop {
+            let Some(status) = wait_until(self.forkserver, deadline)? else {
+                break ExitKind::Timeout;
+            };
+            match status {
+                WaitStatus::Exited(pid, code) => {
+                    tracees.remove(&pid);
+                    if pid == child {
+                        #[allow(clippy::cast_possible_truncation)]
+                        let crashed = self.crash_exitcode == Some(code as i8);
+                        break if crashed {
+                            ExitKind::Crash
+                        } else {
+                            ExitKind::Ok
+                        };
+                    }
+                }
+                // Only the death of the child itself is a crash, not the one of a process it spawned
+                WaitStatus::Signaled(pid, ..) => {
+                    tracees.remove(&pid);
+                    if pid == child {
+                        break ExitKind::Crash;
+                    }
+                }
+                WaitStatus::PtraceEvent(pid, _, event) => {
+                    if matches!(
+                        event,
+                        libc::PTRACE_EVENT_CLONE
+                            | libc::PTRACE_EVENT_FORK
+                            | libc::PTRACE_EVENT_VFORK
+                    ) {
+                        #[allow(clippy::cast_possible_truncation)]
+                        let new = Pid::from_raw(ptrace::getevent(pid)? as libc::pid_t);
+                        // Its initial stop may have been reported already
+                        if tracees.insert(new) {
+                            starting.insert(new);
+                        }
+                    }
+                    ptrace::cont(pid, None)?;
+                }
+                WaitStatus::Stopped(pid, Signal::SIGTRAP) => self.handle_trap(pid)?,
+                WaitStatus::Stopped(pid, Signal::SIGSTOP)
+                    if starting.remove(&pid) || tracees.insert(pid) =>
+                {
+                    ptrace::cont(pid, None)?;
+                }
+                // Deliver the signal, fatal signals are reported once the tracee dies
+                WaitStatus::Stopped(pid, signal) => ptrace::cont(pid, signal)?,
+                status => {
+                    if let Some(pid) = status.pid() {
+                        let _ = ptrace::cont(pid, None);
+                    }
+                }
+            }
+        };
+
+        // Kill the processes the child left behind, or all of them on timeout
+        self.kill_tracees(&mut tracees)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<C, OT, S> UsesState for PtraceBreakpointExecutor<C, OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<C, OT, S> UsesObservers for PtraceBreakpointExecutor<C, OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: State,
+{
+    type Observers = OT;
+}
+
+impl<C, OT, S> HasObservers for PtraceBreakpointExecutor<C, OT, S>
+where
+    OT: ObserversTuple<S>,
+    S: State,
+{
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+    use std::{
+        env, fs,
+        process::{self, Command},
+    };
+
+    use libafl_bolts::tuples::{tuple_list, Handled};
+
+    use super::{BreakpointBlocks, PtraceBreakpointExecutor};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::test::NopFuzzer,
+        inputs::BytesInput,
+        observers::{MapObserver, StdMapObserver},
+        state::NopState,
+    };
+
+    #[test]
+    fn test_breakpoint_blocks_parse() {
+        let blocks =
+            BreakpointBlocks::parse("# blocks\n0x401000\n401010\n\n0x401000\n", 0x400000).unwrap();
+        assert_eq!(blocks.offsets(), &[0x1000, 0x1010]);
+        assert!(BreakpointBlocks::parse("main\n", 0).is_err());
+
+        // Extra columns of a control flow graph export
+        let blocks =
+            BreakpointBlocks::parse("0x401000 16 main\n0x401010,4,main\n", 0x400000).unwrap();
+        assert_eq!(blocks.offsets(), &[0x1000, 0x1010]);
+    }
+
+    #[test]
+    fn test_breakpoint_blocks_drcov() {
+        let mut drcov =
+            b"DRCOV VERSION: 2\nDRCOV FLAVOR: libafl\nModule Table: version 2, count 2\n\
+            Columns: id, base, end, entry, checksum, timestamp, path\n\
+            000, 0x1000, 0x2000, 0x0, 0x0, 0x0, /usr/bin/target\n\
+            001, 0x8000, 0x9000, 0x0, 0x0, 0x0, /lib/libc, with comma.so\n\
+            BB Table: 3 bbs\n"
+                .to_vec();
+        for (offset, module) in [(0x10_u32, 0_u16), (0x20, 1), (0x30, 0)] {
+            drcov.extend(offset.to_le_bytes());
+            drcov.extend(4_u16.to_le_bytes());
+            drcov.extend(module.to_le_bytes());
+        }
+
+        let blocks = BreakpointBlocks::from_drcov(&drcov, "target").unwrap();
+        assert_eq!(blocks.offsets(), &[0x10, 0x30]);
+        let blocks = BreakpointBlocks::from_drcov(&drcov, "libc, with comma.so").unwrap();
+        assert_eq!(blocks.offsets(), &[0x20]);
+        // Whole path components only
+        assert!(BreakpointBlocks::from_drcov(&drcov, "get").is_err());
+        assert!(BreakpointBlocks::from_drcov(&drcov[..drcov.len() - 1], "target").is_err());
+        assert!(BreakpointBlocks::from_drcov(b"0x1000\n", "target").is_err());
+    }
+
+    const TARGET: &str = r"
+#include <pthread.h>
+#include <signal.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+__attribute__((noinline)) int covered(int x) { return x * 3; }
+
+__attribute__((noinline)) void *in_thread(void *arg) {
+  return (void *)(long)covered((int)(long)arg);
+}
+
+int main(void) {
+  char buf[16] = {0};
+  if (read(0, buf, sizeof(buf) - 1) < 1) return 0;
+  if (buf[0] == 'f') {
+    /* Both processes hit the breakpoint */
+    pid_t pid = fork();
+    covered(1);
+    if (pid == 0) _exit(0);
+    waitpid(pid, NULL, 0);
+  } else if (buf[0] == 't') {
+    pthread_t thread;
+    pthread_create(&thread, NULL, in_thread, NULL);
+    covered(2);
+    pthread_join(thread, NULL);
+  } else if (buf[0] == 'k') {
+    /* A crashing grandchild does not crash the target */
+    pid_t pid = fork();
+    if (pid == 0) raise(SIGSEGV);
+    waitpid(pid, NULL, 0);
+  } else if (buf[0] == 'c') {
+    raise(SIGSEGV);
+  }
+  return 0;
+}
+";
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_ptrace_breakpoint_executor() {
+        let dir = env::temp_dir().join(format!("libafl_ptrace_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("target.c");
+        let target = dir.join("target");
+        fs::write(&source, TARGET).unwrap();
+        let compiled = Command::new("cc")
+            .args(["-O0", "-fPIE", "-pie", "-pthread", "-o"])
+            .arg(&target)
+            .arg(&source)
+            .status();
+        if !compiled.is_ok_and(|status| status.success()) {
+            log::warn!("No C compiler, skipping the ptrace executor test");
+            return;
+        }
+
+        // PIE, the symbol values are offsets already
+        let symbols = Command::new("nm").arg(&target).output().unwrap();
+        let symbols = String::from_utf8(symbols.stdout).unwrap();
+        let address = |name: &str| {
+            symbols
+                .lines()
+                .find_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    let address = fields.next()?;
+                    (fields.nth(1)? == name).then(|| u64::from_str_radix(address, 16).unwrap())
+                })
+                .unwrap()
+        };
+        let blocks =
+            BreakpointBlocks::from_addresses(["main", "covered", "in_thread"].map(address), 0);
+
+        let observer = StdMapObserver::owned("ptrace", vec![0_u8; 3]);
+        let handle = observer.handle();
+        let mut executor = PtraceBreakpointExecutor::builder()
+            .program(&target)
+            .blocks(blocks)
+            .build(handle.clone(), tuple_list!(observer))
+            .unwrap();
+
+        let mut state = NopState::<BytesInput>::new();
+        let mut run = |executor: &mut PtraceBreakpointExecutor<_, _, _>, input: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(input.to_vec()),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(run(&mut executor, b"f"), ExitKind::Ok);
+        assert_eq!(executor.remaining_breakpoints(), 1);
+        assert_eq!(run(&mut executor, b"t"), ExitKind::Ok);
+        assert_eq!(executor.remaining_breakpoints(), 0);
+        let map = &executor.observers.0;
+        assert_eq!(map.count_bytes(), 3);
+
+        assert_eq!(run(&mut executor, b"k"), ExitKind::Ok);
+        assert_eq!(run(&mut executor, b"c"), ExitKind::Crash);
+        assert_eq!(run(&mut executor, b"f"), ExitKind::Ok);
+
+        drop(executor);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
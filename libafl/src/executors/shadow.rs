@@ -1,11 +1,14 @@
 //! A `ShadowExecutor` wraps an executor to have shadow observer that will not be considered by the feedbacks and the manager
 
-use core::fmt::{self, Debug, Formatter};
+use core::{
+    fmt::{self, Debug, Formatter},
+    time::Duration,
+};
 
 use libafl_bolts::tuples::RefIndexable;
 
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{Executor, ExitKind, HasObservers, HasTimeout, ReportsTimeouts},
     observers::{ObserversTuple, UsesObservers},
     state::UsesState,
     Error,
@@ -105,3 +108,20 @@ where
         self.executor.observers_mut()
     }
 }
+
+impl<E, SOT> HasTimeout for ShadowExecutor<E, SOT>
+where
+    E: HasTimeout,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
+
+impl<E, SOT> ReportsTimeouts for ShadowExecutor<E, SOT> where E: ReportsTimeouts {}
@@ -0,0 +1,447 @@
+//! The [`TimeoutCalibrator`] wraps an executor implementing [`HasTimeout`], and derives its timeout
+//! from the execution times measured by the [`crate::stages::CalibrationStage`], like AFL does.
+//!
+//! Runs that time out with the calibrated timeout are run again with a longer timeout,
+//! and only reported as [`ExitKind::Timeout`] if they time out again. This needs an executor
+//! that [`ReportsTimeouts`].
+
+use core::time::Duration;
+
+use libafl_bolts::{impl_serdeany, tuples::RefIndexable};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    observers::{ObserversTuple, UsesObservers},
+    state::UsesState,
+    Error, HasMetadata,
+};
+
+/// The calibrated timeout is rounded up to a multiple of this, as AFL's `EXEC_TM_ROUND`
+const TIMEOUT_ROUND: Duration = Duration::from_millis(20);
+
+/// The execution times measured by the [`crate::stages::CalibrationStage`],
+/// from which the [`TimeoutCalibrator`] derives the timeout
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TimeoutCalibrationMetadata {
+    /// The sum of the average execution times of the calibrated entries
+    exec_time_sum: Duration,
+    /// The average execution time of the slowest calibrated entry
+    max_exec_time: Duration,
+    /// The number of calibrated entries
+    entries: u64,
+}
+
+impl_serdeany!(TimeoutCalibrationMetadata);
+
+impl TimeoutCalibrationMetadata {
+    /// Create a new, empty [`struct@TimeoutCalibrationMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the average execution time of a newly calibrated entry
+    pub fn add_entry(&mut self, exec_time: Duration) {
+        self.exec_time_sum += exec_time;
+        self.max_exec_time = self.max_exec_time.max(exec_time);
+        self.entries += 1;
+    }
+
+    /// The number of calibrated entries
+    #[must_use]
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// The average execution time over all calibrated entries
+    #[must_use]
+    pub fn avg_exec_time(&self) -> Option<Duration> {
+        (self.entries > 0)
+            .then(|| self.exec_time_sum / u32::try_from(self.entries).unwrap_or(u32::MAX))
+    }
+
+    /// The average execution time of the slowest calibrated entry
+    #[must_use]
+    pub fn max_exec_time(&self) -> Duration {
+        self.max_exec_time
+    }
+
+    /// The timeout derived from the execution times, the same way as AFL:
+    /// 5 times the average execution time (2 or 3 times for slow targets),
+    /// but at least the execution time of the slowest entry, rounded up to the next 20 ms.
+    ///
+    /// Returns `None` if no entry has been calibrated yet.
+    #[must_use]
+    pub fn calibrated_timeout(&self) -> Option<Duration> {
+        let avg = self.avg_exec_time()?;
+        let factor = if avg > Duration::from_millis(50) {
+            2
+        } else if avg > Duration::from_millis(10) {
+            3
+        } else {
+            5
+        };
+        let timeout = (avg * factor).max(self.max_exec_time);
+        let rounds = timeout.as_nanos() / TIMEOUT_ROUND.as_nanos() + 1;
+        Some(TIMEOUT_ROUND * u32::try_from(rounds).unwrap_or(u32::MAX))
+    }
+}
+
+/// An executor that returns [`ExitKind::Timeout`] for runs that timed out, so that they can be run again.
+///
+/// The in-process executors don't: their signal handlers report the hang as an objective and restart the
+/// fuzzer before the run returns. So they can't be wrapped in a [`TimeoutCalibrator`], use an
+/// [`crate::executors::InProcessForkExecutor`] instead.
+pub trait ReportsTimeouts: HasTimeout {}
+
+/// Wraps an executor implementing [`ReportsTimeouts`] and calibrates its timeout.
+///
+/// The timeout is derived from the [`struct@TimeoutCalibrationMetadata`] in the state,
+/// which is filled by the [`crate::stages::CalibrationStage`], and capped at `max_timeout`.
+/// Until the first entry is calibrated, the executor runs with `max_timeout`.
+///
+/// A run that times out is run again with the `hang_timeout` (by default `max_timeout`),
+/// so that slow inputs are not reported as false hangs.
+#[derive(Debug)]
+pub struct TimeoutCalibrator<E> {
+    executor: E,
+    max_timeout: Duration,
+    hang_timeout: Duration,
+    /// The number of calibrated entries the current timeout is derived from
+    calibrated_entries: u64,
+}
+
+impl<E> TimeoutCalibrator<E>
+where
+    E: ReportsTimeouts,
+{
+    /// Create a new [`TimeoutCalibrator`], capping the calibrated timeout of the `executor` at `max_timeout`.
+    pub fn new(mut executor: E, max_timeout: Duration) -> Self {
+        executor.set_timeout(max_timeout);
+        Self {
+            executor,
+            max_timeout,
+            hang_timeout: max_timeout,
+            calibrated_entries: 0,
+        }
+    }
+
+    /// Set the timeout to re-run runs with, that timed out with the calibrated timeout.
+    #[must_use]
+    pub fn hang_timeout(mut self, hang_timeout: Duration) -> Self {
+        self.hang_timeout = hang_timeout;
+        self
+    }
+
+    /// The maximum timeout
+    #[must_use]
+    pub fn max_timeout(&self) -> Duration {
+        self.max_timeout
+    }
+
+    /// The wrapped executor
+    pub fn executor(&self) -> &E {
+        &self.executor
+    }
+
+    /// The wrapped executor (mutable)
+    pub fn executor_mut(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    /// Update the timeout of the executor, if new entries were calibrated since the last update.
+    fn update_timeout<S>(&mut self, state: &S)
+    where
+        S: HasMetadata,
+    {
+        let Ok(metadata) = state.metadata::<TimeoutCalibrationMetadata>() else {
+            return;
+        };
+        if metadata.entries() == self.calibrated_entries {
+            return;
+        }
+        self.calibrated_entries = metadata.entries();
+
+        if let Some(timeout) = metadata.calibrated_timeout() {
+            let timeout = timeout.min(self.max_timeout);
+            if timeout != self.executor.timeout() {
+                log::info!(
+                    "Calibrated the timeout to {timeout:?} (average exec time {:?})",
+                    metadata.avg_exec_time().unwrap_or_default()
+                );
+                self.executor.set_timeout(timeout);
+            }
+        }
+    }
+}
+
+impl<E, EM, Z> Executor<EM, Z> for TimeoutCalibrator<E>
+where
+    E: Executor<EM, Z> + HasObservers + ReportsTimeouts,
+    E::Observers: ObserversTuple<E::State>,
+    E::State: HasMetadata,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        self.update_timeout(state);
+
+        let exit_kind = self.executor.run_target(fuzzer, state, mgr, input)?;
+        let timeout = self.executor.timeout();
+        if exit_kind != ExitKind::Timeout || self.hang_timeout <= timeout {
+            return Ok(exit_kind);
+        }
+
+        log::debug!(
+            "Run timed out after {timeout:?}, running it again with {:?}",
+            self.hang_timeout
+        );
+        let mut observers = self.executor.observers_mut();
+        observers.post_exec_all(state, input, &exit_kind)?;
+        observers.pre_exec_all(state, input)?;
+
+        self.executor.set_timeout(self.hang_timeout);
+        let exit_kind = self.executor.run_target(fuzzer, state, mgr, input);
+        self.executor.set_timeout(timeout);
+        exit_kind
+    }
+}
+
+impl<E> HasTimeout for TimeoutCalibrator<E>
+where
+    E: HasTimeout,
+{
+    /// The current, calibrated timeout
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    /// Set the timeout, until it is calibrated again
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
+
+impl<E> UsesState for TimeoutCalibrator<E>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E> UsesObservers for TimeoutCalibrator<E>
+where
+    E: HasObservers,
+{
+    type Observers = E::Observers;
+}
+
+impl<E> HasObservers for TimeoutCalibrator<E>
+where
+    E: HasObservers,
+{
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executor.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    use core::{marker::PhantomData, time::Duration};
+
+    use libafl_bolts::{tuples::RefIndexable, AsSlice};
+
+    use super::{ReportsTimeouts, TimeoutCalibrationMetadata, TimeoutCalibrator};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers, HasTimeout},
+        fuzzer::test::NopFuzzer,
+        inputs::{BytesInput, HasTargetBytes},
+        observers::UsesObservers,
+        state::{HasExecutions, NopState, State, UsesState},
+        Error, HasMetadata,
+    };
+
+    /// Runs take as many milliseconds as the first byte of the input, and time out after the timeout
+    #[derive(Debug)]
+    struct SlowExecutor<S> {
+        timeout: Duration,
+        /// The timeout of each run
+        runs: Vec<Duration>,
+        observers: (),
+        phantom: PhantomData<S>,
+    }
+
+    impl<S> UsesState for SlowExecutor<S>
+    where
+        S: State,
+    {
+        type State = S;
+    }
+
+    impl<S> UsesObservers for SlowExecutor<S>
+    where
+        S: State,
+    {
+        type Observers = ();
+    }
+
+    impl<S> HasObservers for SlowExecutor<S>
+    where
+        S: State,
+    {
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    impl<S> HasTimeout for SlowExecutor<S> {
+        fn timeout(&self) -> Duration {
+            self.timeout
+        }
+
+        fn set_timeout(&mut self, timeout: Duration) {
+            self.timeout = timeout;
+        }
+    }
+
+    impl<S> ReportsTimeouts for SlowExecutor<S> {}
+
+    impl<EM, S, Z> Executor<EM, Z> for SlowExecutor<S>
+    where
+        EM: UsesState<State = S>,
+        S: State + HasExecutions,
+        S::Input: HasTargetBytes,
+        Z: UsesState<State = S>,
+    {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            state: &mut Self::State,
+            _mgr: &mut EM,
+            input: &Self::Input,
+        ) -> Result<ExitKind, Error> {
+            *state.executions_mut() += 1;
+            self.runs.push(self.timeout);
+            let exec_time = Duration::from_millis(input.target_bytes().as_slice()[0].into());
+            Ok(if exec_time > self.timeout {
+                ExitKind::Timeout
+            } else {
+                ExitKind::Ok
+            })
+        }
+    }
+
+    #[test]
+    fn test_rerun_timeouts() {
+        let executor = SlowExecutor {
+            timeout: Duration::ZERO,
+            runs: Vec::new(),
+            observers: (),
+            phantom: PhantomData,
+        };
+        let mut executor = TimeoutCalibrator::new(executor, Duration::from_millis(200))
+            .hang_timeout(Duration::from_millis(100));
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::<BytesInput>::new();
+        let mut mgr = NopEventManager::new();
+
+        // Until the first entry is calibrated, we run with the maximum timeout
+        let fast = BytesInput::new(vec![5]);
+        let slow = BytesInput::new(vec![50]);
+        let hang = BytesInput::new(vec![150]);
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &slow)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(executor.executor().runs, [Duration::from_millis(200)]);
+
+        // Calibrated to 20 ms
+        state
+            .metadata_or_insert_with(TimeoutCalibrationMetadata::new)
+            .add_entry(Duration::from_millis(2));
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &fast)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(executor.timeout(), Duration::from_millis(20));
+
+        // Slow runs pass with the hang timeout, and the calibrated timeout is restored
+        executor.executor_mut().runs.clear();
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &slow)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(
+            executor.executor().runs,
+            [Duration::from_millis(20), Duration::from_millis(100)]
+        );
+        assert_eq!(executor.timeout(), Duration::from_millis(20));
+
+        // Hangs time out twice
+        executor.executor_mut().runs.clear();
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &hang)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+        assert_eq!(
+            executor.executor().runs,
+            [Duration::from_millis(20), Duration::from_millis(100)]
+        );
+        assert_eq!(*state.executions(), 6);
+    }
+
+    #[test]
+    fn test_calibrated_timeout() {
+        let mut metadata = TimeoutCalibrationMetadata::new();
+        assert_eq!(metadata.calibrated_timeout(), None);
+
+        // 5 times the average, rounded up to the next 20 ms
+        metadata.add_entry(Duration::from_millis(1));
+        metadata.add_entry(Duration::from_millis(3));
+        assert_eq!(metadata.avg_exec_time(), Some(Duration::from_millis(2)));
+        assert_eq!(
+            metadata.calibrated_timeout(),
+            Some(Duration::from_millis(20))
+        );
+
+        // at least the slowest entry
+        metadata.add_entry(Duration::from_millis(200));
+        assert_eq!(
+            metadata.calibrated_timeout(),
+            Some(Duration::from_millis(220))
+        );
+
+        // 2 times the average for slow targets
+        let mut metadata = TimeoutCalibrationMetadata::new();
+        metadata.add_entry(Duration::from_millis(200));
+        assert_eq!(
+            metadata.calibrated_timeout(),
+            Some(Duration::from_millis(420))
+        );
+    }
+}
@@ -1,11 +1,11 @@
 //! A wrapper for any [`Executor`] to make it implement [`HasObservers`] using a given [`ObserversTuple`].
 
-use core::fmt::Debug;
+use core::{fmt::Debug, time::Duration};
 
 use libafl_bolts::tuples::RefIndexable;
 
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{Executor, ExitKind, HasObservers, HasTimeout, ReportsTimeouts},
     observers::{ObserversTuple, UsesObservers},
     state::UsesState,
     Error,
@@ -64,6 +64,23 @@ where
     }
 }
 
+impl<E, OT> HasTimeout for WithObservers<E, OT>
+where
+    E: HasTimeout,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
+
+impl<E, OT> ReportsTimeouts for WithObservers<E, OT> where E: ReportsTimeouts {}
+
 impl<E, OT> WithObservers<E, OT> {
     /// Wraps the given [`Executor`] with the given [`ObserversTuple`] to implement [`HasObservers`].
     ///
@@ -0,0 +1,112 @@
+//! Feedback and metadata for sanitizer reports in the `compiler-rt` `AddressSanitizer` format.
+
+use alloc::{borrow::Cow, string::String};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    observers::{AsanReportObserver, ObserversTuple},
+    state::State,
+    Error, HasMetadata,
+};
+
+/// Metadata for [`AsanReportToMetadataFeedback`], the reports of the run in the
+/// `compiler-rt` `AddressSanitizer` format.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AsanReportMetadata {
+    #[allow(missing_docs)]
+    pub report: String,
+}
+
+impl_serdeany!(AsanReportMetadata);
+
+/// Nop feedback that annotates the sanitizer reports in the new testcase. The testcase
+/// is never interesting (use with an OR).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AsanReportToMetadataFeedback {
+    o_ref: Handle<AsanReportObserver>,
+}
+
+impl<S> Feedback<S> for AsanReportToMetadataFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    /// Append the report to the testcase, if the sanitizer found an error.
+    #[inline]
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("AsanReportObserver is missing"))?;
+        if let Some(report) = observer.report() {
+            testcase.metadata_map_mut().insert(AsanReportMetadata {
+                report: report.into(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Discard the stored metadata in case that the testcase is not added to the corpus.
+    #[inline]
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+impl Named for AsanReportToMetadataFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl AsanReportToMetadataFeedback {
+    /// Creates a new [`AsanReportToMetadataFeedback`].
+    #[must_use]
+    pub fn new(observer: &AsanReportObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Feedback and metadata for the core dumps of crashing targets.
+
+use alloc::borrow::Cow;
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    observers::{CoreDumpObserver, ObserversTuple},
+    state::State,
+    Error, HasMetadata,
+};
+
+/// Metadata for [`CoreDumpToMetadataFeedback`], the path of the core dump of the testcase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreDumpMetadata {
+    #[allow(missing_docs)]
+    pub path: PathBuf,
+}
+
+impl_serdeany!(CoreDumpMetadata);
+
+/// Nop feedback that annotates the core dump of the run in the new testcase. The testcase
+/// is never interesting (use with an OR, e.g. with the objective).
+///
+/// The core dumps of runs whose testcase is discarded are deleted, so that only the core dumps of
+/// the solutions are kept.
+/// Combine it with [`crate::feedback_or`], not [`crate::feedback_or_fast`], so that it sees every run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CoreDumpToMetadataFeedback {
+    o_ref: Handle<CoreDumpObserver>,
+    /// The core dump of the last run, until it is kept or discarded
+    #[serde(skip)]
+    last_core_dump: Option<PathBuf>,
+}
+
+impl<S> Feedback<S> for CoreDumpToMetadataFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("CoreDumpObserver is missing"))?;
+        self.last_core_dump = observer.core_dump().map(PathBuf::from);
+        Ok(false)
+    }
+
+    /// Append the path of the core dump to the testcase, if the target dumped core.
+    #[inline]
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if let Some(path) = self.last_core_dump.take() {
+            testcase
+                .metadata_map_mut()
+                .insert(CoreDumpMetadata { path });
+        }
+
+        Ok(())
+    }
+
+    /// Delete the core dump in case that the testcase is not added to the corpus.
+    #[inline]
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        if let Some(path) = self.last_core_dump.take() {
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != ErrorKind::NotFound {
+                    log::warn!("Failed to remove core dump {}: {err}", path.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+impl Named for CoreDumpToMetadataFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl CoreDumpToMetadataFeedback {
+    /// Creates a new [`CoreDumpToMetadataFeedback`].
+    #[must_use]
+    pub fn new(observer: &CoreDumpObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+            last_core_dump: None,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 //! Diff Feedback, comparing the content of two observers of the same type.
+//! The [`MultiDiffFeedback`] compares any number of observers and reports which ones disagree.
 //!
 
-use alloc::borrow::Cow;
+use alloc::{borrow::Cow, vec::Vec};
 use core::{
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
@@ -15,12 +16,15 @@ use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "track_hit_feedbacks")]
 use crate::feedbacks::premature_last_result_err;
+#[cfg(feature = "std")]
+use crate::observers::StdOutObserver;
 use crate::{
+    corpus::Testcase,
     events::EventFirer,
     executors::ExitKind,
     feedbacks::{Feedback, FeedbackFactory},
     inputs::Input,
-    observers::{Observer, ObserversTuple},
+    observers::{Observer, ObserversTuple, ValueObserver},
     state::State,
     Error, HasMetadata,
 };
@@ -192,6 +196,307 @@ where
     }
 }
 
+/// Which executors of a [`crate::executors::MultiDiffExecutor`] disagreed on an input,
+/// as reported by a [`MultiDiffFeedback`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct MultiDiffMetadata {
+    /// The indexes of the executors, grouped by equal outputs, largest group first
+    pub groups: Vec<Vec<usize>>,
+}
+
+libafl_bolts::impl_serdeany!(MultiDiffMetadata);
+
+impl MultiDiffMetadata {
+    /// The executors agreeing with the majority, or `None` if the largest groups are tied
+    #[must_use]
+    pub fn majority(&self) -> Option<&[usize]> {
+        match self.groups.as_slice() {
+            [first] => Some(first),
+            [first, second, ..] if first.len() > second.len() => Some(first),
+            _ => None,
+        }
+    }
+
+    /// The executors disagreeing with the majority, or all executors if there is no majority
+    #[must_use]
+    pub fn minority(&self) -> Vec<usize> {
+        let skip = usize::from(self.majority().is_some());
+        let mut minority: Vec<usize> = self.groups.iter().skip(skip).flatten().copied().collect();
+        minority.sort_unstable();
+        minority
+    }
+}
+
+/// Group the indexes of `keys` by equal keys, largest group first.
+/// Groups of the same size keep the order of their first appearance.
+fn group_keys<K: PartialEq>(keys: &[K]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(&K, Vec<usize>)> = vec![];
+    for (idx, key) in keys.iter().enumerate() {
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(idx),
+            None => groups.push((key, vec![idx])),
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_iter().map(|(_, group)| group).collect();
+    groups.sort_by(|a, b| b.len().cmp(&a.len()));
+    groups
+}
+
+/// A [`MultiDiffFeedback`] compares one [`Observer`] per executor of a
+/// [`crate::executors::MultiDiffExecutor`].
+///
+/// The `key_fn` maps each observer to the output that should be compared, e.g. `stdout_output`,
+/// [`value_output`] or a normalized output, see `OutputNormalizer`.
+/// If not all outputs are equal, the input is interesting and a [`MultiDiffMetadata`] with the
+/// majority and minority of executors is added to the testcase.
+pub struct MultiDiffFeedback<F, K, O, S>
+where
+    F: FnMut(&O) -> K,
+{
+    /// This feedback's name
+    name: Cow<'static, str>,
+    /// The observers to compare, one per executor
+    o_refs: Vec<Handle<O>>,
+    /// The function mapping each observer to its output
+    key_fn: F,
+    /// The groups found during the last run, if the outputs differed
+    last_groups: Option<Vec<Vec<usize>>>,
+    // The previous run's result of `Self::is_interesting`
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+    phantom: PhantomData<S>,
+}
+
+impl<F, K, O, S> MultiDiffFeedback<F, K, O, S>
+where
+    F: FnMut(&O) -> K,
+    O: Named,
+{
+    /// Create a new [`MultiDiffFeedback`] comparing the given observers, in executor order.
+    pub fn new(name: &'static str, observers: &[&O], key_fn: F) -> Result<Self, Error> {
+        if observers.len() < 2 {
+            return Err(Error::illegal_argument(
+                "MultiDiffFeedback: need at least two observers to compare",
+            ));
+        }
+        let o_refs: Vec<Handle<O>> = observers.iter().map(|o| o.handle()).collect();
+        for (i, o_ref) in o_refs.iter().enumerate() {
+            if o_refs[..i].iter().any(|other| other.name() == o_ref.name()) {
+                return Err(Error::illegal_argument(format!(
+                    "MultiDiffFeedback: observer names must be different ({} is used twice)",
+                    o_ref.name()
+                )));
+            }
+        }
+        Ok(Self {
+            name: Cow::from(name),
+            o_refs,
+            key_fn,
+            last_groups: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<F, K, O, S, T> FeedbackFactory<MultiDiffFeedback<F, K, O, S>, T>
+    for MultiDiffFeedback<F, K, O, S>
+where
+    F: FnMut(&O) -> K + Clone,
+    K: PartialEq,
+    O: Observer<S> + Named,
+    S: HasMetadata + State,
+{
+    fn create_feedback(&self, _ctx: &T) -> MultiDiffFeedback<F, K, O, S> {
+        Self {
+            name: self.name.clone(),
+            o_refs: self.o_refs.clone(),
+            key_fn: self.key_fn.clone(),
+            last_groups: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<F, K, O, S> Named for MultiDiffFeedback<F, K, O, S>
+where
+    F: FnMut(&O) -> K,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<F, K, O, S> Debug for MultiDiffFeedback<F, K, O, S>
+where
+    F: FnMut(&O) -> K,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiDiffFeedback")
+            .field("name", self.name())
+            .field("observers", &self.o_refs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, K, O, S> Feedback<S> for MultiDiffFeedback<F, K, O, S>
+where
+    F: FnMut(&O) -> K,
+    K: PartialEq,
+    O: Observer<S>,
+    S: HasMetadata + State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S> + MatchName,
+    {
+        let mut keys = Vec::with_capacity(self.o_refs.len());
+        for o_ref in &self.o_refs {
+            let o: &O = observers.get(o_ref).ok_or_else(|| {
+                Error::illegal_argument(format!(
+                    "MultiDiffFeedback: observer {} not found",
+                    o_ref.name()
+                ))
+            })?;
+            keys.push((self.key_fn)(o));
+        }
+        let groups = group_keys(&keys);
+        let res = groups.len() > 1;
+        self.last_groups = res.then_some(groups);
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if let Some(groups) = self.last_groups.take() {
+            testcase.add_metadata(MultiDiffMetadata { groups });
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.last_groups = None;
+        Ok(())
+    }
+}
+
+/// Compare the captured stdout of each executor, for [`MultiDiffFeedback`]
+#[cfg(feature = "std")]
+#[must_use]
+pub fn stdout_output(observer: &StdOutObserver) -> Option<Vec<u8>> {
+    observer.stdout.clone()
+}
+
+/// Compare the values, e.g. the return values of the harnesses, for [`MultiDiffFeedback`]
+#[must_use]
+pub fn value_output<T>(observer: &ValueObserver<T>) -> T
+where
+    T: Clone + Debug + Serialize + serde::de::DeserializeOwned,
+{
+    observer.get_ref().clone()
+}
+
+/// Normalizes outputs before comparing them in a [`MultiDiffFeedback`], e.g. to ignore timestamps,
+/// addresses or other output that is expected to differ between executors.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone, Default)]
+pub struct OutputNormalizer {
+    replacements: Vec<(regex::bytes::Regex, Vec<u8>)>,
+}
+
+#[cfg(feature = "regex")]
+impl OutputNormalizer {
+    /// Create a new [`OutputNormalizer`], not changing anything yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A normalizer replacing common date and time formats with `<TIME>`
+    #[must_use]
+    pub fn timestamps() -> Self {
+        Self::new()
+            .replace(
+                r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?",
+                b"<TIME>",
+            )
+            .and_then(|n| n.replace(r"\d{1,2}:\d{2}:\d{2}(\.\d+)?", b"<TIME>"))
+            .expect("The timestamp regexes are valid")
+    }
+
+    /// Replace all matches of the regex `pattern` with `replacement` before comparing.
+    ///
+    /// The `replacement` may refer to capture groups, like `$1`.
+    pub fn replace(mut self, pattern: &str, replacement: &[u8]) -> Result<Self, Error> {
+        let regex = regex::bytes::Regex::new(pattern).map_err(|e| {
+            Error::illegal_argument(format!("OutputNormalizer: invalid regex {pattern}: {e}"))
+        })?;
+        self.replacements.push((regex, replacement.to_vec()));
+        Ok(self)
+    }
+
+    /// Remove all matches of the regex `pattern` before comparing
+    pub fn ignore(self, pattern: &str) -> Result<Self, Error> {
+        self.replace(pattern, b"")
+    }
+
+    /// Apply all replacements to `output`, in order
+    #[must_use]
+    pub fn normalize(&self, output: &[u8]) -> Vec<u8> {
+        let mut output = output.to_vec();
+        for (regex, replacement) in &self.replacements {
+            output = regex
+                .replace_all(&output, replacement.as_slice())
+                .into_owned();
+        }
+        output
+    }
+
+    /// The normalized stdout of `observer`, to be used as key function of a [`MultiDiffFeedback`]
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn stdout(&self, observer: &StdOutObserver) -> Option<Vec<u8>> {
+        observer
+            .stdout
+            .as_deref()
+            .map(|stdout| self.normalize(stdout))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::borrow::Cow;
@@ -202,7 +507,10 @@ mod tests {
     use crate::{
         events::EventFirer,
         executors::ExitKind,
-        feedbacks::{differential::DiffResult, DiffFeedback, Feedback},
+        feedbacks::{
+            differential::{group_keys, DiffResult, MultiDiffFeedback, MultiDiffMetadata},
+            DiffFeedback, Feedback,
+        },
         inputs::{BytesInput, UsesInput},
         observers::Observer,
         state::{NopState, State, UsesState},
@@ -299,4 +607,75 @@ mod tests {
     fn test_diff_neq() {
         test_diff(false);
     }
+
+    #[test]
+    fn test_multi_diff() {
+        let mut nop_state = NopState::new();
+
+        let o1 = NopObserver::new("o1", true);
+        let o2 = NopObserver::new("o2", false);
+        let o3 = NopObserver::new("o3", true);
+
+        let mut diff_feedback = MultiDiffFeedback::new(
+            "multi_diff_feedback",
+            &[&o1, &o2, &o3],
+            |o: &NopObserver| o.value,
+        )
+        .unwrap();
+        let observers = tuple_list![o1, o2, o3];
+        assert!(diff_feedback
+            .is_interesting(
+                &mut nop_state,
+                &mut NopEventFirer {
+                    phantom: PhantomData
+                },
+                &BytesInput::new(vec![0]),
+                &observers,
+                &ExitKind::Ok
+            )
+            .unwrap());
+        assert_eq!(diff_feedback.last_groups, Some(vec![vec![0, 2], vec![1]]));
+
+        let o1 = NopObserver::new("o1", true);
+        let dup = MultiDiffFeedback::<_, _, _, NopState<BytesInput>>::new(
+            "multi_diff_feedback",
+            &[&o1, &o1],
+            |o: &NopObserver| o.value,
+        );
+        assert!(dup.is_err());
+    }
+
+    #[test]
+    fn test_multi_diff_groups() {
+        let metadata = MultiDiffMetadata {
+            groups: group_keys(&[1, 2, 2, 3, 2]),
+        };
+        assert_eq!(metadata.groups, vec![vec![1, 2, 4], vec![0], vec![3]]);
+        assert_eq!(metadata.majority(), Some([1, 2, 4].as_slice()));
+        assert_eq!(metadata.minority(), vec![0, 3]);
+
+        let tied = MultiDiffMetadata {
+            groups: group_keys(&["a", "b"]),
+        };
+        assert_eq!(tied.majority(), None);
+        assert_eq!(tied.minority(), vec![0, 1]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_output_normalizer() {
+        use crate::feedbacks::differential::OutputNormalizer;
+
+        let normalizer = OutputNormalizer::timestamps()
+            .ignore(r"0x[0-9a-f]+")
+            .unwrap();
+        assert_eq!(
+            normalizer.normalize(b"[2024-05-01 12:00:01.123] ptr 0xdeadbeef"),
+            normalizer.normalize(b"[2023-01-31T23:59:59Z] ptr 0x1000")
+        );
+        assert_ne!(
+            normalizer.normalize(b"12:00:01 ok"),
+            normalizer.normalize(b"12:00:01 fail")
+        );
+    }
 }
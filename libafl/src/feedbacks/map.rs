@@ -9,6 +9,7 @@ use core::{
     ops::{BitAnd, BitOr, Deref, DerefMut},
 };
 
+use hashbrown::HashSet;
 #[rustversion::nightly]
 use libafl_bolts::AsSlice;
 use libafl_bolts::{
@@ -325,6 +326,9 @@ where
     pub history_map: Vec<T>,
     /// Tells us how many non-initial entries there are in `history_map`
     pub num_covered_map_indexes: usize,
+    /// Indexes that are never novel, e.g. because they are unstable
+    #[serde(default)]
+    pub masked_indexes: HashSet<usize>,
 }
 
 libafl_bolts::impl_serdeany!(
@@ -342,6 +346,7 @@ where
         Self {
             history_map: vec![T::default(); map_size],
             num_covered_map_indexes: 0,
+            masked_indexes: HashSet::new(),
         }
     }
 
@@ -356,9 +361,27 @@ where
         Self {
             history_map,
             num_covered_map_indexes,
+            masked_indexes: HashSet::new(),
         }
     }
 
+    /// Mask the index, so that it is never considered novel by the [`MapFeedback`]
+    pub fn mask_index(&mut self, idx: usize) {
+        self.masked_indexes.insert(idx);
+    }
+
+    /// If the index is masked out of the novelty checks
+    #[must_use]
+    pub fn is_masked(&self, idx: usize) -> bool {
+        self.masked_indexes.contains(&idx)
+    }
+
+    /// The indexes masked out of the novelty checks
+    #[must_use]
+    pub fn masked_indexes(&self) -> &HashSet<usize> {
+        &self.masked_indexes
+    }
+
     /// Reset the map
     pub fn reset(&mut self) -> Result<(), Error> {
         let cnt = self.history_map.len();
@@ -601,6 +624,7 @@ where
         debug_assert!(map.len() >= size);
 
         let history_map = map_state.history_map.as_slice();
+        let masked_indexes = &map_state.masked_indexes;
 
         // Non vector implementation for reference
         /*for (i, history) in history_map.iter_mut().enumerate() {
@@ -626,11 +650,11 @@ where
                 let items = VectorType::from_slice(&map[i..]);
 
                 if items.simd_max(history) != history {
-                    interesting = true;
                     unsafe {
                         for j in i..(i + VectorType::LEN) {
                             let item = *map.get_unchecked(j);
-                            if item > *history_map.get_unchecked(j) {
+                            if item > *history_map.get_unchecked(j) && !masked_indexes.contains(&j)
+                            {
                                 novelties.push(j);
                             }
                         }
@@ -641,12 +665,12 @@ where
             for j in (size - left)..size {
                 unsafe {
                     let item = *map.get_unchecked(j);
-                    if item > *history_map.get_unchecked(j) {
-                        interesting = true;
+                    if item > *history_map.get_unchecked(j) && !masked_indexes.contains(&j) {
                         novelties.push(j);
                     }
                 }
             }
+            interesting = !novelties.is_empty();
         } else {
             for step in 0..steps {
                 let i = step * VectorType::LEN;
@@ -654,8 +678,13 @@ where
                 let items = VectorType::from_slice(&map[i..]);
 
                 if items.simd_max(history) != history {
-                    interesting = true;
-                    break;
+                    // Check the single entries only if some are masked
+                    interesting = masked_indexes.is_empty()
+                        || (i..(i + VectorType::LEN))
+                            .any(|j| map[j] > history_map[j] && !masked_indexes.contains(&j));
+                    if interesting {
+                        break;
+                    }
                 }
             }
 
@@ -663,7 +692,7 @@ where
                 for j in (size - left)..size {
                     unsafe {
                         let item = *map.get_unchecked(j);
-                        if item > *history_map.get_unchecked(j) {
+                        if item > *history_map.get_unchecked(j) && !masked_indexes.contains(&j) {
                             interesting = true;
                             break;
                         }
@@ -778,6 +807,7 @@ where
         }
 
         let history_map = map_state.history_map.as_slice();
+        let masked_indexes = &map_state.masked_indexes;
 
         let initial = observer.initial();
 
@@ -791,7 +821,7 @@ where
             {
                 let existing = unsafe { *history_map.get_unchecked(i) };
                 let reduced = R::reduce(existing, item);
-                if N::is_novel(existing, reduced) {
+                if N::is_novel(existing, reduced) && !masked_indexes.contains(&i) {
                     interesting = true;
                     novelties.push(i);
                 }
@@ -805,7 +835,7 @@ where
             {
                 let existing = unsafe { *history_map.get_unchecked(i) };
                 let reduced = R::reduce(existing, item);
-                if N::is_novel(existing, reduced) {
+                if N::is_novel(existing, reduced) && !masked_indexes.contains(&i) {
                     interesting = true;
                     break;
                 }
@@ -818,7 +848,87 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::feedbacks::{AllIsNovel, IsNovel, NextPow2IsNovel};
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            AllIsNovel, Feedback, HasObserverHandle, IsNovel, MapFeedbackMetadata, MaxMapFeedback,
+            NextPow2IsNovel,
+        },
+        inputs::BytesInput,
+        observers::{CanTrack, MapObserver, StdMapObserver},
+        state::StdState,
+        HasNamedMetadata,
+    };
+
+    /// Check that hits of masked indexes are never novel, in the 128 bit vectors and the rest of
+    /// a map of `$entry`s, with and without tracking the novelties
+    macro_rules! test_masked_indexes {
+        ($entry:ty) => {{
+            for track_novelties in [false, true] {
+                // 32 entries in vectors, and 8 left
+                let mut observer =
+                    StdMapObserver::owned("map", vec![<$entry>::default(); 40]).track_novelties();
+                let mut feedback = MaxMapFeedback::new(&observer);
+                if !track_novelties {
+                    feedback.novelties = None;
+                }
+                let mut objective = ();
+                let mut state = StdState::new(
+                    StdRand::with_seed(0),
+                    InMemoryCorpus::<BytesInput>::new(),
+                    InMemoryCorpus::new(),
+                    &mut feedback,
+                    &mut objective,
+                )
+                .unwrap();
+                let meta = state
+                    .named_metadata_mut::<MapFeedbackMetadata<$entry>>(
+                        feedback.observer_handle().name(),
+                    )
+                    .unwrap();
+                meta.mask_index(5);
+                meta.mask_index(35);
+
+                let mut mgr = NopEventManager::new();
+                let input = BytesInput::new(vec![]);
+
+                observer.as_mut().set(5, 1);
+                observer.as_mut().set(35, 1);
+                let observers = tuple_list!(observer);
+                assert!(!feedback
+                    .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                    .unwrap());
+                if let Some(novelties) = &feedback.novelties {
+                    assert!(novelties.is_empty());
+                }
+
+                let (mut observer, ()) = observers;
+                observer.as_mut().set(6, 1);
+                let observers = tuple_list!(observer);
+                assert!(feedback
+                    .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)
+                    .unwrap());
+                if let Some(novelties) = &feedback.novelties {
+                    assert_eq!(novelties, &[6]);
+                }
+            }
+        }};
+    }
+
+    /// On nightly, maps of `u8` take the vectorized path
+    #[test]
+    fn test_masked_indexes_u8() {
+        test_masked_indexes!(u8);
+    }
+
+    #[test]
+    fn test_masked_indexes_generic() {
+        test_masked_indexes!(u16);
+    }
 
     #[test]
     fn test_map_is_novel() {
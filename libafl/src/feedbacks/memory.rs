@@ -0,0 +1,105 @@
+//! Feedback and metadata for the peak memory usage of the target.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    observers::{MemoryObserver, ObserversTuple},
+    state::State,
+    Error, HasMetadata,
+};
+
+/// Metadata for [`MemoryUsageToMetadataFeedback`], the peak memory usage of the testcase in bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryUsageMetadata {
+    #[allow(missing_docs)]
+    pub peak_memory: u64,
+}
+
+impl_serdeany!(MemoryUsageMetadata);
+
+/// Nop feedback that annotates the peak memory usage of the run in the new testcase. The testcase
+/// is never interesting (use with an OR, e.g. with an [`crate::feedbacks::OomFeedback`] objective).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MemoryUsageToMetadataFeedback {
+    o_ref: Handle<MemoryObserver>,
+}
+
+impl<S> Feedback<S> for MemoryUsageToMetadataFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    /// Append the peak memory usage to the testcase, if it is known.
+    #[inline]
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("MemoryObserver is missing"))?;
+        if let Some(peak_memory) = observer.peak_memory() {
+            testcase
+                .metadata_map_mut()
+                .insert(MemoryUsageMetadata { peak_memory });
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+impl Named for MemoryUsageToMetadataFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl MemoryUsageToMetadataFeedback {
+    /// Creates a new [`MemoryUsageToMetadataFeedback`].
+    #[must_use]
+    pub fn new(observer: &MemoryObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
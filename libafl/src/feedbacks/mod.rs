@@ -12,15 +12,20 @@ use core::{
     marker::PhantomData,
 };
 
+pub use asan::{AsanReportMetadata, AsanReportToMetadataFeedback};
 #[cfg(feature = "std")]
 pub use concolic::ConcolicFeedback;
-pub use differential::DiffFeedback;
+#[cfg(all(feature = "std", unix))]
+pub use core_dump::{CoreDumpMetadata, CoreDumpToMetadataFeedback};
+pub use differential::{DiffFeedback, MultiDiffFeedback};
 use libafl_bolts::{
     tuples::{Handle, Handled, MatchNameRef},
     Named,
 };
 pub use list::*;
 pub use map::*;
+#[cfg(all(feature = "std", unix))]
+pub use memory::{MemoryUsageMetadata, MemoryUsageToMetadataFeedback};
 #[cfg(feature = "nautilus")]
 pub use nautilus::*;
 #[cfg(feature = "std")]
@@ -37,8 +42,11 @@ use crate::{
     state::State,
     Error,
 };
+pub mod asan;
 #[cfg(feature = "std")]
 pub mod concolic;
+#[cfg(all(feature = "std", unix))]
+pub mod core_dump;
 #[cfg(feature = "std")]
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
@@ -46,6 +54,8 @@ pub mod differential;
 /// The module for list feedback
 pub mod list;
 pub mod map;
+#[cfg(all(feature = "std", unix))]
+pub mod memory;
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "std")]
@@ -1061,6 +1071,77 @@ impl<T> FeedbackFactory<TimeoutFeedback, T> for TimeoutFeedback {
     }
 }
 
+/// An [`OomFeedback`] reports as interesting if the target ran out of memory,
+/// e.g. exceeded the limit of a [`crate::executors::hooks::memory::MemoryLimitHook`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OomFeedback {
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<S> Feedback<S> for OomFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        let res = matches!(exit_kind, ExitKind::Oom);
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
+impl Named for OomFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("OomFeedback");
+        &NAME
+    }
+}
+
+impl OomFeedback {
+    /// Returns a new [`OomFeedback`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl Default for OomFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FeedbackFactory<OomFeedback, T> for OomFeedback {
+    fn create_feedback(&self, _ctx: &T) -> OomFeedback {
+        OomFeedback::new()
+    }
+}
+
 /// A [`DiffExitKindFeedback`] checks if there is a difference in the [`crate::executors::ExitKind`]s in a [`crate::executors::DiffExecutor`].
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DiffExitKindFeedback {
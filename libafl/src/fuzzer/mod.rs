@@ -9,7 +9,7 @@ use serde::{de::DeserializeOwned, Serialize};
 use crate::{
     corpus::{Corpus, CorpusId, HasCurrentCorpusId, HasTestcase, Testcase},
     events::{Event, EventConfig, EventFirer, EventProcessor, ProgressReporter},
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{BatchExecutor, Executor, ExitKind, HasObservers},
     feedbacks::Feedback,
     inputs::UsesInput,
     mark_feature_time,
@@ -207,6 +207,21 @@ pub trait Evaluator<E, EM>: UsesState {
     ) -> Result<CorpusId, Error>;
 }
 
+/// Evaluate several inputs at once, running them concurrently on a [`BatchExecutor`]
+pub trait BatchEvaluator<E, EM>: UsesState {
+    /// Runs the inputs, in batches of [`BatchExecutor::batch_size`], and triggers observers and feedback
+    /// for each of them in order.
+    /// Returns, for each input, if it is interesting and (option) the index of the new testcase in the corpus
+    fn evaluate_inputs_batch(
+        &mut self,
+        state: &mut Self::State,
+        executor: &mut E,
+        manager: &mut EM,
+        inputs: Vec<<Self::State as UsesInput>::Input>,
+        send_events: bool,
+    ) -> Result<Vec<(ExecuteInputResult, Option<CorpusId>)>, Error>;
+}
+
 /// The main fuzzer trait.
 pub trait Fuzzer<E, EM, ST>: Sized + UsesState
 where
@@ -756,6 +771,67 @@ where
     }
 }
 
+impl<CS, E, EM, F, OF, OT> BatchEvaluator<E, EM> for StdFuzzer<CS, F, OF>
+where
+    CS: Scheduler,
+    E: HasObservers<State = Self::State, Observers = OT> + BatchExecutor<EM, Self>,
+    EM: EventFirer<State = Self::State>,
+    F: Feedback<Self::State>,
+    OF: Feedback<Self::State>,
+    OT: ObserversTuple<Self::State> + Serialize + DeserializeOwned,
+    CS::State: HasCorpus + HasSolutions + HasExecutions,
+{
+    fn evaluate_inputs_batch(
+        &mut self,
+        state: &mut Self::State,
+        executor: &mut E,
+        manager: &mut EM,
+        inputs: Vec<<Self::State as UsesInput>::Input>,
+        send_events: bool,
+    ) -> Result<Vec<(ExecuteInputResult, Option<CorpusId>)>, Error> {
+        let batch_size = executor.batch_size().max(1);
+        let mut results = Vec::with_capacity(inputs.len());
+        let mut inputs = inputs.into_iter().peekable();
+
+        while inputs.peek().is_some() {
+            let batch: Vec<_> = inputs.by_ref().take(batch_size).collect();
+
+            start_timer!(state);
+            let exit_kinds = executor.run_batch(self, state, manager, &batch)?;
+            mark_feature_time!(state, PerfFeature::TargetExecution);
+
+            // Replay the observers for each run in order, as if they were executed one after the other.
+            // The results of the runs, including their runtime, are loaded by the executor.
+            for (idx, (input, exit_kind)) in batch.into_iter().zip(exit_kinds).enumerate() {
+                start_timer!(state);
+                executor.observers_mut().pre_exec_all(state, &input)?;
+                mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+                executor.load_batch_result(idx)?;
+
+                start_timer!(state);
+                executor
+                    .observers_mut()
+                    .post_exec_all(state, &input, &exit_kind)?;
+                mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+                let observers = executor.observers();
+                self.scheduler.on_evaluation(state, &input, &*observers)?;
+                results.push(self.evaluate_execution(
+                    state,
+                    manager,
+                    input,
+                    &*observers,
+                    &exit_kind,
+                    send_events,
+                )?);
+            }
+        }
+
+        Ok(results)
+    }
+}
+
 impl<CS, E, EM, F, OF, ST> Fuzzer<E, EM, ST> for StdFuzzer<CS, F, OF>
 where
     CS: Scheduler,
@@ -933,15 +1009,22 @@ where
 
 #[cfg(test)]
 pub mod test {
-    use core::marker::PhantomData;
+    use alloc::{vec, vec::Vec};
+    use core::{marker::PhantomData, time::Duration};
 
-    use libafl_bolts::Error;
+    use libafl_bolts::{rands::StdRand, Error};
 
+    use super::{BatchEvaluator, ExecuteInputResult, StdFuzzer};
     use crate::{
-        corpus::CorpusId,
-        events::{EventProcessor, ProgressReporter},
+        corpus::{Corpus, CorpusId, InMemoryCorpus},
+        events::{EventProcessor, NopEventManager, ProgressReporter},
+        executors::{test::NopBatchExecutor, HasObservers},
+        feedback_or,
+        feedbacks::{MaxMapFeedback, TimeFeedback},
+        inputs::{BytesInput, HasMutatorBytes},
+        schedulers::QueueScheduler,
         stages::{HasCurrentStage, StagesTuple},
-        state::{HasExecutions, HasLastReportTime, State, UsesState},
+        state::{HasCorpus, HasExecutions, HasLastReportTime, State, StdState, UsesState},
         Fuzzer, HasMetadata,
     };
 
@@ -989,4 +1072,61 @@ pub mod test {
             unimplemented!()
         }
     }
+
+    #[test]
+    fn test_evaluate_inputs_batch() {
+        let mut executor = NopBatchExecutor::new(2, 16);
+        let mut feedback = {
+            let observers = executor.observers();
+            feedback_or!(
+                MaxMapFeedback::new(&observers.0),
+                TimeFeedback::new(&observers.1 .0)
+            )
+        };
+        let mut objective = ();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        // Three batches, the last input covers the same edge as the first one
+        let inputs = vec![
+            BytesInput::new(vec![3]),
+            BytesInput::new(vec![5]),
+            BytesInput::new(vec![7]),
+            BytesInput::new(vec![3]),
+            BytesInput::new(vec![3]),
+        ];
+        let results = fuzzer
+            .evaluate_inputs_batch(&mut state, &mut executor, &mut mgr, inputs, false)
+            .unwrap();
+
+        assert_eq!(executor.batches, [2, 2, 1]);
+        assert_eq!(*state.executions(), 5);
+        assert_eq!(
+            results
+                .iter()
+                .map(|(result, _)| *result == ExecuteInputResult::Corpus)
+                .collect::<Vec<_>>(),
+            [true, true, true, false, false]
+        );
+        assert!(results[3].1.is_none());
+        assert_eq!(state.corpus().count(), 3);
+
+        // Each testcase keeps the runtime of its own run
+        for ((_, id), expected) in results.iter().zip([3, 5, 7]) {
+            let testcase = state.corpus().get(id.unwrap()).unwrap().borrow();
+            assert_eq!(
+                testcase.input().as_ref().unwrap().bytes(),
+                &[u8::try_from(expected).unwrap()]
+            );
+            assert_eq!(*testcase.exec_time(), Some(Duration::from_millis(expected)));
+        }
+    }
 }
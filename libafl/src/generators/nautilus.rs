@@ -1,5 +1,6 @@
 //! Generators for the [`Nautilus`](https://github.com/RUB-SysSec/nautilus) grammar fuzzer
 use alloc::{
+    boxed::Box,
     string::{String, ToString},
     vec::Vec,
 };
@@ -10,14 +11,24 @@ use libafl_bolts::rands::Rand;
 
 pub use crate::common::nautilus::grammartec::newtypes::NTermId;
 use crate::{
-    common::nautilus::grammartec::context::Context, generators::Generator,
-    inputs::nautilus::NautilusInput, state::HasRand, Error,
+    common::nautilus::{
+        formats::{add_to_context, antlr, ebnf, ParsedGrammar},
+        grammartec::{
+            actions::{UnparseActions, UnparseState},
+            context::Context,
+        },
+    },
+    generators::Generator,
+    inputs::nautilus::NautilusInput,
+    state::HasRand,
+    Error,
 };
 
 /// The nautilus context for a generator
 pub struct NautilusContext {
     /// The nautilus context for a generator
     pub ctx: Context,
+    actions: UnparseActions,
 }
 
 impl Debug for NautilusContext {
@@ -39,7 +50,10 @@ impl NautilusContext {
         let root = "{".to_string() + &rules[0][0] + "}";
         ctx.add_rule("START", root.as_bytes());
         ctx.initialize(tree_depth);
-        Self { ctx }
+        Self {
+            ctx,
+            actions: UnparseActions::default(),
+        }
     }
 
     /// Returns a new [`NautilusContext`] with support for non UTF-8 rules.
@@ -80,7 +94,10 @@ impl NautilusContext {
         let root = format!("{{{}}}", rules.first()?.0);
         ctx.add_rule("START", root.as_bytes());
         ctx.initialize(tree_depth);
-        Some(Self { ctx })
+        Some(Self {
+            ctx,
+            actions: UnparseActions::default(),
+        })
     }
 
     /// Create a new [`NautilusContext`] from a file
@@ -92,6 +109,133 @@ impl NautilusContext {
             serde_json::from_reader(reader).expect("Cannot parse grammar file");
         Self::new(tree_depth, &rules)
     }
+
+    /// Create a new [`NautilusContext`] from ANTLR4 grammars.
+    ///
+    /// A grammar split into a lexer and a parser grammar can be passed as a whole;
+    /// the first parser rule of the first grammar is the starting rule.
+    /// Repetitions, optional parts and character sets are desugared into plain Nautilus rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libafl::generators::nautilus::NautilusContext;
+    ///
+    /// let grammar = r"
+    ///     grammar Calc;
+    ///     expr : NUMBER (('+' | '*') NUMBER)* ;
+    ///     NUMBER : [0-9]+ ;
+    ///     WS : [ \t\r\n]+ -> skip ;
+    /// ";
+    /// let context = NautilusContext::from_antlr4(100, &[grammar]).unwrap();
+    /// ```
+    pub fn from_antlr4(tree_depth: usize, grammars: &[&str]) -> Result<Self, Error> {
+        Self::from_parsed(tree_depth, &antlr::parse_all(grammars)?)
+    }
+
+    /// Create a new [`NautilusContext`] from ANTLR4 grammar files, see [`NautilusContext::from_antlr4`].
+    pub fn from_antlr4_files<P: AsRef<Path>>(
+        tree_depth: usize,
+        grammar_files: &[P],
+    ) -> Result<Self, Error> {
+        let grammars = grammar_files
+            .iter()
+            .map(fs::read_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let grammars: Vec<&str> = grammars.iter().map(String::as_str).collect();
+        Self::from_antlr4(tree_depth, &grammars)
+    }
+
+    /// Create a new [`NautilusContext`] from a W3C-style EBNF grammar, as used in the XML specification.
+    ///
+    /// The first rule is the starting rule.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libafl::generators::nautilus::NautilusContext;
+    ///
+    /// let grammar = r#"
+    ///     list ::= '[' (item (',' item)*)? ']'
+    ///     item ::= [a-z]+ | #x30 | list
+    /// "#;
+    /// let context = NautilusContext::from_ebnf(100, grammar).unwrap();
+    /// ```
+    pub fn from_ebnf(tree_depth: usize, grammar: &str) -> Result<Self, Error> {
+        Self::from_parsed(tree_depth, &ebnf::parse(grammar)?)
+    }
+
+    /// Create a new [`NautilusContext`] from a W3C-style EBNF grammar file, see [`NautilusContext::from_ebnf`].
+    pub fn from_ebnf_file<P: AsRef<Path>>(
+        tree_depth: usize,
+        grammar_file: P,
+    ) -> Result<Self, Error> {
+        Self::from_ebnf(tree_depth, &fs::read_to_string(grammar_file)?)
+    }
+
+    fn from_parsed(tree_depth: usize, grammar: &ParsedGrammar) -> Result<Self, Error> {
+        let productions = grammar.desugar()?;
+        let mut ctx = Context::new();
+        add_to_context(&mut ctx, &productions);
+        ctx.initialize(tree_depth);
+        Ok(Self {
+            ctx,
+            actions: UnparseActions::default(),
+        })
+    }
+
+    /// Attach a semantic action to all rules of the nonterminal `nonterm`.
+    ///
+    /// When a [`NautilusInput`] is unparsed, every child of such a rule (terminals and
+    /// nonterminals, in order) is unparsed on its own and the action returns the bytes the rule
+    /// unparses to. This allows fixing up length fields, checksums or identifiers without touching
+    /// the trees the mutators work on. The [`UnparseState`] is shared by all actions of one unparse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libafl::generators::nautilus::NautilusContext;
+    ///
+    /// let rules = [
+    ///     ("MSG", "{LEN}{DATA}".as_bytes()),
+    ///     ("LEN", "0".as_bytes()),
+    ///     ("DATA", "a{DATA}".as_bytes()),
+    ///     ("DATA", "b".as_bytes()),
+    /// ];
+    /// let mut context = NautilusContext::with_rules(100, &rules).unwrap();
+    /// // Replace the placeholder length with the real length of `DATA`
+    /// context
+    ///     .add_action("MSG", |_state, children| {
+    ///         let mut res = vec![children[1].len() as u8];
+    ///         res.extend_from_slice(&children[1]);
+    ///         res
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn add_action<F>(&mut self, nonterm: &str, action: F) -> Result<(), Error>
+    where
+        F: Fn(&mut UnparseState, &[Vec<u8>]) -> Vec<u8> + Send + Sync + 'static,
+    {
+        let nt = self.ctx.try_nt_id(nonterm).ok_or_else(|| {
+            Error::key_not_found(format!("No nonterminal {nonterm} in this grammar"))
+        })?;
+        self.actions.set_action(nt, Box::new(action));
+        Ok(())
+    }
+
+    /// Add a hook that runs on the whole unparsed input, after all actions, e.g. to append a checksum.
+    pub fn add_post_unparse_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&mut UnparseState, &mut Vec<u8>) + Send + Sync + 'static,
+    {
+        self.actions.add_post_hook(Box::new(hook));
+    }
+
+    /// The semantic actions and hooks run when unparsing inputs of this grammar
+    #[must_use]
+    pub fn actions(&self) -> &UnparseActions {
+        &self.actions
+    }
 }
 
 #[derive(Clone)]
@@ -129,6 +129,15 @@ impl<I> MultipartInput<I> {
         self.names.push(name);
     }
 
+    /// Removes the part at `idx`, returning its name and the part, or `None` if out of bounds.
+    pub fn remove_part(&mut self, idx: usize) -> Option<(String, I)> {
+        if idx < self.parts.len() {
+            Some((self.names.remove(idx), self.parts.remove(idx)))
+        } else {
+            None
+        }
+    }
+
     /// Iterate over the parts of this input; no order is specified.
     pub fn iter(&self) -> impl Iterator<Item = (&str, &I)> {
         self.names.iter().map(String::as_ref).zip(self.parts())
@@ -10,6 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     common::nautilus::grammartec::{
+        actions::UnparseState,
         newtypes::NodeId,
         rule::RuleIdOrCustom,
         tree::{Tree, TreeLike},
@@ -79,9 +80,24 @@ impl NautilusInput {
     }
 
     /// Generate a `Nautilus` input from the given bytes
+    ///
+    /// Runs the semantic actions and post-unparse hooks registered on the `context`.
     pub fn unparse(&self, context: &NautilusContext, bytes: &mut Vec<u8>) {
         bytes.clear();
-        self.tree.unparse(NodeId::from(0), &context.ctx, bytes);
+        let actions = context.actions();
+        if actions.is_empty() {
+            self.tree.unparse(NodeId::from(0), &context.ctx, bytes);
+        } else {
+            let mut state = UnparseState::default();
+            self.tree.unparse_with_actions(
+                NodeId::from(0),
+                &context.ctx,
+                actions,
+                &mut state,
+                bytes,
+            );
+            actions.run_post_hooks(&mut state, bytes);
+        }
     }
 
     /// Get the tree representation of this input
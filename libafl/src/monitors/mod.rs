@@ -901,12 +901,22 @@ pub struct ClientPerfMonitor {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-/// Count the imported testcase from other nodes that came with observers
+/// Count the imported testcase from other nodes that came with observers,
+/// and keep track of the outgoing llmp queue depth
 pub struct ScalabilityMonitor {
     /// Imported testcase received with observer
     pub testcase_with_observers: usize,
     /// Imported testcase received without observer
     pub testcase_without_observers: usize,
+    /// Bytes on our outgoing llmp pages the broker did not read yet
+    #[serde(default)]
+    pub llmp_pending_bytes: usize,
+    /// Low priority llmp messages dropped because we were over our outgoing budget
+    #[serde(default)]
+    pub llmp_dropped_msgs: u64,
+    /// Low priority llmp messages superseded by a newer one while we were over our outgoing budget
+    #[serde(default)]
+    pub llmp_coalesced_msgs: u64,
 }
 
 impl ScalabilityMonitor {
@@ -916,6 +926,9 @@ impl ScalabilityMonitor {
         Self {
             testcase_with_observers: 0,
             testcase_without_observers: 0,
+            llmp_pending_bytes: 0,
+            llmp_dropped_msgs: 0,
+            llmp_coalesced_msgs: 0,
         }
     }
 }
@@ -0,0 +1,631 @@
+//! Reports in the format of the `compiler-rt` `AddressSanitizer`, for binary-only sanitizers.
+//!
+//! The [`AsanReport`] writer lets runtimes such as `libafl_qemu` and `libafl_frida` emit the same
+//! text as a compiler-instrumented target, so that tooling parsing `ASan` output can be used
+//! for binary-only crashes, too.
+//! The [`AsanReportObserver`] holds the reports of the last run.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::fmt::{self, Display, Write};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{inputs::UsesInput, observers::Observer, Error};
+
+/// The separator line printed before each report
+pub const ASAN_REPORT_SEPARATOR: &str =
+    "=================================================================";
+
+/// The number of shadow bytes in each row of the shadow memory dump
+pub const ASAN_SHADOW_ROW_SIZE: usize = 16;
+
+/// The number of rows printed before and after the row of the buggy address
+pub const ASAN_SHADOW_CONTEXT_ROWS: usize = 5;
+
+const SHADOW_LEGEND: &str = "Shadow byte legend (one shadow byte represents 8 application bytes):
+  Addressable:           00
+  Partially addressable: 01 02 03 04 05 06 07 
+  Heap left redzone:       fa
+  Freed heap region:       fd
+  Stack left redzone:      f1
+  Stack mid redzone:       f2
+  Stack right redzone:     f3
+  Stack after return:      f5
+  Stack use after scope:   f8
+  Global redzone:          f9
+  Global init order:       f6
+  Poisoned by user:        f7
+  Container overflow:      fc
+  Array cookie:            ac
+  Intra object redzone:    bb
+  ASan internal:           fe
+  Left alloca redzone:     ca
+  Right alloca redzone:    cb
+";
+
+/// The bug type `compiler-rt` reports for an access to memory with the given shadow byte
+#[must_use]
+pub fn bug_type_for_shadow_byte(shadow: u8) -> &'static str {
+    match shadow {
+        // 0xe9 is the generic heap redzone of `QASan`
+        0xfa | 0xfb | 0xe9 => "heap-buffer-overflow",
+        0xfd => "heap-use-after-free",
+        0xf1..=0xf3 => "stack-buffer-overflow",
+        0xf5 => "stack-use-after-return",
+        0xf8 => "stack-use-after-scope",
+        0xf9 => "global-buffer-overflow",
+        0xf6 => "initialization-order-fiasco",
+        0xf7 => "use-after-poison",
+        0xfc => "container-overflow",
+        0xca | 0xcb => "dynamic-stack-buffer-overflow",
+        0xbb => "intra-object-overflow",
+        _ => "unknown-crash",
+    }
+}
+
+/// A symbolized frame of a stack trace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsanStackFrame {
+    /// The address of the frame
+    pub pc: u64,
+    /// The (demangled) name of the function, if known
+    pub function: Option<String>,
+    /// The source file, if debug info is available
+    pub file: Option<String>,
+    /// The source line, if debug info is available
+    pub line: Option<u32>,
+    /// The source column, if debug info is available
+    pub column: Option<u32>,
+    /// The module containing the frame
+    pub module: Option<String>,
+    /// The offset of `pc` in `module`
+    pub module_offset: u64,
+}
+
+impl AsanStackFrame {
+    /// Create an unsymbolized frame
+    #[must_use]
+    pub fn new(pc: u64) -> Self {
+        Self {
+            pc,
+            ..Self::default()
+        }
+    }
+
+    /// Write the location of this frame, as in `file:line:column` or `(module+0xoffset)`
+    fn write_location<W: Write>(&self, out: &mut W) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(out, "{file}")?;
+            if let Some(line) = self.line {
+                write!(out, ":{line}")?;
+                if let Some(column) = self.column {
+                    write!(out, ":{column}")?;
+                }
+            }
+        } else if let Some(module) = &self.module {
+            write!(out, "({module}+{:#x})", self.module_offset)?;
+        } else {
+            write!(out, "(<unknown module>)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The direction of a memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AsanAccessKind {
+    /// A load
+    Read,
+    /// A store
+    Write,
+}
+
+impl Display for AsanAccessKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsanAccessKind::Read => write!(f, "READ"),
+            AsanAccessKind::Write => write!(f, "WRITE"),
+        }
+    }
+}
+
+/// The kind of bug an [`AsanReport`] is about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AsanBug {
+    /// An invalid memory access, of type `bug_type` such as `heap-buffer-overflow`
+    Access {
+        /// The bug type, see [`bug_type_for_shadow_byte`]
+        bug_type: Cow<'static, str>,
+        /// The accessed address
+        addr: u64,
+        /// The direction of the access
+        kind: AsanAccessKind,
+        /// The size of the access
+        size: usize,
+    },
+    /// A free of an already freed chunk
+    DoubleFree {
+        /// The freed address
+        addr: u64,
+    },
+    /// A free of an address that was not returned by the allocator
+    BadFree {
+        /// The freed address
+        addr: u64,
+    },
+    /// Allocations never freed, reported in the `LeakSanitizer` format
+    Leak {
+        /// The size of the leaked chunk
+        size: u64,
+    },
+    /// A deadly signal, such as `SEGV`
+    Signal {
+        /// The name of the signal, such as `SEGV` or `ABRT`
+        name: Cow<'static, str>,
+        /// The faulting address, if known
+        addr: Option<u64>,
+        /// The kind of the faulting access, if known
+        kind: Option<AsanAccessKind>,
+    },
+}
+
+impl AsanBug {
+    /// The bug type printed in the `SUMMARY` line
+    #[must_use]
+    pub fn bug_type(&self) -> &str {
+        match self {
+            AsanBug::Access { bug_type, .. } => bug_type,
+            AsanBug::DoubleFree { .. } => "double-free",
+            AsanBug::BadFree { .. } => "bad-free",
+            AsanBug::Leak { .. } => "memory-leak",
+            AsanBug::Signal { name, .. } => name,
+        }
+    }
+}
+
+/// A heap chunk related to the buggy address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsanChunkInfo {
+    /// The start of the chunk
+    pub start: u64,
+    /// The size of the chunk, as requested by the user
+    pub size: u64,
+    /// The stack of the allocation
+    pub alloc_stack: Vec<AsanStackFrame>,
+    /// The stack of the free, if the chunk was freed
+    pub free_stack: Option<Vec<AsanStackFrame>>,
+}
+
+/// The shadow memory around the buggy address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsanShadowWindow {
+    /// The address of the first shadow byte, aligned to [`ASAN_SHADOW_ROW_SIZE`]
+    pub start: u64,
+    /// The shadow bytes, in the `compiler-rt` encoding
+    pub bytes: Vec<u8>,
+    /// The index of the shadow byte of the buggy address in `bytes`
+    pub buggy: usize,
+}
+
+impl AsanShadowWindow {
+    /// Read the shadow rows around `shadow_addr`, the shadow address of the buggy address,
+    /// using `read` to read a single shadow byte.
+    ///
+    /// Rows in which `read` fails for some byte are left out.
+    pub fn around<F>(shadow_addr: u64, mut read: F) -> Self
+    where
+        F: FnMut(u64) -> Option<u8>,
+    {
+        let row_size = ASAN_SHADOW_ROW_SIZE as u64;
+        let context = (ASAN_SHADOW_CONTEXT_ROWS as u64) * row_size;
+        let buggy_row = shadow_addr & !(row_size - 1);
+        let mut start = buggy_row.saturating_sub(context);
+        let mut bytes = Vec::new();
+        let mut row = start;
+        while row <= buggy_row.saturating_add(context) {
+            let values: Option<Vec<u8>> = (row..row + row_size).map(&mut read).collect();
+            match values {
+                Some(values) => bytes.extend(values),
+                None if row < buggy_row => {
+                    start = row + row_size;
+                    bytes.clear();
+                }
+                None => break,
+            }
+            row += row_size;
+        }
+        Self {
+            start,
+            bytes,
+            buggy: (shadow_addr - start) as usize,
+        }
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> fmt::Result {
+        writeln!(out, "Shadow bytes around the buggy address:")?;
+        for (row_idx, row) in self.bytes.chunks(ASAN_SHADOW_ROW_SIZE).enumerate() {
+            let row_start = row_idx * ASAN_SHADOW_ROW_SIZE;
+            let has_buggy = (row_start..row_start + row.len()).contains(&self.buggy);
+            let prefix = if has_buggy { "=>" } else { "  " };
+            write!(out, "{prefix}0x{:012x}:", self.start + row_start as u64)?;
+            for (i, byte) in row.iter().enumerate() {
+                let idx = row_start + i;
+                let before = if idx == self.buggy {
+                    "["
+                } else if i != 0 && idx == self.buggy + 1 {
+                    ""
+                } else {
+                    " "
+                };
+                let after = if idx == self.buggy { "]" } else { "" };
+                write!(out, "{before}{byte:02x}{after}")?;
+            }
+            writeln!(out)?;
+        }
+        write!(out, "{SHADOW_LEGEND}")
+    }
+}
+
+/// A report of a memory error, printed like `compiler-rt` `AddressSanitizer` does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsanReport {
+    /// The pid printed in the `==pid==` prefixes
+    pub pid: u32,
+    /// The bug
+    pub bug: AsanBug,
+    /// The program counter at the time of the error
+    pub pc: u64,
+    /// The frame pointer at the time of the error
+    pub bp: u64,
+    /// The stack pointer at the time of the error
+    pub sp: u64,
+    /// The stack at the time of the error, innermost frame first
+    pub stack: Vec<AsanStackFrame>,
+    /// The heap chunk the buggy address belongs to, or is next to
+    pub chunk: Option<AsanChunkInfo>,
+    /// The shadow memory around the buggy address
+    pub shadow: Option<AsanShadowWindow>,
+}
+
+impl AsanReport {
+    /// Create a new report for `bug`, without stack, chunk and shadow information
+    #[must_use]
+    pub fn new(pid: u32, bug: AsanBug, pc: u64, bp: u64, sp: u64) -> Self {
+        Self {
+            pid,
+            bug,
+            pc,
+            bp,
+            sp,
+            stack: Vec::new(),
+            chunk: None,
+            shadow: None,
+        }
+    }
+
+    /// Set the stack at the time of the error, innermost frame first
+    #[must_use]
+    pub fn with_stack(mut self, stack: Vec<AsanStackFrame>) -> Self {
+        self.stack = stack;
+        self
+    }
+
+    /// Set the heap chunk related to the buggy address
+    #[must_use]
+    pub fn with_chunk(mut self, chunk: AsanChunkInfo) -> Self {
+        self.chunk = Some(chunk);
+        self
+    }
+
+    /// Set the shadow memory around the buggy address
+    #[must_use]
+    pub fn with_shadow(mut self, shadow: AsanShadowWindow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    /// The `SUMMARY` line of this report, without the trailing newline
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        self.write_summary(&mut out).unwrap();
+        out
+    }
+
+    /// The address the bug is about, if any
+    fn buggy_addr(&self) -> Option<u64> {
+        match &self.bug {
+            AsanBug::Access { addr, .. }
+            | AsanBug::DoubleFree { addr }
+            | AsanBug::BadFree { addr } => Some(*addr),
+            AsanBug::Leak { .. } => self.chunk.as_ref().map(|chunk| chunk.start),
+            AsanBug::Signal { addr, .. } => *addr,
+        }
+    }
+
+    fn write_summary<W: Write>(&self, out: &mut W) -> fmt::Result {
+        if let AsanBug::Leak { size } = self.bug {
+            return write!(
+                out,
+                "SUMMARY: AddressSanitizer: {size} byte(s) leaked in 1 allocation(s)."
+            );
+        }
+        write!(out, "SUMMARY: AddressSanitizer: {}", self.bug.bug_type())?;
+        if let Some(frame) = self.stack.first() {
+            write!(out, " ")?;
+            frame.write_location(out)?;
+            if let Some(function) = &frame.function {
+                write!(out, " in {function}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_stack<W: Write>(out: &mut W, stack: &[AsanStackFrame]) -> fmt::Result {
+        if stack.is_empty() {
+            writeln!(out, "    <empty stack>")?;
+        }
+        for (i, frame) in stack.iter().enumerate() {
+            write!(out, "    #{i} {:#x}", frame.pc)?;
+            if let Some(function) = &frame.function {
+                write!(out, " in {function}")?;
+            }
+            write!(out, " ")?;
+            frame.write_location(out)?;
+            writeln!(out)?;
+        }
+        writeln!(out)
+    }
+
+    fn write_chunk<W: Write>(&self, out: &mut W, chunk: &AsanChunkInfo) -> fmt::Result {
+        let end = chunk.start + chunk.size;
+        if let Some(addr) = self.buggy_addr() {
+            let (distance, relation) = if addr < chunk.start {
+                (chunk.start - addr, "to the left of")
+            } else if addr >= end && !(chunk.size == 0 && addr == chunk.start) {
+                (addr - end, "to the right of")
+            } else {
+                (addr - chunk.start, "inside of")
+            };
+            writeln!(
+                out,
+                "0x{addr:012x} is located {distance} bytes {relation} {}-byte region [0x{:012x},0x{end:012x})",
+                chunk.size, chunk.start
+            )?;
+        }
+        match &chunk.free_stack {
+            Some(free_stack) => {
+                writeln!(out, "freed by thread T0 here:")?;
+                Self::write_stack(out, free_stack)?;
+                writeln!(out, "previously allocated by thread T0 here:")?;
+            }
+            None => writeln!(out, "allocated by thread T0 here:")?,
+        }
+        Self::write_stack(out, &chunk.alloc_stack)
+    }
+
+    fn write_leak<W: Write>(&self, out: &mut W, size: u64) -> fmt::Result {
+        writeln!(
+            out,
+            "=={}==ERROR: LeakSanitizer: detected memory leaks",
+            self.pid
+        )?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Direct leak of {size} byte(s) in 1 object(s) allocated from:"
+        )?;
+        let stack = self.chunk.as_ref().map_or(&self.stack, |c| &c.alloc_stack);
+        Self::write_stack(out, stack)?;
+        self.write_summary(out)?;
+        writeln!(out)
+    }
+
+    /// Write the full report to `out`
+    pub fn write<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let pid = self.pid;
+        writeln!(out, "{ASAN_REPORT_SEPARATOR}")?;
+        match &self.bug {
+            AsanBug::Leak { size } => return self.write_leak(out, *size),
+            AsanBug::Access {
+                bug_type,
+                addr,
+                kind,
+                size,
+            } => {
+                writeln!(
+                    out,
+                    "=={pid}==ERROR: AddressSanitizer: {bug_type} on address 0x{addr:012x} at pc 0x{:012x} bp 0x{:012x} sp 0x{:012x}",
+                    self.pc, self.bp, self.sp
+                )?;
+                writeln!(out, "{kind} of size {size} at 0x{addr:012x} thread T0")?;
+            }
+            AsanBug::DoubleFree { addr } => {
+                writeln!(
+                    out,
+                    "=={pid}==ERROR: AddressSanitizer: attempting double-free on 0x{addr:012x} in thread T0:"
+                )?;
+            }
+            AsanBug::BadFree { addr } => {
+                writeln!(
+                    out,
+                    "=={pid}==ERROR: AddressSanitizer: attempting free on address which was not malloc()-ed: 0x{addr:012x} in thread T0"
+                )?;
+            }
+            AsanBug::Signal { name, addr, kind } => {
+                writeln!(
+                    out,
+                    "=={pid}==ERROR: AddressSanitizer: {name} on unknown address 0x{:012x} (pc 0x{:012x} bp 0x{:012x} sp 0x{:012x} T0)",
+                    addr.unwrap_or_default(),
+                    self.pc,
+                    self.bp,
+                    self.sp
+                )?;
+                if let Some(kind) = kind {
+                    writeln!(
+                        out,
+                        "=={pid}==The signal is caused by a {kind} memory access."
+                    )?;
+                }
+            }
+        }
+        Self::write_stack(out, &self.stack)?;
+        if let Some(chunk) = &self.chunk {
+            self.write_chunk(out, chunk)?;
+        } else if matches!(self.bug, AsanBug::Signal { .. }) {
+            writeln!(out, "AddressSanitizer can not provide additional info.")?;
+        }
+        self.write_summary(out)?;
+        writeln!(out)?;
+        if let Some(shadow) = &self.shadow {
+            shadow.write(out)?;
+        }
+        writeln!(out, "=={pid}==ABORTING")
+    }
+}
+
+impl Display for AsanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+/// An observer holding the [`AsanReport`]s of the last run, as text.
+///
+/// The sanitizer runtime fills it in after each execution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AsanReportObserver {
+    name: Cow<'static, str>,
+    report: Option<String>,
+}
+
+impl AsanReportObserver {
+    /// Create a new [`AsanReportObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            report: None,
+        }
+    }
+
+    /// The reports of the last run, if any error was found
+    #[must_use]
+    pub fn report(&self) -> Option<&str> {
+        self.report.as_deref()
+    }
+
+    /// Add a report to the ones of the current run
+    pub fn observe_report(&mut self, report: &str) {
+        match &mut self.report {
+            Some(reports) => reports.push_str(report),
+            None => self.report = Some(report.into()),
+        }
+    }
+}
+
+impl Named for AsanReportObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for AsanReportObserver
+where
+    S: UsesInput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.report = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::{
+        AsanAccessKind, AsanBug, AsanChunkInfo, AsanReport, AsanShadowWindow, AsanStackFrame,
+    };
+
+    fn frame(pc: u64, function: &str, line: u32) -> AsanStackFrame {
+        AsanStackFrame {
+            pc,
+            function: Some(function.into()),
+            file: Some("/src/test.c".into()),
+            line: Some(line),
+            column: None,
+            module: Some("/bin/test".into()),
+            module_offset: pc - 0x400000,
+        }
+    }
+
+    #[test]
+    fn test_asan_report_heap_overflow() {
+        let shadow = AsanShadowWindow::around(0x0c04_7fff_8002, |addr| {
+            Some(match addr {
+                0x0c04_7fff_8002 => 0x05,
+                0x0c04_7fff_8000..=0x0c04_7fff_8003 => 0xfa,
+                _ => 0x00,
+            })
+        });
+        assert_eq!(shadow.bytes.len(), 11 * 16);
+        let report = AsanReport::new(
+            42,
+            AsanBug::Access {
+                bug_type: "heap-buffer-overflow".into(),
+                addr: 0x6020_0000_0015,
+                kind: AsanAccessKind::Read,
+                size: 1,
+            },
+            0x401234,
+            0x7ffc_0000_0010,
+            0x7ffc_0000_0000,
+        )
+        .with_stack(vec![frame(0x401234, "main", 5)])
+        .with_chunk(AsanChunkInfo {
+            start: 0x6020_0000_0010,
+            size: 5,
+            alloc_stack: vec![AsanStackFrame::new(0x7f00_0000_1000)],
+            free_stack: None,
+        })
+        .with_shadow(shadow);
+        let text = report.to_string();
+
+        assert!(text.contains("==42==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000015 at pc 0x000000401234"));
+        assert!(text.contains(
+            "READ of size 1 at 0x602000000015 thread T0\n    #0 0x401234 in main /src/test.c:5\n"
+        ));
+        assert!(text.contains(
+            "0x602000000015 is located 0 bytes to the right of 5-byte region [0x602000000010,0x602000000015)\nallocated by thread T0 here:\n    #0 0x7f0000001000 (<unknown module>)\n"
+        ));
+        assert!(text
+            .contains("SUMMARY: AddressSanitizer: heap-buffer-overflow /src/test.c:5 in main\n"));
+        assert!(text.contains("=>0x0c047fff8000: fa fa[05]fa 00"));
+        assert!(text.ends_with("==42==ABORTING\n"));
+    }
+
+    #[test]
+    fn test_asan_report_double_free() {
+        let report = AsanReport::new(1, AsanBug::DoubleFree { addr: 0x1000 }, 0, 0, 0)
+            .with_stack(vec![frame(0x401000, "free", 1)])
+            .with_chunk(AsanChunkInfo {
+                start: 0x1000,
+                size: 16,
+                alloc_stack: vec![],
+                free_stack: Some(vec![frame(0x401100, "first_free", 2)]),
+            });
+        let text = report.to_string();
+
+        assert!(text.contains("attempting double-free on 0x000000001000 in thread T0:"));
+        assert!(text.contains("is located 0 bytes inside of 16-byte region"));
+        assert!(text.contains("freed by thread T0 here:\n    #0 0x401100 in first_free"));
+        assert!(text.contains("previously allocated by thread T0 here:\n    <empty stack>\n"));
+        assert_eq!(
+            report.summary(),
+            "SUMMARY: AddressSanitizer: double-free /src/test.c:1 in free"
+        );
+        assert!(report.shadow.is_none());
+    }
+}
@@ -0,0 +1,58 @@
+//! The [`CoreDumpObserver`] holds the path of the core dump written by a crashing target.
+//!
+//! The core dumps are collected by executors running the target in a separate process,
+//! such as the [`crate::executors::ForkserverExecutor`] with `core_dumps` set.
+//! Use the [`crate::feedbacks::CoreDumpToMetadataFeedback`] to keep them for the objectives only.
+
+use alloc::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{inputs::UsesInput, observers::Observer, Error};
+
+/// An observer holding the path of the core dump of the last run, if the target dumped core.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CoreDumpObserver {
+    name: Cow<'static, str>,
+    core_dump: Option<PathBuf>,
+}
+
+impl CoreDumpObserver {
+    /// Create a new [`CoreDumpObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            core_dump: None,
+        }
+    }
+
+    /// The path of the core dump of the last run
+    #[must_use]
+    pub fn core_dump(&self) -> Option<&Path> {
+        self.core_dump.as_deref()
+    }
+
+    /// Set the path of the core dump of the last run, called by the executor
+    pub fn set_core_dump(&mut self, core_dump: Option<PathBuf>) {
+        self.core_dump = core_dump;
+    }
+}
+
+impl Named for CoreDumpObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for CoreDumpObserver
+where
+    S: UsesInput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.core_dump = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+//! The [`MemoryObserver`] holds the peak memory usage of the last run.
+//!
+//! The peak is recorded by the [`crate::executors::hooks::memory::MemoryLimitHook`] for the
+//! in-process executors, by the [`crate::executors::InProcessForkExecutor`] for its children,
+//! and by the [`crate::executors::ForkserverExecutor`] with a memory cgroup.
+//! Use the [`crate::feedbacks::MemoryUsageToMetadataFeedback`] to keep it in the testcases.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    executors::{hooks::memory::take_last_peak_memory, ExitKind},
+    inputs::UsesInput,
+    observers::Observer,
+    Error,
+};
+
+/// An observer holding the peak memory usage of the last run in bytes, if it is known.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MemoryObserver {
+    name: Cow<'static, str>,
+    peak_memory: Option<u64>,
+}
+
+impl MemoryObserver {
+    /// Create a new [`MemoryObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            peak_memory: None,
+        }
+    }
+
+    /// The peak memory usage of the last run in bytes
+    #[must_use]
+    pub fn peak_memory(&self) -> Option<u64> {
+        self.peak_memory
+    }
+
+    /// Set the peak memory usage of the last run in bytes, called by the executor
+    pub fn set_peak_memory(&mut self, peak_memory: Option<u64>) {
+        self.peak_memory = peak_memory;
+    }
+}
+
+impl Named for MemoryObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for MemoryObserver
+where
+    S: UsesInput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.peak_memory = None;
+        // Drop the peak of a run we did not observe
+        take_last_peak_memory();
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &S::Input,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        // Unless the executor set it, take the peak recorded by the hook or executor
+        let last_peak_memory = take_last_peak_memory();
+        if self.peak_memory.is_none() {
+            self.peak_memory = last_peak_memory;
+        }
+        Ok(())
+    }
+}
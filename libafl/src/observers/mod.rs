@@ -4,11 +4,24 @@ use alloc::borrow::Cow;
 pub mod cmp;
 pub use cmp::*;
 
+pub mod asan;
+pub use asan::{AsanReport, AsanReportObserver};
+
 #[cfg(feature = "std")]
 pub mod stdio;
 #[cfg(feature = "std")]
 pub use stdio::{StdErrObserver, StdOutObserver};
 
+#[cfg(all(feature = "std", unix))]
+pub mod core_dump;
+#[cfg(all(feature = "std", unix))]
+pub use core_dump::CoreDumpObserver;
+
+#[cfg(all(feature = "std", unix))]
+pub mod memory;
+#[cfg(all(feature = "std", unix))]
+pub use memory::MemoryObserver;
+
 #[cfg(feature = "regex")]
 pub mod stacktrace;
 #[cfg(feature = "regex")]
@@ -385,6 +398,14 @@ impl TimeObserver {
     pub fn last_runtime(&self) -> &Option<Duration> {
         &self.last_runtime
     }
+
+    /// Sets the runtime of the current execution, measured by the executor.
+    ///
+    /// Call this between `pre_exec` and `post_exec`, for executions that did not run in between,
+    /// such as the runs of a [`crate::executors::BatchExecutor`]. `post_exec` then keeps it.
+    pub fn set_runtime(&mut self, runtime: Duration) {
+        self.last_runtime = Some(runtime);
+    }
 }
 
 impl<S> Observer<S> for TimeObserver
@@ -412,7 +433,9 @@ where
         _input: &S::Input,
         _exit_kind: &ExitKind,
     ) -> Result<(), Error> {
-        self.last_runtime = Some(self.start_time.elapsed());
+        if self.last_runtime.is_none() {
+            self.last_runtime = Some(self.start_time.elapsed());
+        }
         Ok(())
     }
 
@@ -423,7 +446,9 @@ where
         _input: &S::Input,
         _exit_kind: &ExitKind,
     ) -> Result<(), Error> {
-        self.last_runtime = current_time().checked_sub(self.start_time);
+        if self.last_runtime.is_none() {
+            self.last_runtime = current_time().checked_sub(self.start_time);
+        }
         Ok(())
     }
 }
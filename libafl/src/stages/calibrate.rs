@@ -15,13 +15,13 @@ use serde::{Deserialize, Serialize};
 use crate::{
     corpus::{Corpus, SchedulerTestcaseMetadata},
     events::{Event, EventFirer, LogSeverity},
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{Executor, ExitKind, HasObservers, TimeoutCalibrationMetadata},
     feedbacks::{map::MapFeedbackMetadata, HasObserverHandle},
     fuzzer::Evaluator,
     monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::{MapObserver, ObserversTuple},
     schedulers::powersched::SchedulerMetadata,
-    stages::{RetryCountRestartHelper, Stage},
+    stages::{RetryCountRestartHelper, StabilityMetadata, Stage},
     state::{HasCorpus, HasCurrentTestcase, HasExecutions, UsesState},
     Error, HasMetadata, HasNamedMetadata,
 };
@@ -171,12 +171,15 @@ where
             })?,
         };
         let map_first_entries = map_first.to_vec();
+        let map_first_initial = map_first.initial();
         let map_first_len = map_first.to_vec().len();
         let mut unstable_entries: Vec<usize> = vec![];
         // Run CAL_STAGE_START - 1 times, increase by 2 for every time a new
         // run is found to be unstable or to crash with CAL_STAGE_MAX total runs.
         let mut i = 1;
         let mut has_errors = false;
+        // Only entries running without errors give the `TimeoutCalibrator` meaningful exec times
+        let mut exec_times_valid = exit_kind == ExitKind::Ok;
 
         while i < iter {
             let input = state.current_input_cloned()?;
@@ -186,6 +189,7 @@ where
 
             let exit_kind = executor.run_target(fuzzer, state, mgr, &input)?;
             if exit_kind != ExitKind::Ok {
+                exec_times_valid = false;
                 if !has_errors {
                     mgr.log(
                         state,
@@ -212,6 +216,15 @@ where
                     .as_ref()
                     .to_vec();
 
+                // Contribute to the instability scores of the `StabilityStage`, if there is one
+                if exit_kind == ExitKind::Ok {
+                    if let Ok(stability) =
+                        state.named_metadata_mut::<StabilityMetadata>(&self.map_name)
+                    {
+                        stability.record(&map_first_entries, map, map_first_initial);
+                    }
+                }
+
                 let history_map = &mut state
                     .named_metadata_map_mut()
                     .get_mut::<MapFeedbackMetadata<O::Entry>>(&self.map_name)
@@ -240,6 +253,12 @@ where
             i += 1;
         }
 
+        if exec_times_valid {
+            state
+                .metadata_or_insert_with(TimeoutCalibrationMetadata::new)
+                .add_entry(total_time / (iter as u32));
+        }
+
         let mut send_default_stability = false;
         let unstable_found = !unstable_entries.is_empty();
         if unstable_found {
@@ -0,0 +1,178 @@
+//! The [`CheckpointStage`] periodically writes the whole fuzzer state to disk,
+//! so that a campaign can resume after a reboot.
+
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::current_time;
+
+use crate::{
+    stages::Stage,
+    state::{HasStartTime, StateCheckpointer, UsesState},
+    Error,
+};
+
+/// A [`Stage`] that writes a checkpoint of the state, using a [`StateCheckpointer`],
+/// once every `interval`.
+///
+/// The interval is measured from the latest checkpoint on disk (or the start of the campaign),
+/// so frequent client restarts do not delay checkpoints.
+#[derive(Debug)]
+pub struct CheckpointStage<EM, Z> {
+    checkpointer: StateCheckpointer,
+    interval: Duration,
+    last_checkpoint: Option<Duration>,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> UsesState for CheckpointStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for CheckpointStage<EM, Z>
+where
+    EM: UsesState,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasStartTime,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let last_checkpoint = if let Some(last_checkpoint) = self.last_checkpoint {
+            last_checkpoint
+        } else {
+            let last_checkpoint = self
+                .checkpointer
+                .latest_time()?
+                .unwrap_or(*state.start_time());
+            self.last_checkpoint = Some(last_checkpoint);
+            last_checkpoint
+        };
+
+        let now = current_time();
+        if now.saturating_sub(last_checkpoint) < self.interval {
+            return Ok(());
+        }
+
+        let path = self.checkpointer.save(state)?;
+        log::info!("Wrote checkpoint {path:?}");
+        self.last_checkpoint = Some(now);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<EM, Z> CheckpointStage<EM, Z> {
+    /// Create a new [`CheckpointStage`], writing a checkpoint every `interval`
+    #[must_use]
+    pub fn new(checkpointer: StateCheckpointer, interval: Duration) -> Self {
+        Self {
+            checkpointer,
+            interval,
+            last_checkpoint: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The [`StateCheckpointer`] used by this stage
+    #[must_use]
+    pub fn checkpointer(&self) -> &StateCheckpointer {
+        &self.checkpointer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{env, fs, process};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::CheckpointStage;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::test::NopExecutor,
+        feedbacks::ConstFeedback,
+        fuzzer::test::NopFuzzer,
+        inputs::BytesInput,
+        stages::Stage,
+        state::{HasCorpus, HasExecutions, StateCheckpointer, StdState},
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_checkpoint_stage() {
+        let dir = env::temp_dir().join(format!("libafl_checkpoint_stage_test_{}", process::id()));
+        let checkpointer = StateCheckpointer::new(&dir).unwrap();
+
+        let mut corpus = InMemoryCorpus::new();
+        corpus.add(Testcase::new(vec![0; 4].into())).unwrap();
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        *state.executions_mut() = 42;
+        let mut fuzzer = NopFuzzer::new();
+        let mut executor = NopExecutor::new();
+        let mut mgr = NopEventManager::new();
+
+        // The interval starts with the campaign
+        let mut stage = CheckpointStage::new(checkpointer.clone(), Duration::from_secs(3600));
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+        assert_eq!(checkpointer.latest().unwrap(), None);
+
+        let mut stage = CheckpointStage::new(checkpointer.clone(), Duration::ZERO);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+        let first = checkpointer.latest().unwrap().unwrap();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+        let latest = checkpointer.latest().unwrap().unwrap();
+        assert_ne!(first, latest);
+
+        // A new stage, e.g. after a restart, waits for the interval since the latest checkpoint
+        let mut stage = CheckpointStage::new(checkpointer.clone(), Duration::from_secs(3600));
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+        assert_eq!(checkpointer.latest().unwrap(), Some(latest));
+
+        let restored: TestState = checkpointer.load().unwrap().unwrap();
+        assert_eq!(*restored.executions(), 42);
+        assert_eq!(restored.corpus().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
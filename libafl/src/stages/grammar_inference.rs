@@ -0,0 +1,733 @@
+//! The [`GrammarInferenceStage`] infers an approximate context-free grammar from the corpus.
+//!
+//! It builds on the gaps discovered by the [`crate::stages::GeneralizationStage`]: every generalized
+//! testcase becomes a rule of the start symbol, where each gap is replaced by a nonterminal.
+//! Gaps that appear in the same syntactic context (the bytes surrounding them) are merged into the
+//! same nonterminal, GLADE/Arvada-style, as long as swapping their contents keeps the coverage
+//! novelties of the testcase. The result can be exported as a Nautilus JSON grammar or, with the
+//! `nautilus` feature, turned into a `NautilusContext` directly.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData};
+use std::{fs, path::PathBuf};
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled},
+    AsSlice, Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "nautilus")]
+use crate::generators::nautilus::NautilusContext;
+use crate::{
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId},
+    executors::{Executor, HasObservers},
+    feedbacks::map::MapNoveltiesMetadata,
+    inputs::{BytesInput, GeneralizedInputMetadata, GeneralizedItem, HasMutatorBytes, UsesInput},
+    mark_feature_time,
+    observers::{CanTrack, MapObserver, ObserversTuple},
+    require_novelties_tracking,
+    stages::{RetryCountRestartHelper, Stage},
+    start_timer,
+    state::{HasCorpus, HasExecutions, UsesState},
+    Error, HasMetadata, HasNamedMetadata,
+};
+#[cfg(feature = "introspection")]
+use crate::{monitors::PerfFeature, state::HasClientPerfMonitor};
+
+/// The name for grammar inference stage
+pub static GRAMMAR_INFERENCE_STAGE_NAME: &str = "grammar_inference";
+
+/// The start symbol of every inferred grammar
+pub const INFERRED_START_SYMBOL: &str = "INPUT";
+
+/// Stands in for the backslashes a terminal ends with when a nonterminal follows, see
+/// [`InferredRule::nautilus_format`]
+pub const INFERRED_BACKSLASH_SYMBOL: &str = "BACKSLASH";
+
+/// The default amount of known alternatives checked against a gap before it is merged
+pub const DEFAULT_MAX_MERGE_CHECKS: usize = 4;
+
+/// A symbol on the right-hand side of an inferred rule
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GrammarSymbol {
+    /// A terminal, emitted verbatim
+    Term(Vec<u8>),
+    /// A reference to a nonterminal
+    NonTerm(String),
+}
+
+/// A single production of the inferred grammar
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InferredRule {
+    /// The nonterminal this rule expands
+    pub nonterm: String,
+    /// The symbols this rule expands to
+    pub children: Vec<GrammarSymbol>,
+}
+
+impl InferredRule {
+    /// Render the right-hand side in the Nautilus rule format, escaping braces in terminals.
+    ///
+    /// Nautilus reads `\{` as an escaped brace and has no escape for backslashes, so backslashes
+    /// that end a terminal right before a nonterminal become [`INFERRED_BACKSLASH_SYMBOL`].
+    #[must_use]
+    pub fn nautilus_format(&self) -> Vec<u8> {
+        let mut format = vec![];
+        for (i, child) in self.children.iter().enumerate() {
+            match child {
+                GrammarSymbol::Term(bytes) => {
+                    let end = self.term_end(i);
+                    for &b in &bytes[..end] {
+                        if b == b'{' || b == b'}' {
+                            format.push(b'\\');
+                        }
+                        format.push(b);
+                    }
+                    for _ in end..bytes.len() {
+                        format.push(b'{');
+                        format.extend_from_slice(INFERRED_BACKSLASH_SYMBOL.as_bytes());
+                        format.push(b'}');
+                    }
+                }
+                GrammarSymbol::NonTerm(name) => {
+                    format.push(b'{');
+                    format.extend_from_slice(name.as_bytes());
+                    format.push(b'}');
+                }
+            }
+        }
+        format
+    }
+
+    /// The length of the terminal at `idx` without the backslashes that need
+    /// [`INFERRED_BACKSLASH_SYMBOL`]
+    fn term_end(&self, idx: usize) -> usize {
+        let GrammarSymbol::Term(bytes) = &self.children[idx] else {
+            return 0;
+        };
+        let mut end = bytes.len();
+        if matches!(self.children.get(idx + 1), Some(GrammarSymbol::NonTerm(_))) {
+            while end > 0 && bytes[end - 1] == b'\\' {
+                end -= 1;
+            }
+        }
+        end
+    }
+
+    /// Returns `true` if [`Self::nautilus_format`] refers to [`INFERRED_BACKSLASH_SYMBOL`]
+    fn needs_backslash_symbol(&self) -> bool {
+        self.children
+            .iter()
+            .enumerate()
+            .any(|(i, child)| match child {
+                GrammarSymbol::Term(bytes) => self.term_end(i) < bytes.len(),
+                GrammarSymbol::NonTerm(_) => false,
+            })
+    }
+}
+
+/// The grammar inferred so far, stored in the state
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InferredGrammarMetadata {
+    rules: Vec<InferredRule>,
+    known_rules: HashSet<InferredRule>,
+    /// Nonterminals per gap context, in creation order
+    contexts: HashMap<String, Vec<String>>,
+    /// Observed gap contents per nonterminal
+    samples: HashMap<String, Vec<Vec<u8>>>,
+    processed: HashSet<CorpusId>,
+}
+
+impl_serdeany!(InferredGrammarMetadata);
+
+impl InferredGrammarMetadata {
+    /// The rules of the inferred grammar. The first rule always expands [`INFERRED_START_SYMBOL`].
+    #[must_use]
+    pub fn rules(&self) -> &[InferredRule] {
+        &self.rules
+    }
+
+    /// Returns `true` if no testcase contributed to the grammar yet
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The amount of testcases that have been folded into the grammar
+    #[must_use]
+    pub fn processed_count(&self) -> usize {
+        self.processed.len()
+    }
+
+    fn add_rule(&mut self, rule: InferredRule) {
+        if self.known_rules.insert(rule.clone()) {
+            self.rules.push(rule);
+        }
+    }
+
+    /// Add the productions every gap nonterminal has: empty, the observed content and repetition.
+    fn add_gap_rules(&mut self, nonterm: &str, content: &[u8]) {
+        if !self.samples.contains_key(nonterm) {
+            self.add_rule(InferredRule {
+                nonterm: nonterm.to_owned(),
+                children: vec![],
+            });
+            self.add_rule(InferredRule {
+                nonterm: nonterm.to_owned(),
+                children: vec![
+                    GrammarSymbol::NonTerm(nonterm.to_owned()),
+                    GrammarSymbol::NonTerm(nonterm.to_owned()),
+                ],
+            });
+        }
+        let samples = self.samples.entry(nonterm.to_owned()).or_default();
+        if !content.is_empty() && !samples.iter().any(|s| s == content) {
+            samples.push(content.to_vec());
+            self.add_rule(InferredRule {
+                nonterm: nonterm.to_owned(),
+                children: vec![GrammarSymbol::Term(content.to_vec())],
+            });
+        }
+    }
+
+    /// The nonterminal and Nautilus rule format of all rules, including the rule for
+    /// [`INFERRED_BACKSLASH_SYMBOL`] if any rule needs it.
+    fn nautilus_formats(&self) -> Vec<(&str, Vec<u8>)> {
+        let mut formats: Vec<_> = self
+            .rules
+            .iter()
+            .map(|rule| (rule.nonterm.as_str(), rule.nautilus_format()))
+            .collect();
+        if self.rules.iter().any(InferredRule::needs_backslash_symbol) {
+            formats.push((INFERRED_BACKSLASH_SYMBOL, b"\\".to_vec()));
+        }
+        formats
+    }
+
+    /// Export the grammar in the Nautilus JSON rule format, as read by `NautilusContext::from_file`.
+    ///
+    /// Returns an error if a terminal is not valid UTF-8, as it cannot be represented in JSON.
+    /// Use [`Self::nautilus_context`] to fuzz with such a grammar directly.
+    pub fn to_nautilus_rules(&self) -> Result<Vec<Vec<String>>, Error> {
+        self.nautilus_formats()
+            .into_iter()
+            .map(|(nonterm, format)| {
+                let format = String::from_utf8(format).map_err(|_| {
+                    Error::illegal_argument(format!(
+                        "The inferred rule for {nonterm} contains terminals that are not valid UTF-8 and cannot be exported to JSON"
+                    ))
+                })?;
+                Ok(vec![nonterm.to_owned(), format])
+            })
+            .collect()
+    }
+
+    /// Write the grammar as Nautilus JSON to the given file
+    pub fn write_nautilus_json<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let json = serde_json::to_string_pretty(&self.to_nautilus_rules()?)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Build a [`NautilusContext`] from the inferred grammar, e.g. to feed a
+    /// [`crate::generators::NautilusGenerator`] while fuzzing.
+    ///
+    /// Returns `None` if the grammar is still empty.
+    #[cfg(feature = "nautilus")]
+    #[must_use]
+    pub fn nautilus_context(&self, tree_depth: usize) -> Option<NautilusContext> {
+        let formats = self.nautilus_formats();
+        let rules: Vec<_> = formats
+            .iter()
+            .map(|(nonterm, format)| (*nonterm, format.as_slice()))
+            .collect();
+        NautilusContext::with_rules(tree_depth, &rules)
+    }
+}
+
+/// A piece of an aligned generalized input
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    /// Bytes that are required to keep the novelties
+    Term(Vec<u8>),
+    /// Bytes that could be removed without losing the novelties
+    Gap(Vec<u8>),
+}
+
+/// Recover the contents of each gap by aligning the generalized input with the original bytes.
+fn align_gaps(original: &[u8], generalized: &[GeneralizedItem]) -> Option<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut pos = 0;
+    let mut gap_start = None;
+    for item in generalized {
+        match item {
+            GeneralizedItem::Gap => {
+                if gap_start.is_none() {
+                    gap_start = Some(pos);
+                }
+            }
+            // An empty terminal does not anchor anything
+            GeneralizedItem::Bytes(bytes) if bytes.is_empty() => {}
+            GeneralizedItem::Bytes(bytes) => {
+                let found = if gap_start.is_some() {
+                    pos + original[pos..]
+                        .windows(bytes.len())
+                        .position(|w| w == bytes.as_slice())?
+                } else if original[pos..].starts_with(bytes) {
+                    pos
+                } else {
+                    return None;
+                };
+                if let Some(start) = gap_start.take() {
+                    segments.push(Segment::Gap(original[start..found].to_vec()));
+                }
+                segments.push(Segment::Term(bytes.clone()));
+                pos = found + bytes.len();
+            }
+        }
+    }
+    if let Some(start) = gap_start {
+        segments.push(Segment::Gap(original[start..].to_vec()));
+    } else if pos != original.len() {
+        return None;
+    }
+    Some(segments)
+}
+
+/// The context key of a gap: the bytes directly before and after it
+fn gap_context(segments: &[Segment], idx: usize) -> String {
+    let before = idx
+        .checked_sub(1)
+        .and_then(|i| match &segments[i] {
+            Segment::Term(t) => t.last().copied(),
+            Segment::Gap(_) => None,
+        })
+        .map_or_else(|| "BOF".to_owned(), |b| format!("{b:02x}"));
+    let after = segments
+        .get(idx + 1)
+        .and_then(|s| match s {
+            Segment::Term(t) => t.first().copied(),
+            Segment::Gap(_) => None,
+        })
+        .map_or_else(|| "EOF".to_owned(), |b| format!("{b:02x}"));
+    format!("GAP_{before}_{after}")
+}
+
+/// Concatenate the segments, replacing the gap at `idx` with `replacement`
+fn assemble(segments: &[Segment], idx: usize, replacement: &[u8]) -> BytesInput {
+    let mut bytes = vec![];
+    for (i, segment) in segments.iter().enumerate() {
+        match segment {
+            _ if i == idx => bytes.extend_from_slice(replacement),
+            Segment::Term(b) | Segment::Gap(b) => bytes.extend_from_slice(b),
+        }
+    }
+    BytesInput::new(bytes)
+}
+
+/// A stage that infers a grammar from generalized testcases.
+///
+/// Needs to run after a [`crate::stages::GeneralizationStage`] on the same map observer;
+/// testcases without [`GeneralizedInputMetadata`] are ignored.
+#[derive(Clone, Debug)]
+pub struct GrammarInferenceStage<C, EM, O, OT, Z> {
+    name: Cow<'static, str>,
+    map_observer_handle: Handle<C>,
+    max_merge_checks: usize,
+    grammar_file: Option<PathBuf>,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(EM, O, OT, Z)>,
+}
+
+impl<C, EM, O, OT, Z> Named for GrammarInferenceStage<C, EM, O, OT, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, EM, O, OT, Z> UsesState for GrammarInferenceStage<C, EM, O, OT, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<C, E, EM, O, Z> Stage<E, EM, Z> for GrammarInferenceStage<C, EM, O, E::Observers, Z>
+where
+    O: MapObserver,
+    C: CanTrack + AsRef<O> + Named,
+    E: Executor<EM, Z, State = Self::State> + HasObservers,
+    Self::State:
+        UsesInput<Input = BytesInput> + HasExecutions + HasMetadata + HasCorpus + HasNamedMetadata,
+    EM: UsesState,
+    Z: UsesState<State = Self::State>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(corpus_id) = state.current_corpus_id()? else {
+            return Err(Error::illegal_state(
+                "state is not currently processing a corpus index",
+            ));
+        };
+
+        if state
+            .metadata_or_insert_with(InferredGrammarMetadata::default)
+            .processed
+            .contains(&corpus_id)
+        {
+            return Ok(());
+        }
+
+        start_timer!(state);
+        let loaded = {
+            let corpus = state.corpus();
+            let mut testcase = corpus.get(corpus_id)?.borrow_mut();
+            let generalized = testcase
+                .metadata_map()
+                .get::<GeneralizedInputMetadata>()
+                .cloned();
+            let novelties = testcase
+                .metadata_map()
+                .get::<MapNoveltiesMetadata>()
+                .map(|meta| meta.as_slice().to_vec());
+            match (generalized, novelties) {
+                (Some(generalized), Some(novelties)) => {
+                    corpus.load_input_into(&mut testcase)?;
+                    let original = testcase.input().as_ref().unwrap().bytes().to_vec();
+                    Some((original, generalized, novelties))
+                }
+                _ => None,
+            }
+        };
+        mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+
+        // Testcases that were not generalized cannot contribute to the grammar
+        let Some((original, generalized, novelties)) = loaded else {
+            return Ok(());
+        };
+
+        let Some(segments) = align_gaps(&original, generalized.generalized()) else {
+            log::debug!("Could not align generalized testcase #{corpus_id} with its input");
+            return Ok(());
+        };
+
+        let mut children = vec![];
+        let mut assigned = vec![];
+        for (idx, segment) in segments.iter().enumerate() {
+            match segment {
+                Segment::Term(bytes) => children.push(GrammarSymbol::Term(bytes.clone())),
+                Segment::Gap(content) => {
+                    let nonterm = self.merge_gap(
+                        fuzzer, executor, state, manager, &segments, idx, content, &novelties,
+                    )?;
+                    children.push(GrammarSymbol::NonTerm(nonterm.clone()));
+                    assigned.push((nonterm, content.clone()));
+                }
+            }
+        }
+
+        let grammar = state.metadata_mut::<InferredGrammarMetadata>()?;
+        let known_rules = grammar.rules.len();
+        grammar.add_rule(InferredRule {
+            nonterm: INFERRED_START_SYMBOL.to_owned(),
+            children,
+        });
+        for (nonterm, content) in assigned {
+            grammar.add_gap_rules(&nonterm, &content);
+        }
+        grammar.processed.insert(corpus_id);
+
+        if let Some(path) = &self.grammar_file {
+            // Rules are only ever added, so the grammar changed if there are more
+            if grammar.rules.len() != known_rules {
+                grammar.write_nautilus_json(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+impl<C, EM, O, OT, Z> GrammarInferenceStage<C, EM, O, OT, Z>
+where
+    EM: UsesState,
+    O: MapObserver,
+    C: CanTrack + AsRef<O> + Named,
+    OT: ObserversTuple<<Self as UsesState>::State>,
+    <Self as UsesState>::State:
+        UsesInput<Input = BytesInput> + HasExecutions + HasMetadata + HasCorpus,
+{
+    /// Create a new [`GrammarInferenceStage`].
+    #[must_use]
+    pub fn new(map_observer: &C) -> Self {
+        require_novelties_tracking!("GrammarInferenceStage", C);
+        let name = map_observer.name().clone();
+        Self {
+            name: Cow::Owned(
+                GRAMMAR_INFERENCE_STAGE_NAME.to_owned() + ":" + name.into_owned().as_str(),
+            ),
+            map_observer_handle: map_observer.handle(),
+            max_merge_checks: DEFAULT_MAX_MERGE_CHECKS,
+            grammar_file: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Set how many known alternatives of a nonterminal are executed in place of a gap before
+    /// the gap is merged into it. `0` merges all gaps of the same context without checking.
+    #[must_use]
+    pub fn with_max_merge_checks(mut self, max_merge_checks: usize) -> Self {
+        self.max_merge_checks = max_merge_checks;
+        self
+    }
+
+    /// Write the grammar as Nautilus JSON to `path` every time it changes
+    #[must_use]
+    pub fn with_grammar_file(mut self, path: PathBuf) -> Self {
+        self.grammar_file = Some(path);
+        self
+    }
+
+    /// Find a nonterminal for the gap at `idx`, creating a new one if no existing one fits.
+    #[allow(clippy::too_many_arguments)]
+    fn merge_gap<E>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut <Self as UsesState>::State,
+        manager: &mut EM,
+        segments: &[Segment],
+        idx: usize,
+        content: &[u8],
+        novelties: &[usize],
+    ) -> Result<String, Error>
+    where
+        E: Executor<EM, Z> + HasObservers<Observers = OT, State = <Self as UsesState>::State>,
+        Z: UsesState<State = <Self as UsesState>::State>,
+    {
+        let context = gap_context(segments, idx);
+        let candidates = {
+            let grammar = state.metadata::<InferredGrammarMetadata>()?;
+            grammar
+                .contexts
+                .get(&context)
+                .map(|nonterms| {
+                    nonterms
+                        .iter()
+                        .map(|nonterm| {
+                            let samples = grammar
+                                .samples
+                                .get(nonterm)
+                                .map(|samples| {
+                                    samples
+                                        .iter()
+                                        .filter(|s| s.as_slice() != content)
+                                        .take(self.max_merge_checks)
+                                        .cloned()
+                                        .collect::<Vec<_>>()
+                                })
+                                .unwrap_or_default();
+                            (nonterm.clone(), samples)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+
+        'candidates: for (nonterm, samples) in candidates {
+            for sample in samples {
+                let candidate = assemble(segments, idx, &sample);
+                if !self.verify_input(fuzzer, executor, state, manager, novelties, &candidate)? {
+                    continue 'candidates;
+                }
+            }
+            return Ok(nonterm);
+        }
+
+        let grammar = state.metadata_mut::<InferredGrammarMetadata>()?;
+        let nonterms = grammar.contexts.entry(context.clone()).or_default();
+        let nonterm = if nonterms.is_empty() {
+            context
+        } else {
+            format!("{context}_{}", nonterms.len())
+        };
+        nonterms.push(nonterm.clone());
+        Ok(nonterm)
+    }
+
+    fn verify_input<E>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut <Self as UsesState>::State,
+        manager: &mut EM,
+        novelties: &[usize],
+        input: &BytesInput,
+    ) -> Result<bool, Error>
+    where
+        E: Executor<EM, Z> + HasObservers<Observers = OT, State = <Self as UsesState>::State>,
+        Z: UsesState<State = <Self as UsesState>::State>,
+    {
+        start_timer!(state);
+        executor.observers_mut().pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        start_timer!(state);
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        let cnt = executor.observers()[&self.map_observer_handle]
+            .as_ref()
+            .how_many_set(novelties);
+
+        Ok(cnt == novelties.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, vec::Vec};
+
+    use super::{align_gaps, gap_context, InferredGrammarMetadata, InferredRule, Segment};
+    use crate::{
+        inputs::{GeneralizedInputMetadata, GeneralizedItem},
+        stages::grammar_inference::GrammarSymbol,
+    };
+
+    #[test]
+    fn test_align_gaps() {
+        let original = b"f(abc);";
+        let payload: Vec<_> = b"f(___);"
+            .iter()
+            .map(|&b| if b == b'_' { None } else { Some(b) })
+            .collect();
+        let generalized = GeneralizedInputMetadata::generalized_from_options(&payload);
+        assert_eq!(
+            generalized.generalized().first(),
+            Some(&GeneralizedItem::Gap)
+        );
+
+        let segments = align_gaps(original, generalized.generalized()).unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Gap(vec![]),
+                Segment::Term(b"f(".to_vec()),
+                Segment::Gap(b"abc".to_vec()),
+                Segment::Term(b");".to_vec()),
+                Segment::Gap(vec![]),
+            ]
+        );
+        assert_eq!(gap_context(&segments, 2), "GAP_28_29");
+        assert_eq!(gap_context(&segments, 0), "GAP_BOF_66");
+
+        // Empty terminals are skipped instead of being searched for
+        let generalized = [
+            GeneralizedItem::Gap,
+            GeneralizedItem::Bytes(vec![]),
+            GeneralizedItem::Bytes(b"x".to_vec()),
+            GeneralizedItem::Bytes(vec![]),
+        ];
+        assert_eq!(
+            align_gaps(b"abx", &generalized).unwrap(),
+            vec![Segment::Gap(b"ab".to_vec()), Segment::Term(b"x".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_nautilus_format_escapes() {
+        let rule = InferredRule {
+            nonterm: "INPUT".into(),
+            children: vec![
+                GrammarSymbol::Term(b"{".to_vec()),
+                GrammarSymbol::NonTerm("GAP_7b_7d".into()),
+                GrammarSymbol::Term(b"}".to_vec()),
+            ],
+        };
+        assert_eq!(rule.nautilus_format(), b"\\{{GAP_7b_7d}\\}".to_vec());
+    }
+
+    #[test]
+    fn test_nautilus_format_escapes_backslash() {
+        let rule = InferredRule {
+            nonterm: "INPUT".into(),
+            children: vec![
+                GrammarSymbol::Term(b"a\\\\".to_vec()),
+                GrammarSymbol::NonTerm("GAP_5c_EOF".into()),
+                GrammarSymbol::Term(b"\\{\\".to_vec()),
+            ],
+        };
+        assert_eq!(
+            rule.nautilus_format(),
+            b"a{BACKSLASH}{BACKSLASH}{GAP_5c_EOF}\\\\{\\".to_vec()
+        );
+
+        let mut grammar = InferredGrammarMetadata::default();
+        grammar.add_rule(rule);
+        assert_eq!(
+            grammar.to_nautilus_rules().unwrap(),
+            vec![
+                vec![
+                    "INPUT".to_owned(),
+                    "a{BACKSLASH}{BACKSLASH}{GAP_5c_EOF}\\\\{\\".to_owned()
+                ],
+                vec!["BACKSLASH".to_owned(), "\\".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nautilus_rules_reject_invalid_utf8() {
+        let mut grammar = InferredGrammarMetadata::default();
+        grammar.add_rule(InferredRule {
+            nonterm: "INPUT".into(),
+            children: vec![GrammarSymbol::Term(b"ok".to_vec())],
+        });
+        assert_eq!(
+            grammar.to_nautilus_rules().unwrap(),
+            vec![vec!["INPUT".to_owned(), "ok".to_owned()]]
+        );
+
+        grammar.add_rule(InferredRule {
+            nonterm: "INPUT".into(),
+            children: vec![GrammarSymbol::Term(b"\xff".to_vec())],
+        });
+        assert!(grammar.to_nautilus_rules().is_err());
+    }
+}
@@ -13,6 +13,8 @@ use alloc::{
 use core::{fmt, marker::PhantomData};
 
 pub use calibrate::CalibrationStage;
+#[cfg(feature = "std")]
+pub use checkpoint::CheckpointStage;
 pub use colorization::*;
 #[cfg(all(feature = "std", unix))]
 pub use concolic::ConcolicTracingStage;
@@ -21,6 +23,8 @@ pub use concolic::SimpleConcolicMutationalStage;
 #[cfg(feature = "std")]
 pub use dump::*;
 pub use generalization::GeneralizationStage;
+#[cfg(feature = "std")]
+pub use grammar_inference::{GrammarInferenceStage, InferredGrammarMetadata};
 use hashbrown::HashSet;
 use libafl_bolts::{
     impl_serdeany,
@@ -28,10 +32,12 @@ use libafl_bolts::{
     Named,
 };
 pub use logics::*;
-pub use mutational::{MutationalStage, StdMutationalStage};
+pub use mutational::{BatchMutationalStage, MutationalStage, StdMutationalStage};
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 use serde::{Deserialize, Serialize};
+pub use stability::{StabilityMetadata, StabilityReport, StabilityStage};
 pub use stats::AflStatsStage;
+pub use structured_tmin::{InputMinimizer, StructuredTMinStage};
 #[cfg(feature = "std")]
 pub use sync::*;
 pub use tmin::{
@@ -62,6 +68,8 @@ pub mod push;
 pub mod tmin;
 
 pub mod calibrate;
+#[cfg(feature = "std")]
+pub mod checkpoint;
 pub mod colorization;
 #[cfg(all(feature = "std", unix))]
 pub mod concolic;
@@ -70,9 +78,13 @@ pub mod dump;
 pub mod generalization;
 /// The [`generation::GenStage`] generates a single input and evaluates it.
 pub mod generation;
+#[cfg(feature = "std")]
+pub mod grammar_inference;
 pub mod logics;
 pub mod power;
+pub mod stability;
 pub mod stats;
+pub mod structured_tmin;
 #[cfg(feature = "std")]
 pub mod sync;
 pub mod tracing;
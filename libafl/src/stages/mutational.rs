@@ -4,6 +4,7 @@
 use alloc::{
     borrow::{Cow, ToOwned},
     string::ToString,
+    vec::Vec,
 };
 use core::marker::PhantomData;
 
@@ -11,7 +12,7 @@ use libafl_bolts::{rands::Rand, Named};
 
 use crate::{
     corpus::{Corpus, CorpusId, Testcase},
-    fuzzer::Evaluator,
+    fuzzer::{BatchEvaluator, Evaluator},
     inputs::Input,
     mark_feature_time,
     mutators::{MultiMutator, MutationResult, Mutator},
@@ -402,3 +403,264 @@ impl<E, EM, I, M, Z> MultiMutationalStage<E, EM, I, M, Z> {
         }
     }
 }
+
+/// A mutational stage that evaluates its mutants in batches, using [`BatchEvaluator::evaluate_inputs_batch`].
+///
+/// With a [`crate::executors::BatchExecutor`], such as the `ForkserverPoolExecutor`, the mutants of a batch
+/// run concurrently. The post-execution steps of the mutator run in order, once the whole batch is evaluated.
+#[derive(Clone, Debug)]
+pub struct BatchMutationalStage<E, EM, I, M, Z> {
+    name: Cow<'static, str>,
+    mutator: M,
+    /// The maximum amount of iterations we should do each round
+    max_iterations: usize,
+    /// The number of mutants evaluated at once
+    batch_size: usize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, Z)>,
+}
+
+/// The unique id for batch mutational stage
+static mut BATCH_MUTATIONAL_STAGE_ID: usize = 0;
+/// The name for batch mutational stage
+pub static BATCH_MUTATIONAL_STAGE_NAME: &str = "batchmutational";
+
+impl<E, EM, I, M, Z> UsesState for BatchMutationalStage<E, EM, I, M, Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, I, M, Z> Named for BatchMutationalStage<E, EM, I, M, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, M, Z> Stage<E, EM, Z> for BatchMutationalStage<E, EM, I, M, Z>
+where
+    E: UsesState<State = Self::State>,
+    EM: UsesState<State = Self::State>,
+    M: Mutator<I, Self::State>,
+    Z: BatchEvaluator<E, EM>,
+    Self::State: HasCorpus + HasRand + HasNamedMetadata,
+    I: MutatedTransform<Self::Input, Self::State> + Clone,
+{
+    #[inline]
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        // Make sure we don't get stuck crashing on a single testcase
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        start_timer!(state);
+        let num = 1 + state.rand_mut().below(self.max_iterations);
+        let mut testcase = state.current_testcase_mut()?;
+        let Ok(input) = I::try_transform_from(&mut testcase, state) else {
+            return Ok(());
+        };
+        drop(testcase);
+        mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+
+        let mut remaining = num;
+        while remaining > 0 {
+            let batch_len = remaining.min(self.batch_size);
+            remaining -= batch_len;
+
+            let mut untransformed = Vec::with_capacity(batch_len);
+            let mut posts = Vec::with_capacity(batch_len);
+            for _ in 0..batch_len {
+                let mut input = input.clone();
+
+                start_timer!(state);
+                let mutated = self.mutator.mutate(state, &mut input)?;
+                mark_feature_time!(state, PerfFeature::Mutate);
+
+                if mutated == MutationResult::Skipped {
+                    continue;
+                }
+
+                let (new_input, post) = input.try_transform_into(state)?;
+                untransformed.push(new_input);
+                posts.push(post);
+            }
+
+            // Time is measured directly the `evaluate_inputs_batch` function
+            let results =
+                fuzzer.evaluate_inputs_batch(state, executor, manager, untransformed, true)?;
+
+            start_timer!(state);
+            for ((_, corpus_id), post) in results.into_iter().zip(posts) {
+                self.mutator.post_exec(state, corpus_id)?;
+                post.post_exec(state, corpus_id)?;
+            }
+            mark_feature_time!(state, PerfFeature::MutatePostExec);
+        }
+
+        #[cfg(feature = "introspection")]
+        state.introspection_monitor_mut().finish_stage();
+
+        Ok(())
+    }
+}
+
+impl<E, EM, M, Z> BatchMutationalStage<E, EM, Z::Input, M, Z>
+where
+    Z: UsesState,
+{
+    /// Creates a new [`BatchMutationalStage`], evaluating `batch_size` mutants at once
+    pub fn new(mutator: M, batch_size: usize) -> Self {
+        Self::transforming_with_max_iterations(
+            mutator,
+            batch_size,
+            DEFAULT_MUTATIONAL_MAX_ITERATIONS,
+        )
+    }
+
+    /// Creates a new [`BatchMutationalStage`] with the given max iterations
+    pub fn with_max_iterations(mutator: M, batch_size: usize, max_iterations: usize) -> Self {
+        Self::transforming_with_max_iterations(mutator, batch_size, max_iterations)
+    }
+}
+
+impl<E, EM, I, M, Z> BatchMutationalStage<E, EM, I, M, Z> {
+    /// Creates a new transforming [`BatchMutationalStage`] with the given max iterations
+    pub fn transforming_with_max_iterations(
+        mutator: M,
+        batch_size: usize,
+        max_iterations: usize,
+    ) -> Self {
+        // unsafe but impossible that you create two threads both instantiating this instance
+        let stage_id = unsafe {
+            let ret = BATCH_MUTATIONAL_STAGE_ID;
+            BATCH_MUTATIONAL_STAGE_ID += 1;
+            ret
+        };
+        Self {
+            name: Cow::Owned(
+                BATCH_MUTATIONAL_STAGE_NAME.to_owned() + ":" + stage_id.to_string().as_str(),
+            ),
+            mutator,
+            max_iterations,
+            batch_size: batch_size.max(1),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, rc::Rc, vec, vec::Vec};
+    use core::cell::RefCell;
+
+    use libafl_bolts::{rands::StdRand, Named};
+
+    use super::BatchMutationalStage;
+    use crate::{
+        corpus::{Corpus, CorpusId, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{test::NopBatchExecutor, HasObservers},
+        feedbacks::MaxMapFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{MutationResult, Mutator},
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, HasExecutions, StdState},
+        Error, StdFuzzer,
+    };
+
+    /// Replaces the byte of the input with a new one each time, so that each mutant covers a new edge
+    #[derive(Debug)]
+    struct CountingMutator {
+        next: u8,
+        post_execs: Rc<RefCell<Vec<Option<CorpusId>>>>,
+    }
+
+    impl Named for CountingMutator {
+        fn name(&self) -> &Cow<'static, str> {
+            static NAME: Cow<'static, str> = Cow::Borrowed("CountingMutator");
+            &NAME
+        }
+    }
+
+    impl<S> Mutator<BytesInput, S> for CountingMutator {
+        fn mutate(
+            &mut self,
+            _state: &mut S,
+            input: &mut BytesInput,
+        ) -> Result<MutationResult, Error> {
+            input.bytes_mut()[0] = self.next;
+            self.next += 1;
+            Ok(MutationResult::Mutated)
+        }
+
+        fn post_exec(
+            &mut self,
+            _state: &mut S,
+            new_corpus_id: Option<CorpusId>,
+        ) -> Result<(), Error> {
+            self.post_execs.borrow_mut().push(new_corpus_id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_batch_mutational_stage() {
+        let mut executor = NopBatchExecutor::new(3, 64);
+        let mut feedback = MaxMapFeedback::new(&executor.observers().0);
+        let mut objective = ();
+
+        let mut corpus = InMemoryCorpus::new();
+        let id = corpus.add(Testcase::new(BytesInput::new(vec![0]))).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        state.set_corpus_id(id).unwrap();
+
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        let post_execs = Rc::new(RefCell::new(Vec::new()));
+        let mutator = CountingMutator {
+            next: 1,
+            post_execs: post_execs.clone(),
+        };
+        let mut stage = BatchMutationalStage::with_max_iterations(mutator, 3, 8);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+
+        // All batches but the last one are full
+        let executions = *state.executions();
+        let (last, full) = executor.batches.split_last().unwrap();
+        assert!(full.iter().all(|&len| len == 3));
+        assert!((1..=3).contains(last));
+        assert_eq!(executor.batches.iter().sum::<usize>() as u64, executions);
+
+        // Each mutant was new, and its corpus id got back to the mutator in order
+        let post_execs = post_execs.borrow();
+        assert_eq!(post_execs.len() as u64, executions);
+        assert_eq!(state.corpus().count() as u64, executions + 1);
+        for (expected, new_id) in (1_u8..).zip(post_execs.iter()) {
+            let testcase = state.corpus().get(new_id.unwrap()).unwrap().borrow();
+            assert_eq!(testcase.input().as_ref().unwrap().bytes(), &[expected]);
+        }
+    }
+}
@@ -0,0 +1,525 @@
+//! The [`StabilityStage`] continuously samples re-executions of corpus entries to find unstable map
+//! indexes, and masks them out of the novelty checks of the [`crate::feedbacks::MapFeedback`].
+//!
+//! Each map index gets an instability score, the share of sampled re-executions in which it changed.
+//! The [`crate::stages::CalibrationStage`] contributes its runs to the same scores.
+//! A per-edge and per-function report is available with [`StabilityMetadata::report`].
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    format,
+    string::String,
+    vec::Vec,
+};
+use core::{fmt, marker::PhantomData};
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::{impl_serdeany, rands::Rand, tuples::Handle, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, EventFirer, LogSeverity},
+    executors::{Executor, ExitKind, HasObservers},
+    feedbacks::{map::MapFeedbackMetadata, HasObserverHandle},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{MapObserver, ObserversTuple},
+    stages::{RetryCountRestartHelper, Stage},
+    state::{HasCorpus, HasCurrentTestcase, HasRand, UsesState},
+    Error, HasNamedMetadata,
+};
+
+/// Default name for [`StabilityStage`]
+pub const STABILITY_STAGE_NAME: &str = "stability";
+
+/// The default probability to sample the current corpus entry
+pub const DEFAULT_SAMPLE_PROBABILITY: f64 = 0.1;
+
+/// The default amount of re-executions per sample
+pub const DEFAULT_REEXECUTIONS: usize = 3;
+
+/// The per-index instability scores of a map, stored as named metadata with the name of the
+/// [`crate::feedbacks::MapFeedback`].
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StabilityMetadata {
+    /// For each index, the amount of re-executions in which it was set in either run
+    samples: Vec<u32>,
+    /// For each index, the amount of re-executions in which it differed from the first run
+    flips: Vec<u32>,
+    /// The indexes masked out of the novelty checks
+    masked: HashSet<usize>,
+    /// The total amount of re-executions
+    reexecutions: u64,
+}
+impl_serdeany!(StabilityMetadata);
+
+impl StabilityMetadata {
+    /// Create a new [`struct@StabilityMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a re-execution, comparing the map of the first run to the map of the re-execution.
+    /// Returns if any index changed.
+    pub fn record<T>(&mut self, first: &[T], current: &[T], initial: T) -> bool
+    where
+        T: PartialEq + Copy,
+    {
+        let len = first.len().min(current.len());
+        if self.samples.len() < len {
+            self.samples.resize(len, 0);
+            self.flips.resize(len, 0);
+        }
+
+        let mut changed = false;
+        for (idx, (first, current)) in first.iter().zip(current).enumerate() {
+            if *first == initial && *current == initial {
+                continue;
+            }
+            self.samples[idx] = self.samples[idx].saturating_add(1);
+            if first != current {
+                self.flips[idx] = self.flips[idx].saturating_add(1);
+                changed = true;
+            }
+        }
+        self.reexecutions += 1;
+        changed
+    }
+
+    /// The amount of re-executions in which the index was set
+    #[must_use]
+    pub fn samples(&self, idx: usize) -> u32 {
+        self.samples.get(idx).copied().unwrap_or(0)
+    }
+
+    /// The amount of re-executions in which the index changed
+    #[must_use]
+    pub fn flips(&self, idx: usize) -> u32 {
+        self.flips.get(idx).copied().unwrap_or(0)
+    }
+
+    /// The instability score of the index, from `0.0` (always stable) to `1.0` (changed every time)
+    #[must_use]
+    pub fn score(&self, idx: usize) -> f64 {
+        score(self.samples(idx), self.flips(idx))
+    }
+
+    /// The total amount of recorded re-executions
+    #[must_use]
+    pub fn reexecutions(&self) -> u64 {
+        self.reexecutions
+    }
+
+    /// The indexes that changed in at least one re-execution
+    pub fn unstable_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.flips
+            .iter()
+            .enumerate()
+            .filter(|(_, flips)| **flips > 0)
+            .map(|(idx, _)| idx)
+    }
+
+    /// The indexes masked out of the novelty checks by the [`StabilityStage`]
+    #[must_use]
+    pub fn masked_indexes(&self) -> &HashSet<usize> {
+        &self.masked
+    }
+
+    /// Build a report of all indexes seen in the re-executions
+    #[must_use]
+    pub fn report(&self) -> StabilityReport {
+        let edges = self
+            .samples
+            .iter()
+            .zip(&self.flips)
+            .enumerate()
+            .filter(|(_, (samples, _))| **samples > 0)
+            .map(|(index, (samples, flips))| EdgeStability {
+                index,
+                samples: *samples,
+                flips: *flips,
+                masked: self.masked.contains(&index),
+            })
+            .collect();
+        StabilityReport {
+            reexecutions: self.reexecutions,
+            edges,
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn score(samples: u32, flips: u32) -> f64 {
+    if samples == 0 {
+        0.0
+    } else {
+        f64::from(flips) / f64::from(samples)
+    }
+}
+
+/// The stability of a single map index
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EdgeStability {
+    /// The map index
+    pub index: usize,
+    /// The amount of re-executions in which the index was set
+    pub samples: u32,
+    /// The amount of re-executions in which the index changed
+    pub flips: u32,
+    /// If the index is masked out of the novelty checks
+    pub masked: bool,
+}
+
+impl EdgeStability {
+    /// The instability score, see [`StabilityMetadata::score`]
+    #[must_use]
+    pub fn score(&self) -> f64 {
+        score(self.samples, self.flips)
+    }
+}
+
+/// The stability of all map indexes belonging to a function
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FunctionStability {
+    /// The name of the function
+    pub name: String,
+    /// The amount of indexes of this function seen in the re-executions
+    pub edges: usize,
+    /// The amount of indexes of this function that changed
+    pub unstable_edges: usize,
+    /// The amount of indexes of this function that are masked
+    pub masked_edges: usize,
+}
+
+/// A per-edge stability report, see [`StabilityMetadata::report`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StabilityReport {
+    /// The total amount of re-executions
+    pub reexecutions: u64,
+    /// All indexes seen in the re-executions, ordered by index
+    pub edges: Vec<EdgeStability>,
+}
+
+impl StabilityReport {
+    /// The indexes that changed in at least one re-execution
+    pub fn unstable_edges(&self) -> impl Iterator<Item = &EdgeStability> {
+        self.edges.iter().filter(|edge| edge.flips > 0)
+    }
+
+    /// The share of stable indexes, like the stability of AFL++
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn stability(&self) -> f64 {
+        if self.edges.is_empty() {
+            1.0
+        } else {
+            let stable = self.edges.len() - self.unstable_edges().count();
+            stable as f64 / self.edges.len() as f64
+        }
+    }
+
+    /// Group the indexes by function. `function_of` maps an index to the name of its function,
+    /// e.g. from the symbols of the instrumented binary. Indexes without a function are skipped.
+    /// The functions are ordered by their amount of unstable indexes, most unstable first.
+    pub fn by_function<F>(&self, mut function_of: F) -> Vec<FunctionStability>
+    where
+        F: FnMut(usize) -> Option<String>,
+    {
+        let mut functions: HashMap<String, FunctionStability> = HashMap::new();
+        for edge in &self.edges {
+            let Some(name) = function_of(edge.index) else {
+                continue;
+            };
+            let function = functions
+                .entry(name)
+                .or_insert_with_key(|name| FunctionStability {
+                    name: name.clone(),
+                    edges: 0,
+                    unstable_edges: 0,
+                    masked_edges: 0,
+                });
+            function.edges += 1;
+            function.unstable_edges += usize::from(edge.flips > 0);
+            function.masked_edges += usize::from(edge.masked);
+        }
+
+        let mut functions: Vec<_> = functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.unstable_edges
+                .cmp(&a.unstable_edges)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        functions
+    }
+}
+
+impl fmt::Display for StabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "stability: {:.2}% of {} edges over {} re-executions",
+            self.stability() * 100.0,
+            self.edges.len(),
+            self.reexecutions
+        )?;
+        for edge in self.unstable_edges() {
+            writeln!(
+                f,
+                "  edge {}: changed in {}/{} runs{}",
+                edge.index,
+                edge.flips,
+                edge.samples,
+                if edge.masked { " (masked)" } else { "" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The stability stage re-executes a sample of the scheduled corpus entries, compares the maps
+/// of the runs, and masks indexes whose instability score reaches the threshold out of the
+/// novelty checks of the [`crate::feedbacks::MapFeedback`].
+///
+/// Place it after the [`crate::stages::CalibrationStage`] with the same map feedback.
+#[derive(Clone, Debug)]
+pub struct StabilityStage<C, E, O, OT> {
+    map_observer_handle: Handle<C>,
+    map_name: Cow<'static, str>,
+    name: Cow<'static, str>,
+    sample_probability: f64,
+    reexecutions: usize,
+    mask_threshold: f64,
+    min_samples: u32,
+    phantom: PhantomData<(E, O, OT)>,
+}
+
+impl<C, E, O, OT> UsesState for StabilityStage<C, E, O, OT>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<C, E, EM, O, OT, Z> Stage<E, EM, Z> for StabilityStage<C, E, O, OT>
+where
+    E: Executor<EM, Z> + HasObservers<Observers = OT>,
+    EM: EventFirer<State = Self::State>,
+    O: MapObserver,
+    C: AsRef<O>,
+    for<'de> <O as MapObserver>::Entry: Serialize + Deserialize<'de> + 'static,
+    OT: ObserversTuple<Self::State>,
+    Self::State: HasCorpus + HasRand + HasNamedMetadata,
+    Z: UsesState<State = Self::State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        mgr: &mut EM,
+    ) -> Result<(), Error> {
+        if !state.rand_mut().coinflip(self.sample_probability) {
+            return Ok(());
+        }
+
+        let input = state.current_input_cloned()?;
+
+        executor.observers_mut().pre_exec_all(state, &input)?;
+        let exit_kind = executor.run_target(fuzzer, state, mgr, &input)?;
+        executor
+            .observers_mut()
+            .post_exec_all(state, &input, &exit_kind)?;
+        // Crashes and timeouts do not tell us anything about the stability
+        if exit_kind != ExitKind::Ok {
+            return Ok(());
+        }
+
+        let observers = executor.observers();
+        let map = observers[&self.map_observer_handle].as_ref();
+        let (map_first, initial) = (map.to_vec(), map.initial());
+
+        for _ in 0..self.reexecutions {
+            executor.observers_mut().pre_exec_all(state, &input)?;
+            let exit_kind = executor.run_target(fuzzer, state, mgr, &input)?;
+            executor
+                .observers_mut()
+                .post_exec_all(state, &input, &exit_kind)?;
+            if exit_kind != ExitKind::Ok {
+                continue;
+            }
+
+            let map = executor.observers()[&self.map_observer_handle]
+                .as_ref()
+                .to_vec();
+            state
+                .named_metadata_or_insert_with(&self.map_name, StabilityMetadata::new)
+                .record(&map_first, &map, initial);
+        }
+
+        let newly_masked = self.mask_unstable::<Self::State>(state);
+        if newly_masked > 0 {
+            let masked = state
+                .named_metadata_map()
+                .get::<StabilityMetadata>(&self.map_name)
+                .map_or(0, |meta| meta.masked.len());
+            mgr.log(
+                state,
+                LogSeverity::Info,
+                format!("Masked {newly_masked} unstable map indexes, {masked} in total"),
+            )?;
+            mgr.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from("masked_edges"),
+                    value: UserStats::new(
+                        UserStatsValue::Number(masked as u64),
+                        AggregatorOps::Max,
+                    ),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        // Unstable targets may crash on re-execution, do not retry
+        RetryCountRestartHelper::no_retry(state, &self.name)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+impl<C, E, O, OT> StabilityStage<C, E, O, OT>
+where
+    O: MapObserver,
+    for<'de> <O as MapObserver>::Entry: Serialize + Deserialize<'de> + 'static,
+    C: AsRef<O>,
+{
+    /// Create a new [`StabilityStage`] for the map of the given feedback.
+    #[must_use]
+    pub fn new<F>(map_feedback: &F) -> Self
+    where
+        F: HasObserverHandle<Observer = C> + Named,
+    {
+        let map_name = map_feedback.name().clone();
+        Self {
+            map_observer_handle: map_feedback.observer_handle().clone(),
+            name: Cow::Owned(STABILITY_STAGE_NAME.to_owned() + ":" + &map_name),
+            map_name,
+            sample_probability: DEFAULT_SAMPLE_PROBABILITY,
+            reexecutions: DEFAULT_REEXECUTIONS,
+            mask_threshold: 0.0,
+            min_samples: 1,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Set the probability to sample the scheduled corpus entry, `0.1` by default.
+    #[must_use]
+    pub fn with_sample_probability(mut self, sample_probability: f64) -> Self {
+        self.sample_probability = sample_probability;
+        self
+    }
+
+    /// Set the amount of re-executions per sample, `3` by default.
+    #[must_use]
+    pub fn with_reexecutions(mut self, reexecutions: usize) -> Self {
+        self.reexecutions = reexecutions;
+        self
+    }
+
+    /// Mask indexes once their instability score exceeds the threshold after at least `min_samples`
+    /// re-executions. By default, any index that changed once is masked, like the AFL++ stability.
+    #[must_use]
+    pub fn with_mask_threshold(mut self, mask_threshold: f64, min_samples: u32) -> Self {
+        self.mask_threshold = mask_threshold;
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Mask the unstable indexes in the [`MapFeedbackMetadata`], returns the amount of newly masked indexes
+    fn mask_unstable<S>(&self, state: &mut S) -> usize
+    where
+        S: HasNamedMetadata,
+    {
+        let Some(stability) = state
+            .named_metadata_map()
+            .get::<StabilityMetadata>(&self.map_name)
+        else {
+            return 0;
+        };
+        let to_mask: Vec<usize> = stability
+            .unstable_indexes()
+            .filter(|idx| {
+                !stability.masked.contains(idx)
+                    && stability.samples(*idx) >= self.min_samples
+                    && stability.score(*idx) >= self.mask_threshold
+            })
+            .collect();
+        if to_mask.is_empty() {
+            return 0;
+        }
+
+        if let Some(map_state) = state
+            .named_metadata_map_mut()
+            .get_mut::<MapFeedbackMetadata<O::Entry>>(&self.map_name)
+        {
+            for idx in &to_mask {
+                map_state.mask_index(*idx);
+            }
+        }
+        let stability = state
+            .named_metadata_map_mut()
+            .get_mut::<StabilityMetadata>(&self.map_name)
+            .unwrap();
+        stability.masked.extend(&to_mask);
+        to_mask.len()
+    }
+}
+
+impl<C, E, O, OT> Named for StabilityStage<C, E, O, OT> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::StabilityMetadata;
+
+    #[test]
+    fn test_stability_report() {
+        let mut meta = StabilityMetadata::new();
+        assert!(!meta.record(&[1_u8, 0, 2, 0], &[1, 0, 2, 0], 0));
+        assert!(meta.record(&[1_u8, 0, 2, 0], &[1, 3, 2, 0], 0));
+        assert!(meta.record(&[1_u8, 0, 2, 0], &[1, 0, 4, 0], 0));
+
+        assert_eq!(meta.reexecutions(), 3);
+        assert_eq!(meta.samples(0), 3);
+        assert_eq!(meta.flips(0), 0);
+        assert_eq!(meta.samples(1), 1);
+        assert!((meta.score(1) - 1.0).abs() < f64::EPSILON);
+        assert!((meta.score(2) - 1.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(meta.samples(3), 0);
+        assert_eq!(meta.unstable_indexes().collect::<Vec<_>>(), [1, 2]);
+
+        let report = meta.report();
+        assert_eq!(report.edges.len(), 3);
+        assert!((report.stability() - 1.0 / 3.0).abs() < f64::EPSILON);
+
+        let functions = report.by_function(|idx| (idx > 0).then(|| "parse".to_string()));
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].edges, 2);
+        assert_eq!(functions[0].unstable_edges, 2);
+    }
+}
@@ -0,0 +1,652 @@
+//! The [`StructuredTMinStage`] minimizes corpus entries along the structure of their input type.
+//!
+//! Unlike the [`crate::stages::StdTMinMutationalStage`], which tries random size-reducing
+//! mutations, an [`InputMinimizer`] deterministically walks the input: it replaces grammar
+//! subtrees with their smallest derivation, drops loops in grammar automata or removes whole
+//! parts of a `MultipartInput`. Every reduction is only kept if the target behaves the same,
+//! i.e. it exits the same way and the feedback created by the factory (e.g. a
+//! [`crate::stages::MapEqualityFactory`]) agrees.
+//! Solutions can be minimized, too, in which case a reduction is kept if the objective still
+//! deems it interesting.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{hash::Hash, marker::PhantomData};
+
+use ahash::RandomState;
+#[cfg(feature = "nautilus")]
+use hashbrown::HashSet;
+#[cfg(feature = "nautilus")]
+use libafl_bolts::rands::StdRand;
+use libafl_bolts::{impl_serdeany, Named};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "multipart_inputs")]
+use crate::inputs::MultipartInput;
+#[cfg(feature = "nautilus")]
+use crate::{
+    common::nautilus::grammartec::{
+        context::Context,
+        mutator::Mutator as GrammarMutator,
+        tree::{TreeLike, TreeMutation},
+    },
+    generators::nautilus::NautilusContext,
+    inputs::NautilusInput,
+};
+use crate::{
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId, Testcase},
+    events::EventFirer,
+    executors::HasObservers,
+    feedbacks::{Feedback, FeedbackFactory},
+    inputs::{GramatronInput, HasMutatorBytes},
+    observers::UsesObservers,
+    schedulers::RemovableScheduler,
+    stages::{RetryCountRestartHelper, Stage},
+    state::{HasCorpus, HasCurrentTestcase, HasExecutions, HasSolutions, UsesState},
+    Error, ExecutesInput, ExecutionProcessor, HasFeedback, HasMetadata, HasNamedMetadata,
+    HasObjective, HasScheduler,
+};
+
+/// The name of the [`StructuredTMinStage`]
+pub static STRUCTURED_TMIN_STAGE_NAME: &str = "structured_tmin";
+
+/// Shrinks an input along its structure, for the [`StructuredTMinStage`]
+pub trait InputMinimizer<I, S> {
+    /// Minimize `input` in place.
+    ///
+    /// `test` executes a smaller candidate and returns `true` if it behaves like the original input,
+    /// in which case the candidate should replace `input`.
+    fn minimize<T>(&mut self, state: &mut S, input: &mut I, test: &mut T) -> Result<(), Error>
+    where
+        T: FnMut(&mut S, &I) -> Result<bool, Error>;
+}
+
+/// Delta debugging for byte inputs: removes chunks of halving sizes, down to single bytes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesMinimizer;
+
+impl<I, S> InputMinimizer<I, S> for BytesMinimizer
+where
+    I: HasMutatorBytes + Clone,
+{
+    fn minimize<T>(&mut self, state: &mut S, input: &mut I, test: &mut T) -> Result<(), Error>
+    where
+        T: FnMut(&mut S, &I) -> Result<bool, Error>,
+    {
+        let mut chunk = input.bytes().len() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start < input.bytes().len() {
+                let end = (start + chunk).min(input.bytes().len());
+                let mut candidate = input.clone();
+                candidate.drain(start..end);
+                if test(state, &candidate)? {
+                    *input = candidate;
+                } else {
+                    start = end;
+                }
+            }
+            chunk /= 2;
+        }
+        Ok(())
+    }
+}
+
+/// Minimizes [`GramatronInput`]s by cutting out loops in the automaton.
+///
+/// Whenever two terminals leave the same automaton state, everything in between can be dropped
+/// without leaving the language of the grammar. The longest loops are tried first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GramatronMinimizer;
+
+impl<S> InputMinimizer<GramatronInput, S> for GramatronMinimizer {
+    fn minimize<T>(
+        &mut self,
+        state: &mut S,
+        input: &mut GramatronInput,
+        test: &mut T,
+    ) -> Result<(), Error>
+    where
+        T: FnMut(&mut S, &GramatronInput) -> Result<bool, Error>,
+    {
+        let mut i = 0;
+        while i < input.terminals().len() {
+            let loop_state = input.terminals()[i].state;
+            let mut reduced = false;
+            for j in (i + 1..input.terminals().len()).rev() {
+                if input.terminals()[j].state != loop_state {
+                    continue;
+                }
+                let terms = input.terminals();
+                let mut candidate = Vec::with_capacity(terms.len() - (j - i));
+                candidate.extend_from_slice(&terms[..i]);
+                candidate.extend_from_slice(&terms[j..]);
+                let candidate = GramatronInput::new(candidate);
+                if test(state, &candidate)? {
+                    *input = candidate;
+                    reduced = true;
+                    break;
+                }
+            }
+            // After a reduction, the terminal at `i` may start another loop
+            if !reduced {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Minimizes [`NautilusInput`]s, as done by the original Nautilus.
+///
+/// Every subtree is replaced by the smallest derivation of its nonterminal, and recursive subtrees
+/// are replaced by their recursive children, until no more reduction keeps the behavior.
+#[cfg(feature = "nautilus")]
+#[derive(Debug)]
+pub struct NautilusMinimizer<'a> {
+    ctx: &'a NautilusContext,
+    mutator: GrammarMutator,
+}
+
+#[cfg(feature = "nautilus")]
+impl<'a> NautilusMinimizer<'a> {
+    /// Create a new [`NautilusMinimizer`] for the given grammar
+    #[must_use]
+    pub fn new(ctx: &'a NautilusContext) -> Self {
+        Self {
+            ctx,
+            mutator: GrammarMutator::new(&ctx.ctx),
+        }
+    }
+}
+
+#[cfg(feature = "nautilus")]
+impl<S> InputMinimizer<NautilusInput, S> for NautilusMinimizer<'_> {
+    fn minimize<T>(
+        &mut self,
+        state: &mut S,
+        input: &mut NautilusInput,
+        test: &mut T,
+    ) -> Result<(), Error>
+    where
+        T: FnMut(&mut S, &NautilusInput) -> Result<bool, Error>,
+    {
+        // The minimal derivations don't depend on the seed
+        let mut rand = StdRand::with_seed(0);
+        let bits = HashSet::new();
+        let ctx = &self.ctx.ctx;
+        let mut tester = |mutation: &TreeMutation, _: &HashSet<usize>, ctx: &Context| {
+            test(state, &NautilusInput::new(mutation.to_tree(ctx)))
+        };
+
+        let mut tree = input.tree().clone();
+        loop {
+            let size = tree.size();
+            self.mutator.minimize_tree(
+                &mut rand,
+                &mut tree,
+                &bits,
+                ctx,
+                0,
+                usize::MAX,
+                &mut tester,
+            )?;
+            self.mutator
+                .minimize_rec(&mut tree, &bits, ctx, 0, usize::MAX, &mut tester)?;
+            // Every accepted replacement shrinks the tree
+            if tree.size() == size {
+                break;
+            }
+        }
+        *input.tree_mut() = tree;
+        Ok(())
+    }
+}
+
+/// Minimizes [`MultipartInput`]s: first removes whole parts, then minimizes the remaining parts
+/// one by one with the inner [`InputMinimizer`].
+#[cfg(feature = "multipart_inputs")]
+#[derive(Debug, Clone, Default)]
+pub struct MultipartMinimizer<M> {
+    inner: M,
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<M> MultipartMinimizer<M> {
+    /// Create a new [`MultipartMinimizer`], minimizing each part with `inner`
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<I, M, S> InputMinimizer<MultipartInput<I>, S> for MultipartMinimizer<M>
+where
+    I: Clone,
+    M: InputMinimizer<I, S>,
+{
+    fn minimize<T>(
+        &mut self,
+        state: &mut S,
+        input: &mut MultipartInput<I>,
+        test: &mut T,
+    ) -> Result<(), Error>
+    where
+        T: FnMut(&mut S, &MultipartInput<I>) -> Result<bool, Error>,
+    {
+        for idx in (0..input.parts().len()).rev() {
+            let mut candidate = input.clone();
+            candidate.remove_part(idx);
+            if test(state, &candidate)? {
+                *input = candidate;
+            }
+        }
+
+        for idx in 0..input.parts().len() {
+            let mut part = input.parts()[idx].clone();
+            {
+                let whole: &MultipartInput<I> = input;
+                let mut part_test = |state: &mut S, candidate_part: &I| {
+                    let mut candidate = whole.clone();
+                    *candidate.part_mut(idx).unwrap() = candidate_part.clone();
+                    test(state, &candidate)
+                };
+                self.inner.minimize(state, &mut part, &mut part_test)?;
+            }
+            *input.part_mut(idx).unwrap() = part;
+        }
+        Ok(())
+    }
+}
+
+/// Marks a testcase that was already minimized by a [`StructuredTMinStage`]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct StructuredTMinMetadata {
+    /// The number of executions spent minimizing
+    pub executions: u64,
+}
+
+impl_serdeany!(StructuredTMinMetadata);
+
+/// Minimizes the current corpus entry using an [`InputMinimizer`].
+///
+/// A reduced input is only kept if it exits like the original, does not add anything to the
+/// corpus, and the feedback created by the factory is interesting for it.
+/// Each corpus entry is minimized only once.
+///
+/// With [`StructuredTMinStage::with_solutions`], the stage also minimizes every new solution.
+/// There, a reduced input is kept if it exits like the original and the objective is interesting
+/// for it. Note that objectives that only report each crash once (e.g. a
+/// [`crate::feedbacks::NewHashFeedback`]) will reject all reductions.
+#[derive(Debug)]
+pub struct StructuredTMinStage<E, EM, F, FF, M, Z> {
+    name: Cow<'static, str>,
+    minimizer: M,
+    factory: FF,
+    max_execs: u64,
+    solutions: bool,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, F, Z)>,
+}
+
+impl<E, EM, F, FF, M, Z> StructuredTMinStage<E, EM, F, FF, M, Z> {
+    /// Create a new [`StructuredTMinStage`]
+    pub fn new(minimizer: M, factory: FF) -> Self {
+        Self {
+            name: Cow::Borrowed(STRUCTURED_TMIN_STAGE_NAME),
+            minimizer,
+            factory,
+            max_execs: u64::MAX,
+            solutions: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Stop minimizing an entry after `max_execs` executions
+    #[must_use]
+    pub fn with_max_execs(mut self, max_execs: u64) -> Self {
+        self.max_execs = max_execs;
+        self
+    }
+
+    /// Also minimize the solutions, judged by the objective instead of the feedback of the factory
+    #[must_use]
+    pub fn with_solutions(mut self) -> Self {
+        self.solutions = true;
+        self
+    }
+
+    /// The minimizer used by this stage
+    pub fn minimizer_mut(&mut self) -> &mut M {
+        &mut self.minimizer
+    }
+}
+
+impl<E, EM, F, FF, M, Z> UsesState for StructuredTMinStage<E, EM, F, FF, M, Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, F, FF, M, Z> Named for StructuredTMinStage<E, EM, F, FF, M, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, F, FF, M, Z> Stage<E, EM, Z> for StructuredTMinStage<E, EM, F, FF, M, Z>
+where
+    Z: HasScheduler + ExecutionProcessor + ExecutesInput<E, EM> + HasFeedback + HasObjective,
+    Z::Scheduler: RemovableScheduler,
+    E: HasObservers<State = Self::State>,
+    <E as UsesObservers>::Observers: Serialize,
+    EM: EventFirer<State = Self::State>,
+    FF: FeedbackFactory<F, E::Observers>,
+    F: Feedback<Self::State>,
+    M: InputMinimizer<Self::Input, Self::State>,
+    Self::Input: Clone + Hash,
+    Self::State: HasMetadata + HasExecutions + HasSolutions + HasCorpus + HasNamedMetadata,
+{
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        // Minimization is deterministic, retrying an entry that crashed us won't help
+        RetryCountRestartHelper::no_retry(state, &self.name)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(base_corpus_id) = state.current_corpus_id()? else {
+            return Err(Error::illegal_state(
+                "state is not currently processing a corpus index",
+            ));
+        };
+        if !state
+            .current_testcase()?
+            .has_metadata::<StructuredTMinMetadata>()
+        {
+            self.minimize_corpus_entry(fuzzer, executor, state, manager, base_corpus_id)?;
+        }
+        if self.solutions {
+            self.minimize_solutions(fuzzer, executor, state, manager)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E, EM, F, FF, M, Z> StructuredTMinStage<E, EM, F, FF, M, Z>
+where
+    Z: HasScheduler + ExecutionProcessor + ExecutesInput<E, EM> + HasFeedback + HasObjective,
+    Z::Scheduler: RemovableScheduler,
+    E: HasObservers<State = Z::State>,
+    <E as UsesObservers>::Observers: Serialize,
+    EM: EventFirer<State = Z::State>,
+    FF: FeedbackFactory<F, E::Observers>,
+    F: Feedback<Z::State>,
+    M: InputMinimizer<Z::Input, Z::State>,
+    Z::Input: Clone + Hash,
+    Z::State: HasMetadata + HasExecutions + HasSolutions + HasCorpus + HasNamedMetadata,
+{
+    fn minimize_corpus_entry(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+        base_corpus_id: CorpusId,
+    ) -> Result<(), Error> {
+        let mut base = state.current_input_cloned()?;
+        let base_hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(&base);
+        let start_executions = *state.executions();
+
+        let base_exit_kind = fuzzer.execute_input(state, executor, manager, &base)?;
+        let mut feedback = self.factory.create_feedback(&*executor.observers());
+
+        {
+            let max_execs = self.max_execs;
+            let mut execs = 0;
+            let mut test = |state: &mut Z::State, candidate: &Z::Input| -> Result<bool, Error> {
+                if execs >= max_execs {
+                    return Ok(false);
+                }
+                execs += 1;
+
+                let exit_kind = fuzzer.execute_input(state, executor, manager, candidate)?;
+                let observers = executor.observers();
+
+                // Let the fuzzer process this execution, we may find something new on the way
+                let corpus_count = state.corpus().count();
+                fuzzer.evaluate_execution(
+                    state,
+                    manager,
+                    candidate.clone(),
+                    &*observers,
+                    &exit_kind,
+                    false,
+                )?;
+                if exit_kind != base_exit_kind || state.corpus().count() != corpus_count {
+                    return Ok(false);
+                }
+                feedback.is_interesting(state, manager, candidate, &*observers, &exit_kind)
+            };
+            self.minimizer.minimize(state, &mut base, &mut test)?;
+        }
+
+        let new_hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(&base);
+        if base_hash != new_hash {
+            let exit_kind = fuzzer.execute_input(state, executor, manager, &base)?;
+            let observers = executor.observers();
+            // The minimized input behaves like the original, so it won't be interesting on its own
+            fuzzer
+                .feedback_mut()
+                .is_interesting(state, manager, &base, &*observers, &exit_kind)?;
+            let mut testcase = Testcase::with_executions(base, *state.executions());
+            fuzzer
+                .feedback_mut()
+                .append_metadata(state, manager, &*observers, &mut testcase)?;
+            let prev = state.corpus_mut().replace(base_corpus_id, testcase)?;
+            fuzzer
+                .scheduler_mut()
+                .on_replace(state, base_corpus_id, &prev)?;
+        }
+
+        let executions = *state.executions() - start_executions;
+        state
+            .corpus()
+            .get(base_corpus_id)?
+            .borrow_mut()
+            .add_metadata(StructuredTMinMetadata { executions });
+
+        Ok(())
+    }
+
+    fn minimize_solutions(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let ids: Vec<CorpusId> = state.solutions().ids().collect();
+        for id in ids {
+            if state
+                .solutions()
+                .get(id)?
+                .borrow()
+                .has_metadata::<StructuredTMinMetadata>()
+            {
+                continue;
+            }
+
+            let mut base = state.solutions().cloned_input_for_id(id)?;
+            let base_hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(&base);
+            let start_executions = *state.executions();
+
+            let base_exit_kind = fuzzer.execute_input(state, executor, manager, &base)?;
+            {
+                let max_execs = self.max_execs;
+                let mut execs = 0;
+                let mut test = |state: &mut Z::State,
+                                candidate: &Z::Input|
+                 -> Result<bool, Error> {
+                    if execs >= max_execs {
+                        return Ok(false);
+                    }
+                    execs += 1;
+
+                    let exit_kind = fuzzer.execute_input(state, executor, manager, candidate)?;
+                    if exit_kind != base_exit_kind {
+                        return Ok(false);
+                    }
+                    let observers = executor.observers();
+                    fuzzer.objective_mut().is_interesting(
+                        state,
+                        manager,
+                        candidate,
+                        &*observers,
+                        &exit_kind,
+                    )
+                };
+                self.minimizer.minimize(state, &mut base, &mut test)?;
+            }
+
+            let new_hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(&base);
+            if base_hash == new_hash {
+                let executions = *state.executions() - start_executions;
+                state
+                    .solutions()
+                    .get(id)?
+                    .borrow_mut()
+                    .add_metadata(StructuredTMinMetadata { executions });
+                continue;
+            }
+
+            let exit_kind = fuzzer.execute_input(state, executor, manager, &base)?;
+            let observers = executor.observers();
+            // Refresh the objective's metadata for the minimized input
+            fuzzer.objective_mut().is_interesting(
+                state,
+                manager,
+                &base,
+                &*observers,
+                &exit_kind,
+            )?;
+            let mut testcase = Testcase::with_executions(base, *state.executions());
+            fuzzer
+                .objective_mut()
+                .append_metadata(state, manager, &*observers, &mut testcase)?;
+            let executions = *state.executions() - start_executions;
+            testcase.add_metadata(StructuredTMinMetadata { executions });
+            state.solutions_mut().replace(id, testcase)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    #[cfg(feature = "nautilus")]
+    use crate::{
+        common::nautilus::grammartec::{newtypes::RuleId, rule::RuleIdOrCustom, tree::Tree},
+        generators::nautilus::NautilusContext,
+        inputs::NautilusInput,
+        stages::structured_tmin::NautilusMinimizer,
+    };
+    #[cfg(feature = "multipart_inputs")]
+    use crate::{inputs::MultipartInput, stages::structured_tmin::MultipartMinimizer};
+    use crate::{
+        inputs::{BytesInput, GramatronInput, HasMutatorBytes, Terminal},
+        stages::structured_tmin::{BytesMinimizer, GramatronMinimizer, InputMinimizer},
+        Error,
+    };
+
+    #[test]
+    fn test_bytes_minimizer() {
+        let mut input = BytesInput::new(b"xxxxAxxxxxxBxxx".to_vec());
+        let mut test = |(): &mut (), candidate: &BytesInput| -> Result<bool, Error> {
+            let bytes = candidate.bytes();
+            Ok(bytes.contains(&b'A') && bytes.contains(&b'B'))
+        };
+        BytesMinimizer
+            .minimize(&mut (), &mut input, &mut test)
+            .unwrap();
+        assert_eq!(input.bytes(), b"AB");
+    }
+
+    #[test]
+    fn test_gramatron_minimizer() {
+        // A loop through state 1 twice, then the crashing symbol
+        let terms = [(0, "("), (1, "a"), (1, "a"), (1, ")"), (2, "!")]
+            .into_iter()
+            .map(|(state, symbol)| Terminal::new(state, 0, symbol.to_string()))
+            .collect::<Vec<_>>();
+        let mut input = GramatronInput::new(terms);
+        let mut test = |(): &mut (), candidate: &GramatronInput| -> Result<bool, Error> {
+            Ok(candidate.terminals().iter().any(|t| t.symbol == "!"))
+        };
+        GramatronMinimizer
+            .minimize(&mut (), &mut input, &mut test)
+            .unwrap();
+        let mut bytes = vec![];
+        input.unparse(&mut bytes);
+        assert_eq!(bytes, b"()!");
+    }
+
+    #[cfg(feature = "nautilus")]
+    #[test]
+    fn test_nautilus_minimizer() {
+        let ctx =
+            NautilusContext::with_rules(10, &[("A", b"a{A}"), ("A", b"b"), ("A", b"!")]).unwrap();
+        // "aaa!", built from the rule ids in order of definition
+        let rules = [0, 0, 0, 2]
+            .into_iter()
+            .map(|id| RuleIdOrCustom::Rule(RuleId::from(id)))
+            .collect();
+        let mut input = NautilusInput::new(Tree::from_rule_vec(rules, &ctx.ctx));
+        let mut test = |(): &mut (), candidate: &NautilusInput| -> Result<bool, Error> {
+            let mut bytes = vec![];
+            candidate.unparse(&ctx, &mut bytes);
+            Ok(bytes.contains(&b'!'))
+        };
+        NautilusMinimizer::new(&ctx)
+            .minimize(&mut (), &mut input, &mut test)
+            .unwrap();
+        let mut bytes = vec![];
+        input.unparse(&ctx, &mut bytes);
+        assert_eq!(bytes, b"!");
+    }
+
+    #[cfg(feature = "multipart_inputs")]
+    #[test]
+    fn test_multipart_minimizer() {
+        let mut input = MultipartInput::from([
+            ("a", BytesInput::new(b"aaaa".to_vec())),
+            ("b", BytesInput::new(b"bbXbb".to_vec())),
+            ("c", BytesInput::new(b"cc".to_vec())),
+        ]);
+        let mut test =
+            |(): &mut (), candidate: &MultipartInput<BytesInput>| -> Result<bool, Error> {
+                Ok(candidate
+                    .parts_by_name("b")
+                    .any(|(_, part)| part.bytes().contains(&b'X')))
+            };
+        MultipartMinimizer::new(BytesMinimizer)
+            .minimize(&mut (), &mut input, &mut test)
+            .unwrap();
+        assert_eq!(input.names(), ["b"]);
+        assert_eq!(input.parts()[0].bytes(), b"X");
+    }
+}
@@ -0,0 +1,191 @@
+//! On-disk checkpoints of the fuzzer state.
+//!
+//! Unlike the [`libafl_bolts::staterestore::StateRestorer`], which only hands the state over to the next
+//! client after a crash or restart, checkpoints survive reboots of the whole machine.
+//! Use a [`crate::stages::CheckpointStage`] to write them periodically, and
+//! [`crate::events::Launcher`]'s `checkpoint_dir` to resume all clients from their latest checkpoint.
+
+use alloc::{format, vec::Vec};
+use core::time::Duration;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use libafl_bolts::core_affinity::CoreId;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// The file name prefix of a checkpoint, followed by its sequence number
+const CHECKPOINT_PREFIX: &str = "checkpoint_";
+/// The file extension of a checkpoint
+const CHECKPOINT_EXTENSION: &str = "postcard";
+/// The amount of checkpoints kept around by default
+const CHECKPOINTS_KEPT_DEFAULT: usize = 3;
+
+/// Writes serialized states to a directory, and loads the latest one again.
+///
+/// Each checkpoint is first written to a temporary file, synced, and then atomically renamed,
+/// so that a reboot in the middle of a write never leaves a torn checkpoint behind.
+/// Only the newest checkpoints are kept around, older ones get rotated out.
+#[derive(Debug, Clone)]
+pub struct StateCheckpointer {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl StateCheckpointer {
+    /// Create a new [`StateCheckpointer`], writing to (and creating) `dir`
+    pub fn new<P>(dir: P) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|err| Error::os_error(err, format!("Error creating directory {dir:?}")))?;
+        Ok(Self {
+            dir,
+            keep: CHECKPOINTS_KEPT_DEFAULT,
+        })
+    }
+
+    /// Create a new [`StateCheckpointer`] for the client bound to `core_id`, in a subdirectory of `base_dir`.
+    /// This is the directory the [`crate::events::Launcher`] resumes this client from.
+    pub fn for_client<P>(base_dir: P, core_id: CoreId) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(base_dir.as_ref().join(format!("client_{}", core_id.0)))
+    }
+
+    /// Keep the `keep` newest checkpoints around, instead of the default of 3.
+    #[must_use]
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        self
+    }
+
+    /// The directory checkpoints are written to
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// All checkpoints in our directory, as `(sequence number, path)`, oldest first
+    fn checkpoints(&self) -> Result<Vec<(u64, PathBuf)>, Error> {
+        let mut checkpoints = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let seq = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(CHECKPOINT_PREFIX))
+                .and_then(|name| name.strip_suffix(CHECKPOINT_EXTENSION))
+                .and_then(|seq| seq.strip_suffix('.'))
+                .and_then(|seq| seq.parse::<u64>().ok());
+            if let Some(seq) = seq {
+                checkpoints.push((seq, path));
+            }
+        }
+        checkpoints.sort_unstable();
+        Ok(checkpoints)
+    }
+
+    /// The path of the latest checkpoint, if any
+    pub fn latest(&self) -> Result<Option<PathBuf>, Error> {
+        Ok(self.checkpoints()?.pop().map(|(_, path)| path))
+    }
+
+    /// The time the latest checkpoint was written, since the [`UNIX_EPOCH`], if any
+    pub fn latest_time(&self) -> Result<Option<Duration>, Error> {
+        let Some(latest) = self.latest()? else {
+            return Ok(None);
+        };
+        let modified = fs::metadata(latest)?.modified()?;
+        Ok(modified.duration_since(UNIX_EPOCH).ok())
+    }
+
+    /// Write a new checkpoint of `state`, and rotate out old ones.
+    /// Returns the path of the new checkpoint.
+    pub fn save<S>(&self, state: &S) -> Result<PathBuf, Error>
+    where
+        S: Serialize,
+    {
+        let serialized = postcard::to_allocvec(state)?;
+
+        let mut checkpoints = self.checkpoints()?;
+        let seq = checkpoints.last().map_or(0, |(seq, _)| seq + 1);
+        let path = self.dir.join(format!(
+            "{CHECKPOINT_PREFIX}{seq:010}.{CHECKPOINT_EXTENSION}"
+        ));
+        let tmp_path = self.dir.join(format!(".{CHECKPOINT_PREFIX}{seq:010}.tmp"));
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serialized)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp_path, &path)?;
+        // Make sure the rename itself hits the disk
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        checkpoints.push((seq, path.clone()));
+        let rotate_out = checkpoints.len().saturating_sub(self.keep);
+        for (_, old) in checkpoints.drain(..rotate_out) {
+            if let Err(err) = fs::remove_file(&old) {
+                log::warn!("Could not remove old checkpoint {old:?}: {err}");
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Load the latest checkpoint that can be deserialized.
+    /// Returns `None` if there is no (valid) checkpoint.
+    pub fn load<S>(&self) -> Result<Option<S>, Error>
+    where
+        S: DeserializeOwned,
+    {
+        for (_, path) in self.checkpoints()?.into_iter().rev() {
+            let serialized = fs::read(&path)?;
+            match postcard::from_bytes(&serialized) {
+                Ok(state) => {
+                    log::info!("Loaded checkpoint {path:?}");
+                    return Ok(Some(state));
+                }
+                Err(err) => log::warn!("Skipping broken checkpoint {path:?}: {err}"),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::StateCheckpointer;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_checkpoint_rotation() {
+        let dir = env::temp_dir().join(format!("libafl_checkpoint_test_{}", process::id()));
+        let checkpointer = StateCheckpointer::new(&dir).unwrap().with_keep(2);
+        assert_eq!(checkpointer.load::<u64>().unwrap(), None);
+
+        for state in 0..5_u64 {
+            checkpointer.save(&state).unwrap();
+        }
+        assert_eq!(checkpointer.checkpoints().unwrap().len(), 2);
+        assert_eq!(checkpointer.load::<u64>().unwrap(), Some(4));
+
+        // A torn checkpoint falls back to the previous one
+        fs::write(checkpointer.latest().unwrap().unwrap(), []).unwrap();
+        assert_eq!(checkpointer.load::<u64>().unwrap(), Some(3));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -68,11 +68,11 @@ use core::{
     cmp::max,
     fmt::Debug,
     hint,
-    mem::size_of,
+    mem::{self, size_of},
     num::NonZeroUsize,
     ops::{BitAnd, BitOr, Not},
     ptr, slice,
-    sync::atomic::{fence, AtomicU16, Ordering},
+    sync::atomic::{fence, AtomicU16, AtomicUsize, Ordering},
     time::Duration,
 };
 #[cfg(feature = "std")]
@@ -143,6 +143,10 @@ pub const LLMP_FLAG_INITIALIZED: Flags = Flags(0x0);
 pub const LLMP_FLAG_COMPRESSED: Flags = Flags(0x1);
 /// From another broker.
 pub const LLMP_FLAG_FROM_B2B: Flags = Flags(0x2);
+/// This message is of [`LlmpPriority::Low`], and may be dropped or coalesced under load.
+pub const LLMP_FLAG_PRIORITY_LOW: Flags = Flags(0x4);
+/// This message is of [`LlmpPriority::High`].
+pub const LLMP_FLAG_PRIORITY_HIGH: Flags = Flags(0x8);
 
 /// Timt the broker 2 broker connection waits for incoming data,
 /// before checking for own data to forward again.
@@ -159,8 +163,14 @@ const _LLMP_BIND_ADDR: &str = "127.0.0.1";
 /// An env var of this value indicates that the set value was a NULL PTR
 const _NULL_ENV_STR: &str = "_NULL";
 
-/// Magic indicating that a got initialized correctly
-const PAGE_INITIALIZED_MAGIC: u64 = 0x1A1A1A1A1A1A1AF1;
+/// The amount of messages a receiver reads before it updates [`LlmpPage::size_read`] again
+const LLMP_SIZE_READ_INTERVAL: usize = 64;
+
+/// Magic indicating that a got initialized correctly.
+///
+/// Changed with the layout of [`LlmpPage`], so that pages of incompatible versions are rejected:
+/// `0x1A1A1A1A1A1A1AF1` before [`LlmpPage::size_read`] was added.
+const PAGE_INITIALIZED_MAGIC: u64 = 0x1A1A1A1A1A1A1AF2;
 
 /// Magic indicating that a got deinitialized correctly, after use
 const PAGE_DEINITIALIZED_MAGIC: u64 = 0xDEADC0FEAF1BEEF1;
@@ -209,6 +219,12 @@ impl Debug for Flags {
         if *self & LLMP_FLAG_FROM_B2B == LLMP_FLAG_FROM_B2B {
             f.write_str("FROM_B2B")?;
         }
+        if *self & LLMP_FLAG_PRIORITY_LOW == LLMP_FLAG_PRIORITY_LOW {
+            f.write_str("PRIORITY_LOW")?;
+        }
+        if *self & LLMP_FLAG_PRIORITY_HIGH == LLMP_FLAG_PRIORITY_HIGH {
+            f.write_str("PRIORITY_HIGH")?;
+        }
         f.write_str(" )")
     }
 }
@@ -237,6 +253,63 @@ impl Not for Flags {
     }
 }
 
+/// The priority class of a message.
+///
+/// In each round of [`LlmpBroker::broker_once`], the broker forwards the [`LlmpPriority::High`] and
+/// [`LlmpPriority::Normal`] messages as it reads them, and the [`LlmpPriority::Low`] ones of all
+/// clients after them.
+/// A sender over its outgoing budget (see [`LlmpSender::set_out_budget`]) may drop or coalesce
+/// [`LlmpPriority::Low`] messages, according to its [`LlmpBackpressurePolicy`].
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum LlmpPriority {
+    /// Informational messages, such as stats, that may be delayed, coalesced, or dropped.
+    Low,
+    /// Regular messages
+    #[default]
+    Normal,
+    /// Messages that should never wait behind others, such as objectives and control messages.
+    High,
+}
+
+impl LlmpPriority {
+    /// The [`Flags`] marking a message with this priority
+    #[must_use]
+    pub fn flags(self) -> Flags {
+        match self {
+            LlmpPriority::Low => LLMP_FLAG_PRIORITY_LOW,
+            LlmpPriority::Normal => LLMP_FLAG_INITIALIZED,
+            LlmpPriority::High => LLMP_FLAG_PRIORITY_HIGH,
+        }
+    }
+
+    /// Reads the priority from the [`Flags`] of a message
+    #[must_use]
+    pub fn from_flags(flags: Flags) -> Self {
+        if flags & LLMP_FLAG_PRIORITY_HIGH == LLMP_FLAG_PRIORITY_HIGH {
+            LlmpPriority::High
+        } else if flags & LLMP_FLAG_PRIORITY_LOW == LLMP_FLAG_PRIORITY_LOW {
+            LlmpPriority::Low
+        } else {
+            LlmpPriority::Normal
+        }
+    }
+}
+
+/// What an [`LlmpSender`] does with [`LlmpPriority::Low`] messages while it is over its outgoing budget
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LlmpBackpressurePolicy {
+    /// Send them anyway, the budget only applies to the other policies.
+    #[default]
+    Send,
+    /// Drop them.
+    Drop,
+    /// Keep only the latest message per coalesce key, and send it once the receiver caught up.
+    /// Messages without a coalesce key get dropped.
+    Coalesce,
+}
+
 /// The message ID, an ever-increasing number, unique only to a sharedmap/page.
 #[cfg(target_pointer_width = "64")]
 #[repr(transparent)]
@@ -549,6 +622,7 @@ unsafe fn llmp_page_init<SHM: ShMem>(shmem: &mut SHM, sender_id: ClientId, allow
     // Don't forget to subtract our own header size
     (*page).size_total = map_size - LLMP_PAGE_HEADER_LEN;
     (*page).size_used = 0;
+    (*page).size_read.store(0, Ordering::Relaxed);
     (*(*page).messages.as_mut_ptr()).message_id = MessageId(0);
     (*(*page).messages.as_mut_ptr()).tag = LLMP_TAG_UNSET;
     (*page).receivers_joined_count.store(0, Ordering::Release);
@@ -843,6 +917,15 @@ pub struct LlmpPage {
     /// The maximum amount of bytes that ever got allocated on this page in one go.
     /// An inidactor of what to use as size for future pages
     pub max_alloc_size: usize,
+    /// How many bytes of this page the receiver has read so far.
+    /// Used by the sender to know how far the receiver lags behind.
+    ///
+    /// Only updated by the broker, the single reader of its clients' pages, every
+    /// `LLMP_SIZE_READ_INTERVAL` messages and whenever it caught up with the sender.
+    ///
+    /// Added after the other fields, which keep their offsets. The messages start after it, so
+    /// pages are not compatible with versions before 0.14, which have a different [`Self::magic`].
+    pub size_read: AtomicUsize,
     /// Pointer to the messages, from here on.
     pub messages: [LlmpMsg; 0],
 }
@@ -911,10 +994,27 @@ where
     keep_pages_forever: bool,
     /// True, if we allocatd a message, but didn't call [`Self::send()`] yet
     has_unsent_message: bool,
+    /// The outgoing budget and what to do with low priority messages once we exceed it
+    backpressure: LlmpBackpressure,
     /// The sharedmem provider to get new sharaed maps if we're full
     shmem_provider: SP,
 }
 
+/// Backpressure state of an [`LlmpSender`]
+#[derive(Debug, Default)]
+struct LlmpBackpressure {
+    /// The maximum amount of unread bytes on our outgoing pages, before [`LlmpPriority::Low`] messages get throttled
+    out_budget: Option<usize>,
+    /// What to do with [`LlmpPriority::Low`] messages while over budget
+    policy: LlmpBackpressurePolicy,
+    /// Coalesced messages, waiting to be sent once we are below budget again: `(key, tag, flags, buf)`
+    coalesced: Vec<(u64, Tag, Flags, Vec<u8>)>,
+    /// Amount of low priority messages that got dropped
+    dropped_msgs: u64,
+    /// Amount of low priority messages that got superseded by a newer message with the same key
+    coalesced_msgs: u64,
+}
+
 /// An actor on the sending part of the shared map
 impl<SP> LlmpSender<SP>
 where
@@ -944,6 +1044,7 @@ where
             // drop pages to the broker if it already read them
             keep_pages_forever,
             has_unsent_message: false,
+            backpressure: LlmpBackpressure::default(),
             shmem_provider,
             unused_shmem_cache: vec![],
         })
@@ -1087,6 +1188,7 @@ where
             // drop pages to the broker if it already read them
             keep_pages_forever: false,
             has_unsent_message: false,
+            backpressure: LlmpBackpressure::default(),
             shmem_provider,
             unused_shmem_cache: vec![],
         })
@@ -1545,6 +1647,155 @@ where
         }
     }
 
+    /// Sends a `buf` with the given [`LlmpPriority`].
+    ///
+    /// While this sender is over its outgoing budget, [`LlmpPriority::Low`] messages are handled
+    /// according to the [`LlmpBackpressurePolicy`]. For [`LlmpBackpressurePolicy::Coalesce`],
+    /// only the latest message for each `coalesce_key` is kept, and sent once the receiver caught up.
+    /// Returns `true` if the message got sent right away.
+    pub fn send_buf_prioritized(
+        &mut self,
+        tag: Tag,
+        flags: Flags,
+        priority: LlmpPriority,
+        coalesce_key: Option<u64>,
+        buf: &[u8],
+    ) -> Result<bool, Error> {
+        let over_budget = self.is_over_budget();
+        if priority == LlmpPriority::Low {
+            let backpressure = &mut self.backpressure;
+            if over_budget {
+                match (backpressure.policy, coalesce_key) {
+                    (LlmpBackpressurePolicy::Send, _) => (),
+                    (LlmpBackpressurePolicy::Coalesce, Some(key)) => {
+                        let msg = (key, tag, flags, buf.to_vec());
+                        if let Some(pending) =
+                            backpressure.coalesced.iter_mut().find(|(k, ..)| *k == key)
+                        {
+                            *pending = msg;
+                            backpressure.coalesced_msgs += 1;
+                        } else {
+                            backpressure.coalesced.push(msg);
+                        }
+                        return Ok(false);
+                    }
+                    (LlmpBackpressurePolicy::Drop | LlmpBackpressurePolicy::Coalesce, _) => {
+                        backpressure.dropped_msgs += 1;
+                        return Ok(false);
+                    }
+                }
+            } else if let Some(key) = coalesce_key {
+                // This message supersedes any coalesced message with the same key.
+                let len_before = backpressure.coalesced.len();
+                backpressure.coalesced.retain(|(k, ..)| *k != key);
+                backpressure.coalesced_msgs += (len_before - backpressure.coalesced.len()) as u64;
+            }
+        }
+        if !over_budget {
+            self.flush_coalesced()?;
+        }
+        self.send_buf_with_flags(tag, flags | priority.flags(), buf)?;
+        Ok(true)
+    }
+
+    /// Sends all coalesced [`LlmpPriority::Low`] messages, if we are below our outgoing budget again.
+    /// Returns the amount of messages sent.
+    pub fn flush_coalesced(&mut self) -> Result<usize, Error> {
+        if self.backpressure.coalesced.is_empty() || self.is_over_budget() {
+            return Ok(0);
+        }
+        let coalesced = mem::take(&mut self.backpressure.coalesced);
+        let count = coalesced.len();
+        for (_, tag, flags, buf) in coalesced {
+            self.send_buf_with_flags(tag, flags | LLMP_FLAG_PRIORITY_LOW, &buf)?;
+        }
+        Ok(count)
+    }
+
+    /// Sets the maximum amount of unread bytes on our outgoing pages,
+    /// after which [`LlmpPriority::Low`] messages get throttled according to the [`LlmpBackpressurePolicy`].
+    /// `None` (the default) means unbounded.
+    pub fn set_out_budget(&mut self, out_budget: Option<usize>) {
+        self.backpressure.out_budget = out_budget;
+    }
+
+    /// The maximum amount of unread bytes on our outgoing pages, see [`Self::set_out_budget`].
+    #[must_use]
+    pub fn out_budget(&self) -> Option<usize> {
+        self.backpressure.out_budget
+    }
+
+    /// Sets what to do with [`LlmpPriority::Low`] messages while over budget
+    pub fn set_backpressure_policy(&mut self, policy: LlmpBackpressurePolicy) {
+        self.backpressure.policy = policy;
+    }
+
+    /// What this sender does with [`LlmpPriority::Low`] messages while over budget
+    #[must_use]
+    pub fn backpressure_policy(&self) -> LlmpBackpressurePolicy {
+        self.backpressure.policy
+    }
+
+    /// The amount of bytes on our outgoing pages the receiver did not read yet.
+    ///
+    /// The receiver reports its progress in batches, see [`LlmpPage::size_read`].
+    #[must_use]
+    pub fn pending_bytes(&self) -> usize {
+        self.out_shmems
+            .iter()
+            .map(|map| unsafe {
+                let page = map.page();
+                if (*page).receivers_left_count.load(Ordering::Relaxed) != 0 {
+                    // The receiver is done with this page
+                    0
+                } else if (*page).receivers_joined_count.load(Ordering::Relaxed) == 0 {
+                    (*page).size_used
+                } else {
+                    (*page)
+                        .size_used
+                        .saturating_sub((*page).size_read.load(Ordering::Relaxed))
+                }
+            })
+            .sum()
+    }
+
+    /// The amount of outgoing pages the receiver did not map yet
+    #[must_use]
+    pub fn pending_pages(&self) -> usize {
+        self.out_shmems
+            .iter()
+            .filter(|map| unsafe {
+                (*map.page()).receivers_joined_count.load(Ordering::Relaxed) == 0
+            })
+            .count()
+    }
+
+    /// If we have more unread bytes on our outgoing pages than our budget allows
+    #[must_use]
+    pub fn is_over_budget(&self) -> bool {
+        self.backpressure
+            .out_budget
+            .is_some_and(|budget| self.pending_bytes() > budget)
+    }
+
+    /// The amount of [`LlmpPriority::Low`] messages dropped because we were over budget
+    #[must_use]
+    pub fn dropped_msgs(&self) -> u64 {
+        self.backpressure.dropped_msgs
+    }
+
+    /// The amount of [`LlmpPriority::Low`] messages superseded by a newer message with the same coalesce key
+    #[must_use]
+    pub fn coalesced_msgs(&self) -> u64 {
+        self.backpressure.coalesced_msgs
+    }
+
+    /// The amount of coalesced messages currently waiting to be sent
+    #[must_use]
+    pub fn coalesced_pending(&self) -> usize {
+        self.backpressure.coalesced.len()
+    }
+
     /// Describe this [`LlmpClient`] in a way that it can be restored later, using [`Self::on_existing_from_description`].
     pub fn describe(&self) -> Result<LlmpDescription, Error> {
         let map = self.out_shmems.last().unwrap();
@@ -1599,6 +1850,10 @@ where
     current_recv_shmem: LlmpSharedMap<SP::ShMem>,
     /// Caches the highest msg id we've seen so far
     highest_msg_id: MessageId,
+    /// If we update [`LlmpPage::size_read`], only done if we are the single reader of the page
+    report_read: bool,
+    /// The amount of messages read since we last updated [`LlmpPage::size_read`]
+    unreported_msgs: usize,
 }
 
 /// Receiving end of an llmp channel
@@ -1645,6 +1900,8 @@ where
             last_msg_recvd,
             shmem_provider,
             highest_msg_id: MessageId(0),
+            report_read: false,
+            unreported_msgs: 0,
             // We don't know the last received time, just assume the current time.
             #[cfg(feature = "std")]
             last_msg_time: current_time(),
@@ -1683,6 +1940,8 @@ where
             Some((*page).messages.as_mut_ptr())
         } else if (*last_msg).message_id == current_msg_id {
             /* Oops! No new message! */
+            // We caught up, let the sender know.
+            self.report_read();
             None
         } else {
             if loaded {
@@ -1743,6 +2002,8 @@ where
                     // Set last msg we received to null (as the map may no longer exist)
                     self.last_msg_recvd = ptr::null();
                     self.highest_msg_id = MessageId(0);
+                    // Leaving the page tells the sender we read all of it
+                    self.unreported_msgs = 0;
 
                     // Mark the old page save to remap.
                     (*page).receiver_left();
@@ -1772,10 +2033,32 @@ where
 
             // Store the last msg for next time
             self.last_msg_recvd = msg;
+
+            if self.report_read {
+                self.unreported_msgs += 1;
+                if self.unreported_msgs >= LLMP_SIZE_READ_INTERVAL {
+                    self.report_read();
+                }
+            }
         };
         Ok(ret)
     }
 
+    /// Lets the sender know how far we read, by updating [`LlmpPage::size_read`],
+    /// if we read any messages since the last update.
+    #[inline]
+    unsafe fn report_read(&mut self) {
+        if self.unreported_msgs == 0 {
+            return;
+        }
+        self.unreported_msgs = 0;
+        let page = self.current_recv_shmem.page_mut();
+        let msg = self.last_msg_recvd;
+        let read_until = msg as usize + size_of::<LlmpMsg>() + (*msg).buf_len_padded as usize
+            - (*page).messages.as_ptr() as usize;
+        (*page).size_read.store(read_until, Ordering::Relaxed);
+    }
+
     /// Blocks/spins until the next message gets posted to the page,
     /// then returns that message.
     /// # Safety
@@ -2061,6 +2344,9 @@ where
     pub exit_cleanly_after: Option<NonZeroUsize>,
     /// Clients that should be removed soon
     clients_to_remove: Vec<ClientId>,
+    /// [`LlmpPriority::Low`] messages, handled after all other messages of the current `broker_once` round:
+    /// `(client_id, broker_id, tag, flags, buf)`
+    deferred_msgs: Vec<(ClientId, BrokerId, Tag, Flags, Vec<u8>)>,
     /// The `ShMemProvider` to use
     shmem_provider: SP,
 }
@@ -2616,6 +2902,10 @@ where
             }
         }
 
+        if !self.inner.deferred_msgs.is_empty() {
+            self.handle_deferred_msgs()?;
+        }
+
         let possible_remove = self.inner.clients_to_remove.len();
         if possible_remove > 0 {
             self.inner.clients_to_remove.sort_unstable();
@@ -2637,6 +2927,36 @@ where
         Ok(new_messages)
     }
 
+    /// Handles the [`LlmpPriority::Low`] messages deferred during this round, in the order they arrived in.
+    fn handle_deferred_msgs(&mut self) -> Result<(), Error> {
+        let mut deferred_msgs = mem::take(&mut self.inner.deferred_msgs);
+        for (client_id, broker_id, mut tag, mut flags, mut buf) in deferred_msgs.drain(..) {
+            let mut new_msgs: Vec<(Tag, Flags, Vec<u8>)> = Vec::new();
+            if let LlmpMsgHookResult::ForwardToClients = self.hooks.on_new_message_all(
+                &mut self.inner,
+                client_id,
+                &mut tag,
+                &mut flags,
+                &mut buf,
+                &mut new_msgs,
+            )? {
+                self.inner
+                    .forward_buf(client_id, broker_id, tag, flags, &buf)?;
+            }
+
+            for (new_msg_tag, new_msg_flag, new_msg) in new_msgs {
+                self.inner.llmp_out.send_buf_with_flags(
+                    new_msg_tag,
+                    new_msg_flag,
+                    new_msg.as_ref(),
+                )?;
+            }
+        }
+        // Keep the allocation around for the next round
+        self.inner.deferred_msgs = deferred_msgs;
+        Ok(())
+    }
+
     /// Broker broadcast to its own page for all others to read
     /// Returns `true` if new messages were broker-ed
     /// It is supposed that the message is never unmapped.
@@ -2732,6 +3052,8 @@ where
                                 last_msg_recvd: ptr::null_mut(),
                                 shmem_provider: self.inner.shmem_provider.clone(),
                                 highest_msg_id: MessageId(0),
+                                report_read: false,
+                                unreported_msgs: 0,
                                 // We don't know the last received time, just assume the current time.
                                 #[cfg(feature = "std")]
                                 last_msg_time: current_time(),
@@ -2763,6 +3085,18 @@ where
                     let map = &mut self.inner.llmp_clients[pos].current_recv_shmem;
                     let msg_buf = (*msg).try_as_slice_mut(map)?;
 
+                    // Low priority messages (such as stats) must not delay the others of this round.
+                    if LlmpPriority::from_flags((*msg).flags) == LlmpPriority::Low {
+                        self.inner.deferred_msgs.push((
+                            client_id,
+                            (*msg).broker,
+                            (*msg).tag,
+                            (*msg).flags,
+                            msg_buf.to_vec(),
+                        ));
+                        continue;
+                    }
+
                     // The message is not specifically for use. Let the user handle it, then forward it to the clients, if necessary.
                     let mut new_msgs: Vec<(Tag, Flags, Vec<u8>)> = Vec::new();
                     if let LlmpMsgHookResult::ForwardToClients = self.hooks.on_new_message_all(
@@ -2838,11 +3172,13 @@ where
                 )],
                 keep_pages_forever,
                 has_unsent_message: false,
+                backpressure: LlmpBackpressure::default(),
                 shmem_provider: shmem_provider.clone(),
                 unused_shmem_cache: vec![],
             },
             llmp_clients: vec![],
             clients_to_remove: Vec::new(),
+            deferred_msgs: Vec::new(),
             listeners: vec![],
             exit_cleanly_after: None,
             num_clients_seen: 0,
@@ -2905,6 +3241,8 @@ where
     pub fn add_client(&mut self, mut client_receiver: LlmpReceiver<SP>) -> ClientId {
         let id = self.peek_next_client_id();
         client_receiver.id = id;
+        // We are the only reader of the client's pages
+        client_receiver.report_read = true;
         self.llmp_clients.push(client_receiver);
         self.num_clients_seen += 1;
         id
@@ -2928,6 +3266,8 @@ where
             last_msg_recvd: ptr::null_mut(),
             shmem_provider: self.shmem_provider.clone(),
             highest_msg_id: MessageId(0),
+            report_read: false,
+            unreported_msgs: 0,
             // We don't know the last received time, just assume the current time.
             #[cfg(feature = "std")]
             last_msg_time: current_time(),
@@ -3023,6 +3363,27 @@ where
         Ok(())
     }
 
+    /// For internal use: Forward a copied message to the out map, keeping its original sender.
+    fn forward_buf(
+        &mut self,
+        sender: ClientId,
+        broker: BrokerId,
+        tag: Tag,
+        flags: Flags,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        unsafe {
+            let out: *mut LlmpMsg = self.alloc_next(buf.len())?;
+            (*out).tag = tag;
+            (*out).flags = flags;
+            (*out).sender = sender;
+            (*out).broker = broker;
+            buf.as_ptr()
+                .copy_to_nonoverlapping((*out).buf.as_mut_ptr(), buf.len());
+            self.llmp_out.send(out, false)
+        }
+    }
+
     /// Internal function, returns true when shuttdown is requested by a `SIGINT` signal
     #[inline]
     #[cfg(any(unix, all(windows, feature = "std")))]
@@ -3361,6 +3722,7 @@ where
                 // drop pages to the broker, if it already read them.
                 keep_pages_forever: false,
                 has_unsent_message: false,
+                backpressure: LlmpBackpressure::default(),
                 shmem_provider: shmem_provider_bg.clone(),
                 unused_shmem_cache: vec![],
             };
@@ -3583,6 +3945,7 @@ where
                 // drop pages to the broker if it already read them
                 keep_pages_forever: false,
                 has_unsent_message: false,
+                backpressure: LlmpBackpressure::default(),
                 shmem_provider: shmem_provider.clone(),
                 unused_shmem_cache: vec![],
             },
@@ -3593,6 +3956,8 @@ where
                 last_msg_recvd: ptr::null_mut(),
                 shmem_provider,
                 highest_msg_id: MessageId(0),
+                report_read: false,
+                unreported_msgs: 0,
                 // We don't know the last received time, just assume the current time.
                 #[cfg(feature = "std")]
                 last_msg_time: current_time(),
@@ -3628,6 +3993,20 @@ where
         self.sender.send_buf_with_flags(tag, flags, buf)
     }
 
+    /// Sends a `buf` with the given [`LlmpPriority`], see [`LlmpSender::send_buf_prioritized`].
+    /// Returns `true` if the message got sent right away.
+    pub fn send_buf_prioritized(
+        &mut self,
+        tag: Tag,
+        flags: Flags,
+        priority: LlmpPriority,
+        coalesce_key: Option<u64>,
+        buf: &[u8],
+    ) -> Result<bool, Error> {
+        self.sender
+            .send_buf_prioritized(tag, flags, priority, coalesce_key, buf)
+    }
+
     /// A client receives a broadcast message.
     /// Returns null if no message is availiable
     /// # Safety
@@ -3764,14 +4143,15 @@ where
 #[cfg(all(unix, feature = "std", not(target_os = "haiku")))]
 mod tests {
 
+    use alloc::vec::Vec;
     use std::{thread::sleep, time::Duration};
 
     use serial_test::serial;
 
     use super::{
-        LlmpClient,
+        ClientId, LlmpBackpressurePolicy, LlmpBroker, LlmpClient,
         LlmpConnection::{self, IsBroker, IsClient},
-        Tag,
+        LlmpPriority, LlmpReceiver, LlmpSender, LlmpSharedMap, Tag, LLMP_FLAG_INITIALIZED,
     };
     use crate::shmem::{ShMemProvider, StdShMemProvider};
 
@@ -3823,4 +4203,93 @@ mod tests {
         // We want at least the tcp and sender clients.
         assert_eq!(broker.inner.llmp_clients.len(), 2);
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    pub fn test_llmp_backpressure() {
+        let shmem_provider = StdShMemProvider::new().unwrap();
+        let mut sender = LlmpSender::new(shmem_provider, ClientId(0), false).unwrap();
+        let tag = Tag(0x1337);
+
+        // Nobody reads our page, so anything we send counts against the budget.
+        sender.set_out_budget(Some(0));
+        sender.set_backpressure_policy(LlmpBackpressurePolicy::Coalesce);
+        assert!(sender
+            .send_buf_prioritized(tag, LLMP_FLAG_INITIALIZED, LlmpPriority::Low, Some(1), &[0])
+            .unwrap());
+        assert!(sender.is_over_budget());
+
+        // Low priority messages get coalesced per key, or dropped without a key
+        for i in 1..=3 {
+            assert!(!sender
+                .send_buf_prioritized(tag, LLMP_FLAG_INITIALIZED, LlmpPriority::Low, Some(1), &[i])
+                .unwrap());
+        }
+        assert!(!sender
+            .send_buf_prioritized(tag, LLMP_FLAG_INITIALIZED, LlmpPriority::Low, None, &[4])
+            .unwrap());
+        assert_eq!(sender.coalesced_pending(), 1);
+        assert_eq!(sender.coalesced_msgs(), 2);
+        assert_eq!(sender.dropped_msgs(), 1);
+
+        // Everything else always goes out
+        assert!(sender
+            .send_buf_prioritized(tag, LLMP_FLAG_INITIALIZED, LlmpPriority::High, None, &[5])
+            .unwrap());
+        assert_eq!(
+            LlmpPriority::from_flags(LlmpPriority::High.flags()),
+            LlmpPriority::High
+        );
+
+        sender.set_out_budget(None);
+        assert_eq!(sender.flush_coalesced().unwrap(), 1);
+        assert_eq!(sender.coalesced_pending(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    pub fn test_llmp_broker_priorities() {
+        let shmem_provider = StdShMemProvider::new().unwrap();
+        let mut broker = LlmpBroker::new(shmem_provider.clone(), ()).unwrap();
+        let mut out = LlmpReceiver::on_existing_from_description(
+            shmem_provider.clone(),
+            &broker.inner.llmp_out.describe().unwrap(),
+        )
+        .unwrap();
+
+        let mut senders = Vec::new();
+        for _ in 0..2 {
+            let mut shmem_provider = shmem_provider.clone();
+            let sender = LlmpSender::new(shmem_provider.clone(), ClientId(0), false).unwrap();
+            broker.inner.register_client(LlmpSharedMap::existing(
+                shmem_provider
+                    .shmem_from_description(sender.describe().unwrap().shmem)
+                    .unwrap(),
+            ));
+            senders.push(sender);
+        }
+
+        let tag = Tag(0x1337);
+        for (sender, bufs) in senders.iter_mut().zip([[0, 1, 2], [3, 4, 5]]) {
+            for (priority, buf) in [LlmpPriority::Low, LlmpPriority::Normal, LlmpPriority::High]
+                .into_iter()
+                .zip(bufs)
+            {
+                sender
+                    .send_buf_prioritized(tag, LLMP_FLAG_INITIALIZED, priority, None, &[buf])
+                    .unwrap();
+            }
+        }
+        broker.broker_once().unwrap();
+        // The broker caught up with both clients
+        broker.broker_once().unwrap();
+        assert!(senders.iter().all(|sender| sender.pending_bytes() == 0));
+
+        // The high and normal priority messages as they arrived, then the low ones of all clients
+        let mut forwarded = Vec::new();
+        while let Some((_, _, buf)) = out.recv_buf().unwrap() {
+            forwarded.push(buf[0]);
+        }
+        assert_eq!(forwarded, [1, 2, 4, 5, 0, 3]);
+    }
 }
@@ -137,6 +137,60 @@ pub fn peak_rss_mb_child_processes() -> Result<i64, Error> {
     Ok(rss.ru_maxrss >> 10)
 }
 
+/// Get the current rss (Resident Set Size) of this process in bytes, from `/proc/self/statm`
+///
+/// This does not allocate, so that it can be polled from a watchdog thread.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn current_rss() -> Result<u64, Error> {
+    statm_rss("/proc/self/statm")
+}
+
+/// Get the current rss (Resident Set Size) of the process `pid` in bytes, from `/proc/<pid>/statm`
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn process_rss(pid: i32) -> Result<u64, Error> {
+    statm_rss(&format!("/proc/{pid}/statm"))
+}
+
+/// Read the rss in bytes from a `statm` file
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn statm_rss(path: &str) -> Result<u64, Error> {
+    use std::io::Read;
+
+    let mut statm = [0_u8; 128];
+    let len = File::open(path)?.read(&mut statm)?;
+    // The second field is the number of resident pages
+    let pages = statm[..len]
+        .split(|c| *c == b' ')
+        .nth(1)
+        .and_then(|pages| core::str::from_utf8(pages).ok())
+        .and_then(|pages| pages.parse::<u64>().ok())
+        .ok_or_else(|| Error::illegal_state(format!("Could not parse {path}")))?;
+    #[allow(clippy::cast_sign_loss)]
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    Ok(pages * page_size)
+}
+
+/// Get the peak rss (Resident Set Size) of this process in bytes, from `VmHWM` in `/proc/self/status`
+///
+/// The peak can be reset with [`reset_peak_rss`].
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn peak_rss() -> Result<u64, Error> {
+    let status = std::fs::read_to_string("/proc/self/status")?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+        .map(|kb| kb << 10)
+        .ok_or_else(|| Error::illegal_state("Could not parse VmHWM in /proc/self/status"))
+}
+
+/// Reset the peak rss of this process to the current rss, see [`peak_rss`]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn reset_peak_rss() -> Result<(), Error> {
+    std::fs::write("/proc/self/clear_refs", "5")?;
+    Ok(())
+}
+
 /// "Safe" wrapper around dup2
 ///
 /// # Safety
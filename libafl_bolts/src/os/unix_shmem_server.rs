@@ -472,6 +472,14 @@ where
     }
 }
 
+/// The fd of a map of the server, which is its [`ShMemId`]
+fn server_fd(id: ShMemId) -> Result<RawFd, Error> {
+    let id_str = id.as_str();
+    id_str
+        .parse()
+        .map_err(|_| Error::illegal_state(format!("ShMemId {id_str} is not an fd")))
+}
+
 /// The struct for the worker, handling incoming requests for [`ShMem`].
 #[allow(clippy::type_complexity)]
 struct ServedShMemServiceWorker<SP>
@@ -549,7 +557,7 @@ where
                 let description = new_shmem.description();
                 let new_rc = Rc::new(RefCell::new(new_shmem));
                 self.all_shmems
-                    .insert(description.id.into(), Rc::downgrade(&new_rc));
+                    .insert(server_fd(description.id)?, Rc::downgrade(&new_rc));
                 Ok(ServedShMemResponse::Mapping(new_rc))
             }
             ServedShMemRequest::ExistingMap(description) => {
@@ -633,11 +641,11 @@ where
         match response {
             ServedShMemResponse::Mapping(mapping) => {
                 let id = mapping.as_ref().borrow().id();
-                let server_fd: i32 = id.to_string().parse().unwrap();
+                let server_fd = server_fd(id)?;
                 let client = self.clients.get_mut(&client_id).unwrap();
                 client
                     .stream
-                    .send_fds(id.to_string().as_bytes(), &[server_fd])?;
+                    .send_fds(server_fd.to_string().as_bytes(), &[server_fd])?;
                 client.maps.entry(server_fd).or_default().push(mapping);
             }
             ServedShMemResponse::Id(id) => {
@@ -751,6 +759,45 @@ where
     }
 }
 
+#[cfg(test)]
+mod served_tests {
+    use serial_test::serial;
+
+    use super::server_fd;
+    use crate::shmem::ShMemId;
+
+    #[test]
+    fn test_server_fd() {
+        assert_eq!(server_fd(ShMemId::from_string("42")).unwrap(), 42);
+        assert!(server_fd(ShMemId::from_string("1234:42")).is_err());
+        assert!(server_fd(ShMemId::from_string("")).is_err());
+    }
+
+    /// Pass memfds to the clients of the served provider
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(target_os = "linux")]
+    fn test_served_memfd_shmem() {
+        use crate::shmem::{MemfdShMemProvider, ServedShMemProvider, ShMem, ShMemProvider};
+
+        let mut provider = ServedShMemProvider::<MemfdShMemProvider>::new().unwrap();
+        let mut map = provider.new_shmem(1000).unwrap();
+        assert_eq!(map.len(), 1000);
+        map[999] = 42;
+
+        // Clients map the fds they receive, other than the ones of the server
+        let other = provider.shmem_from_description(map.description()).unwrap();
+        assert_eq!(other[999], 42);
+        assert_eq!(map.server_fd, other.server_fd);
+        assert_ne!(map.inner.fd(), other.inner.fd());
+
+        let mut forked = provider.clone();
+        let from_other_client = forked.shmem_from_description(map.description()).unwrap();
+        assert_eq!(from_other_client[999], 42);
+    }
+}
+
 /*
 TODO: Fix test
 
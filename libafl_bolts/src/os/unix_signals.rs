@@ -259,7 +259,7 @@ use libc::{
 pub use libc::{c_void, siginfo_t};
 #[cfg(feature = "alloc")]
 use libc::{
-    malloc, sigaction, sigaddset, sigaltstack, sigemptyset, stack_t, SA_NODEFER, SA_ONSTACK,
+    free, malloc, sigaction, sigaddset, sigaltstack, sigemptyset, stack_t, SA_NODEFER, SA_ONSTACK,
     SA_SIGINFO,
 };
 use num_enum::{IntoPrimitive, TryFromPrimitive};
@@ -440,6 +440,60 @@ unsafe fn handle_signal(sig: c_int, info: *mut siginfo_t, void: *mut c_void) {
     );
 }
 
+/// An alternate signal stack of the size the handlers of [`setup_signal_handler`] need, for the current thread.
+///
+/// Signal handlers run on the alternate signal stack of the thread the signal arrives on.
+/// [`setup_signal_handler`] only sets it up for the calling thread, and the one the Rust standard library
+/// sets up for the threads it spawns is too small for the `LibAFL` handlers. Threads that may crash,
+/// e.g. the worker threads of a harness, need to install one when they start, and keep it while they run.
+/// The previous alternate signal stack is restored when dropped.
+#[cfg(feature = "alloc")]
+pub struct SignalStack {
+    stack: *mut c_void,
+    previous: stack_t,
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for SignalStack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignalStack")
+            .field("stack", &self.stack)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SignalStack {
+    /// Install a new alternate signal stack for the current thread
+    pub fn install() -> Result<Self, Error> {
+        unsafe {
+            let stack = malloc(SIGNAL_STACK_SIZE);
+            if stack.is_null() {
+                return Err(Error::unknown("Failed to allocate the signal stack"));
+            }
+            let mut ss: stack_t = mem::zeroed();
+            ss.ss_size = SIGNAL_STACK_SIZE;
+            ss.ss_sp = stack;
+            let mut previous: stack_t = mem::zeroed();
+            if sigaltstack(addr_of_mut!(ss), addr_of_mut!(previous)) < 0 {
+                free(stack);
+                return Err(Error::unknown("Could not set up the signal stack"));
+            }
+            Ok(Self { stack, previous })
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Drop for SignalStack {
+    fn drop(&mut self) {
+        unsafe {
+            sigaltstack(addr_of_mut!(self.previous), ptr::null_mut());
+            free(self.stack);
+        }
+    }
+}
+
 /// Setup signal handlers in a somewhat rusty way.
 /// This will allocate a signal stack and set the signal handlers accordingly.
 /// It is, for example, used in `LibAFL's` `InProcessExecutor` to restart the fuzzer in case of a crash,
@@ -448,6 +502,7 @@ unsafe fn handle_signal(sig: c_int, info: *mut siginfo_t, void: *mut c_void) {
 /// # Safety
 ///
 /// The signal handlers will be called on any signal. They should (tm) be async safe.
+/// Other threads the handlers may run on need a [`SignalStack`].
 /// The handler pointer will be dereferenced, and the data the pointer points to may therefore not move.
 /// A lot can go south in signal handling. Be sure you know what you are doing.
 #[cfg(feature = "alloc")]
@@ -527,3 +582,41 @@ pub fn ucontext() -> Result<ucontext_t, Error> {
         Ok(ucontext)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::{mem, ptr};
+
+    use libc::{sigaltstack, stack_t};
+
+    use super::{SignalStack, SIGNAL_STACK_SIZE};
+
+    /// The alternate signal stack of the current thread
+    fn current_signal_stack() -> stack_t {
+        unsafe {
+            let mut ss: stack_t = mem::zeroed();
+            assert_eq!(sigaltstack(ptr::null(), &mut ss), 0);
+            ss
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_signal_stack() {
+        std::thread::spawn(|| {
+            let previous = current_signal_stack();
+
+            let signal_stack = SignalStack::install().unwrap();
+            let ss = current_signal_stack();
+            assert_eq!(ss.ss_sp, signal_stack.stack);
+            assert_eq!(ss.ss_size, SIGNAL_STACK_SIZE);
+
+            drop(signal_stack);
+            let ss = current_signal_stack();
+            assert_eq!(ss.ss_sp, previous.ss_sp);
+            assert_eq!(ss.ss_size, previous.ss_size);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -18,6 +18,8 @@ use std::io::Read;
 use std::io::Write;
 
 use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use unix_shmem::memfd::{MemfdShMem, MemfdShMemProvider};
 #[cfg(all(
     feature = "std",
     unix,
@@ -1305,6 +1307,418 @@ pub mod unix_shmem {
             }
         }
     }
+
+    /// Module containing `memfd_create` shared memory support.
+    ///
+    /// The maps are anonymous files: they are sealed against resizing, and vanish with the
+    /// last process mapping them, so crashed campaigns do not leave `SysV` segments or
+    /// `/dev/shm` files behind.
+    #[cfg(all(target_os = "linux", feature = "std"))]
+    pub mod memfd {
+        use core::{
+            mem::MaybeUninit,
+            ops::{Deref, DerefMut},
+            ptr, slice,
+        };
+        use std::{env, ffi::CString, fs, process};
+
+        use libc::{
+            c_int, c_void, close, fcntl, fstat, ftruncate, madvise, memfd_create, mmap, munmap,
+            F_ADD_SEALS, F_GET_SEALS, F_SEAL_GROW, F_SEAL_SEAL, F_SEAL_SHRINK, MADV_HUGEPAGE,
+            MAP_SHARED, MFD_ALLOW_SEALING, MFD_CLOEXEC, MFD_HUGETLB, PROT_READ, PROT_WRITE,
+        };
+
+        use crate::{
+            shmem::{ShMem, ShMemId, ShMemProvider},
+            Error,
+        };
+
+        /// Env variable to select the [`HugePages`] of the [`MemfdShMemProvider`]:
+        /// `thp` for transparent huge pages, or `hugetlb` for explicit huge pages.
+        pub const LIBAFL_SHMEM_HUGEPAGES: &str = "LIBAFL_SHMEM_HUGEPAGES";
+
+        /// Maps smaller than this do not use huge pages by default
+        pub const HUGE_PAGES_MIN_SIZE_DEFAULT: usize = 2 << 20;
+
+        /// The huge page size, if `/proc/meminfo` does not tell us
+        const HUGE_PAGE_SIZE_DEFAULT: usize = 2 << 20;
+
+        /// The huge pages backing a [`MemfdShMem`]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub enum HugePages {
+            /// Regular pages
+            #[default]
+            None,
+            /// Advise the kernel to use transparent huge pages (THP), with `madvise`.
+            /// Requires `shmem_enabled` in `/sys/kernel/mm/transparent_hugepage` to be `advise` or `always`.
+            Transparent,
+            /// Explicit huge pages (`MFD_HUGETLB`), from the pool configured in `/proc/sys/vm/nr_hugepages`.
+            /// Falls back to [`HugePages::Transparent`] if the pool is exhausted.
+            HugeTlb,
+        }
+
+        impl HugePages {
+            /// Read the huge pages from the [`LIBAFL_SHMEM_HUGEPAGES`] env variable
+            #[must_use]
+            pub fn from_env() -> Self {
+                match env::var(LIBAFL_SHMEM_HUGEPAGES).as_deref() {
+                    Ok("thp" | "transparent") => Self::Transparent,
+                    Ok("hugetlb") => Self::HugeTlb,
+                    _ => Self::None,
+                }
+            }
+        }
+
+        /// The size of explicit huge pages, from `/proc/meminfo`
+        fn huge_page_size() -> usize {
+            fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| {
+                    meminfo
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Hugepagesize:"))
+                        .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse().ok())
+                })
+                .map_or(HUGE_PAGE_SIZE_DEFAULT, |kb: usize| kb << 10)
+        }
+
+        /// A `memfd_create` based shared map for linux.
+        ///
+        /// Its id is the fd of the memfd, which is only valid in the process owning it.
+        /// Other processes receive the fd from the [`crate::shmem::ShMemService`] over a unix socket,
+        /// see [`MemfdShMemProvider`].
+        #[derive(Clone, Debug)]
+        pub struct MemfdShMem {
+            id: ShMemId,
+            map: *mut u8,
+            map_size: usize,
+            /// The size of the mapping, rounded up to the page size of the memfd
+            mapping_size: usize,
+            fd: c_int,
+        }
+
+        impl MemfdShMem {
+            /// Create a new sealed memfd of `map_size` bytes, and map it.
+            pub fn new(map_size: usize, huge_pages: HugePages) -> Result<Self, Error> {
+                if huge_pages == HugePages::HugeTlb {
+                    match Self::create(map_size, true) {
+                        Ok(shmem) => return Ok(shmem),
+                        Err(err) => log::warn!(
+                            "Could not allocate {map_size} bytes of explicit huge pages, falling back to THP: {err}"
+                        ),
+                    }
+                }
+                let shmem = Self::create(map_size, false)?;
+                if huge_pages != HugePages::None {
+                    shmem.advise_huge_pages();
+                }
+                Ok(shmem)
+            }
+
+            fn create(map_size: usize, hugetlb: bool) -> Result<Self, Error> {
+                let name = CString::new(format!("libafl_{}", process::id())).unwrap();
+                let mut flags = MFD_CLOEXEC | MFD_ALLOW_SEALING;
+                if hugetlb {
+                    flags |= MFD_HUGETLB;
+                }
+
+                unsafe {
+                    let fd = memfd_create(name.as_ptr(), flags);
+                    if fd == -1 {
+                        return Err(Error::last_os_error("Failed to create a memfd"));
+                    }
+
+                    let page_size = if hugetlb {
+                        huge_page_size()
+                    } else {
+                        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                        let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+                        page_size
+                    };
+                    let mapping_size = (map_size.max(1) + page_size - 1) / page_size * page_size;
+
+                    if ftruncate(fd, mapping_size.try_into()?) != 0 {
+                        let err = Error::last_os_error(format!(
+                            "Failed to resize the memfd to {mapping_size} bytes"
+                        ));
+                        close(fd);
+                        return Err(err);
+                    }
+
+                    // Nobody may shrink the map under our feet, which would SIGBUS all users
+                    if fcntl(fd, F_ADD_SEALS, F_SEAL_SHRINK | F_SEAL_GROW | F_SEAL_SEAL) != 0 {
+                        let err = Error::last_os_error("Failed to seal the memfd");
+                        close(fd);
+                        return Err(err);
+                    }
+
+                    let map = mmap(
+                        ptr::null_mut(),
+                        mapping_size,
+                        PROT_READ | PROT_WRITE,
+                        MAP_SHARED,
+                        fd,
+                        0,
+                    );
+                    if map == libc::MAP_FAILED || map.is_null() {
+                        let err = Error::last_os_error(format!(
+                            "Failed to map the memfd of {mapping_size} bytes"
+                        ));
+                        close(fd);
+                        return Err(err);
+                    }
+
+                    Ok(Self {
+                        id: ShMemId::from_string(&format!("{fd}")),
+                        map: map as *mut u8,
+                        map_size,
+                        mapping_size,
+                        fd,
+                    })
+                }
+            }
+
+            /// Map an existing memfd by its fd, taking ownership of the fd.
+            ///
+            /// The fd needs to be a memfd this process owns, e.g. one received from the
+            /// [`crate::shmem::ShMemService`].
+            pub fn shmem_from_id_and_size(
+                id: ShMemId,
+                map_size: usize,
+                huge_pages: HugePages,
+            ) -> Result<Self, Error> {
+                let id_str = id.as_str();
+                let fd: c_int = id_str
+                    .parse()
+                    .map_err(|_| Error::illegal_argument(format!("Invalid memfd id {id_str}")))?;
+                // Ids of other processes are not valid here, or refer to other files
+                let seals = unsafe { fcntl(fd, F_GET_SEALS) };
+                if seals == -1 || seals & F_SEAL_SEAL == 0 {
+                    return Err(Error::illegal_argument(format!(
+                        "The id {id_str} is no memfd of this process. Maps of the MemfdShMemProvider can only be shared through a ServedShMemProvider"
+                    )));
+                }
+
+                unsafe {
+                    let mut stat = MaybeUninit::<libc::stat>::uninit();
+                    if fstat(fd, stat.as_mut_ptr()) != 0 {
+                        let err =
+                            Error::last_os_error(format!("Failed to stat the memfd {id_str}"));
+                        close(fd);
+                        return Err(err);
+                    }
+                    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                    let mapping_size = stat.assume_init().st_size as usize;
+                    if mapping_size < map_size {
+                        close(fd);
+                        return Err(Error::illegal_argument(format!(
+                            "The memfd {id_str} has {mapping_size} bytes, but {map_size} were requested"
+                        )));
+                    }
+
+                    let map = mmap(
+                        ptr::null_mut(),
+                        mapping_size,
+                        PROT_READ | PROT_WRITE,
+                        MAP_SHARED,
+                        fd,
+                        0,
+                    );
+                    if map == libc::MAP_FAILED || map.is_null() {
+                        let err = Error::last_os_error(format!("Failed to map the memfd {id_str}"));
+                        close(fd);
+                        return Err(err);
+                    }
+
+                    let shmem = Self {
+                        id,
+                        map: map as *mut u8,
+                        map_size,
+                        mapping_size,
+                        fd,
+                    };
+                    if huge_pages != HugePages::None {
+                        shmem.advise_huge_pages();
+                    }
+                    Ok(shmem)
+                }
+            }
+
+            /// The file descriptor of the memfd in this process
+            #[must_use]
+            pub fn fd(&self) -> c_int {
+                self.fd
+            }
+
+            fn advise_huge_pages(&self) {
+                if unsafe { madvise(self.map as *mut c_void, self.mapping_size, MADV_HUGEPAGE) }
+                    != 0
+                {
+                    log::debug!(
+                        "Could not advise transparent huge pages for the memfd {}",
+                        self.id
+                    );
+                }
+            }
+        }
+
+        impl ShMem for MemfdShMem {
+            fn id(&self) -> ShMemId {
+                self.id
+            }
+        }
+
+        impl Deref for MemfdShMem {
+            type Target = [u8];
+
+            fn deref(&self) -> &[u8] {
+                unsafe { slice::from_raw_parts(self.map, self.map_size) }
+            }
+        }
+
+        impl DerefMut for MemfdShMem {
+            fn deref_mut(&mut self) -> &mut [u8] {
+                unsafe { slice::from_raw_parts_mut(self.map, self.map_size) }
+            }
+        }
+
+        /// [`Drop`] implementation for [`MemfdShMem`], which unmaps and closes the memfd.
+        /// The memory is freed once no process maps it anymore.
+        impl Drop for MemfdShMem {
+            fn drop(&mut self) {
+                unsafe {
+                    munmap(self.map as *mut c_void, self.mapping_size);
+                    close(self.fd);
+                }
+            }
+        }
+
+        /// A [`ShMemProvider`] which uses `memfd_create` to provide shared memory mappings.
+        ///
+        /// The ids of its maps are fds, so it is only usable in a [`crate::shmem::ServedShMemProvider`],
+        /// which passes the fds to other processes over unix sockets, like the ashmem maps on Android.
+        /// Used directly, mapping an id in another process fails.
+        /// The [`LIBAFL_SHMEM_HUGEPAGES`] env variable selects the huge pages.
+        #[derive(Clone, Debug)]
+        pub struct MemfdShMemProvider {
+            huge_pages: HugePages,
+            huge_pages_min_size: usize,
+        }
+
+        unsafe impl Send for MemfdShMemProvider {}
+
+        impl Default for MemfdShMemProvider {
+            fn default() -> Self {
+                Self::new().unwrap()
+            }
+        }
+
+        impl MemfdShMemProvider {
+            /// Set the huge pages backing new maps, overriding the [`LIBAFL_SHMEM_HUGEPAGES`] env variable.
+            #[must_use]
+            pub fn with_huge_pages(mut self, huge_pages: HugePages) -> Self {
+                self.huge_pages = huge_pages;
+                self
+            }
+
+            /// Only back maps of at least this size with huge pages, 2 MiB by default.
+            #[must_use]
+            pub fn with_huge_pages_min_size(mut self, huge_pages_min_size: usize) -> Self {
+                self.huge_pages_min_size = huge_pages_min_size;
+                self
+            }
+
+            fn huge_pages_for(&self, map_size: usize) -> HugePages {
+                if map_size < self.huge_pages_min_size {
+                    HugePages::None
+                } else {
+                    self.huge_pages
+                }
+            }
+        }
+
+        /// Implement [`ShMemProvider`] for [`MemfdShMemProvider`].
+        impl ShMemProvider for MemfdShMemProvider {
+            type ShMem = MemfdShMem;
+
+            fn new() -> Result<Self, Error> {
+                Ok(Self {
+                    huge_pages: HugePages::from_env(),
+                    huge_pages_min_size: HUGE_PAGES_MIN_SIZE_DEFAULT,
+                })
+            }
+
+            fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
+                MemfdShMem::new(map_size, self.huge_pages_for(map_size))
+            }
+
+            fn shmem_from_id_and_size(
+                &mut self,
+                id: ShMemId,
+                size: usize,
+            ) -> Result<Self::ShMem, Error> {
+                MemfdShMem::shmem_from_id_and_size(id, size, self.huge_pages_for(size))
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use core::mem::MaybeUninit;
+            use std::{fs, os::fd::AsRawFd};
+
+            use serial_test::serial;
+
+            use super::{huge_page_size, HugePages, MemfdShMem, MemfdShMemProvider};
+            use crate::shmem::{ShMem, ShMemId, ShMemProvider};
+
+            #[test]
+            #[serial]
+            #[cfg_attr(miri, ignore)]
+            fn test_memfd_shmem() {
+                let mut provider = MemfdShMemProvider::new()
+                    .unwrap()
+                    .with_huge_pages(HugePages::Transparent);
+                let mut map = provider.new_shmem(1000).unwrap();
+                assert_eq!(map.len(), 1000);
+                map[999] = 42;
+                assert_eq!(map.id().as_str(), format!("{}", map.fd()));
+
+                // The map is sealed against resizing
+                assert_ne!(unsafe { libc::ftruncate(map.fd(), 1 << 20) }, 0);
+
+                // Other files are no maps
+                let file = fs::File::open("/proc/self/stat").unwrap();
+                let id = ShMemId::from_string(&format!("{}", file.as_raw_fd()));
+                assert!(provider.shmem_from_id_and_size(id, 1000).is_err());
+            }
+
+            #[test]
+            #[serial]
+            #[cfg_attr(miri, ignore)]
+            fn test_memfd_hugetlb_fallback() {
+                // More explicit huge pages than are free, so they can not be mapped
+                let free_huge_pages: usize = fs::read_to_string("/proc/meminfo")
+                    .unwrap()
+                    .lines()
+                    .find_map(|line| line.strip_prefix("HugePages_Free:"))
+                    .map_or(0, |free| free.trim().parse().unwrap());
+                let map_size = (free_huge_pages + 1) * huge_page_size();
+
+                let mut map = MemfdShMem::new(map_size, HugePages::HugeTlb).unwrap();
+                assert_eq!(map.len(), map_size);
+                map[map_size - 1] = 42;
+
+                // Backed by regular (transparent huge) pages instead of hugetlbfs
+                let mut stat = MaybeUninit::<libc::stat>::uninit();
+                assert_eq!(unsafe { libc::fstat(map.fd(), stat.as_mut_ptr()) }, 0);
+                #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                let block_size = unsafe { stat.assume_init() }.st_blksize as usize;
+                #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+                assert_eq!(block_size, page_size);
+            }
+        }
+    }
 }
 
 /// Then `win32` implementation for shared memory.
@@ -163,7 +163,10 @@ impl FridaRuntime for AsanRuntime {
     ) {
         self.allocator.init();
 
-        AsanErrors::get_mut_blocking().set_continue_on_error(self.continue_on_error);
+        let mut errors = AsanErrors::get_mut_blocking();
+        errors.set_continue_on_error(self.continue_on_error);
+        errors.set_shadow_bit(self.allocator.shadow_bit() as usize);
+        drop(errors);
 
         self.module_map = Some(module_map.clone());
         self.suppressed_addresses
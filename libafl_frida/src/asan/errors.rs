@@ -16,9 +16,12 @@ use libafl::{
     corpus::Testcase,
     events::EventFirer,
     executors::ExitKind,
-    feedbacks::Feedback,
+    feedbacks::{AsanReportMetadata, Feedback},
     inputs::{HasTargetBytes, UsesInput},
-    observers::{Observer, ObserversTuple},
+    observers::{
+        asan::{AsanAccessKind, AsanBug, AsanChunkInfo, AsanShadowWindow, AsanStackFrame},
+        AsanReport, Observer, ObserversTuple,
+    },
     state::State,
     Error, HasMetadata,
 };
@@ -90,7 +93,227 @@ pub(crate) enum AsanError {
     BadFuncArgWrite((String, usize, usize, usize, Backtrace)),
 }
 
+/// Symbolize `backtrace` into frames of an [`AsanReport`], leaving out the frames of the runtime
+fn report_frames(backtrace: &Backtrace) -> Vec<AsanStackFrame> {
+    let mut backtrace = backtrace.clone();
+    backtrace.resolve();
+    let mut frames = vec![];
+    for frame in backtrace.frames() {
+        let pc = frame.ip() as u64;
+        let symbol = frame.symbols().first();
+        let function = symbol.and_then(|s| s.name()).map(|name| name.to_string());
+        if matches!(&function, Some(name) if name.starts_with("libafl_frida::")) {
+            continue;
+        }
+        let mut report_frame = report_frame(pc);
+        report_frame.function = function;
+        if let Some(symbol) = symbol {
+            report_frame.file = symbol.filename().map(|f| f.display().to_string());
+            report_frame.line = symbol.lineno();
+            report_frame.column = symbol.colno();
+        }
+        frames.push(report_frame);
+    }
+    frames
+}
+
+/// An unsymbolized frame of an [`AsanReport`], with its module and module offset
+fn report_frame(pc: u64) -> AsanStackFrame {
+    let mut frame = AsanStackFrame::new(pc);
+    if let Some(module_details) = ModuleDetails::with_address(pc) {
+        frame.module_offset = pc - module_details.range().base_address().0 as u64;
+        frame.module = Some(module_details.path());
+    }
+    frame
+}
+
+/// The frame and stack pointers among the saved registers
+fn frame_registers(registers: &[usize; ASAN_SAVE_REGISTER_COUNT]) -> (u64, u64) {
+    #[cfg(target_arch = "x86_64")]
+    let (bp, sp) = (registers[4], registers[5]);
+    #[cfg(target_arch = "aarch64")]
+    let (bp, sp) = (registers[29], registers[31]);
+    (bp as u64, sp as u64)
+}
+
+/// The heap chunk of `metadata`, for an [`AsanReport`]
+fn report_chunk(metadata: &AllocationMetadata) -> AsanChunkInfo {
+    AsanChunkInfo {
+        start: (metadata.address + 0x1000) as u64,
+        size: metadata.size as u64,
+        alloc_stack: metadata
+            .allocation_site_backtrace
+            .as_ref()
+            .map(report_frames)
+            .unwrap_or_default(),
+        free_stack: metadata.freed.then(|| {
+            metadata
+                .release_site_backtrace
+                .as_ref()
+                .map(report_frames)
+                .unwrap_or_default()
+        }),
+    }
+}
+
+/// The shadow memory of the allocation of `metadata` around `fault_address`, converted from
+/// the bitmap of the `libafl_frida` allocator to the `compiler-rt` encoding.
+fn report_shadow(
+    shadow_bit: usize,
+    metadata: &AllocationMetadata,
+    fault_address: usize,
+) -> AsanShadowWindow {
+    let map_to_shadow =
+        |address: usize| (1 << shadow_bit) + ((address >> 3) & ((1 << (shadow_bit + 1)) - 1));
+    // Only the shadow of the allocation itself is known to be mapped
+    let start = map_to_shadow(metadata.address) as u64;
+    let end = map_to_shadow(metadata.address + metadata.actual_size) as u64;
+    AsanShadowWindow::around(map_to_shadow(fault_address) as u64, |shadow_addr| {
+        if shadow_addr < start || shadow_addr >= end {
+            return None;
+        }
+        let bits = unsafe { (shadow_addr as *const u8).read() };
+        Some(match bits.leading_ones() {
+            8 => 0x00,
+            0 if metadata.freed => 0xfd,
+            0 => 0xfa,
+            valid => valid as u8,
+        })
+    })
+}
+
 impl AsanError {
+    /// The report of this error in the `compiler-rt` `AddressSanitizer` format
+    #[allow(clippy::too_many_lines)]
+    fn asan_report(&self, shadow_bit: Option<usize>) -> AsanReport {
+        let pid = std::process::id();
+        let heap_access = |error: &AsanReadWriteError, bug_type: &'static str, kind| {
+            let fault_address = error.fault.3;
+            let (bp, sp) = frame_registers(&error.registers);
+            // The size of the faulting access is not recorded
+            let mut report = AsanReport::new(
+                pid,
+                AsanBug::Access {
+                    bug_type: bug_type.into(),
+                    addr: fault_address as u64,
+                    kind,
+                    size: 0,
+                },
+                error.pc as u64,
+                bp,
+                sp,
+            );
+            let mut stack = vec![report_frame(error.pc as u64)];
+            stack.extend(report_frames(&error.backtrace));
+            report = report
+                .with_stack(stack)
+                .with_chunk(report_chunk(&error.metadata));
+            if let Some(shadow_bit) = shadow_bit {
+                report =
+                    report.with_shadow(report_shadow(shadow_bit, &error.metadata, fault_address));
+            }
+            report
+        };
+        let fault = |registers: &[usize; ASAN_SAVE_REGISTER_COUNT],
+                     pc: usize,
+                     backtrace: &Backtrace,
+                     bug: AsanBug| {
+            let (bp, sp) = frame_registers(registers);
+            let mut stack = vec![report_frame(pc as u64)];
+            stack.extend(report_frames(backtrace));
+            AsanReport::new(pid, bug, pc as u64, bp, sp).with_stack(stack)
+        };
+
+        match self {
+            AsanError::OobRead(error) => {
+                heap_access(error, "heap-buffer-overflow", AsanAccessKind::Read)
+            }
+            AsanError::OobWrite(error) => {
+                heap_access(error, "heap-buffer-overflow", AsanAccessKind::Write)
+            }
+            AsanError::ReadAfterFree(error) => {
+                heap_access(error, "heap-use-after-free", AsanAccessKind::Read)
+            }
+            AsanError::WriteAfterFree(error) => {
+                heap_access(error, "heap-use-after-free", AsanAccessKind::Write)
+            }
+            AsanError::DoubleFree((ptr, metadata, backtrace)) => {
+                AsanReport::new(pid, AsanBug::DoubleFree { addr: *ptr as u64 }, 0, 0, 0)
+                    .with_stack(report_frames(backtrace))
+                    .with_chunk(report_chunk(metadata))
+            }
+            AsanError::UnallocatedFree((ptr, backtrace)) => {
+                AsanReport::new(pid, AsanBug::BadFree { addr: *ptr as u64 }, 0, 0, 0)
+                    .with_stack(report_frames(backtrace))
+            }
+            AsanError::Leak((_ptr, metadata)) => AsanReport::new(
+                pid,
+                AsanBug::Leak {
+                    size: metadata.size as u64,
+                },
+                0,
+                0,
+                0,
+            )
+            .with_chunk(report_chunk(metadata)),
+            AsanError::Unknown((registers, pc, (_, _, _, fault_address), backtrace)) => fault(
+                registers,
+                *pc,
+                backtrace,
+                AsanBug::Signal {
+                    name: "SEGV".into(),
+                    addr: Some(*fault_address as u64),
+                    kind: None,
+                },
+            ),
+            AsanError::StackOobRead((registers, pc, (_, _, _, fault_address), backtrace))
+            | AsanError::StackOobWrite((registers, pc, (_, _, _, fault_address), backtrace)) => {
+                let kind = if matches!(self, AsanError::StackOobRead(_)) {
+                    AsanAccessKind::Read
+                } else {
+                    AsanAccessKind::Write
+                };
+                fault(
+                    registers,
+                    *pc,
+                    backtrace,
+                    AsanBug::Access {
+                        bug_type: "stack-buffer-overflow".into(),
+                        addr: *fault_address as u64,
+                        kind,
+                        size: 0,
+                    },
+                )
+            }
+            AsanError::BadFuncArgRead((name, pc, address, size, backtrace))
+            | AsanError::BadFuncArgWrite((name, pc, address, size, backtrace)) => {
+                let kind = if matches!(self, AsanError::BadFuncArgRead(_)) {
+                    AsanAccessKind::Read
+                } else {
+                    AsanAccessKind::Write
+                };
+                // The hooked function is the innermost frame, as for the ASan interceptors
+                let mut frame = report_frame(*pc as u64);
+                frame.function = Some(name.clone());
+                let mut stack = vec![frame];
+                stack.extend(report_frames(backtrace));
+                AsanReport::new(
+                    pid,
+                    AsanBug::Access {
+                        bug_type: "unknown-crash".into(),
+                        addr: *address as u64,
+                        kind,
+                        size: *size,
+                    },
+                    *pc as u64,
+                    0,
+                    0,
+                )
+                .with_stack(stack)
+            }
+        }
+    }
+
     pub fn description(&self) -> &str {
         match self {
             AsanError::OobRead(_) => "heap out-of-bounds read",
@@ -114,7 +337,9 @@ impl AsanError {
 #[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
 pub struct AsanErrors {
     continue_on_error: bool,
+    shadow_bit: Option<usize>,
     pub(crate) errors: Vec<AsanError>,
+    reports: Vec<String>,
 }
 
 impl AsanErrors {
@@ -123,13 +348,22 @@ impl AsanErrors {
     pub const fn new(continue_on_error: bool) -> Self {
         Self {
             errors: Vec::new(),
+            reports: Vec::new(),
             continue_on_error,
+            shadow_bit: None,
         }
     }
 
     /// Clears this `AsanErrors` struct
     pub fn clear(&mut self) {
         self.errors.clear();
+        self.reports.clear();
+    }
+
+    /// The reports of the errors, in the `compiler-rt` `AddressSanitizer` format
+    #[must_use]
+    pub fn reports(&self) -> &[String] {
+        &self.reports
     }
 
     /// Gets the amount of `AsanErrors` in this struct
@@ -154,6 +388,11 @@ impl AsanErrors {
         self.continue_on_error = continue_on_error;
     }
 
+    /// Sets the shadow bit of the allocator, to include the shadow memory in the reports.
+    pub fn set_shadow_bit(&mut self, shadow_bit: usize) {
+        self.shadow_bit = Some(shadow_bit);
+    }
+
     /// Report an error
     #[allow(clippy::too_many_lines)]
     pub(crate) fn report_error(&mut self, error: AsanError) {
@@ -557,6 +796,10 @@ impl AsanErrors {
             }
         };
 
+        let report = error.asan_report(self.shadow_bit).to_string();
+        write!(output, "{report}").unwrap();
+        self.reports.push(report);
+
         self.errors.push(error);
 
         #[allow(clippy::manual_assert)]
@@ -691,6 +934,9 @@ where
         OT: ObserversTuple<S>,
     {
         if let Some(errors) = &self.errors {
+            testcase.add_metadata(AsanReportMetadata {
+                report: errors.reports().concat(),
+            });
             testcase.add_metadata(errors.clone());
         }
 
@@ -0,0 +1,443 @@
+//! Input-to-state hooks on arbitrary target functions, configured from a TOML or YAML spec.
+//!
+//! Each hooked function gets a description of its arguments (integers, buffers with a length,
+//! C strings). On every call, the configured argument pairs are logged to the `CmpLog` map as if
+//! they were compared, so that they end up in the `CmpValuesMetadata` for input-to-state mutations.
+//! Optionally, the return value of the function can be forced, e.g. to bypass a checksum check.
+//!
+//! The TOML format maps function names to their definition:
+//!
+//! ```toml
+//! [checksum_verify]
+//! module = "libtarget.so"
+//! args = [
+//!     { kind = "buffer", len_arg = 1 },
+//!     { kind = "int" },
+//!     { kind = "int", size = 4 },
+//! ]
+//! compare = [[0, 2]]
+//! return_value = 1
+//! ```
+//!
+//! The YAML format is a list of the same definitions, with an additional `name` field.
+//! Functions are resolved by their exported symbol (the name, or `symbol` if given), or by
+//! `offset` into `module` for internal functions. At most 6 integer or pointer arguments are supported.
+
+use core::ffi::c_void;
+use std::{fmt::Display, fs, path::Path, rc::Rc};
+
+use frida_gum::{interceptor::Interceptor, Gum, Module, ModuleMap, NativePointer};
+use hashbrown::HashMap;
+use libafl::{
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+use libafl_targets::{
+    cmps::{__libafl_targets_cmplog_instructions, __libafl_targets_cmplog_routines_len},
+    CMPLOG_MAP_W,
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::helper::FridaRuntime;
+
+/// The maximum number of arguments of a hooked function
+pub const MAX_HOOK_ARGS: usize = 6;
+
+/// Operands longer than this are truncated, like `CmpLog` routines
+const MAX_OPERAND_LEN: usize = 32;
+
+fn default_int_size() -> u8 {
+    8
+}
+
+/// The kind of an argument of a hooked function
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HookArgument {
+    /// An integer of `size` bytes
+    Int {
+        /// The size in bytes, 1, 2, 4 or 8
+        #[serde(default = "default_int_size")]
+        size: u8,
+    },
+    /// A pointer to a buffer, with the length given by another integer argument or fixed
+    Buffer {
+        /// The index of the argument holding the length of the buffer
+        #[serde(default)]
+        len_arg: Option<usize>,
+        /// The fixed length of the buffer
+        #[serde(default)]
+        len: Option<usize>,
+    },
+    /// A pointer to a nul-terminated string
+    CString,
+}
+
+/// The definition of a hooked function
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FunctionHookDefinition {
+    /// The module containing the function, all modules if unset
+    #[serde(default)]
+    pub module: Option<String>,
+    /// The exported symbol of the function, if different from the name of the definition
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// The offset of the function in `module`, for functions that are not exported
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// The arguments of the function
+    #[serde(default)]
+    pub args: Vec<HookArgument>,
+    /// Pairs of argument indexes to log as compared to each other
+    #[serde(default)]
+    pub compare: Vec<[usize; 2]>,
+    /// Force the function to return this value, after calling it
+    #[serde(default)]
+    pub return_value: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct YamlFunctionHookEntry {
+    name: String,
+    #[serde(flatten)]
+    definition: FunctionHookDefinition,
+}
+
+/// Parses a hook spec in TOML format
+fn parse_toml<P: AsRef<Path> + Display>(
+    path: P,
+) -> Result<HashMap<String, FunctionHookDefinition>, Error> {
+    toml::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| Error::serialize(format!("Failed to deserialize toml at {path}: {e}")))
+}
+
+/// Parses a hook spec in YAML format
+fn parse_yaml<P: AsRef<Path> + Display>(
+    path: P,
+) -> Result<HashMap<String, FunctionHookDefinition>, Error> {
+    let entries: Vec<YamlFunctionHookEntry> = serde_yaml::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| Error::serialize(format!("Failed to deserialize yaml at {path}: {e}")))?;
+    yaml_entries_to_definitions(entries)
+}
+
+/// Converts the yaml list format to the toml-like map
+fn yaml_entries_to_definitions(
+    entries: Vec<YamlFunctionHookEntry>,
+) -> Result<HashMap<String, FunctionHookDefinition>, Error> {
+    let mut ret = HashMap::new();
+    for entry in entries {
+        if ret.insert(entry.name.clone(), entry.definition).is_some() {
+            return Err(Error::illegal_argument(format!(
+                "Entry {} was multiply defined!",
+                entry.name
+            )));
+        }
+    }
+    Ok(ret)
+}
+
+impl FunctionHookDefinition {
+    fn validate(&self, name: &str) -> Result<(), Error> {
+        let invalid = |msg: &str| Err(Error::illegal_argument(format!("Hook {name}: {msg}")));
+        if self.args.len() > MAX_HOOK_ARGS {
+            return invalid("at most 6 arguments are supported");
+        }
+        if self.offset.is_some() && self.module.is_none() {
+            return invalid("an offset needs a module");
+        }
+        for arg in &self.args {
+            match arg {
+                HookArgument::Int { size } if ![1, 2, 4, 8].contains(size) => {
+                    return invalid("integer sizes must be 1, 2, 4 or 8");
+                }
+                HookArgument::Buffer { len_arg, len } => match (len_arg, len) {
+                    (Some(len_arg), None) => {
+                        if !matches!(self.args.get(*len_arg), Some(HookArgument::Int { .. })) {
+                            return invalid("len_arg must refer to an integer argument");
+                        }
+                    }
+                    (None, Some(_)) => {}
+                    _ => return invalid("buffers need either len_arg or len"),
+                },
+                _ => {}
+            }
+        }
+        for [first, second] in &self.compare {
+            if *first >= self.args.len() || *second >= self.args.len() {
+                return invalid("compared argument out of range");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A resolved hook, passed to the replacement function
+#[derive(Debug)]
+struct FunctionHook {
+    name: String,
+    definition: FunctionHookDefinition,
+    target: NativePointer,
+}
+
+impl FunctionHook {
+    /// The bytes of an argument, integers are stored little endian in `scratch`
+    unsafe fn operand<'a>(
+        &self,
+        args: &[usize; MAX_HOOK_ARGS],
+        idx: usize,
+        scratch: &'a mut [u8; MAX_OPERAND_LEN],
+    ) -> Option<&'a [u8]> {
+        let value = args[idx];
+        let (ptr, len) = match self.definition.args[idx] {
+            HookArgument::Int { size } => {
+                scratch[..8].copy_from_slice(&(value as u64).to_le_bytes());
+                return Some(&scratch[..size as usize]);
+            }
+            HookArgument::Buffer { len_arg, len } => {
+                let len = len.or_else(|| len_arg.map(|len_arg| args[len_arg]))?;
+                (value as *const u8, len)
+            }
+            HookArgument::CString => {
+                let ptr = value as *const u8;
+                if ptr.is_null() {
+                    return None;
+                }
+                let mut len = 0;
+                while len < MAX_OPERAND_LEN && *ptr.add(len) != 0 {
+                    len += 1;
+                }
+                (ptr, len)
+            }
+        };
+        if ptr.is_null() || len == 0 {
+            return None;
+        }
+        let len = len.min(MAX_OPERAND_LEN);
+        scratch[..len].copy_from_slice(core::slice::from_raw_parts(ptr, len));
+        Some(&scratch[..len])
+    }
+
+    /// Log the configured argument pairs to the `CmpLog` map
+    unsafe fn log(&self, args: &[usize; MAX_HOOK_ARGS]) {
+        for (pair, [first, second]) in self.definition.compare.iter().enumerate() {
+            let mut k = (self.target.0 as usize).wrapping_add(pair);
+            k = (k >> 4) ^ (k << 8);
+            k &= CMPLOG_MAP_W - 1;
+
+            if let (HookArgument::Int { size }, HookArgument::Int { .. }) =
+                (self.definition.args[*first], self.definition.args[*second])
+            {
+                __libafl_targets_cmplog_instructions(
+                    k,
+                    size,
+                    args[*first] as u64,
+                    args[*second] as u64,
+                );
+                continue;
+            }
+
+            let mut first_bytes = [0; MAX_OPERAND_LEN];
+            let mut second_bytes = [0; MAX_OPERAND_LEN];
+            let (Some(op1), Some(op2)) = (
+                self.operand(args, *first, &mut first_bytes),
+                self.operand(args, *second, &mut second_bytes),
+            ) else {
+                continue;
+            };
+            let len = op1.len().min(op2.len());
+            __libafl_targets_cmplog_routines_len(k, op1.as_ptr(), op2.as_ptr(), len);
+        }
+    }
+}
+
+#[allow(clippy::many_single_char_names)]
+unsafe extern "C" fn replacement_function_hook(
+    a: usize,
+    b: usize,
+    c: usize,
+    d: usize,
+    e: usize,
+    f: usize,
+) -> usize {
+    let mut invocation = Interceptor::current_invocation();
+    let hook = &*(invocation.replacement_data().unwrap().0 as *const FunctionHook);
+    hook.log(&[a, b, c, d, e, f]);
+
+    let original = core::mem::transmute::<
+        *mut c_void,
+        extern "C" fn(usize, usize, usize, usize, usize, usize) -> usize,
+    >(hook.target.0);
+    let ret = original(a, b, c, d, e, f);
+    hook.definition
+        .return_value
+        .map_or(ret, |return_value| return_value as usize)
+}
+
+/// Frida runtime hooking user-specified functions to log their arguments for input-to-state mutations.
+///
+/// Needs a `CmpLogObserver` to collect the logged values.
+#[derive(Debug)]
+pub struct FunctionHookRuntime {
+    definitions: HashMap<String, FunctionHookDefinition>,
+    // Boxed, as the replacement functions get a pointer to their hook
+    #[allow(clippy::vec_box)]
+    hooks: Vec<Box<FunctionHook>>,
+}
+
+impl FunctionHookRuntime {
+    /// Create a [`FunctionHookRuntime`] from a YAML spec
+    pub fn from_yaml<P: AsRef<Path> + Display>(yaml_file: P) -> Result<Self, Error> {
+        Self::new(parse_yaml(yaml_file)?)
+    }
+
+    /// Create a [`FunctionHookRuntime`] from a TOML spec
+    pub fn from_toml<P: AsRef<Path> + Display>(toml_file: P) -> Result<Self, Error> {
+        Self::new(parse_toml(toml_file)?)
+    }
+
+    /// Create a [`FunctionHookRuntime`] from hook definitions, keyed by function name
+    pub fn new(definitions: HashMap<String, FunctionHookDefinition>) -> Result<Self, Error> {
+        for (name, definition) in &definitions {
+            definition.validate(name)?;
+        }
+        Ok(Self {
+            definitions,
+            hooks: Vec::new(),
+        })
+    }
+
+    /// The hook definitions
+    #[must_use]
+    pub fn definitions(&self) -> &HashMap<String, FunctionHookDefinition> {
+        &self.definitions
+    }
+
+    /// Find the address of a hooked function
+    fn resolve(name: &str, definition: &FunctionHookDefinition) -> Option<NativePointer> {
+        if let Some(offset) = definition.offset {
+            let module = definition.module.as_deref()?;
+            return Module::enumerate_modules()
+                .into_iter()
+                .find(|details| details.name == module || details.path == module)
+                .map(|details| NativePointer((details.base_address + offset) as *mut c_void));
+        }
+        let symbol = definition.symbol.as_deref().unwrap_or(name);
+        Module::find_export_by_name(definition.module.as_deref(), symbol)
+    }
+}
+
+impl FridaRuntime for FunctionHookRuntime {
+    fn init(
+        &mut self,
+        gum: &Gum,
+        _ranges: &RangeMap<usize, (u16, String)>,
+        _module_map: &Rc<ModuleMap>,
+    ) {
+        let mut interceptor = Interceptor::obtain(gum);
+        for (name, definition) in &self.definitions {
+            let Some(target) = Self::resolve(name, definition) else {
+                log::warn!("Could not find function {name} to hook");
+                continue;
+            };
+            log::info!("Hooking {name} at {:p}", target.0);
+            let hook = Box::new(FunctionHook {
+                name: name.clone(),
+                definition: definition.clone(),
+                target,
+            });
+            let hook_ptr = core::ptr::from_ref(hook.as_ref()) as *mut c_void;
+            if let Err(err) = interceptor.replace(
+                target,
+                NativePointer(replacement_function_hook as *mut c_void),
+                NativePointer(hook_ptr),
+            ) {
+                log::warn!("Could not hook {}: {err:?}", hook.name);
+                continue;
+            }
+            self.hooks.push(hook);
+        }
+    }
+
+    fn deinit(&mut self, gum: &Gum) {
+        let mut interceptor = Interceptor::obtain(gum);
+        for hook in self.hooks.drain(..) {
+            interceptor.revert(hook.target);
+        }
+    }
+
+    fn pre_exec<I: Input + HasTargetBytes>(&mut self, _input: &I) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn post_exec<I: Input + HasTargetBytes>(&mut self, _input: &I) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashMap;
+
+    use super::{
+        yaml_entries_to_definitions, FunctionHookDefinition, FunctionHookRuntime, HookArgument,
+        YamlFunctionHookEntry,
+    };
+
+    #[test]
+    fn test_toml_parsing() {
+        let definitions: HashMap<String, FunctionHookDefinition> = toml::from_str(
+            r#"
+            [checksum_verify]
+            module = "libtarget.so"
+            args = [{ kind = "buffer", len_arg = 1 }, { kind = "int" }, { kind = "int", size = 4 }]
+            compare = [[0, 2]]
+            return_value = 1
+            "#,
+        )
+        .unwrap();
+        let definition = &definitions["checksum_verify"];
+        assert_eq!(
+            definition.args[0],
+            HookArgument::Buffer {
+                len_arg: Some(1),
+                len: None
+            }
+        );
+        assert_eq!(definition.args[2], HookArgument::Int { size: 4 });
+        assert_eq!(definition.return_value, Some(1));
+        assert!(FunctionHookRuntime::new(definitions).is_ok());
+    }
+
+    #[test]
+    fn test_yaml_parsing() {
+        let entries: Vec<YamlFunctionHookEntry> = serde_yaml::from_str(
+            r"
+            - name: check_magic
+              offset: 0x1234
+              module: target
+              args:
+                - kind: c_string
+                - kind: buffer
+                  len: 4
+              compare: [[0, 1]]
+            ",
+        )
+        .unwrap();
+        let definitions = yaml_entries_to_definitions(entries).unwrap();
+        assert_eq!(definitions["check_magic"].offset, Some(0x1234));
+        assert!(FunctionHookRuntime::new(definitions).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_definition() {
+        let definitions: HashMap<String, FunctionHookDefinition> = toml::from_str(
+            r#"
+            [f]
+            args = [{ kind = "buffer" }]
+            "#,
+        )
+        .unwrap();
+        assert!(FunctionHookRuntime::new(definitions).is_err());
+    }
+}
@@ -84,6 +84,10 @@ pub mod pthread_hook;
 /// The frida cmplog runtime
 pub mod cmplog_rt;
 
+#[cfg(feature = "function_hooks")]
+/// The frida runtime logging arguments of user-specified functions to cmplog
+pub mod function_hook_rt;
+
 /// The `LibAFL` firda helper
 pub mod helper;
 
@@ -96,6 +96,8 @@ int __libqasan_malloc_initialized;
 static struct chunk_begin *quarantine_top;
 static struct chunk_begin *quarantine_end;
 static size_t              quarantine_bytes;
+// set by the host on init, 0 disables the quarantine
+static size_t quarantine_max_bytes = QUARANTINE_MAX_BYTES;
 
 #ifdef __BIONIC__
 static pthread_mutex_t quarantine_lock;
@@ -111,11 +113,11 @@ static pthread_spinlock_t quarantine_lock;
 
 // need qasan disabled
 static int quarantine_push(struct chunk_begin *ck) {
-  if (ck->requested_size >= QUARANTINE_MAX_BYTES) return 0;
+  if (ck->requested_size >= quarantine_max_bytes) return 0;
 
   if (LOCK_TRY(&quarantine_lock)) return 0;
 
-  while (ck->requested_size + quarantine_bytes >= QUARANTINE_MAX_BYTES) {
+  while (ck->requested_size + quarantine_bytes >= quarantine_max_bytes) {
     struct chunk_begin *tmp = quarantine_end;
     quarantine_end = tmp->prev;
 
@@ -146,6 +148,9 @@ void __libqasan_init_malloc(void) {
 
   LOCK_INIT(&quarantine_lock, PTHREAD_PROCESS_PRIVATE);
 
+  long max_bytes = QASAN_QUARANTINE_SIZE();
+  if (max_bytes >= 0) quarantine_max_bytes = max_bytes;
+
   __libqasan_malloc_initialized = 1;
   QASAN_LOG("\n");
   QASAN_LOG("Allocator initialization done.\n");
@@ -218,6 +223,13 @@ void __libqasan_free(void *ptr) {
   QASAN_LOAD(p, sizeof(struct chunk_begin) - REDZONE_SIZE);
   size_t n = p->requested_size;
 
+  if (n && QASAN_IS_POISON(ptr, n)) {
+    // Already freed: let the host report the double free, but don't put the
+    // chunk in the quarantine list a second time
+    QASAN_DEALLOC(ptr);
+    return;
+  }
+
   QASAN_STORE(ptr, n);
   int state = QASAN_SWAP(QASAN_DISABLED);  // disable qasan for this thread
 
@@ -44,6 +44,7 @@ enum {
   QASAN_ACTION_ENABLE,
   QASAN_ACTION_DISABLE,
   QASAN_ACTION_SWAP_STATE,
+  QASAN_ACTION_QUARANTINE_SIZE,
 };
 
 /* shadow map byte values */
@@ -102,6 +103,7 @@ int  qasan_swap(int state);
     qasan_alloc((const char *)(start), (const char *)(end))
   #define QASAN_DEALLOC(ptr) qasan_dealloc((const char *)(ptr))
   #define QASAN_SWAP(state) qasan_swap((int)(state))
+  #define QASAN_QUARANTINE_SIZE() ((long)QUARANTINE_MAX_BYTES)
 #else
 
   #define QASAN_CALL0(action) \
@@ -128,6 +130,7 @@ int  qasan_swap(int state);
   #define QASAN_DEALLOC(ptr) QASAN_CALL1(QASAN_ACTION_DEALLOC, ptr)
 
   #define QASAN_SWAP(state) QASAN_CALL1(QASAN_ACTION_SWAP_STATE, state)
+  #define QASAN_QUARANTINE_SIZE() QASAN_CALL0(QASAN_ACTION_QUARANTINE_SIZE)
 #endif
 
 #endif
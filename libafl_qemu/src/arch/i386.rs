@@ -46,6 +46,7 @@ pub fn get_exit_arch_regs() -> &'static EnumMap<ExitArgs, Regs> {
 #[allow(non_upper_case_globals)]
 impl Regs {
     pub const Sp: Regs = Regs::Esp;
+    pub const Fp: Regs = Regs::Ebp;
     pub const Pc: Regs = Regs::Eip;
 }
 
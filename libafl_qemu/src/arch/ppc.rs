@@ -110,6 +110,7 @@ pub fn get_exit_arch_regs() -> &'static EnumMap<ExitArgs, Regs> {
 impl Regs {
     pub const Pc: Regs = Regs::Nip;
     pub const Sp: Regs = Regs::R1;
+    pub const Fp: Regs = Regs::R31;
 }
 
 #[cfg(feature = "python")]
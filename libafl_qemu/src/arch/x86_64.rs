@@ -54,6 +54,7 @@ pub fn get_exit_arch_regs() -> &'static EnumMap<ExitArgs, Regs> {
 #[allow(non_upper_case_globals)]
 impl Regs {
     pub const Sp: Regs = Regs::Rsp;
+    pub const Fp: Regs = Regs::Rbp;
     pub const Pc: Regs = Regs::Rip;
 }
 
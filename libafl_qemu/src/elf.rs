@@ -74,6 +74,97 @@ impl<'a> EasyElf<'a> {
         None
     }
 
+    /// Resolve the function `name` defined in this ELF, looking also at the dynamic symbols.
+    ///
+    /// Contrary to [`Self::resolve_symbol`], imports and `IFUNC` resolvers are skipped.
+    #[must_use]
+    pub fn resolve_function(&self, name: &str, load_addr: GuestAddr) -> Option<GuestAddr> {
+        let base = if self.is_pic() { load_addr } else { 0 };
+        for (syms, strtab) in [
+            (&self.elf.syms, &self.elf.strtab),
+            (&self.elf.dynsyms, &self.elf.dynstrtab),
+        ] {
+            for sym in syms.iter() {
+                if !sym.is_function() || sym.st_value == 0 || sym.st_shndx == 0 {
+                    continue;
+                }
+                if strtab.get_at(sym.st_name) != Some(name) {
+                    continue;
+                }
+                #[cfg(cpu_target = "arm")]
+                // Required because of arm interworking addresses aka bit(0) for thumb mode
+                let addr = (sym.st_value as GuestAddr + base) & !(0x1 as GuestAddr);
+                #[cfg(not(cpu_target = "arm"))]
+                let addr = sym.st_value as GuestAddr + base;
+                return Some(addr);
+            }
+        }
+        None
+    }
+
+    /// The addresses of the slots, e.g. in the GOT, the dynamic linker fills with the address of
+    /// the imported symbol `name`.
+    ///
+    /// Once the symbol is bound, they hold its final address, e.g. the implementation picked by an
+    /// `IFUNC` resolver.
+    #[must_use]
+    pub fn import_slots(&self, name: &str, load_addr: GuestAddr) -> Vec<GuestAddr> {
+        let base = if self.is_pic() { load_addr } else { 0 };
+        let mut slots = vec![];
+        for relocs in [&self.elf.pltrelocs, &self.elf.dynrelas, &self.elf.dynrels] {
+            for reloc in relocs.iter() {
+                if reloc.r_sym == 0 {
+                    continue;
+                }
+                let Some(sym) = self.elf.dynsyms.get(reloc.r_sym) else {
+                    continue;
+                };
+                if self.elf.dynstrtab.get_at(sym.st_name) == Some(name) {
+                    slots.push(reloc.r_offset as GuestAddr + base);
+                }
+            }
+        }
+        slots
+    }
+
+    /// Find the function symbol containing `addr`, returning its name and the offset of `addr` in it
+    #[must_use]
+    pub fn resolve_address(
+        &self,
+        addr: GuestAddr,
+        load_addr: GuestAddr,
+    ) -> Option<(&'a str, GuestAddr)> {
+        let base = if self.is_pic() { load_addr } else { 0 };
+        let mut best: Option<(&'a str, GuestAddr)> = None;
+        for (syms, strtab) in [
+            (&self.elf.syms, &self.elf.strtab),
+            (&self.elf.dynsyms, &self.elf.dynstrtab),
+        ] {
+            for sym in syms.iter() {
+                if !sym.is_function() || sym.st_value == 0 {
+                    continue;
+                }
+                #[cfg(cpu_target = "arm")]
+                // Required because of arm interworking addresses aka bit(0) for thumb mode
+                let start = (sym.st_value as GuestAddr + base) & !(0x1 as GuestAddr);
+                #[cfg(not(cpu_target = "arm"))]
+                let start = sym.st_value as GuestAddr + base;
+                let end = start + sym.st_size.max(1) as GuestAddr;
+                if addr < start || addr >= end {
+                    continue;
+                }
+                let Some(name) = strtab.get_at(sym.st_name) else {
+                    continue;
+                };
+                // Prefer the innermost symbol
+                if best.map_or(true, |(_, offset)| addr - start < offset) {
+                    best = Some((name, addr - start));
+                }
+            }
+        }
+        best
+    }
+
     #[must_use]
     pub fn get_section(&self, name: &str, load_addr: GuestAddr) -> Option<Range<GuestAddr>> {
         for section in &self.elf.section_headers {
@@ -115,3 +206,20 @@ impl<'a> EasyElf<'a> {
         self.elf.header.e_type == ET_DYN
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::EasyElf;
+
+    #[test]
+    fn test_import_slots() {
+        let mut buffer = vec![];
+        let elf = EasyElf::from_file(env::current_exe().unwrap(), &mut buffer).unwrap();
+        // Test binaries import `memcpy` from the libc, so it's only found through its slots
+        assert!(elf.resolve_function("memcpy", 0).is_none());
+        assert!(!elf.import_slots("memcpy", 0).is_empty());
+        assert!(elf.import_slots("libafl_no_such_function", 0).is_empty());
+    }
+}
@@ -33,6 +33,8 @@ use libafl_bolts::{
 
 #[cfg(emulation_mode = "usermode")]
 use crate::emu::EmulatorModules;
+#[cfg(all(emulation_mode = "usermode", not(cpu_target = "hexagon")))]
+use crate::modules::{HasSnapshotModule, SnapshotModule};
 use crate::{command::CommandManager, modules::EmulatorModuleTuple, Emulator, EmulatorExitHandler};
 
 /// A version of `QemuExecutor` with a state accessible from the harness.
@@ -255,6 +257,24 @@ where
     }
 }
 
+#[cfg(all(emulation_mode = "usermode", not(cpu_target = "hexagon")))]
+impl<'a, CM, EH, H, OT, ET, S> HasSnapshotModule for QemuExecutor<'a, CM, EH, H, OT, ET, S>
+where
+    CM: CommandManager<EH, ET, S>,
+    EH: EmulatorExitHandler<ET, S>,
+    H: FnMut(&S::Input) -> ExitKind,
+    S: Unpin + State + HasExecutions + HasCorpus + HasSolutions,
+    OT: ObserversTuple<S>,
+    ET: EmulatorModuleTuple<S> + Debug,
+{
+    fn snapshot_module_mut(&mut self) -> Option<&mut SnapshotModule> {
+        self.state
+            .emulator_mut()
+            .modules_mut()
+            .get_mut::<SnapshotModule>()
+    }
+}
+
 impl<'a, CM, EH, ET, S> QemuExecutorState<'a, CM, EH, ET, S>
 where
     CM: CommandManager<EH, ET, S>,
@@ -316,6 +336,10 @@ where
             &mut *self.inner.observers_mut(),
             &mut exit_kind,
         );
+        #[cfg(all(emulation_mode = "usermode", not(cpu_target = "hexagon")))]
+        if let Some(module) = self.snapshot_module_mut() {
+            module.fire_restore_stats(state, mgr)?;
+        }
         Ok(exit_kind)
     }
 }
@@ -1,18 +1,34 @@
 #[cfg(emulation_mode = "usermode")]
+use std::{mem::size_of, ops::Range};
+
+#[cfg(all(
+    emulation_mode = "usermode",
+    not(any(cpu_target = "mips", cpu_target = "hexagon"))
+))]
 use capstone::{arch::BuildsCapstone, Capstone, InsnDetail};
 use hashbrown::HashMap;
+#[cfg(emulation_mode = "usermode")]
+use libafl::{executors::ExitKind, observers::ObserversTuple};
 use libafl::{inputs::UsesInput, HasMetadata};
 use libafl_qemu_sys::GuestAddr;
+#[cfg(emulation_mode = "usermode")]
+use libafl_qemu_sys::VerifyAccess;
 pub use libafl_targets::{
     cmps::{
-        __libafl_targets_cmplog_instructions, __libafl_targets_cmplog_routines, CMPLOG_ENABLED,
+        __libafl_targets_cmplog_instructions, __libafl_targets_cmplog_routines,
+        __libafl_targets_cmplog_routines_len, CMPLOG_ENABLED, CMPLOG_RTN_LEN,
     },
     CmpLogMap, CmpLogObserver, CMPLOG_MAP_H, CMPLOG_MAP_PTR, CMPLOG_MAP_SIZE, CMPLOG_MAP_W,
 };
 use serde::{Deserialize, Serialize};
 
+#[cfg(all(
+    emulation_mode = "usermode",
+    not(any(cpu_target = "mips", cpu_target = "hexagon"))
+))]
+use crate::capstone;
 #[cfg(emulation_mode = "usermode")]
-use crate::{capstone, qemu::ArchExtras, CallingConvention, Qemu};
+use crate::{elf::EasyElf, qemu::ArchExtras, CallingConvention, Qemu};
 use crate::{
     emu::EmulatorModules,
     modules::{
@@ -211,14 +227,20 @@ pub extern "C" fn trace_cmp8_cmplog(_: *const (), id: u64, v0: u64, v1: u64) {
     }
 }
 
-#[cfg(emulation_mode = "usermode")]
+#[cfg(all(
+    emulation_mode = "usermode",
+    not(any(cpu_target = "mips", cpu_target = "hexagon"))
+))]
 #[derive(Debug)]
 pub struct CmpLogRoutinesModule {
     filter: QemuInstrumentationAddressRangeFilter,
     cs: Capstone,
 }
 
-#[cfg(emulation_mode = "usermode")]
+#[cfg(all(
+    emulation_mode = "usermode",
+    not(any(cpu_target = "mips", cpu_target = "hexagon"))
+))]
 impl CmpLogRoutinesModule {
     #[must_use]
     pub fn new(filter: QemuInstrumentationAddressRangeFilter) -> Self {
@@ -347,7 +369,10 @@ impl CmpLogRoutinesModule {
     }
 }
 
-#[cfg(emulation_mode = "usermode")]
+#[cfg(all(
+    emulation_mode = "usermode",
+    not(any(cpu_target = "mips", cpu_target = "hexagon"))
+))]
 impl HasInstrumentationFilter<QemuInstrumentationAddressRangeFilter> for CmpLogRoutinesModule {
     fn filter(&self) -> &QemuInstrumentationAddressRangeFilter {
         &self.filter
@@ -358,7 +383,10 @@ impl HasInstrumentationFilter<QemuInstrumentationAddressRangeFilter> for CmpLogR
     }
 }
 
-#[cfg(emulation_mode = "usermode")]
+#[cfg(all(
+    emulation_mode = "usermode",
+    not(any(cpu_target = "mips", cpu_target = "hexagon"))
+))]
 impl<S> EmulatorModule<S> for CmpLogRoutinesModule
 where
     S: Unpin + UsesInput,
@@ -374,3 +402,356 @@ where
         );
     }
 }
+
+/// How the compared operands are passed to a hooked comparison function
+#[cfg(emulation_mode = "usermode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpLogFunctionKind {
+    /// Two buffers and a length, e.g. `memcmp`
+    Memory,
+    /// Two C strings, e.g. `strcmp`
+    CString,
+    /// Two C strings and a maximum length, e.g. `strncmp`
+    CStringN,
+    /// A `std::string` (`this`) and a C string
+    StdStringCString,
+    /// Two `std::string`s, the first one being `this`
+    StdString,
+}
+
+/// The comparison functions of the libc and of libstdc++ hooked by default
+#[cfg(emulation_mode = "usermode")]
+pub const CMPLOG_DEFAULT_FUNCTIONS: &[(&str, CmpLogFunctionKind)] = &[
+    ("memcmp", CmpLogFunctionKind::Memory),
+    ("bcmp", CmpLogFunctionKind::Memory),
+    ("strcmp", CmpLogFunctionKind::CString),
+    ("strcasecmp", CmpLogFunctionKind::CString),
+    ("strncmp", CmpLogFunctionKind::CStringN),
+    ("strncasecmp", CmpLogFunctionKind::CStringN),
+    // std::string::compare(const char*) const
+    (
+        "_ZNKSt7__cxx1112basic_stringIcSt11char_traitsIcESaIcEE7compareEPKc",
+        CmpLogFunctionKind::StdStringCString,
+    ),
+    // std::string::compare(const std::string&) const
+    (
+        "_ZNKSt7__cxx1112basic_stringIcSt11char_traitsIcESaIcEE7compareERKS4_",
+        CmpLogFunctionKind::StdString,
+    ),
+];
+
+/// Logs the operands of comparison functions, found by symbol in the guest binary and its
+/// libraries, as routine comparisons.
+///
+/// Contrary to `CmpLogRoutinesModule`, the operands are read with the [`CallingConvention`]
+/// of the target at the entry of the functions, which works the same on every architecture.
+/// The functions are resolved in [`EmulatorModule::first_exec`], so the libraries must be loaded
+/// by then.
+/// `IFUNC` symbols, such as the glibc string functions, only point to a resolver picking the
+/// implementation. They are hooked at the address the dynamic linker stores in the import slots
+/// (the GOT) of the callers, as soon as the slots are bound, i.e. right away for binaries linked
+/// with `-z now`, or after the first call otherwise.
+#[cfg(emulation_mode = "usermode")]
+#[derive(Debug)]
+pub struct CmpLogFunctionsModule {
+    filter: QemuInstrumentationAddressRangeFilter,
+    functions: Vec<(String, CmpLogFunctionKind)>,
+    hooked: HashMap<GuestAddr, CmpLogFunctionKind>,
+    pending_slots: Vec<ImportSlot>,
+}
+
+/// An import slot of a hooked function, not bound by the dynamic linker yet
+#[cfg(emulation_mode = "usermode")]
+#[derive(Debug, Clone)]
+struct ImportSlot {
+    addr: GuestAddr,
+    /// The mappings of the file importing the function. Until the slot is bound, it points there,
+    /// to the PLT.
+    importer: Range<GuestAddr>,
+    kind: CmpLogFunctionKind,
+}
+
+#[cfg(emulation_mode = "usermode")]
+impl CmpLogFunctionsModule {
+    /// Hook the [`CMPLOG_DEFAULT_FUNCTIONS`], for calls from addresses allowed by `filter`
+    #[must_use]
+    pub fn new(filter: QemuInstrumentationAddressRangeFilter) -> Self {
+        Self {
+            filter,
+            functions: CMPLOG_DEFAULT_FUNCTIONS
+                .iter()
+                .map(|(name, kind)| ((*name).to_string(), *kind))
+                .collect(),
+            hooked: HashMap::new(),
+            pending_slots: vec![],
+        }
+    }
+
+    /// Hook an additional comparison function
+    #[must_use]
+    pub fn with_function(mut self, name: &str, kind: CmpLogFunctionKind) -> Self {
+        self.functions.push((name.to_string(), kind));
+        self
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(addr)
+    }
+
+    /// Read at most [`CMPLOG_RTN_LEN`] bytes from the guest, up to `len` or the first NUL
+    /// (included) for strings. Returns the buffer and the amount of bytes read.
+    fn read_operand(
+        qemu: Qemu,
+        addr: GuestAddr,
+        len: usize,
+        string: bool,
+    ) -> Option<([u8; CMPLOG_RTN_LEN], usize)> {
+        let mut buf = [0; CMPLOG_RTN_LEN];
+        let len = len.min(CMPLOG_RTN_LEN);
+        if addr == 0 {
+            return None;
+        }
+        let mut read = 0;
+        while read < len {
+            let cur = addr + read as GuestAddr;
+            if !qemu.access_ok(VerifyAccess::Read, cur, 1) {
+                break;
+            }
+            // The access was checked above
+            unsafe { qemu.read_mem(cur, &mut buf[read..=read]) };
+            read += 1;
+            if string && buf[read - 1] == 0 {
+                break;
+            }
+        }
+        (read > 0).then_some((buf, read))
+    }
+
+    /// Read a guest word
+    fn read_word(qemu: Qemu, addr: GuestAddr) -> Option<GuestAddr> {
+        let mut bytes = [0; size_of::<GuestAddr>()];
+        if addr == 0 || !qemu.access_ok(VerifyAccess::Read, addr, bytes.len()) {
+            return None;
+        }
+        // The access was checked above
+        unsafe { qemu.read_mem(addr, &mut bytes) };
+        #[cfg(feature = "be")]
+        let value = GuestAddr::from_be_bytes(bytes);
+        #[cfg(not(feature = "be"))]
+        let value = GuestAddr::from_le_bytes(bytes);
+        Some(value)
+    }
+
+    /// The data pointer and the length of a libstdc++ `std::string`
+    fn read_std_string(qemu: Qemu, addr: GuestAddr) -> Option<(GuestAddr, usize)> {
+        let data = Self::read_word(qemu, addr)?;
+        let len = Self::read_word(qemu, addr + size_of::<GuestAddr>() as GuestAddr)?;
+        Some((data, len as usize))
+    }
+
+    /// Hook the functions of the bound import slots, returning the addresses hooked for the first
+    /// time.
+    fn resolve_import_slots(&mut self, qemu: Qemu) -> Vec<GuestAddr> {
+        let mut new = vec![];
+        let hooked = &mut self.hooked;
+        self.pending_slots.retain(|slot| {
+            let Some(target) = Self::read_word(qemu, slot.addr) else {
+                return true;
+            };
+            // Still pointing to the PLT of the importer, or not filled in at all
+            if target == 0 || slot.importer.contains(&target) {
+                return true;
+            }
+            if hooked.insert(target, slot.kind).is_none() {
+                log::info!(
+                    "CmpLog: hooking the function bound at {:#x} at {target:#x}",
+                    slot.addr
+                );
+                new.push(target);
+            }
+            false
+        });
+        new
+    }
+
+    fn on_function_call<ET, S>(
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: Option<&mut S>,
+        pc: GuestAddr,
+    ) where
+        S: Unpin + UsesInput,
+        ET: EmulatorModuleTuple<S>,
+    {
+        unsafe {
+            if CMPLOG_ENABLED == 0 {
+                return;
+            }
+        }
+
+        let qemu = emulator_modules.qemu();
+        let Some(h) = emulator_modules.get::<Self>() else {
+            return;
+        };
+        let Some(kind) = h.hooked.get(&pc).copied() else {
+            return;
+        };
+        let Some(cpu) = qemu.current_cpu() else {
+            return;
+        };
+
+        let ret_addr: GuestAddr = cpu.read_return_address().unwrap_or(0);
+        if !h.must_instrument(ret_addr) {
+            return;
+        }
+
+        let arg = |idx| -> GuestAddr {
+            cpu.read_function_argument(CallingConvention::Cdecl, idx)
+                .unwrap_or(0)
+        };
+        let operands = match kind {
+            CmpLogFunctionKind::Memory => {
+                let len = arg(2) as usize;
+                Self::read_operand(qemu, arg(0), len, false).zip(Self::read_operand(
+                    qemu,
+                    arg(1),
+                    len,
+                    false,
+                ))
+            }
+            CmpLogFunctionKind::CString => Self::read_operand(qemu, arg(0), CMPLOG_RTN_LEN, true)
+                .zip(Self::read_operand(qemu, arg(1), CMPLOG_RTN_LEN, true)),
+            CmpLogFunctionKind::CStringN => {
+                let len = arg(2) as usize;
+                Self::read_operand(qemu, arg(0), len, true).zip(Self::read_operand(
+                    qemu,
+                    arg(1),
+                    len,
+                    true,
+                ))
+            }
+            CmpLogFunctionKind::StdStringCString => Self::read_std_string(qemu, arg(0))
+                .and_then(|(data, len)| Self::read_operand(qemu, data, len, false))
+                .zip(Self::read_operand(qemu, arg(1), CMPLOG_RTN_LEN, true)),
+            CmpLogFunctionKind::StdString => Self::read_std_string(qemu, arg(0))
+                .and_then(|(data, len)| Self::read_operand(qemu, data, len, false))
+                .zip(
+                    Self::read_std_string(qemu, arg(1))
+                        .and_then(|(data, len)| Self::read_operand(qemu, data, len, false)),
+                ),
+        };
+
+        if let Some(((v0, l0), (v1, l1))) = operands {
+            let k = hash_me(ret_addr.into()) & (CMPLOG_MAP_W as u64 - 1);
+            unsafe {
+                __libafl_targets_cmplog_routines_len(
+                    k as usize,
+                    v0.as_ptr(),
+                    v1.as_ptr(),
+                    l0.max(l1),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(emulation_mode = "usermode")]
+impl Default for CmpLogFunctionsModule {
+    fn default() -> Self {
+        Self::new(QemuInstrumentationAddressRangeFilter::None)
+    }
+}
+
+#[cfg(emulation_mode = "usermode")]
+impl HasInstrumentationFilter<QemuInstrumentationAddressRangeFilter> for CmpLogFunctionsModule {
+    fn filter(&self) -> &QemuInstrumentationAddressRangeFilter {
+        &self.filter
+    }
+
+    fn filter_mut(&mut self) -> &mut QemuInstrumentationAddressRangeFilter {
+        &mut self.filter
+    }
+}
+
+#[cfg(emulation_mode = "usermode")]
+impl<S> EmulatorModule<S> for CmpLogFunctionsModule
+where
+    S: Unpin + UsesInput,
+{
+    const HOOKS_DO_SIDE_EFFECTS: bool = false;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        let qemu = emulator_modules.qemu();
+
+        // The mappings of each file, the first one starting at its load address
+        let mut files: Vec<(String, Range<GuestAddr>)> = vec![];
+        for region in qemu.mappings() {
+            if let Some(path) = region.path() {
+                // skip [heap], [vdso] and friends
+                if path.is_empty() || path.starts_with('[') {
+                    continue;
+                }
+                if let Some((_, range)) = files.iter_mut().find(|(name, _)| name == path) {
+                    range.end = range.end.max(region.end());
+                } else {
+                    files.push((path.clone(), region.start()..region.end()));
+                }
+            }
+        }
+
+        for (file, range) in &files {
+            let mut elf_buffer = Vec::new();
+            let Ok(elf) = EasyElf::from_file(file, &mut elf_buffer) else {
+                continue;
+            };
+            for (name, kind) in &self.functions {
+                if let Some(addr) = elf.resolve_function(name, range.start) {
+                    log::info!("CmpLog: hooking {name} in {file} at {addr:#x}");
+                    self.hooked.insert(addr, *kind);
+                }
+                for addr in elf.import_slots(name, range.start) {
+                    self.pending_slots.push(ImportSlot {
+                        addr,
+                        importer: range.clone(),
+                        kind: *kind,
+                    });
+                }
+            }
+        }
+        self.resolve_import_slots(qemu);
+
+        for addr in self.hooked.keys() {
+            emulator_modules.instructions(
+                *addr,
+                Hook::Function(Self::on_function_call::<ET, S>),
+                true,
+            );
+        }
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _input: &S::Input,
+        _observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        // Lazily bound slots get filled in by the first call through them
+        if self.pending_slots.is_empty() {
+            return;
+        }
+        for addr in self.resolve_import_slots(emulator_modules.qemu()) {
+            emulator_modules.instructions(
+                addr,
+                Hook::Function(Self::on_function_call::<ET, S>),
+                true,
+            );
+        }
+    }
+}
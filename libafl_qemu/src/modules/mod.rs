@@ -26,8 +26,9 @@ pub mod calls;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use calls::CallTracerModule;
 
-#[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
 pub mod cmplog;
+#[cfg(emulation_mode = "usermode")]
+pub use cmplog::CmpLogFunctionsModule;
 #[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
 pub use cmplog::CmpLogModule;
 
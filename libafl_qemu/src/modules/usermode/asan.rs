@@ -1,9 +1,23 @@
 #![allow(clippy::cast_possible_wrap)]
 
-use std::{borrow::Cow, env, fs, path::PathBuf, sync::Mutex};
+use std::{borrow::Cow, collections::VecDeque, env, fs, path::PathBuf, sync::Mutex};
 
 use hashbrown::{HashMap, HashSet};
-use libafl::{executors::ExitKind, inputs::UsesInput, observers::ObserversTuple};
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{
+        asan::{
+            bug_type_for_shadow_byte, AsanAccessKind, AsanBug, AsanChunkInfo, AsanShadowWindow,
+            AsanStackFrame,
+        },
+        AsanReport, AsanReportObserver, ObserversTuple,
+    },
+};
+use libafl_bolts::{
+    os::unix_signals::Signal,
+    tuples::{Handle, Handled, MatchNameRef},
+};
 use libc::{
     c_void, MAP_ANON, MAP_FAILED, MAP_FIXED, MAP_NORESERVE, MAP_PRIVATE, PROT_READ, PROT_WRITE,
 };
@@ -12,6 +26,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use rangemap::RangeMap;
 
 use crate::{
+    elf::EasyElf,
     modules::{
         calls::FullBacktraceCollector, snapshot::SnapshotModule, EmulatorModule,
         EmulatorModuleTuple, HasInstrumentationFilter, IsFilter,
@@ -41,6 +56,9 @@ pub const SHADOW_PAGE_MASK: GuestAddr = !(SHADOW_PAGE_SIZE as GuestAddr - 1);
 
 pub const DEFAULT_REDZONE_SIZE: usize = 128;
 
+/// The default amount of freed memory kept in quarantine, in bytes
+pub const DEFAULT_QUARANTINE_SIZE: usize = 50 * 1024 * 1024;
+
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy)]
 #[repr(u64)]
 pub enum QasanAction {
@@ -55,6 +73,7 @@ pub enum QasanAction {
     Enable,
     Disable,
     SwapState,
+    QuarantineSize,
 }
 
 impl TryFrom<u32> for QasanAction {
@@ -104,6 +123,20 @@ pub enum AsanError {
     BadFree(GuestAddr, Option<Interval<GuestAddr>>),
     MemLeak(Interval<GuestAddr>),
     Signal(i32),
+    /// An access to a chunk that was freed and is still in quarantine
+    UseAfterFree {
+        addr: GuestAddr,
+        size: usize,
+        is_write: bool,
+        chunk: Interval<GuestAddr>,
+        item: AllocTreeItem,
+    },
+    /// A free of a chunk that was already freed and is still in quarantine
+    DoubleFree {
+        addr: GuestAddr,
+        chunk: Interval<GuestAddr>,
+        item: AllocTreeItem,
+    },
 }
 
 impl core::fmt::Display for AsanError {
@@ -119,13 +152,29 @@ impl core::fmt::Display for AsanError {
             },
             AsanError::MemLeak(interval) => write!(fmt, "Memory leak of chunk {interval}"),
             AsanError::Signal(sig) => write!(fmt, "Signal {sig} received"),
+            AsanError::UseAfterFree {
+                addr,
+                size,
+                is_write,
+                chunk,
+                ..
+            } => {
+                let kind = if *is_write { "write" } else { "read" };
+                write!(
+                    fmt,
+                    "Use after free: {size} bytes {kind} at {addr:#x} in the freed chunk {chunk}"
+                )
+            }
+            AsanError::DoubleFree { addr, chunk, .. } => {
+                write!(fmt, "Double free at {addr:#x} of the freed chunk {chunk}")
+            }
         }
     }
 }
 
 pub type AsanErrorCallback = Box<dyn FnMut(&AsanGiovese, Qemu, GuestAddr, AsanError)>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AllocTreeItem {
     backtrace: Vec<GuestAddr>,
     free_backtrace: Vec<GuestAddr>,
@@ -146,6 +195,80 @@ impl AllocTreeItem {
         self.free_backtrace = backtrace;
         self.allocated = false;
     }
+
+    /// The backtrace of the allocation, innermost frame last
+    #[must_use]
+    pub fn backtrace(&self) -> &[GuestAddr] {
+        &self.backtrace
+    }
+
+    /// The backtrace of the free, innermost frame last. Empty if the chunk is still allocated.
+    #[must_use]
+    pub fn free_backtrace(&self) -> &[GuestAddr] {
+        &self.free_backtrace
+    }
+
+    #[must_use]
+    pub fn is_allocated(&self) -> bool {
+        self.allocated
+    }
+}
+
+/// Freed chunks, oldest first.
+///
+/// While a chunk is in quarantine, it stays in the alloc tree, so that accesses to it and further
+/// frees can be reported as use-after-free and double free, with both backtraces.
+/// The guest allocator is told the same size, so it doesn't reuse the memory in the meantime.
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    chunks: VecDeque<Interval<GuestAddr>>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Quarantine {
+    /// Create a new, empty [`Quarantine`] holding at most `max_bytes` of freed memory.
+    ///
+    /// Chunks of `max_bytes` or more never enter the quarantine.
+    #[must_use]
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// The maximum amount of freed memory kept in quarantine, in bytes
+    #[must_use]
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// The amount of freed memory currently in quarantine, in bytes
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Put `chunk` in quarantine, returning the chunks that have to leave it to make room
+    pub fn push(&mut self, chunk: Interval<GuestAddr>) -> Vec<Interval<GuestAddr>> {
+        let size = (chunk.end - chunk.start) as usize;
+        if size >= self.max_bytes {
+            return vec![chunk];
+        }
+        let mut evicted = vec![];
+        while self.bytes + size > self.max_bytes {
+            let Some(old) = self.chunks.pop_front() else {
+                break;
+            };
+            self.bytes -= (old.end - old.start) as usize;
+            evicted.push(old);
+        }
+        self.chunks.push_back(chunk);
+        self.bytes += size;
+        evicted
+    }
 }
 use std::pin::Pin;
 
@@ -160,15 +283,20 @@ pub struct AsanGiovese {
     pub alloc_tree: Mutex<IntervalTree<GuestAddr, AllocTreeItem>>,
     pub saved_tree: IntervalTree<GuestAddr, AllocTreeItem>,
     pub error_callback: Option<AsanErrorCallback>,
+    pub quarantine: Quarantine,
+    pub saved_quarantine: Quarantine,
     pub dirty_shadow: Mutex<HashSet<GuestAddr>>,
     pub saved_shadow: HashMap<GuestAddr, Vec<i8>>,
     pub snapshot_shadow: bool,
+    /// The reports of the errors found in the current run, see [`asan_report`]
+    pub reports: Mutex<Vec<String>>,
 }
 
 impl core::fmt::Debug for AsanGiovese {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("AsanGiovese")
             .field("alloc_tree", &self.alloc_tree)
+            .field("quarantine", &self.quarantine)
             .field("dirty_shadow", &self.dirty_shadow)
             .finish_non_exhaustive()
     }
@@ -214,9 +342,12 @@ impl AsanGiovese {
             alloc_tree: Mutex::new(IntervalTree::new()),
             saved_tree: IntervalTree::new(),
             error_callback: None,
+            quarantine: Quarantine::new(DEFAULT_QUARANTINE_SIZE),
+            saved_quarantine: Quarantine::new(DEFAULT_QUARANTINE_SIZE),
             dirty_shadow: Mutex::new(HashSet::default()),
             saved_shadow: HashMap::default(),
             snapshot_shadow: true, // By default, track the dirty shadow pages
+            reports: Mutex::new(Vec::new()),
         };
         let mut boxed = Box::pin(res);
         qemu_hooks.add_pre_syscall_hook(boxed.as_mut(), Self::fake_syscall);
@@ -266,6 +397,9 @@ impl AsanGiovese {
                     let pc: GuestAddr = qemu.read_reg(Regs::Pc).unwrap();
                     self.deallocation(qemu, pc, a1);
                 }
+                QasanAction::QuarantineSize => {
+                    r = self.quarantine.max_bytes() as GuestAddr;
+                }
                 _ => (),
             }
             SyscallHookResult::new(Some(r))
@@ -282,6 +416,11 @@ impl AsanGiovese {
         self.snapshot_shadow = snapshot_shadow;
     }
 
+    fn set_quarantine_size(&mut self, max_bytes: usize) {
+        self.quarantine = Quarantine::new(max_bytes);
+        self.saved_quarantine = Quarantine::new(max_bytes);
+    }
+
     #[inline]
     #[must_use]
     pub fn is_invalid_access_1(qemu: Qemu, addr: GuestAddr) -> bool {
@@ -477,6 +616,16 @@ impl AsanGiovese {
         }
     }
 
+    /// Keep the text report of an error, to be handed to an [`AsanReportObserver`]
+    pub fn push_report(&self, report: String) {
+        self.reports.lock().unwrap().push(report);
+    }
+
+    /// Take the reports of the errors found since the last call
+    pub fn take_reports(&self) -> Vec<String> {
+        std::mem::take(&mut *self.reports.lock().unwrap())
+    }
+
     pub fn alloc_insert(&mut self, pc: GuestAddr, start: GuestAddr, end: GuestAddr) {
         let backtrace = FullBacktraceCollector::backtrace()
             .map(|r| {
@@ -504,8 +653,16 @@ impl AsanGiovese {
 
     pub fn alloc_free(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         let mut chunk = None;
+        let mut double_free = None;
         self.alloc_map_mut(addr, |interval, item| {
             chunk = Some(*interval);
+            if interval.start != addr {
+                return;
+            }
+            if !item.allocated {
+                double_free = Some(item.clone());
+                return;
+            }
             let backtrace = FullBacktraceCollector::backtrace()
                 .map(|r| {
                     let mut v = r.to_vec();
@@ -519,6 +676,18 @@ impl AsanGiovese {
             if ck.start != addr {
                 // Free not the start of the chunk
                 self.report_or_crash(qemu, pc, AsanError::BadFree(addr, Some(ck)));
+            } else if let Some(item) = double_free {
+                self.report_or_crash(
+                    qemu,
+                    pc,
+                    AsanError::DoubleFree {
+                        addr,
+                        chunk: ck,
+                        item,
+                    },
+                );
+            } else {
+                self.quarantine_push(ck);
             }
         } else {
             // Free of wild ptr
@@ -526,6 +695,50 @@ impl AsanGiovese {
         }
     }
 
+    /// Keep the freed `chunk` in the alloc tree until it leaves the quarantine
+    fn quarantine_push(&mut self, chunk: Interval<GuestAddr>) {
+        let evicted = self.quarantine.push(chunk);
+        if evicted.is_empty() {
+            return;
+        }
+        let mut tree = self.alloc_tree.lock().unwrap();
+        for old in evicted {
+            // The memory may already have been reused by a new allocation
+            let freed = tree
+                .query(old)
+                .any(|entry| *entry.interval == old && !entry.value.allocated);
+            if freed {
+                tree.delete(old);
+            }
+        }
+    }
+
+    /// Classify an invalid access of `size` bytes at `addr`.
+    ///
+    /// Accesses to chunks in quarantine are use-after-free, all others plain invalid accesses.
+    #[must_use]
+    pub fn access_error(&self, addr: GuestAddr, size: usize, is_write: bool) -> AsanError {
+        let end = addr.wrapping_add(size.max(1) as GuestAddr);
+        let freed = self
+            .alloc_tree
+            .lock()
+            .unwrap()
+            .query(addr..end)
+            .find(|entry| !entry.value.allocated)
+            .map(|entry| (*entry.interval, entry.value.clone()));
+        match freed {
+            Some((chunk, item)) => AsanError::UseAfterFree {
+                addr,
+                size,
+                is_write,
+                chunk,
+                item,
+            },
+            None if is_write => AsanError::Write(addr, size),
+            None => AsanError::Read(addr, size),
+        }
+    }
+
     #[must_use]
     pub fn alloc_get_clone(
         &self,
@@ -611,6 +824,7 @@ impl AsanGiovese {
 
             let tree = self.alloc_tree.lock().unwrap();
             self.saved_tree = tree.clone();
+            self.saved_quarantine = self.quarantine.clone();
         }
     }
 
@@ -622,12 +836,16 @@ impl AsanGiovese {
 
             if detect_leaks {
                 for entry in tree.query(0..GuestAddr::MAX) {
-                    leaks.push(*entry.interval);
+                    // Chunks in quarantine were freed
+                    if entry.value.allocated {
+                        leaks.push(*entry.interval);
+                    }
                 }
             }
 
             if self.snapshot_shadow {
                 *tree = self.saved_tree.clone();
+                self.quarantine = self.saved_quarantine.clone();
             }
         }
 
@@ -738,6 +956,7 @@ pub struct AsanModule {
     empty: bool,
     rt: Pin<Box<AsanGiovese>>,
     filter: QemuInstrumentationAddressRangeFilter,
+    report_observer: Option<Handle<AsanReportObserver>>,
 }
 
 impl AsanModule {
@@ -770,6 +989,7 @@ impl AsanModule {
             empty: true,
             rt,
             filter,
+            report_observer: None,
         }
     }
 
@@ -795,6 +1015,7 @@ impl AsanModule {
             empty: true,
             rt,
             filter,
+            report_observer: None,
         }
     }
 
@@ -807,6 +1028,28 @@ impl AsanModule {
         Self::with_error_callback(rt, filter, Box::new(asan_report), options)
     }
 
+    /// Keep up to `max_bytes` of freed memory in quarantine, to detect use-after-free and
+    /// double free. A size of `0` disables the quarantine.
+    #[must_use]
+    pub fn with_quarantine_size(mut self, max_bytes: usize) -> Self {
+        self.rt.set_quarantine_size(max_bytes);
+        self
+    }
+
+    /// Hand the reports of [`asan_report`] to `observer` after each run, so that they can be
+    /// attached to the testcase, e.g. with an [`libafl::feedbacks::AsanReportToMetadataFeedback`].
+    #[must_use]
+    pub fn with_report_observer(mut self, observer: &AsanReportObserver) -> Self {
+        self.report_observer = Some(observer.handle());
+        self
+    }
+
+    /// The runtime tracking the shadow memory and the allocations
+    #[must_use]
+    pub fn rt(&self) -> &AsanGiovese {
+        &self.rt
+    }
+
     #[must_use]
     pub fn must_instrument(&self, addr: GuestAddr) -> bool {
         self.filter.allowed(addr)
@@ -835,65 +1078,75 @@ impl AsanModule {
         AsanGiovese::is_invalid_access(qemu, addr, size)
     }
 
+    fn report_access(
+        &mut self,
+        qemu: Qemu,
+        pc: GuestAddr,
+        addr: GuestAddr,
+        size: usize,
+        is_write: bool,
+    ) {
+        let error = self.rt.access_error(addr, size, is_write);
+        self.rt.report_or_crash(qemu, pc, error);
+    }
+
     pub fn read_1(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_1(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Read(addr, 1));
+            self.report_access(qemu, pc, addr, 1, false);
         }
     }
 
     pub fn read_2(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_2(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Read(addr, 2));
+            self.report_access(qemu, pc, addr, 2, false);
         }
     }
 
     pub fn read_4(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_4(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Read(addr, 4));
+            self.report_access(qemu, pc, addr, 4, false);
         }
     }
 
     pub fn read_8(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_8(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Read(addr, 8));
+            self.report_access(qemu, pc, addr, 8, false);
         }
     }
 
     pub fn read_n(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr, size: usize) {
         if self.enabled() && AsanGiovese::is_invalid_access(qemu, addr, size) {
-            self.rt
-                .report_or_crash(qemu, pc, AsanError::Read(addr, size));
+            self.report_access(qemu, pc, addr, size, false);
         }
     }
 
     pub fn write_1(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_1(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Write(addr, 1));
+            self.report_access(qemu, pc, addr, 1, true);
         }
     }
 
     pub fn write_2(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_2(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Write(addr, 2));
+            self.report_access(qemu, pc, addr, 2, true);
         }
     }
 
     pub fn write_4(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_4(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Write(addr, 4));
+            self.report_access(qemu, pc, addr, 4, true);
         }
     }
 
     pub fn write_8(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr) {
         if self.enabled() && AsanGiovese::is_invalid_access_8(qemu, addr) {
-            self.rt.report_or_crash(qemu, pc, AsanError::Write(addr, 8));
+            self.report_access(qemu, pc, addr, 8, true);
         }
     }
 
     pub fn write_n(&mut self, qemu: Qemu, pc: GuestAddr, addr: GuestAddr, size: usize) {
         if self.enabled() && AsanGiovese::is_invalid_access(qemu, addr, size) {
-            self.rt
-                .report_or_crash(qemu, pc, AsanError::Write(addr, size));
+            self.report_access(qemu, pc, addr, size, true);
         }
     }
 
@@ -981,13 +1234,14 @@ where
             self.rt.snapshot(emulator_modules.qemu());
             self.empty = false;
         }
+        self.rt.take_reports();
     }
 
     fn post_exec<OT, ET>(
         &mut self,
         emulator_modules: &mut EmulatorModules<ET, S>,
         _input: &S::Input,
-        _observers: &mut OT,
+        observers: &mut OT,
         exit_kind: &mut ExitKind,
     ) where
         OT: ObserversTuple<S>,
@@ -996,6 +1250,16 @@ where
         if self.reset(emulator_modules.qemu()) == AsanRollback::HasLeaks {
             *exit_kind = ExitKind::Crash;
         }
+
+        let reports = self.rt.take_reports();
+        if let Some(handle) = &self.report_observer {
+            let observer = observers
+                .get_mut(handle)
+                .expect("An AsanModule with a report observer needs an AsanReportObserver");
+            for report in reports {
+                observer.observe_report(&report);
+            }
+        }
     }
 }
 
@@ -1320,6 +1584,9 @@ where
             QasanAction::SwapState => {
                 h.set_enabled(!h.enabled());
             }
+            QasanAction::QuarantineSize => {
+                return SyscallHookResult::new(Some(h.rt.quarantine.max_bytes() as GuestAddr));
+            }
             _ => (),
         }
         SyscallHookResult::new(Some(0))
@@ -1516,6 +1783,192 @@ mod addr2line_legacy {
     }
 }
 
+/// The shadow byte guarding `addr`
+fn shadow_byte_at(qemu: Qemu, addr: GuestAddr) -> u8 {
+    unsafe {
+        let h = qemu.g2h::<*const c_void>(addr) as isize;
+        let shadow_addr = ((h >> 3) as *mut i8).offset(SHADOW_OFFSET);
+        *shadow_addr as u8
+    }
+}
+
+/// The first invalid byte of an access of `size` bytes at `addr`
+fn first_invalid_byte(qemu: Qemu, addr: GuestAddr, size: usize) -> GuestAddr {
+    (0..size.max(1) as GuestAddr)
+        .map(|i| addr.wrapping_add(i))
+        .find(|a| AsanGiovese::is_invalid_access_1(qemu, *a))
+        .unwrap_or(addr)
+}
+
+/// The `compiler-rt` bug type of an invalid access to `bad_addr`, from its shadow byte
+fn access_bug_type(qemu: Qemu, bad_addr: GuestAddr) -> &'static str {
+    let mut shadow = shadow_byte_at(qemu, bad_addr);
+    if shadow > 0 && shadow < 8 {
+        // Partially addressable, the interesting shadow byte is the next one
+        shadow = shadow_byte_at(qemu, bad_addr.wrapping_add(8));
+    }
+    bug_type_for_shadow_byte(shadow)
+}
+
+/// The shadow memory around `bad_addr`, as `compiler-rt` prints it
+fn shadow_window(qemu: Qemu, bad_addr: GuestAddr) -> AsanShadowWindow {
+    let h = qemu.g2h::<c_void>(bad_addr) as isize;
+    let shadow_addr = ((h >> 3) + SHADOW_OFFSET) as u64;
+    // The whole shadow is mapped with MAP_NORESERVE, reading it is always fine
+    AsanShadowWindow::around(shadow_addr, |a| Some(unsafe { *(a as *const u8) }))
+}
+
+/// Build the [`AsanReport`] of `err`, symbolizing addresses with `resolve_addr`
+#[allow(clippy::unnecessary_cast)]
+fn build_asan_report<F>(
+    rt: &AsanGiovese,
+    qemu: Qemu,
+    pc: GuestAddr,
+    err: &AsanError,
+    mut resolve_addr: F,
+) -> AsanReport
+where
+    F: FnMut(GuestAddr) -> AsanStackFrame,
+{
+    // The backtraces are innermost frame last, ASan prints them innermost frame first
+    let mut stack = |bt: &[GuestAddr]| -> Vec<AsanStackFrame> {
+        bt.iter().rev().map(|addr| resolve_addr(*addr)).collect()
+    };
+    let backtrace = FullBacktraceCollector::backtrace()
+        .map(|r| {
+            let mut v = r.to_vec();
+            v.push(pc);
+            v
+        })
+        .unwrap_or(vec![pc]);
+
+    let pid = std::process::id();
+    let bp: GuestAddr = qemu.read_reg(Regs::Fp).unwrap_or_default();
+    let sp: GuestAddr = qemu.read_reg(Regs::Sp).unwrap_or_default();
+    let new_report = |bug| AsanReport::new(pid, bug, pc as u64, bp as u64, sp as u64);
+
+    let mut shadow = None;
+    let (mut report, chunk) = match err {
+        AsanError::Read(addr, size) | AsanError::Write(addr, size) => {
+            let bad_addr = first_invalid_byte(qemu, *addr, *size);
+            shadow = Some(shadow_window(qemu, bad_addr));
+            let kind = if matches!(err, AsanError::Read(..)) {
+                AsanAccessKind::Read
+            } else {
+                AsanAccessKind::Write
+            };
+            let report = new_report(AsanBug::Access {
+                bug_type: access_bug_type(qemu, bad_addr).into(),
+                addr: *addr as u64,
+                kind,
+                size: *size,
+            });
+            (report, nearest_chunk(rt, *addr))
+        }
+        AsanError::UseAfterFree {
+            addr,
+            size,
+            is_write,
+            chunk,
+            item,
+        } => {
+            let bad_addr = first_invalid_byte(qemu, *addr, *size);
+            shadow = Some(shadow_window(qemu, bad_addr));
+            let kind = if *is_write {
+                AsanAccessKind::Write
+            } else {
+                AsanAccessKind::Read
+            };
+            let report = new_report(AsanBug::Access {
+                bug_type: "heap-use-after-free".into(),
+                addr: *addr as u64,
+                kind,
+                size: *size,
+            });
+            (report, Some((*chunk, item.clone())))
+        }
+        AsanError::DoubleFree { addr, chunk, item } => (
+            new_report(AsanBug::DoubleFree { addr: *addr as u64 }),
+            Some((*chunk, item.clone())),
+        ),
+        AsanError::BadFree(addr, chunk) => (
+            new_report(AsanBug::BadFree { addr: *addr as u64 }),
+            chunk.and_then(|ck| rt.alloc_get_clone(ck.start)),
+        ),
+        AsanError::MemLeak(chunk) => (
+            new_report(AsanBug::Leak {
+                size: (chunk.end - chunk.start) as u64,
+            }),
+            rt.alloc_get_clone(chunk.start),
+        ),
+        AsanError::Signal(sig) => {
+            let name = Signal::try_from(*sig).map_or_else(
+                |_| format!("signal {sig}"),
+                |sig| sig.to_string().trim_start_matches("SIG").to_string(),
+            );
+            (
+                new_report(AsanBug::Signal {
+                    name: name.into(),
+                    addr: None,
+                    kind: None,
+                }),
+                None,
+            )
+        }
+    };
+
+    if !matches!(err, AsanError::MemLeak(_)) {
+        report = report.with_stack(stack(&backtrace));
+    }
+    if let Some((chunk, item)) = chunk {
+        report = report.with_chunk(AsanChunkInfo {
+            start: chunk.start as u64,
+            size: (chunk.end - chunk.start) as u64,
+            alloc_stack: stack(&item.backtrace),
+            free_stack: (!item.allocated).then(|| stack(&item.free_backtrace)),
+        });
+    }
+    if let Some(shadow) = shadow {
+        report = report.with_shadow(shadow);
+    }
+    report
+}
+
+/// The chunk containing `addr`, or the closest one within a redzone of it
+fn nearest_chunk(
+    rt: &AsanGiovese,
+    addr: GuestAddr,
+) -> Option<(Interval<GuestAddr>, AllocTreeItem)> {
+    if let Some(found) = rt.alloc_get_clone(addr) {
+        return Some(found);
+    }
+    let redzone = DEFAULT_REDZONE_SIZE as GuestAddr;
+    let mut found: Option<(Interval<GuestAddr>, AllocTreeItem)> = None;
+    let distance = |chunk: &Interval<GuestAddr>| {
+        if addr < chunk.start {
+            chunk.start - addr
+        } else {
+            addr - chunk.end
+        }
+    };
+    rt.alloc_map_interval(
+        (addr.saturating_sub(redzone)..=addr.saturating_add(redzone)).into(),
+        |chunk, item| {
+            if found
+                .as_ref()
+                .map_or(true, |(best, _)| distance(chunk) < distance(best))
+            {
+                found = Some((*chunk, item.clone()));
+            }
+        },
+    );
+    found
+}
+
+/// Print the error in the `compiler-rt` `AddressSanitizer` format, symbolizing the addresses
+/// with the ELF symbols and the DWARF info of the mapped files.
+///
+/// The report is also kept in the runtime, for [`AsanModule::with_report_observer`].
 #[allow(clippy::unnecessary_cast)]
 #[allow(clippy::too_many_lines)]
 pub fn asan_report(rt: &AsanGiovese, qemu: Qemu, pc: GuestAddr, err: AsanError) {
@@ -1552,7 +2005,11 @@ pub fn asan_report(rt: &AsanGiovese, qemu: Qemu, pc: GuestAddr, err: AsanError)
     let arena_data = typed_arena::Arena::new();
 
     for img in &images {
-        if let Ok(obj) = object::read::File::parse(&*img.1) {
+        let Ok(elf) = EasyElf::from_slice(&img.1) else {
+            resolvers.push(None);
+            continue;
+        };
+        let ctx = object::read::File::parse(&*img.1).ok().and_then(|obj| {
             let endian = if obj.is_little_endian() {
                 addr2line::gimli::RunTimeEndian::Little
             } else {
@@ -1563,162 +2020,65 @@ pub fn asan_report(rt: &AsanGiovese, qemu: Qemu, pc: GuestAddr, err: AsanError)
                 load_file_section(id, &obj, endian, &arena_data)
             };
 
-            let dwarf = addr2line::gimli::Dwarf::load(&mut load_section).unwrap();
-            let ctx = addr2line::Context::from_dwarf(dwarf)
-                .expect("Failed to create an addr2line context");
-
-            //let ctx = addr2line::Context::new(&obj).expect("Failed to create an addr2line context");
-            resolvers.push(Some((obj, ctx)));
-        } else {
-            resolvers.push(None);
-        }
+            let dwarf = addr2line::gimli::Dwarf::load(&mut load_section).ok()?;
+            addr2line::Context::from_dwarf(dwarf).ok()
+        });
+        resolvers.push(Some((elf, ctx)));
     }
 
-    let resolve_addr = |addr: GuestAddr| -> String {
-        let mut info = String::new();
-        if let Some((rng, idx)) = ranges.get_key_value(&addr) {
-            let raddr = (addr - rng.start) as u64;
-            if let Some((obj, ctx)) = resolvers[*idx].as_ref() {
-                let symbols = obj.symbol_map();
-                let mut func = symbols.get(raddr).map(|x| x.name().to_string());
-
-                if func.is_none() {
-                    let pathname = PathBuf::from(images[*idx].0.clone());
-                    let mut split_dwarf_loader = addr2line_legacy::SplitDwarfLoader::new(
-                        |data, endian| {
-                            addr2line::gimli::EndianSlice::new(
-                                arena_data.alloc(Cow::Owned(data.into_owned())),
-                                endian,
-                            )
-                        },
-                        Some(pathname),
-                    );
-
-                    let frames = ctx.find_frames(raddr);
-                    if let Ok(mut frames) = split_dwarf_loader.run(frames) {
-                        if let Some(frame) = frames.next().unwrap_or(None) {
-                            if let Some(function) = frame.function {
-                                if let Ok(name) = function.raw_name() {
-                                    let demangled =
-                                        addr2line::demangle_auto(name, function.language);
-                                    func = Some(demangled.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-
-                if let Some(name) = func {
-                    info += " in ";
-                    info += &name;
-                }
+    let resolve_addr = |addr: GuestAddr| -> AsanStackFrame {
+        let mut frame = AsanStackFrame::new(addr as u64);
+        let Some((rng, idx)) = ranges.get_key_value(&addr) else {
+            return frame;
+        };
+        frame.module = Some(images[*idx].0.clone());
+        frame.module_offset = (addr - rng.start) as u64;
+        let Some((elf, ctx)) = resolvers[*idx].as_ref() else {
+            return frame;
+        };
+        // Non-PIC binaries have absolute addresses in their symbols and debug info
+        let raddr = (if elf.is_pic() { addr - rng.start } else { addr }) as u64;
+
+        if let Some(ctx) = ctx {
+            let pathname = PathBuf::from(images[*idx].0.clone());
+            let mut split_dwarf_loader = addr2line_legacy::SplitDwarfLoader::new(
+                |data, endian| {
+                    addr2line::gimli::EndianSlice::new(
+                        arena_data.alloc(Cow::Owned(data.into_owned())),
+                        endian,
+                    )
+                },
+                Some(pathname),
+            );
 
-                if let Some(loc) = ctx.find_location(raddr).unwrap_or(None) {
-                    if info.is_empty() {
-                        info += " in";
-                    }
-                    info += " ";
-                    if let Some(file) = loc.file {
-                        info += file;
+            let frames = ctx.find_frames(raddr);
+            if let Ok(mut frames) = split_dwarf_loader.run(frames) {
+                if let Some(function) = frames.next().unwrap_or(None).and_then(|f| f.function) {
+                    if let Ok(name) = function.raw_name() {
+                        frame.function =
+                            Some(addr2line::demangle_auto(name, function.language).to_string());
                     }
-                    if let Some(line) = loc.line {
-                        info += ":";
-                        info += &line.to_string();
-                    }
-                } else {
-                    info += &format!(" ({}+{raddr:#x})", images[*idx].0);
                 }
             }
-            if info.is_empty() {
-                info += &format!(" ({}+{raddr:#x})", images[*idx].0);
+
+            if let Some(loc) = ctx.find_location(raddr).unwrap_or(None) {
+                frame.file = loc.file.map(ToString::to_string);
+                frame.line = loc.line;
+                frame.column = loc.column;
             }
         }
-        info
-    };
 
-    eprintln!("=================================================================");
-    let backtrace = FullBacktraceCollector::backtrace()
-        .map(|r| {
-            let mut v = r.to_vec();
-            v.push(pc);
-            v
-        })
-        .unwrap_or(vec![pc]);
-    eprintln!("AddressSanitizer Error: {err}");
-    for (i, addr) in backtrace.iter().rev().enumerate() {
-        eprintln!("\t#{i} {addr:#x}{}", resolve_addr(*addr));
-    }
-    let addr = match err {
-        AsanError::Read(addr, _) | AsanError::Write(addr, _) | AsanError::BadFree(addr, _) => {
-            Some(addr)
+        if frame.function.is_none() {
+            frame.function = elf
+                .resolve_address(addr, rng.start)
+                .map(|(name, _)| addr2line::demangle_auto(name.into(), None).to_string());
         }
-        AsanError::MemLeak(_) | AsanError::Signal(_) => None,
+        frame
     };
-    if let Some(addr) = addr {
-        let print_bts = |item: &AllocTreeItem| {
-            if item.allocated {
-                eprintln!("Allocated at:");
-            } else {
-                eprintln!("Freed at:");
-                for (i, addr) in item.free_backtrace.iter().rev().enumerate() {
-                    eprintln!("\t#{i} {addr:#x}{}", resolve_addr(*addr));
-                }
-                eprintln!("And previously allocated at:");
-            }
-
-            for (i, addr) in item.backtrace.iter().rev().enumerate() {
-                eprintln!("\t#{i} {addr:#x}{}", resolve_addr(*addr));
-            }
-        };
 
-        if let Some((chunk, item)) = rt.alloc_get_clone(addr) {
-            eprintln!(
-                "Address {addr:#x} is {} bytes inside the {}-byte chunk [{:#x},{:#x})",
-                addr - chunk.start,
-                chunk.end - chunk.start,
-                chunk.start,
-                chunk.end
-            );
-            print_bts(&item);
-        } else {
-            let mut found = false;
-            rt.alloc_map_interval(
-                (addr..=(addr + DEFAULT_REDZONE_SIZE as GuestAddr)).into(),
-                |chunk, item| {
-                    if found {
-                        return;
-                    }
-                    found = true;
-                    eprintln!(
-                        "Address {addr:#x} is {} bytes to the left of the {}-byte chunk [{:#x},{:#x})",
-                        chunk.start - addr,
-                        chunk.end - chunk.start,
-                        chunk.start,
-                        chunk.end
-                    );
-                    print_bts(item);
-                },
-            );
-            found = false;
-            rt.alloc_map_interval(
-                ((addr - DEFAULT_REDZONE_SIZE as GuestAddr)..addr).into(),
-                |chunk, item| {
-                    if found {
-                        return;
-                    }
-                    found = true;
-                    eprintln!(
-                        "Address {addr:#x} is {} bytes to the right of the {}-byte chunk [{:#x},{:#x})",
-                        addr - chunk.end,
-                        chunk.end - chunk.start,
-                        chunk.start,
-                        chunk.end
-                    );
-                    print_bts(item);
-                },
-            );
-        }
-    }
+    let report = build_asan_report(rt, qemu, pc, &err, resolve_addr).to_string();
+    eprint!("{report}");
+    rt.push_report(report);
 
     // fix pc in case it is not synced (in hooks)
     qemu.write_reg(Regs::Pc, pc).unwrap();
@@ -1727,3 +2087,43 @@ pub fn asan_report(rt: &AsanGiovese, qemu: Qemu, pc: GuestAddr, err: AsanError)
         qemu.current_cpu().unwrap().display_context()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use meminterval::Interval;
+
+    use super::Quarantine;
+    use crate::GuestAddr;
+
+    fn chunk(start: GuestAddr, size: GuestAddr) -> Interval<GuestAddr> {
+        Interval::new(start, start + size)
+    }
+
+    #[test]
+    fn test_quarantine_eviction() {
+        let mut quarantine = Quarantine::new(0x100);
+        assert!(quarantine.push(chunk(0x1000, 0x80)).is_empty());
+        assert!(quarantine.push(chunk(0x2000, 0x40)).is_empty());
+        // Filling it up to exactly `max_bytes` evicts nothing
+        assert!(quarantine.push(chunk(0x3000, 0x40)).is_empty());
+        assert_eq!(quarantine.bytes(), 0x100);
+
+        // One more byte pushes out the oldest chunk first
+        assert_eq!(quarantine.push(chunk(0x4000, 0x1)), [chunk(0x1000, 0x80)]);
+        assert_eq!(quarantine.bytes(), 0x81);
+
+        // Evict as many chunks as needed, oldest first
+        assert_eq!(
+            quarantine.push(chunk(0x5000, 0xc0)),
+            [chunk(0x2000, 0x40), chunk(0x3000, 0x40)]
+        );
+        assert_eq!(quarantine.bytes(), 0xc1);
+
+        // Chunks too big for the quarantine are released right away
+        assert_eq!(
+            quarantine.push(chunk(0x6000, 0x100)),
+            [chunk(0x6000, 0x100)]
+        );
+        assert_eq!(quarantine.bytes(), 0xc1);
+    }
+}
@@ -0,0 +1,580 @@
+//! Forensic snapshot of the guest state at the time of a crash, stored as testcase metadata.
+//!
+//! The [`CrashContextModule`] captures the registers, memory around the program counter and the
+//! registers pointing to mapped memory, the guest mappings, and the heap chunks of the
+//! [`AsanModule`] near those addresses, if any.
+//! The [`CrashContextObserver`] picks the snapshot up after the run, and the
+//! [`CrashContextFeedback`] attaches it to the testcase, e.g. to the solutions.
+
+use std::{borrow::Cow, fmt::Write, sync::Mutex};
+
+use hashbrown::HashSet;
+use libafl::{
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::UsesInput,
+    observers::{Observer, ObserversTuple},
+    state::State,
+    Error, HasMetadata,
+};
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use libafl_qemu_sys::GuestAddr;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{asan::AsanModule, EmulatorModule, EmulatorModuleTuple},
+    Qemu, Regs,
+};
+
+/// The default amount of bytes captured on each side of an address of interest
+pub const DEFAULT_CRASH_CONTEXT_WINDOW: usize = 64;
+
+/// The default maximum number of heap chunks captured
+pub const DEFAULT_CRASH_CONTEXT_MAX_CHUNKS: usize = 16;
+
+/// The context of the last crash, until the [`CrashContextObserver`] takes it
+static CRASH_CONTEXT: Mutex<Option<CrashContextMetadata>> = Mutex::new(None);
+
+/// A guest register and its value at the time of the crash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashRegister {
+    /// The name of the register, as in [`Regs`]
+    pub name: String,
+    /// The value of the register
+    pub value: GuestAddr,
+}
+
+/// Guest memory around an address of interest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashMemoryWindow {
+    /// What pointed to this memory, such as `pc` or a register name
+    pub label: String,
+    /// The address of interest
+    pub addr: GuestAddr,
+    /// The address of the first byte in `bytes`
+    pub start: GuestAddr,
+    /// The guest memory, clamped to the mapping containing `addr`
+    pub bytes: Vec<u8>,
+}
+
+/// A guest mapping at the time of the crash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashMapping {
+    /// The first address of the mapping
+    pub start: GuestAddr,
+    /// The address right after the mapping
+    pub end: GuestAddr,
+    /// The offset in the mapped file
+    pub offset: GuestAddr,
+    /// The permissions, as in `/proc/self/maps`
+    pub perms: String,
+    /// The mapped file, if any
+    pub path: Option<String>,
+}
+
+/// A heap chunk known to the [`AsanModule`], near an address of interest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashHeapChunk {
+    /// The first address of the chunk
+    pub start: GuestAddr,
+    /// The address right after the chunk
+    pub end: GuestAddr,
+    /// `false` if the chunk was freed and is still in quarantine
+    pub allocated: bool,
+    /// The backtrace of the allocation, innermost frame last
+    pub alloc_backtrace: Vec<GuestAddr>,
+    /// The backtrace of the free, innermost frame last
+    pub free_backtrace: Vec<GuestAddr>,
+}
+
+/// The guest state at the time of a crash
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashContextMetadata {
+    /// The guest signal
+    pub signal: i32,
+    /// The guest program counter
+    pub pc: GuestAddr,
+    /// The registers, as printed by [`crate::CPU::display_context`]
+    pub context: String,
+    /// All registers that could be read
+    pub registers: Vec<CrashRegister>,
+    /// The memory around the program counter and the registers pointing to readable memory
+    pub memory: Vec<CrashMemoryWindow>,
+    /// The guest mappings
+    pub mappings: Vec<CrashMapping>,
+    /// The heap chunks of the [`AsanModule`] near the captured memory, if any
+    pub heap_chunks: Vec<CrashHeapChunk>,
+}
+
+impl_serdeany!(CrashContextMetadata);
+
+impl CrashMapping {
+    /// Format the permissions of a mapping as in `/proc/self/maps`, e.g. `r-xp`
+    #[must_use]
+    pub fn format_perms(readable: bool, writable: bool, executable: bool, private: bool) -> String {
+        format!(
+            "{}{}{}{}",
+            if readable { 'r' } else { '-' },
+            if writable { 'w' } else { '-' },
+            if executable { 'x' } else { '-' },
+            if private { 'p' } else { 's' },
+        )
+    }
+
+    /// Returns `true` if `addr` is inside this mapping
+    #[must_use]
+    pub fn contains(&self, addr: GuestAddr) -> bool {
+        self.start <= addr && addr < self.end
+    }
+}
+
+/// The range `[start, end)` of `window` bytes on each side of `addr`, clamped to the readable
+/// mapping containing it. Returns `None` if `addr` is not in a readable mapping.
+fn memory_window_range(
+    mappings: &[CrashMapping],
+    addr: GuestAddr,
+    window: GuestAddr,
+) -> Option<(GuestAddr, GuestAddr)> {
+    let map = mappings
+        .iter()
+        .find(|m| m.contains(addr) && m.perms.starts_with('r'))?;
+    let start = addr.saturating_sub(window).max(map.start);
+    let end = addr.saturating_add(window).min(map.end);
+    Some((start, end))
+}
+
+impl CrashContextMetadata {
+    /// A human readable dump of the crash context
+    #[must_use]
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "Signal {} at pc {:#x}", self.signal, self.pc).unwrap();
+        write!(out, "Context:\n{}", self.context).unwrap();
+        writeln!(out, "Memory:").unwrap();
+        for window in &self.memory {
+            writeln!(out, "  {} -> {:#x}:", window.label, window.addr).unwrap();
+            for (i, line) in window.bytes.chunks(16).enumerate() {
+                let line_addr = window.start + (i * 16) as GuestAddr;
+                let hex: Vec<String> = line.iter().map(|b| format!("{b:02x}")).collect();
+                writeln!(out, "    {line_addr:#018x}: {}", hex.join(" ")).unwrap();
+            }
+        }
+        writeln!(out, "Mappings:").unwrap();
+        for map in &self.mappings {
+            writeln!(
+                out,
+                "  {:#018x}-{:#018x} {} {:#x} {}",
+                map.start,
+                map.end,
+                map.perms,
+                map.offset,
+                map.path.as_deref().unwrap_or("")
+            )
+            .unwrap();
+        }
+        if !self.heap_chunks.is_empty() {
+            writeln!(out, "Heap chunks:").unwrap();
+            for chunk in &self.heap_chunks {
+                writeln!(
+                    out,
+                    "  [{:#x},{:#x}) {}",
+                    chunk.start,
+                    chunk.end,
+                    if chunk.allocated {
+                        "allocated"
+                    } else {
+                        "freed"
+                    }
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Captures a [`CrashContextMetadata`] when the guest crashes, for the [`CrashContextObserver`]
+#[derive(Debug)]
+pub struct CrashContextModule {
+    window: usize,
+    max_chunks: usize,
+}
+
+impl Default for CrashContextModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrashContextModule {
+    /// Create a new [`CrashContextModule`], capturing [`DEFAULT_CRASH_CONTEXT_WINDOW`] bytes around
+    /// each address of interest and up to [`DEFAULT_CRASH_CONTEXT_MAX_CHUNKS`] heap chunks
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            window: DEFAULT_CRASH_CONTEXT_WINDOW,
+            max_chunks: DEFAULT_CRASH_CONTEXT_MAX_CHUNKS,
+        }
+    }
+
+    /// Capture `window` bytes on each side of the addresses of interest
+    #[must_use]
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Capture at most `max_chunks` heap chunks of the [`AsanModule`]
+    #[must_use]
+    pub fn with_max_chunks(mut self, max_chunks: usize) -> Self {
+        self.max_chunks = max_chunks;
+        self
+    }
+
+    /// Capture the guest state.
+    ///
+    /// The guest faulting address is not known in the crash hook, the windows around the
+    /// program counter and the registers cover it for most faults, as the address usually
+    /// comes from a register.
+    #[allow(clippy::unnecessary_cast)]
+    #[must_use]
+    pub fn capture(
+        &self,
+        qemu: Qemu,
+        signal: i32,
+        asan: Option<&AsanModule>,
+    ) -> Option<CrashContextMetadata> {
+        let cpu = qemu.current_cpu()?;
+        let pc: GuestAddr = cpu.read_reg(Regs::Pc).ok()?;
+
+        let mappings: Vec<CrashMapping> = qemu
+            .mappings()
+            .map(|map| {
+                let flags = map.flags();
+                let perms = CrashMapping::format_perms(
+                    flags.readable(),
+                    flags.writable(),
+                    flags.executable(),
+                    map.is_priv(),
+                );
+                CrashMapping {
+                    start: map.start(),
+                    end: map.end(),
+                    offset: map.offset(),
+                    perms,
+                    path: map.path().cloned(),
+                }
+            })
+            .collect();
+
+        let registers: Vec<CrashRegister> = Regs::iter()
+            .filter_map(|r| {
+                let value: GuestAddr = cpu.read_reg(r).ok()?;
+                Some(CrashRegister {
+                    name: format!("{r:?}"),
+                    value,
+                })
+            })
+            .collect();
+
+        let window = self.window as GuestAddr;
+        let mut memory = vec![];
+        let targets = core::iter::once(("pc".to_string(), pc))
+            .chain(registers.iter().map(|r| (r.name.clone(), r.value)));
+        for (label, addr) in targets {
+            let Some((start, end)) = memory_window_range(&mappings, addr, window) else {
+                continue;
+            };
+            let mut bytes = vec![0; (end - start) as usize];
+            // The range is inside a readable mapping
+            unsafe { cpu.read_mem(start, &mut bytes) };
+            memory.push(CrashMemoryWindow {
+                label,
+                addr,
+                start,
+                bytes,
+            });
+        }
+
+        let mut heap_chunks = vec![];
+        if let Some(asan) = asan {
+            let mut seen = HashSet::new();
+            for window_mem in &memory {
+                let query =
+                    window_mem.addr.saturating_sub(window)..window_mem.addr.saturating_add(window);
+                asan.rt().alloc_map_interval(query.into(), |chunk, item| {
+                    if heap_chunks.len() < self.max_chunks && seen.insert(chunk.start) {
+                        heap_chunks.push(CrashHeapChunk {
+                            start: chunk.start,
+                            end: chunk.end,
+                            allocated: item.is_allocated(),
+                            alloc_backtrace: item.backtrace().to_vec(),
+                            free_backtrace: item.free_backtrace().to_vec(),
+                        });
+                    }
+                });
+            }
+        }
+
+        Some(CrashContextMetadata {
+            signal,
+            pc,
+            context: cpu.display_context(),
+            registers,
+            memory,
+            mappings,
+            heap_chunks,
+        })
+    }
+}
+
+impl<S> EmulatorModule<S> for CrashContextModule
+where
+    S: Unpin + UsesInput,
+{
+    const HOOKS_DO_SIDE_EFFECTS: bool = false;
+
+    fn init_module<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.crash_function(oncrash_crash_context::<ET, S>);
+    }
+}
+
+/// The crash hook of the [`CrashContextModule`], storing the captured context for the
+/// [`CrashContextObserver`]
+pub fn oncrash_crash_context<ET, S>(emulator_modules: &mut EmulatorModules<ET, S>, target_sig: i32)
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let qemu = emulator_modules.qemu();
+    let asan = emulator_modules.get::<AsanModule>();
+    let context = emulator_modules
+        .get::<CrashContextModule>()
+        .and_then(|h| h.capture(qemu, target_sig, asan));
+    *CRASH_CONTEXT.lock().unwrap() = context;
+}
+
+/// An observer taking the [`CrashContextMetadata`] captured by the [`CrashContextModule`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashContextObserver {
+    name: Cow<'static, str>,
+    context: Option<CrashContextMetadata>,
+}
+
+impl CrashContextObserver {
+    /// Create a new [`CrashContextObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            context: None,
+        }
+    }
+
+    /// The context of the crash in the last run, if it crashed
+    #[must_use]
+    pub fn context(&self) -> Option<&CrashContextMetadata> {
+        self.context.as_ref()
+    }
+}
+
+impl Named for CrashContextObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for CrashContextObserver
+where
+    S: UsesInput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.context = None;
+        *CRASH_CONTEXT.lock().unwrap() = None;
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &S::Input,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.context = CRASH_CONTEXT.lock().unwrap().take();
+        Ok(())
+    }
+}
+
+/// Nop feedback that attaches the [`CrashContextMetadata`] to the new testcase. The testcase
+/// is never interesting (use with an OR, e.g. with the objective).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashContextFeedback {
+    o_ref: Handle<CrashContextObserver>,
+}
+
+impl CrashContextFeedback {
+    /// Create a new [`CrashContextFeedback`], reading the context from the given observer
+    #[must_use]
+    pub fn new(observer: &CrashContextObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
+
+impl Named for CrashContextFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl<S> Feedback<S> for CrashContextFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("CrashContextObserver is missing"))?;
+        if let Some(context) = observer.context() {
+            testcase.add_metadata(context.clone());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        memory_window_range, CrashContextMetadata, CrashHeapChunk, CrashMapping, CrashMemoryWindow,
+        GuestAddr,
+    };
+
+    fn mapping(start: GuestAddr, end: GuestAddr, perms: &str) -> CrashMapping {
+        CrashMapping {
+            start,
+            end,
+            offset: 0,
+            perms: perms.to_string(),
+            path: Some("/bin/target".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_format_perms() {
+        assert_eq!(CrashMapping::format_perms(true, false, true, true), "r-xp");
+        assert_eq!(
+            CrashMapping::format_perms(false, true, false, false),
+            "-w-s"
+        );
+    }
+
+    #[test]
+    fn test_memory_window_range() {
+        let mappings = [
+            mapping(0x1000, 0x2000, "r-xp"),
+            mapping(0x3000, 0x4000, "---p"),
+        ];
+        assert_eq!(
+            memory_window_range(&mappings, 0x1800, 0x40),
+            Some((0x17c0, 0x1840))
+        );
+        // Clamped to the mapping on both ends
+        assert_eq!(
+            memory_window_range(&mappings, 0x1010, 0x40),
+            Some((0x1000, 0x1050))
+        );
+        assert_eq!(
+            memory_window_range(&mappings, 0x1ff0, 0x40),
+            Some((0x1fb0, 0x2000))
+        );
+        // Unmapped or unreadable
+        assert_eq!(memory_window_range(&mappings, 0x2800, 0x40), None);
+        assert_eq!(memory_window_range(&mappings, 0x3800, 0x40), None);
+    }
+
+    #[test]
+    fn test_dump() {
+        let metadata = CrashContextMetadata {
+            signal: 11,
+            pc: 0x1010,
+            context: "Rax: 0\n".to_string(),
+            registers: vec![],
+            memory: vec![CrashMemoryWindow {
+                label: "pc".to_string(),
+                addr: 0x1010,
+                start: 0x1000,
+                bytes: (0..18).collect(),
+            }],
+            mappings: vec![mapping(0x1000, 0x2000, "r-xp")],
+            heap_chunks: vec![CrashHeapChunk {
+                start: 0x5000,
+                end: 0x5010,
+                allocated: false,
+                alloc_backtrace: vec![],
+                free_backtrace: vec![],
+            }],
+        };
+        assert_eq!(
+            metadata.dump(),
+            "Signal 11 at pc 0x1010
+Context:
+Rax: 0
+Memory:
+  pc -> 0x1010:
+    0x0000000000001000: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f
+    0x0000000000001010: 10 11
+Mappings:
+  0x0000000000001000-0x0000000000002000 r-xp 0x0 /bin/target
+Heap chunks:
+  [0x5000,0x5010) freed
+"
+        );
+    }
+}
@@ -11,9 +11,13 @@ pub use injections::InjectionModule;
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod snapshot;
 #[cfg(not(cpu_target = "hexagon"))]
+pub use snapshot::HasSnapshotModule;
+#[cfg(not(cpu_target = "hexagon"))]
 pub use snapshot::IntervalSnapshotFilter;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use snapshot::SnapshotModule;
+#[cfg(all(feature = "multipart_inputs", not(cpu_target = "hexagon")))]
+pub use snapshot::SnapshotSuffixStage;
 
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod asan;
@@ -25,6 +29,16 @@ pub mod asan_guest;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use asan_guest::{init_qemu_with_asan_guest, AsanGuestModule};
 
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod crash_context;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use crash_context::{CrashContextFeedback, CrashContextModule, CrashContextObserver};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod virtual_env;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use virtual_env::{VirtualEnvInput, VirtualEnvModule, VirtualFile};
+
 use crate::modules::{HasInstrumentationFilter, QemuInstrumentationAddressRangeFilter};
 
 pub trait StdInstrumentationFilter:
@@ -1,8 +1,29 @@
-use std::{cell::UnsafeCell, mem::MaybeUninit, sync::Mutex};
+use std::{
+    borrow::Cow, cell::UnsafeCell, marker::PhantomData, mem::MaybeUninit, sync::Mutex,
+    time::Duration,
+};
 
 use hashbrown::{HashMap, HashSet};
-use libafl::inputs::UsesInput;
-use libafl_qemu_sys::{GuestAddr, MmapPerms};
+#[cfg(feature = "multipart_inputs")]
+use libafl::{
+    corpus::{CorpusId, HasCurrentCorpusId},
+    fuzzer::{Evaluator, ExecutesInput},
+    inputs::{Input, MultipartInput},
+    mutators::{MutationResult, Mutator},
+    stages::{RetryCountRestartHelper, Stage},
+    state::{HasCorpus, HasCurrentTestcase, HasRand, UsesState},
+    HasNamedMetadata,
+};
+use libafl::{
+    events::{Event, EventFirer},
+    inputs::UsesInput,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    Error,
+};
+use libafl_bolts::current_time;
+#[cfg(feature = "multipart_inputs")]
+use libafl_bolts::{rands::Rand, Named};
+use libafl_qemu_sys::{CPUArchState, GuestAddr, MmapPerms};
 use meminterval::{Interval, IntervalTree};
 use thread_local::ThreadLocal;
 
@@ -79,6 +100,80 @@ pub enum IntervalSnapshotFilter {
     DenyList(Vec<Range<GuestAddr>>),
 }
 
+/// The id of an [`IncrementalSnapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IncrementalSnapshotId(pub usize);
+
+/// A snapshot taken at the incremental snapshot pc, stored as a diff to its parent.
+///
+/// The registers are saved too, so that runs can resume from it.
+pub struct IncrementalSnapshot {
+    /// The parent snapshot, `None` for the root snapshot
+    pub parent: Option<IncrementalSnapshotId>,
+    /// The executions of the incremental snapshot pc before this snapshot
+    pub hits: usize,
+    /// The pages written since the parent snapshot
+    pub pages: HashMap<GuestAddr, Box<[u8; SNAPSHOT_PAGE_SIZE]>>,
+    /// The whole memory mappings at the time of the snapshot
+    pub maps: MappingInfo,
+    /// The program break at the time of the snapshot
+    pub brk: GuestAddr,
+    /// The next `mmap` address at the time of the snapshot
+    pub mmap_start: GuestAddr,
+    /// The registers at the incremental snapshot pc
+    pub cpu: Box<CPUArchState>,
+}
+
+impl core::fmt::Debug for IncrementalSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("IncrementalSnapshot")
+            .field("parent", &self.parent)
+            .field("hits", &self.hits)
+            .field("pages", &self.pages.len())
+            .field("maps", &self.maps)
+            .field("brk", &self.brk)
+            .field("mmap_start", &self.mmap_start)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A run resuming from an [`IncrementalSnapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResumedSnapshot {
+    /// The snapshot restored before the run
+    pub id: IncrementalSnapshotId,
+    /// The executions of the incremental snapshot pc before the snapshot
+    pub hits: usize,
+}
+
+/// The cost of the restores of a [`SnapshotModule`]
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotRestoreStats {
+    /// The number of restores
+    pub restores: u64,
+    /// The restored pages
+    pub pages: u64,
+    /// The total time spent restoring
+    pub time: Duration,
+}
+
+impl SnapshotRestoreStats {
+    /// The average restored pages per restore
+    #[must_use]
+    pub fn pages_per_restore(&self) -> u64 {
+        self.pages.checked_div(self.restores).unwrap_or(0)
+    }
+
+    /// The average time per restore
+    #[must_use]
+    pub fn time_per_restore(&self) -> Duration {
+        u32::try_from(self.restores)
+            .ok()
+            .and_then(|restores| self.time.checked_div(restores))
+            .unwrap_or_default()
+    }
+}
+
 pub struct SnapshotModule {
     pub accesses: ThreadLocal<UnsafeCell<SnapshotAccessInfo>>,
     pub maps: MappingInfo,
@@ -91,6 +186,21 @@ pub struct SnapshotModule {
     pub empty: bool,
     pub accurate_unmap: bool,
     pub interval_filter: Vec<IntervalSnapshotFilter>,
+    incremental_pc: Option<GuestAddr>,
+    incremental: HashMap<IncrementalSnapshotId, IncrementalSnapshot>,
+    next_incremental_id: usize,
+    incremental_request: Option<usize>,
+    incremental_taken: Option<IncrementalSnapshotId>,
+    /// The snapshot the memory was restored to in the last reset
+    base: Option<IncrementalSnapshotId>,
+    /// The snapshot to restore in the next resets
+    branch: Option<IncrementalSnapshotId>,
+    /// Pages to restore in the next reset, of dropped snapshots
+    pending: HashSet<GuestAddr>,
+    pc_hits: usize,
+    /// The snapshot the current run resumes from
+    resumed: Option<ResumedSnapshot>,
+    restore_stats: SnapshotRestoreStats,
 }
 
 impl core::fmt::Debug for SnapshotModule {
@@ -103,6 +213,12 @@ impl core::fmt::Debug for SnapshotModule {
             .field("mmap_start", &self.mmap_start)
             .field("mmap_limit", &self.mmap_limit)
             .field("empty", &self.empty)
+            .field("incremental_pc", &self.incremental_pc)
+            .field("incremental", &self.incremental)
+            .field("base", &self.base)
+            .field("branch", &self.branch)
+            .field("resumed", &self.resumed)
+            .field("restore_stats", &self.restore_stats)
             .finish_non_exhaustive()
     }
 }
@@ -122,6 +238,17 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter: Vec::<IntervalSnapshotFilter>::new(),
+            incremental_pc: None,
+            incremental: HashMap::default(),
+            next_incremental_id: 0,
+            incremental_request: None,
+            incremental_taken: None,
+            base: None,
+            branch: None,
+            pending: HashSet::default(),
+            pc_hits: 0,
+            resumed: None,
+            restore_stats: SnapshotRestoreStats::default(),
         }
     }
 
@@ -139,6 +266,17 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter,
+            incremental_pc: None,
+            incremental: HashMap::default(),
+            next_incremental_id: 0,
+            incremental_request: None,
+            incremental_taken: None,
+            base: None,
+            branch: None,
+            pending: HashSet::default(),
+            pc_hits: 0,
+            resumed: None,
+            restore_stats: SnapshotRestoreStats::default(),
         }
     }
 
@@ -156,6 +294,17 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter: Vec::<IntervalSnapshotFilter>::new(),
+            incremental_pc: None,
+            incremental: HashMap::default(),
+            next_incremental_id: 0,
+            incremental_request: None,
+            incremental_taken: None,
+            base: None,
+            branch: None,
+            pending: HashSet::default(),
+            pc_hits: 0,
+            resumed: None,
+            restore_stats: SnapshotRestoreStats::default(),
         }
     }
 
@@ -163,6 +312,13 @@ impl SnapshotModule {
         self.accurate_unmap = true;
     }
 
+    /// Allow incremental snapshots at `pc`, see [`Self::request_incremental`]
+    #[must_use]
+    pub fn with_incremental_pc(mut self, pc: GuestAddr) -> Self {
+        self.incremental_pc = Some(pc);
+        self
+    }
+
     pub fn to_skip(&self, addr: GuestAddr) -> bool {
         for filter in &self.interval_filter {
             match filter {
@@ -375,17 +531,35 @@ impl SnapshotModule {
         log::info!("Snapshot check OK");
     }
 
+    /// Restore the memory to the state of the snapshot set with [`Self::branch_from`], the
+    /// root snapshot by default.
     pub fn reset(&mut self, qemu: Qemu) {
+        let start_time = current_time();
+        let target = self.branch;
+
+        // The pages differing between the current base and the target are restored as well,
+        // even if they were not written in the last run
+        let mut extra = self.chain_diff_pages(self.base, target);
+        extra.extend(self.pending.drain());
+        if !extra.is_empty() {
+            let acc = self.accesses.get_or_default().get();
+            unsafe { (*acc).dirty.extend(extra) };
+        }
+
+        let mut restored_pages = 0;
         {
             let new_maps = self.new_maps.get_mut().unwrap();
+            let incremental = &self.incremental;
+            let pages = &self.pages;
 
             log::debug!("Start restore");
 
             for acc in &mut self.accesses {
+                restored_pages += unsafe { &(*acc.get()) }.dirty.len();
                 unsafe { &mut (*acc.get()) }.dirty.retain(|page| {
-                    if let Some(info) = self.pages.get_mut(page) {
+                    if let Some(data) = lookup_page(incremental, pages, target, *page) {
                         // TODO avoid duplicated memcpy
-                        if let Some(data) = info.data.as_ref() {
+                        if let Some(data) = data {
                             // Change segment perms to RW if not writeable in current mapping
                             let mut found = false;
                             for entry in new_maps
@@ -421,38 +595,46 @@ impl SnapshotModule {
         self.reset_maps(qemu);
 
         // This one is after that we remapped potential regions mapped at snapshot time but unmapped during execution
+        let mut dirty = vec![];
         for acc in &mut self.accesses {
-            for page in unsafe { &(*acc.get()).dirty } {
-                for entry in self
-                    .maps
-                    .tree
-                    .query_mut(*page..(page + SNAPSHOT_PAGE_SIZE as GuestAddr))
+            dirty.extend(unsafe { &(*acc.get()) }.dirty.iter().copied());
+            unsafe { (*acc.get()).clear() };
+        }
+
+        let target_maps = self.target_maps_mut(target);
+        for page in &dirty {
+            for entry in target_maps
+                .tree
+                .query_mut(*page..(page + SNAPSHOT_PAGE_SIZE as GuestAddr))
+            {
+                if !entry.value.perms.unwrap_or(MmapPerms::None).writable() && !entry.value.changed
                 {
-                    if !entry.value.perms.unwrap_or(MmapPerms::None).writable()
-                        && !entry.value.changed
-                    {
-                        drop(qemu.mprotect(
-                            entry.interval.start,
-                            (entry.interval.end - entry.interval.start) as usize,
-                            MmapPerms::ReadWrite,
-                        ));
-                        entry.value.changed = true;
-                    }
+                    drop(qemu.mprotect(
+                        entry.interval.start,
+                        (entry.interval.end - entry.interval.start) as usize,
+                        MmapPerms::ReadWrite,
+                    ));
+                    entry.value.changed = true;
                 }
+            }
+        }
 
-                if let Some(info) = self.pages.get_mut(page) {
-                    // TODO avoid duplicated memcpy
-                    if let Some(data) = info.data.as_ref() {
-                        unsafe { qemu.write_mem(*page, &data[..]) };
-                    } else {
-                        panic!("Cannot restored a dirty but unsaved page");
-                    }
+        for page in &dirty {
+            if let Some(data) = lookup_page(&self.incremental, &self.pages, target, *page) {
+                // TODO avoid duplicated memcpy
+                if let Some(data) = data {
+                    unsafe { qemu.write_mem(*page, &data[..]) };
+                } else {
+                    panic!("Cannot restored a dirty but unsaved page");
                 }
             }
-            unsafe { (*acc.get()).clear() };
         }
 
-        for entry in self.maps.tree.query_mut(0..GuestAddr::MAX) {
+        for entry in self
+            .target_maps_mut(target)
+            .tree
+            .query_mut(0..GuestAddr::MAX)
+        {
             if entry.value.changed {
                 drop(qemu.mprotect(
                     entry.interval.start,
@@ -463,15 +645,225 @@ impl SnapshotModule {
             }
         }
 
-        qemu.set_brk(self.brk);
-        qemu.set_mmap_start(self.mmap_start);
+        if let Some(snapshot) = target.and_then(|id| self.incremental.get(&id)) {
+            qemu.set_brk(snapshot.brk);
+            qemu.set_mmap_start(snapshot.mmap_start);
+            qemu.current_cpu()
+                .unwrap_or_else(|| qemu.cpu_from_index(0))
+                .restore_state(&snapshot.cpu);
+            self.pc_hits = snapshot.hits;
+        } else {
+            qemu.set_brk(self.brk);
+            qemu.set_mmap_start(self.mmap_start);
+            self.pc_hits = 0;
+        }
+        self.base = target;
+
+        self.restore_stats.restores += 1;
+        self.restore_stats.pages += restored_pages as u64;
+        self.restore_stats.time += current_time() - start_time;
 
         #[cfg(feature = "paranoid_debug")]
-        self.check_snapshot(qemu);
+        if target.is_none() {
+            self.check_snapshot(qemu);
+        }
 
         log::debug!("End restore");
     }
 
+    /// Take a snapshot at the next `hit`-th execution of the incremental snapshot pc, counting
+    /// from the start of the run, or from the hits of the snapshot the run resumes from.
+    pub fn request_incremental(&mut self, hit: usize) {
+        self.incremental_request = Some(hit);
+        self.incremental_taken = None;
+    }
+
+    /// The snapshot taken for the last [`Self::request_incremental`], if the pc was reached
+    pub fn taken_incremental(&mut self) -> Option<IncrementalSnapshotId> {
+        self.incremental_request = None;
+        self.incremental_taken.take()
+    }
+
+    /// Restore the incremental snapshot `base` before the next runs, or the root snapshot if
+    /// `None`.
+    ///
+    /// The registers are restored as well: see [`Self::resumed`] for the harness.
+    pub fn branch_from(&mut self, base: Option<IncrementalSnapshotId>) {
+        debug_assert!(base.map_or(true, |id| self.incremental.contains_key(&id)));
+        self.branch = base;
+    }
+
+    #[must_use]
+    pub fn incremental(&self, id: IncrementalSnapshotId) -> Option<&IncrementalSnapshot> {
+        self.incremental.get(&id)
+    }
+
+    /// Drop the incremental snapshot `id` and the ones taken from it
+    pub fn drop_incremental(&mut self, id: IncrementalSnapshotId) {
+        let Some(parent) = self.incremental.get(&id).map(|snapshot| snapshot.parent) else {
+            return;
+        };
+        let in_chain = |ids: &[IncrementalSnapshotId]| ids.contains(&id);
+
+        // The memory is in the state of the base, so the pages of the dropped snapshots must be
+        // restored on the next reset
+        let base_chain = self.chain(self.base);
+        if in_chain(&base_chain) {
+            for dropped in base_chain.iter().take_while(|dropped| **dropped != id) {
+                self.pending
+                    .extend(self.incremental[dropped].pages.keys().copied());
+            }
+            self.pending
+                .extend(self.incremental[&id].pages.keys().copied());
+            self.base = parent;
+        }
+        if in_chain(&self.chain(self.branch)) {
+            self.branch = parent;
+        }
+
+        let dropped: Vec<IncrementalSnapshotId> = self
+            .incremental
+            .keys()
+            .copied()
+            .filter(|other| self.chain(Some(*other)).contains(&id))
+            .collect();
+        for dropped in dropped {
+            self.incremental.remove(&dropped);
+        }
+    }
+
+    /// The incremental snapshot the current run resumes from, if any.
+    ///
+    /// The registers were then restored at the incremental snapshot pc: the harness must not set
+    /// them up again, only provide the parts of the input consumed after `hits` executions of the
+    /// pc, and run. The harness can reach the module through
+    /// [`EmulatorModules::emulator_modules_mut`].
+    #[must_use]
+    pub fn resumed(&self) -> Option<ResumedSnapshot> {
+        self.resumed
+    }
+
+    /// The cost of the restores so far
+    #[must_use]
+    pub fn restore_stats(&self) -> &SnapshotRestoreStats {
+        &self.restore_stats
+    }
+
+    /// Report the average cost of the restores so far to the monitor.
+    ///
+    /// The executors owning a [`SnapshotModule`] call it after each run.
+    pub fn fire_restore_stats<EM>(
+        &self,
+        state: &mut EM::State,
+        manager: &mut EM,
+    ) -> Result<(), Error>
+    where
+        EM: EventFirer,
+    {
+        let stats = &self.restore_stats;
+        if stats.restores == 0 {
+            return Ok(());
+        }
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::from("snapshot_restore_pages"),
+                value: UserStats::new(
+                    UserStatsValue::Number(stats.pages_per_restore()),
+                    AggregatorOps::Avg,
+                ),
+                phantom: PhantomData,
+            },
+        )?;
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::from("snapshot_restore_us"),
+                value: UserStats::new(
+                    UserStatsValue::Number(stats.time_per_restore().as_micros() as u64),
+                    AggregatorOps::Avg,
+                ),
+                phantom: PhantomData,
+            },
+        )
+    }
+
+    /// Take an incremental snapshot of the current state, as a diff to the current base
+    pub fn take_incremental(&mut self, qemu: Qemu, hits: usize) -> IncrementalSnapshotId {
+        let cpu = qemu.current_cpu().unwrap_or_else(|| qemu.cpu_from_index(0));
+        let maps = self.new_maps.lock().unwrap().clone();
+
+        let mut pages = HashMap::new();
+        for acc in &mut self.accesses {
+            for page in unsafe { &(*acc.get()).dirty } {
+                let readable = maps
+                    .tree
+                    .query(*page..(page + SNAPSHOT_PAGE_SIZE as GuestAddr))
+                    .next()
+                    .and_then(|entry| entry.value.perms)
+                    .is_some_and(|perms| perms.readable());
+                // TODO not just for R pages
+                if readable {
+                    let mut data = Box::new([0; SNAPSHOT_PAGE_SIZE]);
+                    unsafe { qemu.read_mem(*page, &mut data[..]) };
+                    pages.insert(*page, data);
+                }
+            }
+        }
+
+        let id = IncrementalSnapshotId(self.next_incremental_id);
+        self.next_incremental_id += 1;
+        self.incremental.insert(
+            id,
+            IncrementalSnapshot {
+                parent: self.base,
+                hits,
+                pages,
+                maps,
+                brk: qemu.get_brk(),
+                mmap_start: qemu.get_mmap_start(),
+                cpu: Box::new(cpu.save_state()),
+            },
+        );
+        log::debug!("Incremental snapshot {id:?} taken at hit {hits}");
+        id
+    }
+
+    /// The incremental snapshots from `id` to the root, excluded
+    fn chain(&self, mut id: Option<IncrementalSnapshotId>) -> Vec<IncrementalSnapshotId> {
+        let mut chain = vec![];
+        while let Some(cur) = id {
+            chain.push(cur);
+            id = self
+                .incremental
+                .get(&cur)
+                .and_then(|snapshot| snapshot.parent);
+        }
+        chain
+    }
+
+    /// The pages saved by the snapshots in only one of the two chains
+    fn chain_diff_pages(
+        &self,
+        a: Option<IncrementalSnapshotId>,
+        b: Option<IncrementalSnapshotId>,
+    ) -> HashSet<GuestAddr> {
+        let (chain_a, chain_b) = (self.chain(a), self.chain(b));
+        chain_a
+            .iter()
+            .filter(|id| !chain_b.contains(id))
+            .chain(chain_b.iter().filter(|id| !chain_a.contains(id)))
+            .flat_map(|id| self.incremental[id].pages.keys().copied())
+            .collect()
+    }
+
+    fn target_maps_mut(&mut self, target: Option<IncrementalSnapshotId>) -> &mut MappingInfo {
+        match target {
+            Some(id) => &mut self.incremental.get_mut(&id).unwrap().maps,
+            None => &mut self.maps,
+        }
+    }
+
     pub fn is_unmap_allowed(&mut self, start: GuestAddr, mut size: usize) -> bool {
         if size % SNAPSHOT_PAGE_SIZE != 0 {
             size = size + (SNAPSHOT_PAGE_SIZE - size % SNAPSHOT_PAGE_SIZE);
@@ -602,8 +994,12 @@ impl SnapshotModule {
 
     pub fn reset_maps(&mut self, qemu: Qemu) {
         let new_maps = self.new_maps.get_mut().unwrap();
+        let target_maps = match self.branch {
+            Some(id) => &self.incremental[&id].maps,
+            None => &self.maps,
+        };
 
-        for entry in self.maps.tree.query(0..GuestAddr::MAX) {
+        for entry in target_maps.tree.query(0..GuestAddr::MAX) {
             let mut found = vec![]; //  TODO optimize
             for overlap in new_maps.tree.query(*entry.interval) {
                 found.push((
@@ -652,11 +1048,29 @@ impl SnapshotModule {
         }
 
         new_maps.tree.clear();
-        new_maps.tree = self.maps.tree.clone();
-        new_maps.size = self.maps.size;
+        new_maps.tree = target_maps.tree.clone();
+        new_maps.size = target_maps.size;
     }
 }
 
+/// The content of `page` in the snapshot `target`, looking up its parents up to the root
+/// snapshot. `None` if the page is not part of the snapshot.
+#[allow(clippy::option_option)]
+fn lookup_page<'a>(
+    incremental: &'a HashMap<IncrementalSnapshotId, IncrementalSnapshot>,
+    pages: &'a HashMap<GuestAddr, SnapshotPageInfo>,
+    mut target: Option<IncrementalSnapshotId>,
+    page: GuestAddr,
+) -> Option<Option<&'a [u8; SNAPSHOT_PAGE_SIZE]>> {
+    while let Some(snapshot) = target.and_then(|id| incremental.get(&id)) {
+        if let Some(data) = snapshot.pages.get(&page) {
+            return Some(Some(data));
+        }
+        target = snapshot.parent;
+    }
+    pages.get(&page).map(|info| info.data.as_deref())
+}
+
 impl Default for SnapshotModule {
     fn default() -> Self {
         Self::new()
@@ -687,6 +1101,14 @@ where
             emulator_modules.syscalls(Hook::Function(filter_mmap_snapshot::<ET, S>));
         }
         emulator_modules.after_syscalls(Hook::Function(trace_mmap_snapshot::<ET, S>));
+
+        if let Some(pc) = self.incremental_pc {
+            emulator_modules.instructions(
+                pc,
+                Hook::Function(trace_incremental_snapshot::<ET, S>),
+                true,
+            );
+        }
     }
 
     fn pre_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _input: &S::Input)
@@ -698,6 +1120,28 @@ where
         } else {
             self.reset(emulator_modules.qemu());
         }
+        self.resumed = self.base.map(|id| ResumedSnapshot {
+            id,
+            hits: self.pc_hits,
+        });
+    }
+}
+
+pub fn trace_incremental_snapshot<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _pc: GuestAddr,
+) where
+    S: Unpin + UsesInput,
+    ET: EmulatorModuleTuple<S>,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<SnapshotModule>().unwrap();
+    let hit = h.pc_hits;
+    h.pc_hits += 1;
+    if h.incremental_request == Some(hit) {
+        h.incremental_request = None;
+        h.incremental_taken = Some(h.take_incremental(qemu, hit));
     }
 }
 
@@ -873,3 +1317,362 @@ where
     }
     result
 }
+
+/// Executors giving access to the [`SnapshotModule`] of their emulator
+pub trait HasSnapshotModule {
+    /// The [`SnapshotModule`], if the emulator has one
+    fn snapshot_module_mut(&mut self) -> Option<&mut SnapshotModule>;
+}
+
+/// The default maximum number of mutations of [`SnapshotSuffixStage`] per testcase
+#[cfg(feature = "multipart_inputs")]
+pub const DEFAULT_SNAPSHOT_SUFFIX_MAX_ITERATIONS: usize = 128;
+
+/// A stage mutating only a suffix of the parts of a [`MultipartInput`], running the mutants from
+/// an incremental snapshot taken before the first mutated part is consumed.
+///
+/// The part `i` is assumed to be consumed after the `i`-th execution of the incremental
+/// snapshot pc (see [`SnapshotModule::with_incremental_pc`]), e.g. at each call to `recv`.
+/// The harness must handle runs resuming from a snapshot, see [`SnapshotModule::resumed`].
+/// The snapshots are kept for the current testcase: deeper snapshots are taken from the
+/// shallower ones.
+#[cfg(feature = "multipart_inputs")]
+#[derive(Debug)]
+pub struct SnapshotSuffixStage<E, EM, I, M, Z> {
+    name: Cow<'static, str>,
+    mutator: M,
+    max_iterations: usize,
+    corpus_id: Option<CorpusId>,
+    /// The snapshot before each part of the current testcase, if taken
+    snapshots: Vec<Option<IncrementalSnapshotId>>,
+    phantom: PhantomData<(E, EM, I, Z)>,
+}
+
+/// The name for the snapshot suffix stage
+#[cfg(feature = "multipart_inputs")]
+pub static SNAPSHOT_SUFFIX_STAGE_NAME: &str = "snapshot_suffix";
+
+#[cfg(feature = "multipart_inputs")]
+impl<E, EM, I, M, Z> SnapshotSuffixStage<E, EM, I, M, Z> {
+    /// Creates a new stage mutating the parts with `mutator`
+    #[must_use]
+    pub fn new(mutator: M) -> Self {
+        Self::with_max_iterations(mutator, DEFAULT_SNAPSHOT_SUFFIX_MAX_ITERATIONS)
+    }
+
+    /// Creates a new stage with the given max iterations
+    #[must_use]
+    pub fn with_max_iterations(mutator: M, max_iterations: usize) -> Self {
+        Self {
+            name: Cow::Borrowed(SNAPSHOT_SUFFIX_STAGE_NAME),
+            mutator,
+            max_iterations,
+            corpus_id: None,
+            snapshots: vec![],
+            phantom: PhantomData,
+        }
+    }
+
+    fn drop_snapshots(&mut self, module: &mut SnapshotModule) {
+        module.branch_from(None);
+        for id in self.snapshots.drain(..).flatten() {
+            module.drop_incremental(id);
+        }
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<E, EM, I, M, Z> UsesState for SnapshotSuffixStage<E, EM, I, M, Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<E, EM, I, M, Z> Named for SnapshotSuffixStage<E, EM, I, M, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<E, EM, I, M, Z> SnapshotSuffixStage<E, EM, I, M, Z>
+where
+    E: UsesState<State = Z::State> + HasSnapshotModule,
+    EM: UsesState<State = Z::State>,
+    Z: ExecutesInput<E, EM>,
+    Z::State: UsesInput<Input = MultipartInput<I>>,
+{
+    /// The snapshot before the part `split` of `input` is consumed, taking it if needed
+    fn snapshot_before(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+        input: &MultipartInput<I>,
+        split: usize,
+    ) -> Result<Option<IncrementalSnapshotId>, Error> {
+        if let Some(Some(id)) = self.snapshots.get(split) {
+            return Ok(Some(*id));
+        }
+
+        // Start from the deepest snapshot before this one
+        let base = self.snapshots[..split.min(self.snapshots.len())]
+            .iter()
+            .rev()
+            .find_map(|id| *id);
+        let module = executor
+            .snapshot_module_mut()
+            .ok_or_else(|| Error::illegal_state("The emulator has no SnapshotModule"))?;
+        module.branch_from(base);
+        module.request_incremental(split);
+
+        fuzzer.execute_input(state, executor, manager, input)?;
+
+        let id = executor.snapshot_module_mut().unwrap().taken_incremental();
+        if id.is_some() {
+            if self.snapshots.len() <= split {
+                self.snapshots.resize(split + 1, None);
+            }
+            self.snapshots[split] = id;
+        }
+        Ok(id)
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<E, EM, I, M, Z> Stage<E, EM, Z> for SnapshotSuffixStage<E, EM, I, M, Z>
+where
+    E: UsesState<State = Self::State> + HasSnapshotModule,
+    EM: UsesState<State = Self::State> + EventFirer,
+    M: Mutator<I, Self::State>,
+    Z: Evaluator<E, EM> + ExecutesInput<E, EM>,
+    Self::State: HasCorpus
+        + HasRand
+        + HasNamedMetadata
+        + HasCurrentCorpusId
+        + UsesInput<Input = MultipartInput<I>>,
+    I: Input,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(corpus_id) = state.current_corpus_id()? else {
+            return Err(Error::illegal_state(
+                "state is not currently processing a corpus index",
+            ));
+        };
+        if self.corpus_id != Some(corpus_id) {
+            if let Some(module) = executor.snapshot_module_mut() {
+                self.drop_snapshots(module);
+            }
+            self.corpus_id = Some(corpus_id);
+        }
+
+        let input = state.current_input_cloned()?;
+        let parts = input.parts().len();
+        if parts < 2 {
+            return Ok(());
+        }
+
+        let split = state.rand_mut().between(1, parts - 1);
+        let Some(id) = self.snapshot_before(fuzzer, executor, state, manager, &input, split)?
+        else {
+            return Ok(());
+        };
+
+        executor
+            .snapshot_module_mut()
+            .unwrap()
+            .branch_from(Some(id));
+        let iterations = 1 + state.rand_mut().below(self.max_iterations.max(1));
+        let mut result = Ok(());
+        for _ in 0..iterations {
+            let mut mutant = input.clone();
+            let part = state.rand_mut().between(split, parts - 1);
+            match self.mutator.mutate(state, mutant.part_mut(part).unwrap()) {
+                Ok(MutationResult::Mutated) => {}
+                Ok(MutationResult::Skipped) => continue,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+            match fuzzer.evaluate_input(state, executor, manager, mutant) {
+                Ok((_, new_corpus_id)) => self.mutator.post_exec(state, new_corpus_id)?,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        executor.snapshot_module_mut().unwrap().branch_from(None);
+        result
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        // Make sure we don't get stuck crashing on a single testcase
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use hashbrown::{HashMap, HashSet};
+    use libafl_qemu_sys::{GuestAddr, MmapPerms};
+
+    use super::{
+        lookup_page, IncrementalSnapshot, IncrementalSnapshotId, MappingInfo, SnapshotModule,
+        SnapshotPageInfo, SnapshotRestoreStats, SNAPSHOT_PAGE_SIZE,
+    };
+
+    fn page_of(byte: u8) -> Box<[u8; SNAPSHOT_PAGE_SIZE]> {
+        Box::new([byte; SNAPSHOT_PAGE_SIZE])
+    }
+
+    fn incremental(parent: Option<usize>, pages: &[(GuestAddr, u8)]) -> IncrementalSnapshot {
+        IncrementalSnapshot {
+            parent: parent.map(IncrementalSnapshotId),
+            hits: 0,
+            pages: pages
+                .iter()
+                .map(|(addr, byte)| (*addr, page_of(*byte)))
+                .collect(),
+            maps: MappingInfo::default(),
+            brk: 0,
+            mmap_start: 0,
+            cpu: Box::new(unsafe { core::mem::zeroed() }),
+        }
+    }
+
+    /// 0 <- 1 <- 2 and 0 <- 3
+    fn tree() -> HashMap<IncrementalSnapshotId, IncrementalSnapshot> {
+        HashMap::from([
+            (IncrementalSnapshotId(0), incremental(None, &[(0x1000, 0)])),
+            (
+                IncrementalSnapshotId(1),
+                incremental(Some(0), &[(0x1000, 1), (0x2000, 1)]),
+            ),
+            (
+                IncrementalSnapshotId(2),
+                incremental(Some(1), &[(0x3000, 2)]),
+            ),
+            (
+                IncrementalSnapshotId(3),
+                incremental(Some(0), &[(0x4000, 3)]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut module = SnapshotModule::new();
+        module.incremental = tree();
+        let ids = |ids: &[usize]| -> Vec<IncrementalSnapshotId> {
+            ids.iter().copied().map(IncrementalSnapshotId).collect()
+        };
+
+        assert!(module.chain(None).is_empty());
+        assert_eq!(
+            module.chain(Some(IncrementalSnapshotId(2))),
+            ids(&[2, 1, 0])
+        );
+        assert_eq!(module.chain(Some(IncrementalSnapshotId(3))), ids(&[3, 0]));
+
+        // The common root 0 is excluded
+        assert_eq!(
+            module.chain_diff_pages(
+                Some(IncrementalSnapshotId(2)),
+                Some(IncrementalSnapshotId(3))
+            ),
+            HashSet::from([0x1000, 0x2000, 0x3000, 0x4000])
+        );
+        assert_eq!(
+            module.chain_diff_pages(
+                Some(IncrementalSnapshotId(2)),
+                Some(IncrementalSnapshotId(1))
+            ),
+            HashSet::from([0x3000])
+        );
+        assert_eq!(
+            module.chain_diff_pages(None, Some(IncrementalSnapshotId(0))),
+            HashSet::from([0x1000])
+        );
+        assert!(module
+            .chain_diff_pages(
+                Some(IncrementalSnapshotId(1)),
+                Some(IncrementalSnapshotId(1))
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_lookup_page() {
+        let incremental = tree();
+        let pages = HashMap::from([
+            (
+                0x1000,
+                SnapshotPageInfo {
+                    addr: 0x1000,
+                    perms: MmapPerms::ReadWrite,
+                    private: true,
+                    data: Some(page_of(0xff)),
+                },
+            ),
+            (
+                0x5000,
+                SnapshotPageInfo {
+                    addr: 0x5000,
+                    perms: MmapPerms::None,
+                    private: true,
+                    data: None,
+                },
+            ),
+        ]);
+        let byte = |target: Option<usize>, page| {
+            lookup_page(
+                &incremental,
+                &pages,
+                target.map(IncrementalSnapshotId),
+                page,
+            )
+            .map(|data| data.map(|data| data[0]))
+        };
+
+        // The nearest snapshot saving the page wins
+        assert_eq!(byte(Some(2), 0x1000), Some(Some(1)));
+        assert_eq!(byte(Some(3), 0x1000), Some(Some(0)));
+        assert_eq!(byte(None, 0x1000), Some(Some(0xff)));
+        assert_eq!(byte(Some(2), 0x2000), Some(Some(1)));
+        // Not saved by the chain of 3, nor by the root snapshot
+        assert_eq!(byte(Some(3), 0x2000), None);
+        // Saved without data by the root snapshot
+        assert_eq!(byte(Some(2), 0x5000), Some(None));
+    }
+
+    #[test]
+    fn test_restore_stats() {
+        let mut stats = SnapshotRestoreStats::default();
+        assert_eq!(stats.pages_per_restore(), 0);
+        assert_eq!(stats.time_per_restore(), Duration::ZERO);
+
+        stats.restores = 4;
+        stats.pages = 10;
+        stats.time = Duration::from_micros(100);
+        assert_eq!(stats.pages_per_restore(), 2);
+        assert_eq!(stats.time_per_restore(), Duration::from_micros(25));
+    }
+}
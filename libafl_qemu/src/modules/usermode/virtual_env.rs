@@ -0,0 +1,646 @@
+//! Syscall-level virtualization of the guest environment.
+//!
+//! The [`VirtualEnvModule`] serves configured files from the current input or from memory,
+//! fixes the time and the randomness, and feeds accepted connections from the current input,
+//! so that binary-only targets, e.g. network daemons, become deterministic fuzzing targets.
+//!
+//! Only the calls of the guest reaching the kernel are virtualized: calls served by the vDSO,
+//! e.g. `clock_gettime` on some targets, are not seen by the module.
+
+use std::{collections::VecDeque, io};
+
+use hashbrown::HashMap;
+#[cfg(feature = "multipart_inputs")]
+use libafl::inputs::MultipartInput;
+use libafl::inputs::{BytesInput, HasTargetBytes, UsesInput};
+use libafl_bolts::{
+    rands::{Rand, StdRand},
+    AsSlice,
+};
+use libafl_qemu_sys::{GuestAddr, VerifyAccess};
+
+#[cfg(not(cpu_target = "i386"))]
+use crate::SYS_accept;
+#[cfg(not(cpu_target = "aarch64"))]
+use crate::SYS_open;
+#[cfg(any(cpu_target = "arm", cpu_target = "mips", cpu_target = "ppc"))]
+use crate::SYS_send;
+#[cfg(any(
+    cpu_target = "x86_64",
+    cpu_target = "i386",
+    cpu_target = "mips",
+    cpu_target = "ppc"
+))]
+use crate::SYS_time;
+use crate::{
+    emu::EmulatorModules,
+    modules::{EmulatorModule, EmulatorModuleTuple},
+    qemu::{Hook, SyscallHookResult},
+    Qemu, SYS_accept4, SYS_clock_gettime, SYS_close, SYS_getrandom, SYS_gettimeofday, SYS_openat,
+    SYS_sendto, SYS_write,
+};
+
+/// The maximum length of a path read from the guest
+const MAX_PATH_LEN: usize = 4096;
+
+/// The most bytes a single `getrandom` returns, as on Linux
+const MAX_GETRANDOM_LEN: usize = 33_554_431;
+
+/// The bytes `getrandom` generates at once before writing them to the guest
+const GETRANDOM_CHUNK_LEN: usize = 4096;
+
+/// The content of a virtual file
+#[derive(Debug, Clone)]
+pub enum VirtualFile {
+    /// The bytes of the current input
+    Input,
+    /// Fixed bytes
+    Bytes(Vec<u8>),
+}
+
+/// Inputs the [`VirtualEnvModule`] can serve virtual files and packets from
+pub trait VirtualEnvInput {
+    /// The content of the files served as [`VirtualFile::Input`]
+    fn file_bytes(&self) -> Vec<u8>;
+
+    /// The packets fed to accepted connections, if the input consists of packets.
+    ///
+    /// If `None`, the file bytes are split at the delimiter set with
+    /// [`VirtualEnvModule::with_packet_delimiter`], if any.
+    fn packets(&self) -> Option<VecDeque<Vec<u8>>> {
+        None
+    }
+}
+
+impl VirtualEnvInput for BytesInput {
+    fn file_bytes(&self) -> Vec<u8> {
+        self.target_bytes().as_slice().to_vec()
+    }
+}
+
+/// Each part is a packet, files get all parts concatenated
+#[cfg(feature = "multipart_inputs")]
+impl<I> VirtualEnvInput for MultipartInput<I>
+where
+    I: HasTargetBytes,
+{
+    fn file_bytes(&self) -> Vec<u8> {
+        self.parts()
+            .iter()
+            .flat_map(|part| part.target_bytes().as_slice().to_vec())
+            .collect()
+    }
+
+    fn packets(&self) -> Option<VecDeque<Vec<u8>>> {
+        Some(
+            self.parts()
+                .iter()
+                .map(|part| part.target_bytes().as_slice().to_vec())
+                .collect(),
+        )
+    }
+}
+
+/// A descriptor of the guest handed out by the [`VirtualEnvModule`]
+#[derive(Debug)]
+enum VirtualFd {
+    /// A memfd holding the content of a virtual file
+    File,
+    /// The guest end of a socketpair, the packets are written to the `feeder` end
+    Connection { feeder: i32, shut_down: bool },
+}
+
+/// Virtualizes the files, the time, the randomness and the network of the guest.
+///
+/// Virtual files and connections are backed by real descriptors: a memfd holding the content
+/// for files, and a socketpair fed with the packets of the input for connections. All syscalls on
+/// them, e.g. `fstat`, `mmap`, `poll`, `readv` or `dup`, therefore behave as usual.
+/// Descriptors the guest did not close are closed before the next run, duplicates are not tracked.
+#[derive(Debug)]
+pub struct VirtualEnvModule {
+    files: HashMap<String, VirtualFile>,
+    time: Option<u64>,
+    random_seed: Option<u64>,
+    network: bool,
+    packet_delimiter: Option<Vec<u8>>,
+    input: Vec<u8>,
+    packets: VecDeque<Vec<u8>>,
+    fds: HashMap<i32, VirtualFd>,
+    rand: StdRand,
+}
+
+impl Default for VirtualEnvModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualEnvModule {
+    /// A module virtualizing nothing, see the `with_*` methods
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            time: None,
+            random_seed: None,
+            network: false,
+            packet_delimiter: None,
+            input: vec![],
+            packets: VecDeque::new(),
+            fds: HashMap::new(),
+            rand: StdRand::with_seed(0),
+        }
+    }
+
+    /// Serve the current input when the guest opens `path`
+    #[must_use]
+    pub fn with_input_file(mut self, path: &str) -> Self {
+        self.files.insert(path.to_string(), VirtualFile::Input);
+        self
+    }
+
+    /// Serve `content` when the guest opens `path`
+    #[must_use]
+    pub fn with_file(mut self, path: &str, content: Vec<u8>) -> Self {
+        self.files
+            .insert(path.to_string(), VirtualFile::Bytes(content));
+        self
+    }
+
+    /// Fix the time seen by the guest to `secs` since the epoch
+    #[must_use]
+    pub fn with_time(mut self, secs: u64) -> Self {
+        self.time = Some(secs);
+        self
+    }
+
+    /// Serve `getrandom` from a generator reseeded with `seed` before each run
+    #[must_use]
+    pub fn with_random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Feed the connections accepted by the guest from the current input.
+    ///
+    /// Each `recv` returns at most one packet. Each part of a `MultipartInput` is a packet (with
+    /// the `multipart_inputs` feature), other inputs are a single packet, unless a delimiter is
+    /// set with [`Self::with_packet_delimiter`].
+    /// Once all the packets have been consumed, reads return EOF and `accept` fails.
+    #[must_use]
+    pub fn with_network(mut self) -> Self {
+        self.network = true;
+        self
+    }
+
+    /// Split inputs that don't consist of packets at each occurrence of `delimiter`
+    #[must_use]
+    pub fn with_packet_delimiter(mut self, delimiter: Vec<u8>) -> Self {
+        self.packet_delimiter = Some(delimiter);
+        self
+    }
+
+    fn reset<I>(&mut self, input: &I)
+    where
+        I: VirtualEnvInput,
+    {
+        self.close_all();
+        self.input = input.file_bytes();
+        self.packets = match (input.packets(), &self.packet_delimiter) {
+            (Some(packets), _) => packets,
+            (None, Some(delimiter)) if !delimiter.is_empty() => {
+                split_packets(&self.input, delimiter)
+            }
+            (None, _) => VecDeque::from([self.input.clone()]),
+        };
+        self.packets.retain(|p| !p.is_empty());
+        if let Some(seed) = self.random_seed {
+            self.rand.set_seed(seed);
+        }
+    }
+
+    /// Close all descriptors the guest left open
+    fn close_all(&mut self) {
+        for fd in self.fds.keys().copied().collect::<Vec<_>>() {
+            self.forget(fd);
+            // The guest did not close it
+            unsafe { libc::close(fd) };
+        }
+    }
+
+    /// Stop tracking `fd`, as the guest closes it. Returns `false` if it is not virtual.
+    fn forget(&mut self, fd: i32) -> bool {
+        match self.fds.remove(&fd) {
+            Some(VirtualFd::Connection { feeder, .. }) => {
+                unsafe { libc::close(feeder) };
+                true
+            }
+            Some(VirtualFd::File) => true,
+            None => false,
+        }
+    }
+
+    /// Open the virtual file at `path`, as a memfd holding its content.
+    /// Returns `None` if `path` is not virtual, or the errno if the memfd could not be created.
+    fn open(&mut self, path: &str) -> Option<Result<i32, i32>> {
+        let data = match self.files.get(path)? {
+            VirtualFile::Input => &self.input,
+            VirtualFile::Bytes(bytes) => bytes,
+        };
+        let res = memfd_with(data);
+        if let Ok(fd) = res {
+            self.fds.insert(fd, VirtualFd::File);
+        }
+        Some(res)
+    }
+
+    /// Accept a new connection, failing with `ECONNABORTED` once all packets have been consumed
+    fn accept(&mut self) -> Result<i32, i32> {
+        if self.packets.is_empty() {
+            return Err(libc::ECONNABORTED);
+        }
+        let mut pair = [0; 2];
+        if unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, pair.as_mut_ptr()) } < 0 {
+            return Err(last_errno());
+        }
+        let [guest, feeder] = pair;
+        unsafe { libc::fcntl(feeder, libc::F_SETFL, libc::O_NONBLOCK) };
+        self.fds.insert(
+            guest,
+            VirtualFd::Connection {
+                feeder,
+                shut_down: false,
+            },
+        );
+        self.feed_connections();
+        Ok(guest)
+    }
+
+    /// Returns `true` if `fd` is a virtual connection
+    fn is_connection(&self, fd: i32) -> bool {
+        matches!(self.fds.get(&fd), Some(VirtualFd::Connection { .. }))
+    }
+
+    /// Hand the next packet to each connection that consumed its previous one, and drop
+    /// whatever the guest sent. Connections read EOF once all the packets are consumed.
+    fn feed_connections(&mut self) {
+        for (&guest, fd) in &mut self.fds {
+            let VirtualFd::Connection { feeder, shut_down } = fd else {
+                continue;
+            };
+            drain(*feeder);
+            if *shut_down || unread_bytes(guest) > 0 {
+                continue;
+            }
+            if let Some(packet) = self.packets.front_mut() {
+                // A packet larger than the socket buffer is written in several steps
+                let written = unsafe { libc::write(*feeder, packet.as_ptr().cast(), packet.len()) };
+                if let Ok(written) = usize::try_from(written) {
+                    packet.drain(..written);
+                    if packet.is_empty() {
+                        self.packets.pop_front();
+                    }
+                }
+            } else {
+                unsafe { libc::shutdown(*feeder, libc::SHUT_WR) };
+                *shut_down = true;
+            }
+        }
+    }
+}
+
+/// The errno of the last failed libc call
+fn last_errno() -> i32 {
+    io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EIO)
+}
+
+/// Create a memfd holding `data`, positioned at its start
+fn memfd_with(data: &[u8]) -> Result<i32, i32> {
+    let fd = unsafe { libc::memfd_create(c"libafl_virtual_file".as_ptr(), 0) };
+    if fd < 0 {
+        return Err(last_errno());
+    }
+    let mut written = 0;
+    while written < data.len() {
+        let ret = unsafe { libc::write(fd, data[written..].as_ptr().cast(), data.len() - written) };
+        let Ok(ret) = usize::try_from(ret) else {
+            let errno = last_errno();
+            unsafe { libc::close(fd) };
+            return Err(errno);
+        };
+        written += ret;
+    }
+    unsafe { libc::lseek(fd, 0, libc::SEEK_SET) };
+    Ok(fd)
+}
+
+/// The amount of bytes waiting to be read from the socket `fd`
+fn unread_bytes(fd: i32) -> i32 {
+    let mut unread: libc::c_int = 0;
+    if unsafe { libc::ioctl(fd, libc::FIONREAD, &mut unread) } < 0 {
+        return 0;
+    }
+    unread
+}
+
+/// Drop all bytes waiting on the non-blocking socket `fd`
+fn drain(fd: i32) {
+    let mut buf = [0_u8; 4096];
+    while unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+}
+
+fn split_packets(input: &[u8], delimiter: &[u8]) -> VecDeque<Vec<u8>> {
+    let mut packets = VecDeque::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i + delimiter.len() <= input.len() {
+        if &input[i..i + delimiter.len()] == delimiter {
+            packets.push_back(input[start..i].to_vec());
+            i += delimiter.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    packets.push_back(input[start..].to_vec());
+    packets.retain(|p| !p.is_empty());
+    packets
+}
+
+/// The return value of a syscall failing with `errno`
+fn syscall_error(errno: i32) -> SyscallHookResult {
+    SyscallHookResult::new(Some((-errno) as GuestAddr))
+}
+
+/// Encode `value` as a guest word
+fn guest_word(value: u64) -> Vec<u8> {
+    let value = value as GuestAddr;
+    #[cfg(feature = "be")]
+    let bytes = value.to_be_bytes();
+    #[cfg(not(feature = "be"))]
+    let bytes = value.to_le_bytes();
+    bytes.to_vec()
+}
+
+/// Write `buf` to the guest, failing if the memory is not writable
+fn write_guest(qemu: Qemu, addr: GuestAddr, buf: &[u8]) -> bool {
+    if !qemu.access_ok(VerifyAccess::Write, addr, buf.len()) {
+        return false;
+    }
+    // The access was checked above
+    unsafe { qemu.write_mem(addr, buf) };
+    true
+}
+
+/// Read a NUL terminated string from the guest
+fn read_guest_cstr(qemu: Qemu, addr: GuestAddr) -> Option<String> {
+    let mut bytes = vec![];
+    for i in 0..MAX_PATH_LEN {
+        let cur = addr + i as GuestAddr;
+        if !qemu.access_ok(VerifyAccess::Read, cur, 1) {
+            return None;
+        }
+        let mut byte = [0];
+        // The access was checked above
+        unsafe { qemu.read_mem(cur, &mut byte) };
+        if byte[0] == 0 {
+            return String::from_utf8(bytes).ok();
+        }
+        bytes.push(byte[0]);
+    }
+    None
+}
+
+impl<S> EmulatorModule<S> for VirtualEnvModule
+where
+    S: Unpin + UsesInput,
+    S::Input: VirtualEnvInput,
+{
+    fn init_module<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.syscalls(Hook::Function(syscall_virtual_env::<ET, S>));
+    }
+
+    fn pre_exec<ET>(&mut self, _emulator_modules: &mut EmulatorModules<ET, S>, input: &S::Input)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.reset(input);
+    }
+}
+
+/// The syscall hook of the [`VirtualEnvModule`].
+///
+/// Opens virtual files and accepts virtual connections, serves the time and the randomness,
+/// and feeds the next packets to the connections before each syscall of the guest.
+/// All other syscalls, including those on virtual descriptors, reach the kernel.
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+#[allow(non_upper_case_globals)]
+pub fn syscall_virtual_env<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    S: Unpin + UsesInput,
+    S::Input: VirtualEnvInput,
+    ET: EmulatorModuleTuple<S>,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<VirtualEnvModule>().unwrap();
+
+    // Whatever the guest is about to do, e.g. `poll` or `recvmsg`, it sees the next packet
+    h.feed_connections();
+
+    let open = |h: &mut VirtualEnvModule, path: GuestAddr| {
+        let path = read_guest_cstr(qemu, path)?;
+        Some(match h.open(&path)? {
+            Ok(fd) => SyscallHookResult::new(Some(fd as GuestAddr)),
+            Err(errno) => syscall_error(errno),
+        })
+    };
+    let accept = |h: &mut VirtualEnvModule| match h.accept() {
+        Ok(fd) => SyscallHookResult::new(Some(fd as GuestAddr)),
+        Err(errno) => syscall_error(errno),
+    };
+    let write_words = |words: &[u64], addr: GuestAddr| {
+        if addr == 0 {
+            return SyscallHookResult::new(Some(0));
+        }
+        let buf: Vec<u8> = words.iter().flat_map(|w| guest_word(*w)).collect();
+        if write_guest(qemu, addr, &buf) {
+            SyscallHookResult::new(Some(0))
+        } else {
+            syscall_error(libc::EFAULT)
+        }
+    };
+
+    let result = match i64::from(sys_num) {
+        #[cfg(not(cpu_target = "aarch64"))]
+        SYS_open => open(h, a0),
+        SYS_openat => open(h, a1),
+        // Nobody listens on the other end, don't let the guest fill up the socket
+        SYS_write | SYS_sendto if h.is_connection(a0 as i32) => {
+            Some(SyscallHookResult::new(Some(a2)))
+        }
+        #[cfg(any(cpu_target = "arm", cpu_target = "mips", cpu_target = "ppc"))]
+        SYS_send if h.is_connection(a0 as i32) => Some(SyscallHookResult::new(Some(a2))),
+        SYS_close => {
+            // Let the kernel close the descriptor of the guest
+            h.forget(a0 as i32);
+            None
+        }
+        #[cfg(not(cpu_target = "i386"))]
+        SYS_accept if h.network => Some(accept(h)),
+        SYS_accept4 if h.network => Some(accept(h)),
+        #[cfg(any(
+            cpu_target = "x86_64",
+            cpu_target = "i386",
+            cpu_target = "mips",
+            cpu_target = "ppc"
+        ))]
+        SYS_time => h.time.map(|secs| {
+            if a0 != 0 && !write_guest(qemu, a0, &guest_word(secs)) {
+                return syscall_error(libc::EFAULT);
+            }
+            SyscallHookResult::new(Some(secs as GuestAddr))
+        }),
+        // struct timespec
+        SYS_clock_gettime => h.time.map(|secs| write_words(&[secs, 0], a1)),
+        // struct timeval
+        SYS_gettimeofday => h.time.map(|secs| write_words(&[secs, 0], a0)),
+        SYS_getrandom => h.random_seed.map(|_| {
+            let len = (a1 as usize).min(MAX_GETRANDOM_LEN);
+            let mut chunk = [0_u8; GETRANDOM_CHUNK_LEN];
+            let mut written = 0;
+            while written < len {
+                let n = (len - written).min(GETRANDOM_CHUNK_LEN);
+                for byte in &mut chunk[..n] {
+                    *byte = h.rand.next() as u8;
+                }
+                if !write_guest(qemu, a0 + written as GuestAddr, &chunk[..n]) {
+                    // Like the kernel, report the bytes written before the fault
+                    return if written == 0 {
+                        syscall_error(libc::EFAULT)
+                    } else {
+                        SyscallHookResult::new(Some(written as GuestAddr))
+                    };
+                }
+                written += n;
+            }
+            SyscallHookResult::new(Some(written as GuestAddr))
+        }),
+        _ => None,
+    };
+
+    result.unwrap_or_else(|| SyscallHookResult::new(None))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use libafl::inputs::BytesInput;
+    #[cfg(feature = "multipart_inputs")]
+    use libafl::inputs::MultipartInput;
+
+    #[cfg(feature = "multipart_inputs")]
+    use super::VirtualEnvInput;
+    use super::{split_packets, VirtualEnvModule};
+
+    fn read_fd(fd: i32, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        let read = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), len) };
+        buf.truncate(usize::try_from(read).unwrap());
+        buf
+    }
+
+    #[test]
+    fn test_split_packets() {
+        assert_eq!(
+            split_packets(b"GET||POST||||PUT", b"||"),
+            VecDeque::from([b"GET".to_vec(), b"POST".to_vec(), b"PUT".to_vec()])
+        );
+        assert_eq!(
+            split_packets(b"no delimiter", b"||"),
+            VecDeque::from([b"no delimiter".to_vec()])
+        );
+        assert!(split_packets(b"||", b"||").is_empty());
+    }
+
+    #[test]
+    fn test_virtual_file() {
+        let mut module = VirtualEnvModule::new()
+            .with_input_file("/input")
+            .with_file("/etc/config", b"fixed".to_vec());
+        module.reset(&BytesInput::new(b"hello world".to_vec()));
+        assert!(module.open("/etc/passwd").is_none());
+
+        let fd = module.open("/input").unwrap().unwrap();
+        assert_eq!(read_fd(fd, 5), b"hello");
+        // The descriptor is real, seek and fstat work as usual
+        assert_eq!(unsafe { libc::lseek(fd, -3, libc::SEEK_END) }, 8);
+        assert_eq!(read_fd(fd, 16), b"rld");
+        assert_eq!(read_fd(fd, 16), b"");
+        let mut stat: libc::stat = unsafe { core::mem::zeroed() };
+        assert_eq!(unsafe { libc::fstat(fd, core::ptr::addr_of_mut!(stat)) }, 0);
+        assert_eq!(stat.st_size, 11);
+
+        let config = module.open("/etc/config").unwrap().unwrap();
+        assert_eq!(read_fd(config, 16), b"fixed");
+
+        // The next run closes what the guest left open
+        module.reset(&BytesInput::new(vec![]));
+        assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+        assert_eq!(unsafe { libc::fcntl(config, libc::F_GETFD) }, -1);
+    }
+
+    #[test]
+    fn test_connection_packets() {
+        let mut module = VirtualEnvModule::new()
+            .with_network()
+            .with_packet_delimiter(b"||".to_vec());
+        module.reset(&BytesInput::new(b"one||two".to_vec()));
+
+        let fd = module.accept().unwrap();
+        // Only the first packet, even for a larger read
+        assert_eq!(read_fd(fd, 16), b"one");
+        module.feed_connections();
+        assert_eq!(read_fd(fd, 2), b"tw");
+        // Not consumed yet, nothing is fed
+        module.feed_connections();
+        assert_eq!(read_fd(fd, 16), b"o");
+        module.feed_connections();
+        assert_eq!(read_fd(fd, 16), b"");
+        assert_eq!(module.accept(), Err(libc::ECONNABORTED));
+
+        assert!(module.forget(fd));
+        unsafe { libc::close(fd) };
+    }
+
+    #[test]
+    #[cfg(feature = "multipart_inputs")]
+    fn test_multipart_packets() {
+        let input = MultipartInput::from([
+            ("first", BytesInput::new(b"one".to_vec())),
+            ("second", BytesInput::new(b"two".to_vec())),
+        ]);
+        assert_eq!(input.file_bytes(), b"onetwo");
+        assert_eq!(
+            input.packets(),
+            Some(VecDeque::from([b"one".to_vec(), b"two".to_vec()]))
+        );
+    }
+}
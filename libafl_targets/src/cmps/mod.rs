@@ -53,6 +53,14 @@ extern "C" {
     /// Logs a routine for feedback during fuzzing
     pub fn __libafl_targets_cmplog_routines(k: usize, ptr1: *const u8, ptr2: *const u8);
 
+    /// Logs a routine comparing `len` bytes for feedback during fuzzing
+    pub fn __libafl_targets_cmplog_routines_len(
+        k: usize,
+        ptr1: *const u8,
+        ptr2: *const u8,
+        len: usize,
+    );
+
     /// Pointer to the `CmpLog` map
     pub static mut libafl_cmplog_map_ptr: *mut CmpLogMap;
 }
@@ -1,11 +1,23 @@
 //! [`DrCov`](https://dynamorio.org/page_drcov.html) support for `LibAFL` frida mode,
 //! writing basic-block trace files to be read by coverage analysis tools, such as [Lighthouse](https://github.com/gaasedelen/lighthouse),
 //! [bncov](https://github.com/ForAllSecure/bncov), [dragondance](https://github.com/0ffffffffh/dragondance), etc.
+//!
+//! `DrCov` files can be read back with [`DrCovReader`] and merged with [`DrCovCoverage`].
+//! With the `drcov_lines` feature, merged coverage can be mapped to source lines using
+//! the DWARF line tables of the modules, and exported as lcov or Cobertura XML.
 
-use alloc::{string::String, vec::Vec};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "drcov_lines")]
+use core::fmt::Write as _;
 use core::ptr::addr_of;
+#[cfg(feature = "drcov_lines")]
+use std::path::PathBuf;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{BufWriter, Write},
     path::Path,
 };
@@ -93,9 +105,16 @@ impl<'a> DrCovWriter<'a> {
             .unwrap();
         for block in basic_blocks {
             let (range, (id, _)) = self.module_mapping.get_key_value(&block.start).unwrap();
+            let size = u16::try_from(block.end - block.start).map_err(|_| {
+                Error::illegal_argument(format!(
+                    "Basic block at {:#x} is too large for DrCov ({} bytes)",
+                    block.start,
+                    block.end - block.start
+                ))
+            })?;
             let basic_block = DrCovBasicBlockEntry {
                 start: (block.start - range.start) as u32,
-                size: (block.end - block.start) as u16,
+                size,
                 mod_id: *id,
             };
             writer
@@ -109,3 +128,757 @@ impl<'a> DrCovWriter<'a> {
         Ok(())
     }
 }
+
+/// A module listed in the module table of a `DrCov` file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrCovModuleEntry {
+    /// The id of this module, referenced by the basic blocks
+    pub id: u16,
+    /// Start address of this module
+    pub base: usize,
+    /// End address of this module
+    pub end: usize,
+    /// Path of this module
+    pub path: String,
+}
+
+/// A basic block of a `DrCov` file, relative to its module
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrCovBlock {
+    /// The id of the module containing this basic block
+    pub module: u16,
+    /// Offset of this basic block from the module base
+    pub offset: u32,
+    /// Size of this basic block
+    pub size: u16,
+}
+
+/// A reader for `DrCov` files, such as the ones written by [`DrCovWriter`]
+#[derive(Clone, Debug, Default)]
+pub struct DrCovReader {
+    /// The module table
+    pub modules: Vec<DrCovModuleEntry>,
+    /// The basic blocks, in file order
+    pub blocks: Vec<DrCovBlock>,
+}
+
+/// Read the next `\n`-terminated line of `data`, starting at `pos`
+fn drcov_line<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a str, Error> {
+    let rest = &data[*pos..];
+    let Some(len) = rest.iter().position(|c| *c == b'\n') else {
+        return Err(Error::illegal_argument("Truncated DrCov header"));
+    };
+    *pos += len + 1;
+    core::str::from_utf8(&rest[..len])
+        .map(|line| line.trim_end_matches('\r'))
+        .map_err(|_| Error::illegal_argument("Invalid UTF-8 in DrCov header"))
+}
+
+fn drcov_number(value: &str) -> Result<usize, Error> {
+    let value = value.trim();
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| Error::illegal_argument(format!("Invalid number {value} in DrCov file")))
+}
+
+impl DrCovReader {
+    /// Read a `DrCov` file
+    pub fn read<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Parse the content of a `DrCov` file.
+    ///
+    /// Module tables of version 1 to 4 are supported, the basic block table must be binary.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut pos = 0;
+
+        let version = drcov_line(data, &mut pos)?;
+        if !version.starts_with("DRCOV VERSION:") {
+            return Err(Error::illegal_argument("Not a DrCov file"));
+        }
+        let mut line = drcov_line(data, &mut pos)?;
+        if line.starts_with("DRCOV FLAVOR:") {
+            line = drcov_line(data, &mut pos)?;
+        }
+
+        // Either `Module Table: <count>` (version 1) or `Module Table: version <v>, count <count>`
+        let Some(table) = line.strip_prefix("Module Table:") else {
+            return Err(Error::illegal_argument("Missing DrCov module table"));
+        };
+        let (table_version, count) = match table.split_once(',') {
+            Some((version, count)) => (
+                drcov_number(version.trim().trim_start_matches("version"))?,
+                drcov_number(count.trim().trim_start_matches("count"))?,
+            ),
+            None => (1, drcov_number(table)?),
+        };
+
+        let columns: Vec<String> = if table_version > 1 {
+            let line = drcov_line(data, &mut pos)?;
+            let Some(columns) = line.strip_prefix("Columns:") else {
+                return Err(Error::illegal_argument(
+                    "Missing DrCov module table columns",
+                ));
+            };
+            columns.split(',').map(|c| c.trim().to_string()).collect()
+        } else {
+            ["id", "base", "end", "entry", "path"]
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        let column = |names: &[&str]| {
+            columns
+                .iter()
+                .position(|c| names.contains(&c.as_str()))
+                .ok_or_else(|| Error::illegal_argument(format!("Missing DrCov column {names:?}")))
+        };
+        let id_col = column(&["id"])?;
+        let base_col = column(&["base", "start"])?;
+        let end_col = column(&["end"])?;
+        let path_col = column(&["path"])?;
+
+        let mut modules = Vec::with_capacity(count);
+        for _ in 0..count {
+            let line = drcov_line(data, &mut pos)?;
+            // The path is always the last column and may contain commas
+            let fields: Vec<&str> = line.splitn(columns.len(), ',').map(str::trim).collect();
+            if fields.len() != columns.len() {
+                return Err(Error::illegal_argument(format!(
+                    "Malformed DrCov module entry: {line}"
+                )));
+            }
+            let id = drcov_number(fields[id_col])?;
+            modules.push(DrCovModuleEntry {
+                id: u16::try_from(id).map_err(|_| {
+                    Error::illegal_argument(format!("DrCov module id {id} is out of range"))
+                })?,
+                base: drcov_number(fields[base_col])?,
+                end: drcov_number(fields[end_col])?,
+                path: fields[path_col].to_string(),
+            });
+        }
+
+        let line = drcov_line(data, &mut pos)?;
+        let Some(bbs) = line
+            .strip_prefix("BB Table:")
+            .map(|bbs| bbs.trim().trim_end_matches("bbs"))
+        else {
+            return Err(Error::illegal_argument("Missing DrCov basic block table"));
+        };
+        let bbs = drcov_number(bbs)?;
+
+        let table = bbs
+            .checked_mul(8)
+            .and_then(|len| data[pos..].get(..len))
+            .ok_or_else(|| {
+                Error::illegal_argument("Truncated or non-binary DrCov basic block table")
+            })?;
+        let blocks = table
+            .chunks_exact(8)
+            .map(|entry| DrCovBlock {
+                offset: u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                size: u16::from_le_bytes([entry[4], entry[5]]),
+                module: u16::from_le_bytes([entry[6], entry[7]]),
+            })
+            .collect();
+
+        Ok(Self { modules, blocks })
+    }
+
+    /// Get the module with the given id
+    #[must_use]
+    pub fn module(&self, id: u16) -> Option<&DrCovModuleEntry> {
+        self.modules.iter().find(|module| module.id == id)
+    }
+
+    /// The basic blocks with absolute addresses, as passed to [`DrCovWriter::write`]
+    #[must_use]
+    pub fn basic_blocks(&self) -> Vec<DrCovBasicBlock> {
+        self.blocks
+            .iter()
+            .filter_map(|block| {
+                self.module(block.module).map(|module| {
+                    DrCovBasicBlock::with_size(
+                        module.base + block.offset as usize,
+                        block.size as usize,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// A basic block of merged [`DrCovCoverage`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrCovBlockHits {
+    /// Size of this basic block
+    pub size: u16,
+    /// How often this basic block appears in the merged files
+    pub hits: u64,
+}
+
+/// Basic block coverage merged from multiple `DrCov` files.
+///
+/// Blocks are keyed by module path and offset, so files from runs with different
+/// load addresses merge correctly. Every occurrence of a block counts as a hit.
+#[derive(Clone, Debug, Default)]
+pub struct DrCovCoverage {
+    modules: BTreeMap<String, BTreeMap<u32, DrCovBlockHits>>,
+}
+
+impl DrCovCoverage {
+    /// Create new, empty, [`DrCovCoverage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge all the given `DrCov` files
+    pub fn from_files<P, I>(paths: I) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = P>,
+    {
+        let mut coverage = Self::new();
+        for path in paths {
+            coverage.add_file(path)?;
+        }
+        Ok(coverage)
+    }
+
+    /// Read and merge a `DrCov` file
+    pub fn add_file<P>(&mut self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.add(&DrCovReader::read(path)?);
+        Ok(())
+    }
+
+    /// Merge the blocks of a parsed `DrCov` file
+    pub fn add(&mut self, reader: &DrCovReader) {
+        for block in &reader.blocks {
+            let Some(module) = reader.module(block.module) else {
+                continue;
+            };
+            let entry = self
+                .modules
+                .entry(module.path.clone())
+                .or_default()
+                .entry(block.offset)
+                .or_default();
+            entry.size = entry.size.max(block.size);
+            entry.hits += 1;
+        }
+    }
+
+    /// The merged blocks of every module, keyed by module path and block offset
+    #[must_use]
+    pub fn modules(&self) -> &BTreeMap<String, BTreeMap<u32, DrCovBlockHits>> {
+        &self.modules
+    }
+
+    /// Map the merged blocks to source lines, loading the debug info from the module paths
+    #[cfg(feature = "drcov_lines")]
+    #[must_use]
+    pub fn line_coverage(&self) -> LineCoverage {
+        self.line_coverage_with(|path| Some(PathBuf::from(path)))
+    }
+
+    /// Map the merged blocks to source lines.
+    ///
+    /// `debug_file` returns the file holding the debug info of a module path, for example a
+    /// local copy or a separate debug file, or `None` to skip the module.
+    /// Modules without usable DWARF line tables are skipped with a warning.
+    #[cfg(feature = "drcov_lines")]
+    #[must_use]
+    pub fn line_coverage_with<F>(&self, mut debug_file: F) -> LineCoverage
+    where
+        F: FnMut(&str) -> Option<PathBuf>,
+    {
+        let mut coverage = LineCoverage::default();
+        for (module, blocks) in &self.modules {
+            let Some(file) = debug_file(module) else {
+                continue;
+            };
+            match module_line_coverage(&file, blocks) {
+                Ok(files) => {
+                    coverage.modules.insert(module.clone(), files);
+                }
+                Err(err) => log::warn!("Skipping DrCov module {module}: {err}"),
+            }
+        }
+        coverage
+    }
+}
+
+/// Source line hit counts of a single module, keyed by source file and line
+#[cfg(feature = "drcov_lines")]
+pub type SourceLineHits = BTreeMap<String, BTreeMap<u32, u64>>;
+
+#[cfg(feature = "drcov_lines")]
+fn module_line_coverage(
+    path: &Path,
+    blocks: &BTreeMap<u32, DrCovBlockHits>,
+) -> Result<SourceLineHits, Error> {
+    use addr2line::gimli::{self, EndianSlice, RunTimeEndian};
+    use object::{Object, ObjectSection, ObjectSegment};
+
+    let data = fs::read(path)?;
+    let object = object::File::parse(&*data).map_err(|err| {
+        Error::illegal_argument(format!("Could not parse {}: {err}", path.display()))
+    })?;
+    let endian = if object.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+        let section = object
+            .section_by_name(id.name())
+            .and_then(|section| section.data().ok())
+            .unwrap_or(&[]);
+        Ok(EndianSlice::new(section, endian))
+    })
+    .map_err(|err| {
+        Error::illegal_argument(format!("Invalid DWARF in {}: {err}", path.display()))
+    })?;
+    let context = addr2line::Context::from_dwarf(dwarf).map_err(|err| {
+        Error::illegal_argument(format!("Invalid DWARF in {}: {err}", path.display()))
+    })?;
+
+    // DrCov offsets are relative to the start of the first mapped segment
+    let base = match object.relative_address_base() {
+        0 => object
+            .segments()
+            .filter(|segment| segment.file_range().1 != 0)
+            .map(|segment| segment.address())
+            .min()
+            .unwrap_or(0),
+        base => base,
+    };
+
+    let dwarf_error = |err: gimli::Error| {
+        Error::illegal_argument(format!("Invalid DWARF in {}: {err}", path.display()))
+    };
+    let mut files = SourceLineHits::new();
+    let mut record = |location: addr2line::Location<'_>, hits: u64| {
+        if let (Some(file), Some(line)) = (location.file, location.line) {
+            if line != 0 {
+                let entry = files
+                    .entry(file.to_string())
+                    .or_default()
+                    .entry(line)
+                    .or_default();
+                // A line spanning several blocks is hit as often as its hottest block
+                *entry = (*entry).max(hits);
+            }
+        }
+    };
+
+    // Every line with code is valid, even if never hit
+    for (_, _, location) in context
+        .find_location_range(0, u64::MAX)
+        .map_err(dwarf_error)?
+    {
+        record(location, 0);
+    }
+    for (offset, block) in blocks {
+        let start = base + u64::from(*offset);
+        let end = start + u64::from(block.size.max(1));
+        for (_, _, location) in context
+            .find_location_range(start, end)
+            .map_err(dwarf_error)?
+        {
+            record(location, block.hits);
+        }
+    }
+
+    if files.is_empty() {
+        return Err(Error::illegal_argument(format!(
+            "No DWARF line info in {}",
+            path.display()
+        )));
+    }
+    Ok(files)
+}
+
+/// Source line coverage, created from [`DrCovCoverage::line_coverage`]
+#[cfg(feature = "drcov_lines")]
+#[derive(Clone, Debug, Default)]
+pub struct LineCoverage {
+    /// The line hit counts, keyed by module path
+    pub modules: BTreeMap<String, SourceLineHits>,
+}
+
+#[cfg(feature = "drcov_lines")]
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(feature = "drcov_lines")]
+#[allow(clippy::cast_precision_loss)]
+fn line_rate<'a, I>(lines: I) -> (usize, usize, f64)
+where
+    I: IntoIterator<Item = &'a u64>,
+{
+    let (mut valid, mut covered) = (0, 0);
+    for hits in lines {
+        valid += 1;
+        if *hits > 0 {
+            covered += 1;
+        }
+    }
+    let rate = if valid == 0 {
+        0.0
+    } else {
+        covered as f64 / valid as f64
+    };
+    (valid, covered, rate)
+}
+
+#[cfg(feature = "drcov_lines")]
+impl LineCoverage {
+    /// The line hit counts of every source file, merged over all modules
+    #[must_use]
+    pub fn files(&self) -> BTreeMap<&str, BTreeMap<u32, u64>> {
+        let mut files: BTreeMap<&str, BTreeMap<u32, u64>> = BTreeMap::new();
+        for (file, lines) in self.modules.values().flatten() {
+            let merged = files.entry(file.as_str()).or_default();
+            for (line, hits) in lines {
+                *merged.entry(*line).or_default() += hits;
+            }
+        }
+        files
+    }
+
+    /// Export as lcov tracefile
+    #[must_use]
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for (file, lines) in self.files() {
+            let (valid, covered, _) = line_rate(lines.values());
+            writeln!(lcov, "TN:\nSF:{file}").unwrap();
+            for (line, hits) in &lines {
+                writeln!(lcov, "DA:{line},{hits}").unwrap();
+            }
+            writeln!(lcov, "LF:{valid}\nLH:{covered}\nend_of_record").unwrap();
+        }
+        lcov
+    }
+
+    /// Export as Cobertura XML, with a package per module and a class per source file
+    #[must_use]
+    pub fn to_cobertura(&self) -> String {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+        let (valid, covered, rate) = line_rate(
+            self.modules
+                .values()
+                .flat_map(BTreeMap::values)
+                .flat_map(BTreeMap::values),
+        );
+
+        let mut xml = String::from("<?xml version=\"1.0\" ?>\n");
+        xml.push_str(
+            "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">\n",
+        );
+        writeln!(
+            xml,
+            "<coverage line-rate=\"{rate:.4}\" branch-rate=\"0\" lines-covered=\"{covered}\" lines-valid=\"{valid}\" branches-covered=\"0\" branches-valid=\"0\" complexity=\"0\" version=\"libafl\" timestamp=\"{timestamp}\">"
+        )
+        .unwrap();
+        xml.push_str("  <sources>\n    <source>.</source>\n  </sources>\n  <packages>\n");
+        for (module, files) in &self.modules {
+            let (_, _, rate) = line_rate(files.values().flat_map(BTreeMap::values));
+            writeln!(
+                xml,
+                "    <package name=\"{}\" line-rate=\"{rate:.4}\" branch-rate=\"0\" complexity=\"0\">\n      <classes>",
+                xml_escape(module)
+            )
+            .unwrap();
+            for (file, lines) in files {
+                let (_, _, rate) = line_rate(lines.values());
+                let file = xml_escape(file);
+                writeln!(
+                    xml,
+                    "        <class name=\"{file}\" filename=\"{file}\" line-rate=\"{rate:.4}\" branch-rate=\"0\" complexity=\"0\">\n          <methods/>\n          <lines>"
+                )
+                .unwrap();
+                for (line, hits) in lines {
+                    writeln!(
+                        xml,
+                        "            <line number=\"{line}\" hits=\"{hits}\" branch=\"false\"/>"
+                    )
+                    .unwrap();
+                }
+                xml.push_str("          </lines>\n        </class>\n");
+            }
+            xml.push_str("      </classes>\n    </package>\n");
+        }
+        xml.push_str("  </packages>\n</coverage>\n");
+        xml
+    }
+
+    /// Write an lcov tracefile
+    pub fn write_lcov<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_lcov())?;
+        Ok(())
+    }
+
+    /// Write a Cobertura XML report
+    pub fn write_cobertura<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_cobertura())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+    #[cfg(feature = "drcov_lines")]
+    use std::collections::BTreeMap;
+
+    use rangemap::RangeMap;
+
+    #[cfg(feature = "drcov_lines")]
+    use super::LineCoverage;
+    use super::{
+        DrCovBasicBlock, DrCovBlock, DrCovBlockHits, DrCovCoverage, DrCovModuleEntry, DrCovReader,
+        DrCovWriter,
+    };
+
+    fn header(modules: &str, bbs: usize) -> Vec<u8> {
+        format!(
+            "DRCOV VERSION: 2\nDRCOV FLAVOR: test\nModule Table: version 2, count 1\nColumns: id, base, end, entry, checksum, timestamp, path\n{modules}\nBB Table: {bbs} bbs\n"
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_drcov_roundtrip() {
+        let mut mapping = RangeMap::new();
+        mapping.insert(0x1000..0x2000, (0, "/bin/a".to_string()));
+        mapping.insert(0x8000..0x9000, (1, "/lib/b, with comma.so".to_string()));
+        let blocks = vec![
+            DrCovBasicBlock::new(0x1010, 0x1020),
+            DrCovBasicBlock::with_size(0x8100, 4),
+        ];
+
+        let path = std::env::temp_dir().join(format!("libafl_drcov_{}.drcov", std::process::id()));
+        DrCovWriter::new(&mapping).write(&path, &blocks).unwrap();
+        let reader = DrCovReader::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            reader.modules,
+            vec![
+                DrCovModuleEntry {
+                    id: 0,
+                    base: 0x1000,
+                    end: 0x2000,
+                    path: "/bin/a".to_string(),
+                },
+                DrCovModuleEntry {
+                    id: 1,
+                    base: 0x8000,
+                    end: 0x9000,
+                    path: "/lib/b, with comma.so".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            reader.blocks,
+            vec![
+                DrCovBlock {
+                    module: 0,
+                    offset: 0x10,
+                    size: 0x10,
+                },
+                DrCovBlock {
+                    module: 1,
+                    offset: 0x100,
+                    size: 4,
+                },
+            ]
+        );
+        assert_eq!(reader.basic_blocks(), blocks);
+    }
+
+    #[test]
+    fn test_drcov_write_rejects_large_blocks() {
+        let mut mapping = RangeMap::new();
+        mapping.insert(0..0x100_000, (0, "/bin/a".to_string()));
+        let path =
+            std::env::temp_dir().join(format!("libafl_drcov_large_{}.drcov", std::process::id()));
+        let result =
+            DrCovWriter::new(&mapping).write(&path, &[DrCovBasicBlock::with_size(0, 0x10000)]);
+        drop(std::fs::remove_file(&path));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_drcov_reader_errors() {
+        assert!(DrCovReader::from_bytes(b"not drcov\n").is_err());
+
+        // Version 1 module table, no columns line
+        let mut data =
+            b"DRCOV VERSION: 1\nModule Table: 1\n0, 0x1000, 0x2000, 0x0, /bin/a\nBB Table: 1 bbs\n"
+                .to_vec();
+        data.extend([0x10, 0, 0, 0, 4, 0, 0, 0]);
+        let reader = DrCovReader::from_bytes(&data).unwrap();
+        assert_eq!(reader.module(0).unwrap().path, "/bin/a");
+        assert_eq!(
+            reader.basic_blocks(),
+            vec![DrCovBasicBlock::new(0x1010, 0x1014)]
+        );
+
+        // Truncated table
+        let mut data = header("0, 0x1000, 0x2000, 0x0, 0x0, 0x0, /bin/a", 2);
+        data.extend([0; 12]);
+        assert!(DrCovReader::from_bytes(&data).is_err());
+
+        // A block count overflowing the table size
+        let data = header("0, 0x1000, 0x2000, 0x0, 0x0, 0x0, /bin/a", usize::MAX);
+        assert!(DrCovReader::from_bytes(&data).is_err());
+
+        // A module id out of range
+        let data = header("70000, 0x1000, 0x2000, 0x0, 0x0, 0x0, /bin/a", 0);
+        assert!(DrCovReader::from_bytes(&data).is_err());
+    }
+
+    #[test]
+    fn test_drcov_coverage_merge() {
+        let reader = |base, blocks: &[(u32, u16)]| DrCovReader {
+            modules: vec![DrCovModuleEntry {
+                id: 3,
+                base,
+                end: base + 0x1000,
+                path: "/bin/a".to_string(),
+            }],
+            blocks: blocks
+                .iter()
+                .map(|(offset, size)| DrCovBlock {
+                    module: 3,
+                    offset: *offset,
+                    size: *size,
+                })
+                .chain([DrCovBlock {
+                    // Unknown module, skipped
+                    module: 4,
+                    offset: 0,
+                    size: 1,
+                }])
+                .collect(),
+        };
+
+        let mut coverage = DrCovCoverage::new();
+        // Different load addresses, same module
+        coverage.add(&reader(0x1000, &[(0x10, 4), (0x20, 2)]));
+        coverage.add(&reader(0x5000, &[(0x10, 8), (0x10, 8)]));
+
+        let modules = coverage.modules();
+        assert_eq!(modules.len(), 1);
+        let blocks: Vec<(u32, DrCovBlockHits)> = modules["/bin/a"]
+            .iter()
+            .map(|(offset, hits)| (*offset, *hits))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0x10, DrCovBlockHits { size: 8, hits: 3 }),
+                (0x20, DrCovBlockHits { size: 2, hits: 1 }),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "drcov_lines")]
+    fn test_line_coverage_export() {
+        let lines = |lines: &[(u32, u64)]| lines.iter().copied().collect::<BTreeMap<u32, u64>>();
+        let mut coverage = LineCoverage::default();
+        coverage.modules.insert(
+            "/bin/a".to_string(),
+            BTreeMap::from([
+                ("a.c".to_string(), lines(&[(1, 2), (2, 0)])),
+                ("<&>.h".to_string(), lines(&[(7, 1)])),
+            ]),
+        );
+        coverage.modules.insert(
+            "/lib/b.so".to_string(),
+            BTreeMap::from([("a.c".to_string(), lines(&[(2, 5)]))]),
+        );
+
+        // The source files are merged over the modules
+        assert_eq!(
+            coverage.to_lcov(),
+            "TN:\nSF:<&>.h\nDA:7,1\nLF:1\nLH:1\nend_of_record\n\
+             TN:\nSF:a.c\nDA:1,2\nDA:2,5\nLF:2\nLH:2\nend_of_record\n"
+        );
+
+        let xml = coverage.to_cobertura();
+        assert!(xml.contains(
+            "line-rate=\"0.7500\" branch-rate=\"0\" lines-covered=\"3\" lines-valid=\"4\""
+        ));
+        assert!(xml.contains("<package name=\"/bin/a\" line-rate=\"0.6667\""));
+        assert!(xml.contains("<class name=\"&lt;&amp;&gt;.h\" filename=\"&lt;&amp;&gt;.h\""));
+        assert!(xml.contains("<line number=\"2\" hits=\"0\" branch=\"false\"/>"));
+        assert_eq!(xml.matches("<package ").count(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "drcov_lines")]
+    fn test_line_coverage_dwarf() {
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap().to_string();
+        let mut coverage = DrCovCoverage::new();
+        coverage.add(&DrCovReader {
+            modules: vec![DrCovModuleEntry {
+                id: 0,
+                base: 0,
+                end: 0,
+                path: exe.clone(),
+            }],
+            blocks: vec![DrCovBlock {
+                module: 0,
+                offset: 0,
+                size: 1,
+            }],
+        });
+
+        // The test binary has line info, this very file included
+        let lines = coverage.line_coverage();
+        assert!(lines.modules[&exe]
+            .keys()
+            .any(|file| file.ends_with("drcov.rs")));
+        assert!(coverage.line_coverage_with(|_| None).modules.is_empty());
+    }
+}
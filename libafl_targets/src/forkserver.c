@@ -40,11 +40,21 @@
 #define FS_ERROR_OLD_CMPLOG 32
 #define FS_ERROR_OLD_CMPLOG_QEMU 64
 
-#define FS_NEW_VERSION_MAX 1
+#define FS_NEW_VERSION_MAX 2
 #define FS_NEW_OPT_MAPSIZE 0x1
 #define FS_NEW_OPT_SHDMEM_FUZZ 0x2
 #define FS_NEW_OPT_AUTODICT 0x800
 
+// LibAFL protocol version 2, see `libafl::executors::forkserver`
+#define FS_VERSION_ENV_VAR "__LIBAFL_FORKSERVER_VERSION"
+// The hello of AFL++ is "AFL" and the version, LibAFL uses "LIB" from
+// version 2 on, so that its options never clash with new AFL++ options
+#define FS_AFLPP_VERSION_BASE 0x41464c00
+#define FS_LIBAFL_VERSION_BASE 0x4c494200
+#define FS_NEW_OPT_MAP_ADDR 0x4
+#define FS_NEW_OPT_EXTENSIONS 0x1000
+#define FS_EXT_TIMEOUT 1
+
 /* Reporting options */
 #define FS_OPT_ENABLED 0x80000001
 #define FS_OPT_MAPSIZE 0x40000000
@@ -64,6 +74,13 @@
 
 int __afl_sharedmem_fuzzing __attribute__((weak));
 
+// The fixed address of the coverage map, set by LTO instrumentation
+uint64_t __afl_map_addr __attribute__((weak));
+
+// The timeout of the current execution in milliseconds, sent by LibAFL
+// fuzzers speaking protocol version 2, or 0
+uint32_t __libafl_forkserver_exec_timeout_ms;
+
 extern uint8_t *__afl_area_ptr;
 extern size_t   __afl_map_size;
 extern uint8_t *__token_start;
@@ -129,8 +146,9 @@ void __afl_map_shm(void) {
       exit(1);
     }
 
-    shm_base =
-        mmap(0, __afl_map_size, PROT_READ | PROT_WRITE, MAP_SHARED, shm_fd, 0);
+    shm_base = mmap((void *)__afl_map_addr, __afl_map_size,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED | (__afl_map_addr ? MAP_FIXED : 0), shm_fd, 0);
 
     close(shm_fd);
     shm_fd = -1;
@@ -138,19 +156,21 @@ void __afl_map_shm(void) {
     if (shm_base == MAP_FAILED) {
       fprintf(stderr, "mmap() failed\n");
       perror("mmap for map");
-      send_forkserver_error(FS_ERROR_MMAP);
+      send_forkserver_error(__afl_map_addr ? FS_ERROR_MAP_ADDR
+                                           : FS_ERROR_MMAP);
       exit(2);
     }
 
     __afl_area_ptr = shm_base;
 #else
     uint32_t shm_id = atoi(id_str);
-    __afl_area_ptr = (uint8_t *)shmat(shm_id, NULL, 0);
+    __afl_area_ptr = (uint8_t *)shmat(shm_id, (void *)__afl_map_addr, 0);
 
     /* Whooooops. */
 
     if (!__afl_area_ptr || __afl_area_ptr == (void *)-1) {
-      send_forkserver_error(FS_ERROR_SHMAT);
+      send_forkserver_error(__afl_map_addr ? FS_ERROR_MAP_ADDR
+                                           : FS_ERROR_SHMAT);
       perror("shmat for map");
       exit(1);
     }
@@ -227,7 +247,16 @@ void __afl_start_forkserver(void) {
 
   uint32_t already_read_first = 0;
   uint32_t was_killed;
-  uint32_t version = 0x41464c00 + FS_NEW_VERSION_MAX;
+  // AFL++ only speaks version 1, LibAFL tells us if it speaks version 2
+  uint32_t max_version = 1;
+  char    *version_str = getenv(FS_VERSION_ENV_VAR);
+  if (version_str && atoi(version_str) >= 2) {
+    max_version = FS_NEW_VERSION_MAX;
+  }
+  uint32_t version =
+      (max_version >= 2 ? FS_LIBAFL_VERSION_BASE : FS_AFLPP_VERSION_BASE) +
+      max_version;
+  uint8_t  timeout_ext = 0;
   uint32_t tmp = version ^ 0xffffffff;
   uint32_t status = version;
   uint32_t status2 = version;
@@ -257,6 +286,11 @@ void __afl_start_forkserver(void) {
   status = FS_NEW_OPT_MAPSIZE;
   if (__afl_sharedmem_fuzzing) { status |= FS_NEW_OPT_SHDMEM_FUZZ; }
   if (autotokens_on) { status |= FS_NEW_OPT_AUTODICT; }
+  if (max_version >= 2) {
+    if (__afl_map_addr) { status |= FS_NEW_OPT_MAP_ADDR; }
+    status |= FS_NEW_OPT_EXTENSIONS;
+  }
+  uint32_t options = status;
 
   if (write(FORKSRV_FD + 1, msg, 4) != 4) { _exit(1); }
 
@@ -266,6 +300,11 @@ void __afl_start_forkserver(void) {
   status = __afl_map_size;
   if (write(FORKSRV_FD + 1, msg, 4) != 4) { _exit(1); }
 
+  // FS_NEW_OPT_MAP_ADDR - send the fixed address of the map
+  if (options & FS_NEW_OPT_MAP_ADDR) {
+    if (write(FORKSRV_FD + 1, &__afl_map_addr, 8) != 8) { _exit(1); }
+  }
+
   // FS_NEW_OPT_AUTODICT - send autotokens
   if (autotokens_on) {
     // pass the autotokens through the forkserver FD
@@ -291,6 +330,31 @@ void __afl_start_forkserver(void) {
     }
   }
 
+  // FS_NEW_OPT_EXTENSIONS - send the ids of the extensions we support
+  if (options & FS_NEW_OPT_EXTENSIONS) {
+    uint32_t extensions[2] = {1, FS_EXT_TIMEOUT};
+    if (write(FORKSRV_FD + 1, extensions, 8) != 8) { _exit(1); }
+  }
+
+  // In version 2, the fuzzer acknowledges the options and picks the
+  // extensions, whose payloads follow each request for a new child
+  if (max_version >= 2) {
+    uint32_t accepted, count, id;
+    if (read(FORKSRV_FD, &accepted, 4) != 4) { _exit(1); }
+    if (accepted & FS_NEW_OPT_EXTENSIONS) {
+      if (read(FORKSRV_FD, &count, 4) != 4) { _exit(1); }
+      while (count--) {
+        if (read(FORKSRV_FD, &id, 4) != 4) { _exit(1); }
+        if (id == FS_EXT_TIMEOUT) {
+          timeout_ext = 1;
+        } else {
+          write_error("unknown forkserver extension");
+          _exit(1);
+        }
+      }
+    }
+  }
+
   // send welcome message as final message
   status = version;
   if (write(FORKSRV_FD + 1, msg, 4) != 4) { _exit(1); }
@@ -312,6 +376,13 @@ void __afl_start_forkserver(void) {
       }
     }
 
+    /* Read the payloads of the extensions. */
+
+    if (timeout_ext && read(FORKSRV_FD, &__libafl_forkserver_exec_timeout_ms,
+                            4) != 4) {
+      _exit(1);
+    }
+
     /* If we stopped the child in persistent mode, but there was a race
        condition and afl-fuzz already issued SIGKILL, write off the old
        process. */
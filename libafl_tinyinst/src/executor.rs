@@ -9,11 +9,27 @@ use libafl::{
 };
 use libafl_bolts::{
     fs::{InputFile, INPUTFILE_STD},
+    hash_std,
     shmem::{NopShMemProvider, ShMem, ShMemProvider},
-    tuples::RefIndexable,
+    tuples::{Handle, MatchNameRef, RefIndexable},
     AsSlice, AsSliceMut,
 };
-use tinyinst::tinyinst::{litecov::RunResult, TinyInst};
+use tinyinst::tinyinst::TinyInst;
+
+use crate::observer::{TinyInstRunMetadata, TinyInstRunObserver, TinyInstRunResult};
+
+/// What to do when the target does not finish an iteration within the timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HangPolicy {
+    /// Report the hang as [`ExitKind::Timeout`]
+    #[default]
+    Timeout,
+    /// Report the hang as [`ExitKind::Crash`]
+    Crash,
+    /// Run the input again up to the given number of times, and only report [`ExitKind::Timeout`]
+    /// if every run hangs. Filters out hangs caused by a loaded system.
+    Retry(usize),
+}
 
 /// [`TinyInst`](https://github.com/googleprojectzero/TinyInst) executor
 pub struct TinyInstExecutor<S, SP, OT>
@@ -21,8 +37,17 @@ where
     SP: ShMemProvider,
 {
     tinyinst: TinyInst,
+    tinyinst_args: Vec<String>,
+    program_args: Vec<String>,
     coverage_ptr: *mut Vec<u64>,
+    /// Used if no `coverage_ptr` is set
+    coverage: Vec<u64>,
+    coverage_map: Option<(*mut u8, usize)>,
     timeout: Duration,
+    hang_policy: HangPolicy,
+    max_restarts: usize,
+    restarts: usize,
+    run_observer: Option<Handle<TinyInstRunObserver>>,
     observers: OT,
     phantom: PhantomData<S>,
     cur_input: InputFile,
@@ -44,16 +69,99 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("TinyInstExecutor")
             .field("timeout", &self.timeout)
+            .field("hang_policy", &self.hang_policy)
+            .field("restarts", &self.restarts)
             .finish_non_exhaustive()
     }
 }
 
+impl<S, SP, OT> TinyInstExecutor<S, SP, OT>
+where
+    SP: ShMemProvider,
+{
+    /// How often the target was restarted after it died
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// Kill the target and start a fresh `TinyInst` instance. Coverage already reported is reported again.
+    fn restart(&mut self) {
+        log::warn!("TinyInst target died, restarting it");
+        self.tinyinst = unsafe {
+            TinyInst::new(
+                &self.tinyinst_args,
+                &self.program_args,
+                self.timeout.as_millis() as u32,
+            )
+        };
+        self.restarts += 1;
+    }
+
+    /// Run the target once, restarting it if it died, and collect the coverage
+    fn run_once(&mut self, restarts: &mut usize) -> Result<TinyInstRunResult, Error> {
+        loop {
+            let coverage = if self.coverage_ptr.is_null() {
+                &mut self.coverage
+            } else {
+                unsafe { &mut *self.coverage_ptr }
+            };
+            let status = unsafe {
+                let status = self.tinyinst.run();
+                self.tinyinst.vec_coverage(coverage, false);
+                status
+            };
+            if let Some((map, len)) = self.coverage_map {
+                mark_coverage(
+                    unsafe { core::slice::from_raw_parts_mut(map, len) },
+                    coverage,
+                );
+            }
+
+            let result = TinyInstRunResult::from(status);
+            if result != TinyInstRunResult::OtherError {
+                return Ok(result);
+            }
+            if *restarts >= self.max_restarts {
+                return Err(Error::unknown(format!(
+                    "TinyInst target died {restarts} times in a row"
+                )));
+            }
+            *restarts += 1;
+            self.restart();
+        }
+    }
+}
+
+/// Set the entry of each covered offset in `map`.
+///
+/// `TinyInst` reports every covered offset once per run, without hit counts, so entries are only set to `1`.
+fn mark_coverage(map: &mut [u8], offsets: &[u64]) {
+    for offset in offsets {
+        let idx = (hash_std(&offset.to_le_bytes()) % map.len() as u64) as usize;
+        map[idx] = 1;
+    }
+}
+
+/// The [`ExitKind`] of a run that ended with `result`
+fn exit_kind(result: TinyInstRunResult, hang_policy: HangPolicy) -> ExitKind {
+    match result {
+        TinyInstRunResult::Ok => ExitKind::Ok,
+        TinyInstRunResult::Crash => ExitKind::Crash,
+        TinyInstRunResult::Hang => match hang_policy {
+            HangPolicy::Crash => ExitKind::Crash,
+            HangPolicy::Timeout | HangPolicy::Retry(_) => ExitKind::Timeout,
+        },
+        TinyInstRunResult::OtherError => unreachable!("handled by restarting the target"),
+    }
+}
+
 impl<EM, S, SP, OT, Z> Executor<EM, Z> for TinyInstExecutor<S, SP, OT>
 where
     EM: UsesState<State = S>,
     S: State + HasExecutions,
     S::Input: HasTargetBytes,
     SP: ShMemProvider,
+    OT: ObserversTuple<S>,
     Z: UsesState<State = S>,
 {
     #[inline]
@@ -83,22 +191,29 @@ where
             }
         }
 
-        #[allow(unused_assignments)]
-        let mut status = RunResult::OK;
-        unsafe {
-            status = self.tinyinst.run();
-            self.tinyinst
-                .vec_coverage(self.coverage_ptr.as_mut().unwrap(), false);
+        let mut restarts = 0;
+        let mut attempts = 1;
+        let mut result = self.run_once(&mut restarts)?;
+        if let HangPolicy::Retry(retries) = self.hang_policy {
+            while result == TinyInstRunResult::Hang && attempts <= retries {
+                attempts += 1;
+                result = self.run_once(&mut restarts)?;
+            }
         }
 
-        match status {
-            RunResult::CRASH | RunResult::HANG => Ok(ExitKind::Crash),
-            RunResult::OK => Ok(ExitKind::Ok),
-            RunResult::OTHER_ERROR => Err(Error::unknown(
-                "Tinyinst RunResult is other error".to_string(),
-            )),
-            _ => Err(Error::unknown("Tinyinst RunResult is unknown".to_string())),
+        let exit_kind = exit_kind(result, self.hang_policy);
+
+        if let Some(handle) = &self.run_observer {
+            if let Some(observer) = self.observers.get_mut(handle) {
+                observer.set_run(TinyInstRunMetadata {
+                    result,
+                    exit_kind,
+                    attempts,
+                    restarts,
+                });
+            }
         }
+        Ok(exit_kind)
     }
 }
 
@@ -108,12 +223,17 @@ pub struct TinyInstExecutorBuilder<'a, SP> {
     tinyinst_args: Vec<String>,
     program_args: Vec<String>,
     timeout: Duration,
+    hang_policy: HangPolicy,
+    max_restarts: usize,
     coverage_ptr: *mut Vec<u64>,
+    coverage_map: Option<(*mut u8, usize)>,
+    run_observer: Option<Handle<TinyInstRunObserver>>,
     shmem_provider: Option<&'a mut SP>,
 }
 
 const MAX_FILE: usize = 1024 * 1024;
 const SHMEM_FUZZ_HDR_SIZE: usize = 4;
+const MAX_RESTARTS_DEFAULT: usize = 3;
 
 impl<'a> Default for TinyInstExecutorBuilder<'a, NopShMemProvider> {
     fn default() -> Self {
@@ -129,8 +249,12 @@ impl<'a> TinyInstExecutorBuilder<'a, NopShMemProvider> {
             tinyinst_args: vec![],
             program_args: vec![],
             timeout: Duration::new(3, 0),
+            hang_policy: HangPolicy::default(),
+            max_restarts: MAX_RESTARTS_DEFAULT,
             shmem_provider: None,
             coverage_ptr: ptr::null_mut(),
+            coverage_map: None,
+            run_observer: None,
         }
     }
 
@@ -144,8 +268,12 @@ impl<'a> TinyInstExecutorBuilder<'a, NopShMemProvider> {
             tinyinst_args: self.tinyinst_args,
             program_args: self.program_args,
             timeout: self.timeout,
+            hang_policy: self.hang_policy,
+            max_restarts: self.max_restarts,
             shmem_provider: Some(shmem_provider),
-            coverage_ptr: ptr::null_mut(),
+            coverage_ptr: self.coverage_ptr,
+            coverage_map: self.coverage_map,
+            run_observer: self.run_observer,
         }
     }
 }
@@ -237,6 +365,48 @@ where
         self
     }
 
+    /// Set what to do when the target hangs, reports [`ExitKind::Timeout`] by default
+    #[must_use]
+    pub fn hang_policy(mut self, hang_policy: HangPolicy) -> Self {
+        self.hang_policy = hang_policy;
+        self
+    }
+
+    /// How often the target may be restarted in a row after it died, before the run fails
+    #[must_use]
+    pub fn max_restarts(mut self, max_restarts: usize) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Collect edge coverage instead of basic block coverage
+    #[must_use]
+    pub fn edge_coverage(mut self) -> Self {
+        self.tinyinst_args.push("-covtype".to_string());
+        self.tinyinst_args.push("edge".to_string());
+        self
+    }
+
+    /// Report the outcome of each run in the given [`TinyInstRunObserver`]
+    #[must_use]
+    pub fn run_observer(mut self, run_observer: Handle<TinyInstRunObserver>) -> Self {
+        self.run_observer = Some(run_observer);
+        self
+    }
+
+    /// Set a coverage map, e.g. the one of a [`libafl::observers::StdMapObserver`], to be used with a `MapFeedback`.
+    /// Each covered offset is hashed into the map, and its entry set to `1`.
+    /// `TinyInst` does not count hits, so the map needs no hit count classification.
+    ///
+    /// # Safety
+    /// The map must be valid for `len` bytes and outlive the time the [`TinyInstExecutor`] is alive.
+    /// The map will be written during execution. This may not happen concurrently.
+    #[must_use]
+    pub fn coverage_map(mut self, map: *mut u8, len: usize) -> Self {
+        self.coverage_map = Some((map, len));
+        self
+    }
+
     /// Set the pointer to the coverage vec used to observer the execution.
     ///
     /// # Safety
@@ -250,8 +420,13 @@ where
 
     /// Build [`TinyInst`](https://github.com/googleprojectzero/TinyInst) executor
     pub fn build<OT, S>(&mut self, observers: OT) -> Result<TinyInstExecutor<S, SP, OT>, Error> {
-        if self.coverage_ptr.is_null() {
-            return Err(Error::illegal_argument("Coverage pointer may not be null."));
+        if self.coverage_ptr.is_null() && self.coverage_map.is_none() {
+            return Err(Error::illegal_argument(
+                "Either a coverage pointer or a coverage map is needed.",
+            ));
+        }
+        if matches!(self.coverage_map, Some((map, len)) if map.is_null() || len == 0) {
+            return Err(Error::illegal_argument("Coverage map may not be empty."));
         }
         let (map, shmem_id) = match &mut self.shmem_provider {
             Some(provider) => {
@@ -306,8 +481,16 @@ where
 
         Ok(TinyInstExecutor {
             tinyinst,
+            tinyinst_args: self.tinyinst_args.clone(),
+            program_args,
             coverage_ptr: self.coverage_ptr,
+            coverage: Vec::new(),
+            coverage_map: self.coverage_map,
             timeout: self.timeout,
+            hang_policy: self.hang_policy,
+            max_restarts: self.max_restarts,
+            restarts: 0,
+            run_observer: self.run_observer.clone(),
             observers,
             phantom: PhantomData,
             cur_input,
@@ -345,3 +528,45 @@ where
 {
     type Observers = OT;
 }
+
+#[cfg(test)]
+mod tests {
+    use libafl::executors::ExitKind;
+
+    use super::{exit_kind, mark_coverage, HangPolicy};
+    use crate::observer::TinyInstRunResult;
+
+    #[test]
+    fn test_mark_coverage() {
+        let mut map = [0_u8; 64];
+        mark_coverage(&mut map, &[0x1000, 0x1010, 0x1020]);
+        let covered = map.iter().filter(|entry| **entry != 0).count();
+        assert!((1..=3).contains(&covered));
+        assert!(map.iter().all(|entry| *entry <= 1));
+
+        // Reported again after a retry or a restart, nothing changes
+        let before = map;
+        mark_coverage(&mut map, &[0x1000, 0x1010, 0x1020]);
+        assert_eq!(map, before);
+    }
+
+    #[test]
+    fn test_exit_kind() {
+        for policy in [HangPolicy::Timeout, HangPolicy::Crash, HangPolicy::Retry(2)] {
+            assert_eq!(exit_kind(TinyInstRunResult::Ok, policy), ExitKind::Ok);
+            assert_eq!(exit_kind(TinyInstRunResult::Crash, policy), ExitKind::Crash);
+        }
+        assert_eq!(
+            exit_kind(TinyInstRunResult::Hang, HangPolicy::Timeout),
+            ExitKind::Timeout
+        );
+        assert_eq!(
+            exit_kind(TinyInstRunResult::Hang, HangPolicy::Crash),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            exit_kind(TinyInstRunResult::Hang, HangPolicy::Retry(2)),
+            ExitKind::Timeout
+        );
+    }
+}
@@ -66,3 +66,4 @@ The tinyinst module for `LibAFL`.
 
 /// Tinyinst executor
 pub mod executor;
+pub mod observer;
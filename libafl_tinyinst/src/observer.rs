@@ -0,0 +1,245 @@
+//! Observer and feedback reporting how a [`crate::executor::TinyInstExecutor`] run ended.
+//!
+//! The executor fills the [`TinyInstRunObserver`] after every run, and the [`TinyInstRunFeedback`]
+//! attaches the resulting [`TinyInstRunMetadata`] to new testcases, e.g. to the solutions.
+
+use std::borrow::Cow;
+
+use libafl::{
+    corpus::Testcase,
+    events::EventFirer,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::UsesInput,
+    observers::{Observer, ObserversTuple},
+    state::State,
+    Error, HasMetadata,
+};
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+use tinyinst::tinyinst::litecov::RunResult;
+
+/// The result of a `TinyInst` run, as reported by the debugger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TinyInstRunResult {
+    /// The target finished the iteration
+    Ok,
+    /// The target raised an unhandled exception, or was killed by a signal
+    Crash,
+    /// The target did not finish within the timeout
+    Hang,
+    /// The target died or the debugger failed, the target had to be restarted
+    OtherError,
+}
+
+impl From<RunResult> for TinyInstRunResult {
+    fn from(result: RunResult) -> Self {
+        match result {
+            RunResult::OK => Self::Ok,
+            RunResult::CRASH => Self::Crash,
+            RunResult::HANG => Self::Hang,
+            _ => Self::OtherError,
+        }
+    }
+}
+
+/// How a [`crate::executor::TinyInstExecutor`] run ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct TinyInstRunMetadata {
+    /// The result of the last attempt
+    pub result: TinyInstRunResult,
+    /// The [`ExitKind`] reported to the fuzzer
+    pub exit_kind: ExitKind,
+    /// How often the input was run, more than once if it was retried after a hang or target death
+    pub attempts: usize,
+    /// How often the target had to be restarted during this run
+    pub restarts: usize,
+}
+
+impl_serdeany!(TinyInstRunMetadata);
+
+/// Observer filled by the [`crate::executor::TinyInstExecutor`] with the [`TinyInstRunMetadata`] of the last run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TinyInstRunObserver {
+    name: Cow<'static, str>,
+    run: Option<TinyInstRunMetadata>,
+}
+
+impl TinyInstRunObserver {
+    /// Create a new [`TinyInstRunObserver`]
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            run: None,
+        }
+    }
+
+    /// The metadata of the last run
+    #[must_use]
+    pub fn run(&self) -> Option<&TinyInstRunMetadata> {
+        self.run.as_ref()
+    }
+
+    /// Set the metadata of the last run, called by the executor
+    pub fn set_run(&mut self, run: TinyInstRunMetadata) {
+        self.run = Some(run);
+    }
+}
+
+impl Named for TinyInstRunObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for TinyInstRunObserver
+where
+    S: UsesInput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.run = None;
+        Ok(())
+    }
+}
+
+/// Nop feedback that attaches the [`TinyInstRunMetadata`] to the new testcase. The testcase
+/// is never interesting (use with an OR, e.g. with the objective).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TinyInstRunFeedback {
+    o_ref: Handle<TinyInstRunObserver>,
+}
+
+impl TinyInstRunFeedback {
+    /// Create a new [`TinyInstRunFeedback`]
+    #[must_use]
+    pub fn new(observer: &TinyInstRunObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
+
+impl Named for TinyInstRunFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl<S> Feedback<S> for TinyInstRunFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("TinyInstRunObserver is missing"))?;
+        if let Some(run) = observer.run() {
+            testcase.add_metadata(run.clone());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        corpus::Testcase, events::NopEventManager, executors::ExitKind, feedbacks::Feedback,
+        inputs::BytesInput, observers::Observer, state::NopState, HasMetadata,
+    };
+    use libafl_bolts::tuples::tuple_list;
+    use tinyinst::tinyinst::litecov::RunResult;
+
+    use super::{TinyInstRunFeedback, TinyInstRunMetadata, TinyInstRunObserver, TinyInstRunResult};
+
+    #[test]
+    fn test_run_result() {
+        assert_eq!(
+            TinyInstRunResult::from(RunResult::OK),
+            TinyInstRunResult::Ok
+        );
+        assert_eq!(
+            TinyInstRunResult::from(RunResult::CRASH),
+            TinyInstRunResult::Crash
+        );
+        assert_eq!(
+            TinyInstRunResult::from(RunResult::HANG),
+            TinyInstRunResult::Hang
+        );
+        assert_eq!(
+            TinyInstRunResult::from(RunResult::OTHER_ERROR),
+            TinyInstRunResult::OtherError
+        );
+    }
+
+    #[test]
+    fn test_run_feedback() {
+        let mut state = NopState::<BytesInput>::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![0]);
+        let run = TinyInstRunMetadata {
+            result: TinyInstRunResult::Hang,
+            exit_kind: ExitKind::Timeout,
+            attempts: 3,
+            restarts: 1,
+        };
+
+        let mut observer = TinyInstRunObserver::new("tinyinst_run");
+        let mut feedback = TinyInstRunFeedback::new(&observer);
+        observer.set_run(run.clone());
+        let observers = tuple_list!(observer);
+
+        assert!(!feedback
+            .is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Timeout)
+            .unwrap());
+        let mut testcase = Testcase::new(input.clone());
+        feedback
+            .append_metadata(&mut state, &mut mgr, &observers, &mut testcase)
+            .unwrap();
+        assert_eq!(testcase.metadata::<TinyInstRunMetadata>().unwrap(), &run);
+
+        // Cleared before the next run
+        let (mut observer, ()) = observers;
+        observer.pre_exec(&mut state, &input).unwrap();
+        assert!(observer.run().is_none());
+    }
+}